@@ -0,0 +1,107 @@
+use crate::inputs;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// How the p-values of each group are adjusted
+pub enum Adjustment {
+    /// Benjamini-Hochberg FDR
+    BH,
+    /// Bonferroni family-wise error rate
+    Bonferroni,
+    /// No adjustment
+    None,
+}
+
+impl Adjustment {
+    pub fn parse(name: &str) -> PyResult<Adjustment> {
+        match name {
+            "BH" => Ok(Adjustment::BH),
+            "bonferroni" => Ok(Adjustment::Bonferroni),
+            "none" => Ok(Adjustment::None),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown adjustment method '{}'. Expected 'BH', 'bonferroni', or 'none'.",
+                name
+            ))),
+        }
+    }
+
+    pub fn apply(&self, p: &[f64]) -> Vec<f64> {
+        match self {
+            Adjustment::BH => adjust(p, AdjustmentMethod::BH),
+            Adjustment::Bonferroni => p.iter().map(|x| (x * p.len() as f64).min(1.0)).collect(),
+            Adjustment::None => adjust(p, AdjustmentMethod::None),
+        }
+    }
+}
+
+/// Adjust p-values for multiple testing within groups, like per database or per direction of a combined run.
+///
+/// The p-values of each group are adjusted separately, as if each group was its own analysis.
+/// `NaN` p-values, like those of untested sets, are not counted in their group and stay `NaN`.
+///
+/// # Parameters
+/// - `pvals` - List of the p-values
+/// - `groups` - List of the group of each p-value, in the same order. Groups are compared by their string form, so any value can be used.
+/// - `method` - Either `"BH"` for the Benjamini-Hochberg FDR, `"bonferroni"`, or `"none"` (default: `"BH"`)
+///
+/// # Returns
+///
+/// Returns a list of the adjusted p-values, in the order of `pvals`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `pvals` and `groups` have different lengths, or if a p-value is not between `0` and `1`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// directions = ["up" if x["nes"] > 0 else "down" for x in res]
+/// fdr = webgestaltpy.adjust_grouped([x["p"] for x in res], directions)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (pvals, groups, method="BH"))]
+pub fn adjust_grouped(
+    pvals: Vec<f64>,
+    groups: Vec<Bound<'_, PyAny>>,
+    method: &str,
+) -> PyResult<Vec<f64>> {
+    let method = Adjustment::parse(method)?;
+    if pvals.len() != groups.len() {
+        return Err(PyValueError::new_err(format!(
+            "pvals and groups must have the same length, but have {} and {}",
+            pvals.len(),
+            groups.len()
+        )));
+    }
+    if let Some(p) = pvals
+        .iter()
+        .find(|x| !x.is_nan() && !(0.0..=1.0).contains(*x))
+    {
+        return Err(PyValueError::new_err(format!(
+            "p-values must be between 0 and 1, but one is {}",
+            p
+        )));
+    }
+    let mut members: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+    for (i, group) in groups.into_iter().enumerate() {
+        if !pvals[i].is_nan() {
+            members
+                .entry(inputs::analyte_to_string(&group)?)
+                .or_default()
+                .push(i);
+        }
+    }
+    let mut adjusted = vec![f64::NAN; pvals.len()];
+    for indices in members.values() {
+        let p: Vec<f64> = indices.iter().map(|i| pvals[*i]).collect();
+        for (i, value) in indices.iter().zip(method.apply(&p)) {
+            adjusted[*i] = value;
+        }
+    }
+    Ok(adjusted)
+}
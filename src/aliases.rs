@@ -0,0 +1,301 @@
+use crate::gmt::Gmt;
+use crate::inputs;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::{FxHashMap, FxHashSet};
+use webgestalt_lib::readers::utils::Item;
+
+/// Alias table with an alias and its current identifier on each line, separated by a tab. Lines starting with `#` are skipped,
+/// and an alias of more than one identifier is listed once for each.
+pub fn parse_alias_table(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let alias = columns.next().unwrap_or_default().trim();
+        let current = columns
+            .next()
+            .ok_or_else(|| format!("Line {} has no identifier column.", i + 1))?
+            .trim();
+        pairs.push((alias.to_owned(), current.to_owned()));
+    }
+    Ok(pairs)
+}
+
+/// How an identifier is resolved by an [`AliasMap`]
+pub enum Resolution<'a> {
+    /// The identifier is current
+    Current,
+    /// The identifier is an alias of a single current identifier
+    Alias(&'a str),
+    /// The identifier is an alias of more than one current identifier
+    Ambiguous,
+    /// The identifier is not in the alias table
+    Unknown,
+}
+
+/// Maps aliases (like previous gene symbols) to current identifiers
+pub struct AliasMap {
+    targets: FxHashMap<String, Vec<String>>,
+    current: FxHashSet<String>,
+}
+
+impl AliasMap {
+    pub fn new(pairs: Vec<(String, String)>) -> AliasMap {
+        let mut targets: FxHashMap<String, Vec<String>> = FxHashMap::default();
+        let mut current: FxHashSet<String> = FxHashSet::default();
+        for (alias, id) in pairs {
+            current.insert(id.clone());
+            if alias == id {
+                continue;
+            }
+            let ids = targets.entry(alias).or_default();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        AliasMap { targets, current }
+    }
+
+    /// Resolves `id`. Current identifiers are kept even if they are also an alias of another identifier.
+    pub fn resolve(&self, id: &str) -> Resolution<'_> {
+        if self.current.contains(id) {
+            return Resolution::Current;
+        }
+        match self.targets.get(id).map(|x| x.as_slice()) {
+            Some([target]) => Resolution::Alias(target),
+            Some(_) => Resolution::Ambiguous,
+            None => Resolution::Unknown,
+        }
+    }
+
+    /// The current identifier of `id`, or `id` itself if it is current, ambiguous, or unknown
+    pub fn canonical<'a>(&'a self, id: &'a str) -> &'a str {
+        match self.resolve(id) {
+            Resolution::Alias(target) => target,
+            _ => id,
+        }
+    }
+}
+
+/// Gets an alias table input, which can be a path to (or file-like object of) an alias table file,
+/// or a dictionary mapping each alias to its current identifier or a list of them
+pub fn alias_map(obj: &Bound<'_, PyAny>) -> PyResult<AliasMap> {
+    if let Some(pairs) = inputs::parse_text_input(obj, "alias table", parse_alias_table)? {
+        return Ok(AliasMap::new(pairs));
+    }
+    let dict = obj.downcast::<PyDict>().map_err(|_| {
+        PyValueError::new_err("Alias table must be a path, a file-like object, or a dictionary!")
+    })?;
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (alias, ids) in dict.iter() {
+        let alias = inputs::analyte_to_string(&alias)?;
+        if ids.extract::<&str>().is_ok() || ids.extract::<i64>().is_ok() {
+            pairs.push((alias, inputs::analyte_to_string(&ids)?));
+        } else {
+            for id in ids.try_iter()? {
+                pairs.push((alias.clone(), inputs::analyte_to_string(&id?)?));
+            }
+        }
+    }
+    Ok(AliasMap::new(pairs))
+}
+
+/// Remap the analytes of a GMT to current identifiers with an alias table, like to update previous gene symbols
+/// to the current HGNC symbols.
+///
+/// Stale identifiers in a GMT never match the current identifiers of an analyte list, which silently shrinks the sets.
+/// Each member of a set is resolved as:
+///
+/// - Current, if it is an identifier in the alias table. It is kept as it is.
+/// - An alias of a single current identifier. It is replaced by that identifier.
+/// - Ambiguous, if it is an alias of more than one identifier, or unknown, if it is not in the alias table.
+///   These members are reported as unmapped.
+///
+/// Members that become duplicates after remapping are kept once.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`ora`](./ora.md)
+/// - `aliases` - `String` of the path to an alias table file, a file-like object, or a dictionary mapping each alias to its
+///   current identifier (or a list of them). The file has an alias and its current identifier on each line, separated by a tab.
+///   Lines starting with `#` are skipped.
+/// - `keep_unmapped` - If `True`, unmapped members stay in their sets. Otherwise, they are removed (default: `True`)
+/// - `path` - If provided, `String` of the path to write the remapped GMT file to (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `gmt` - The remapped sets as a [`Gmt`](./Gmt.md) object, which can be passed to any analysis
+/// - `remapped` - Number of members replaced by their current identifier
+/// - `unmapped` - Dictionary mapping each set with unmapped members to a list of them
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, or if the file can not be written.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.liftover_gmt("old_symbols.gmt", {"C10orf2": "TWNK", "FAM46C": "TENT5C"})
+/// print(res["remapped"], sum(len(x) for x in res["unmapped"].values()))
+/// ora = webgestaltpy.ora(res["gmt"], "genelist.txt", "reference.txt")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, aliases, keep_unmapped=true, path=None))]
+pub fn liftover_gmt<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    aliases: &Bound<'_, PyAny>,
+    keep_unmapped: bool,
+    path: Option<String>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let gmt = inputs::gmt(gmt)?;
+    let aliases = alias_map(aliases)?;
+    let mut remapped = 0;
+    let unmapped = PyDict::new(py);
+    let mut items: Vec<Item> = Vec::with_capacity(gmt.len());
+    for item in gmt {
+        let mut seen: FxHashSet<String> = FxHashSet::default();
+        let mut parts: Vec<String> = Vec::with_capacity(item.parts.len());
+        let mut missing: Vec<&String> = Vec::new();
+        for analyte in item.parts.iter() {
+            let id = match aliases.resolve(analyte) {
+                Resolution::Current => analyte.clone(),
+                Resolution::Alias(id) => {
+                    remapped += 1;
+                    id.to_owned()
+                }
+                Resolution::Ambiguous | Resolution::Unknown => {
+                    missing.push(analyte);
+                    if !keep_unmapped {
+                        continue;
+                    }
+                    analyte.clone()
+                }
+            };
+            if seen.insert(id.clone()) {
+                parts.push(id);
+            }
+        }
+        if !missing.is_empty() {
+            unmapped.set_item(&item.id, missing)?;
+        }
+        items.push(Item {
+            id: item.id,
+            url: item.url,
+            parts,
+        });
+    }
+    if let Some(path) = path {
+        let content: String = items
+            .iter()
+            .map(|x| format!("{}\t{}\t{}\n", x.id, x.url, x.parts.join("\t")))
+            .collect();
+        inputs::write_file(&path, &content).map_err(|e| {
+            PyValueError::new_err(format!("Error when writing GMT file at: {}. {}", path, e))
+        })?;
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("gmt", Py::new(py, Gmt::from_items(items))?)?;
+    dict.set_item("remapped", remapped)?;
+    dict.set_item("unmapped", unmapped)?;
+    Ok(dict)
+}
+
+/// Matching between the sets of a GMT and an input list that tolerates aliases, by mapping both to current identifiers
+pub struct AliasMatching {
+    /// Original members of each set
+    members: Vec<FxHashSet<String>>,
+    /// Maps each current identifier of the input list to its original identifier
+    input: FxHashMap<String, String>,
+}
+
+impl AliasMatching {
+    /// Maps the members of `gmt` and the identifiers of `input` (given by `id`) to current identifiers.
+    /// Members that become duplicates in a set are kept once. Input items that become duplicates are kept
+    /// once, preferring the item that already had the current identifier, then the first one.
+    pub fn new<T>(
+        aliases: &AliasMap,
+        gmt: Vec<Item>,
+        input: Vec<T>,
+        id: fn(&mut T) -> &mut String,
+    ) -> (AliasMatching, Vec<Item>, Vec<T>) {
+        let mut members: Vec<FxHashSet<String>> = Vec::with_capacity(gmt.len());
+        let gmt: Vec<Item> = gmt
+            .into_iter()
+            .map(|item| {
+                let mut seen: FxHashSet<String> = FxHashSet::default();
+                let parts = item
+                    .parts
+                    .iter()
+                    .map(|x| aliases.canonical(x).to_owned())
+                    .filter(|x| seen.insert(x.clone()))
+                    .collect();
+                members.push(item.parts.into_iter().collect());
+                Item {
+                    id: item.id,
+                    url: item.url,
+                    parts,
+                }
+            })
+            .collect();
+        let mut input = input;
+        let mut originals: FxHashMap<String, String> = FxHashMap::default();
+        for x in input.iter_mut() {
+            let original = id(x).clone();
+            let current = aliases.canonical(&original).to_owned();
+            let replace = match originals.get(&current) {
+                Some(kept) => *kept != current && original == current,
+                None => true,
+            };
+            if replace {
+                originals.insert(current, original);
+            }
+        }
+        let input: Vec<T> = input
+            .into_iter()
+            .filter_map(|mut x| {
+                let original = id(&mut x).clone();
+                let current = aliases.canonical(&original).to_owned();
+                if originals.get(&current) != Some(&original) {
+                    return None;
+                }
+                *id(&mut x) = current;
+                Some(x)
+            })
+            .collect();
+        (
+            AliasMatching {
+                members,
+                input: originals,
+            },
+            gmt,
+            input,
+        )
+    }
+
+    /// Number of members of the set at `index` of the remapped GMT that are in `found` but only match
+    /// the input list through an alias, since their original identifiers differ
+    pub fn alias_matches(
+        &self,
+        index: usize,
+        set: &Item,
+        found: impl Fn(&String) -> bool,
+    ) -> usize {
+        set.parts
+            .iter()
+            .filter(|x| found(x))
+            .filter(|x| {
+                self.input
+                    .get(*x)
+                    .is_some_and(|original| !self.members[index].contains(original))
+            })
+            .count()
+    }
+}
@@ -0,0 +1,148 @@
+use crate::inputs;
+use ahash::AHashSet;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Expression values with an analyte on each row and a sample on each column
+pub struct ExpressionMatrix {
+    pub samples: Vec<String>,
+    pub rows: Vec<(String, Vec<f64>)>,
+}
+
+/// Expression matrix with an analyte on each row, after a header row of sample names.
+/// Values are separated by tabs, or by commas if the header has no tabs. Missing values (empty or `NA`) are `NaN`.
+/// The header may or may not have a name for the column of analytes.
+pub fn parse_expression_matrix(content: &str) -> Result<ExpressionMatrix, String> {
+    let mut lines = content.lines().filter(|x| !x.is_empty());
+    let header = lines.next().unwrap_or_default();
+    let delimiter = if !header.contains('\t') && header.contains(',') {
+        ','
+    } else {
+        '\t'
+    };
+    let rows = lines
+        .enumerate()
+        .map(|(i, line)| {
+            let mut columns = line.split(delimiter);
+            let analyte = columns.next().unwrap_or_default().to_owned();
+            let values = columns
+                .map(|x| match x.trim() {
+                    "" | "NA" | "NaN" | "nan" => Ok(f64::NAN),
+                    x => x
+                        .parse::<f64>()
+                        .map_err(|_| format!("Could not parse value '{}' on line {}.", x, i + 2)),
+                })
+                .collect::<Result<Vec<f64>, String>>()?;
+            Ok((analyte, values))
+        })
+        .collect::<Result<Vec<(String, Vec<f64>)>, String>>()?;
+    let mut samples: Vec<String> = header.split(delimiter).map(|x| x.to_owned()).collect();
+    let columns = rows
+        .first()
+        .map_or(samples.len().saturating_sub(1), |x| x.1.len());
+    if samples.len() > columns {
+        samples.remove(0);
+    }
+    Ok(ExpressionMatrix { samples, rows })
+}
+
+/// Gets an expression matrix from a path to (or file-like object of) a matrix file, a DataFrame with analytes as the index,
+/// or a dictionary mapping each analyte to its values. Samples of a dictionary are named by their position, starting at `1`.
+pub fn expression_matrix(obj: &Bound<'_, PyAny>) -> PyResult<ExpressionMatrix> {
+    if let Some(matrix) =
+        inputs::parse_text_input(obj, "expression matrix", parse_expression_matrix)?
+    {
+        return Ok(matrix);
+    }
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        let rows = dict
+            .iter()
+            .map(|(analyte, values)| {
+                Ok((
+                    inputs::analyte_to_string(&analyte)?,
+                    values
+                        .extract::<Vec<Option<f64>>>()?
+                        .into_iter()
+                        .map(|x| x.unwrap_or(f64::NAN))
+                        .collect(),
+                ))
+            })
+            .collect::<PyResult<Vec<(String, Vec<f64>)>>>()?;
+        let columns = rows.iter().map(|x| x.1.len()).max().unwrap_or(0);
+        return Ok(ExpressionMatrix {
+            samples: (1..=columns).map(|x| x.to_string()).collect(),
+            rows,
+        });
+    }
+    if obj.hasattr("index")? && obj.hasattr("to_numpy")? {
+        let analytes = obj.getattr("index")?.call_method0("tolist")?;
+        let values: Vec<Vec<f64>> = obj
+            .call_method0("to_numpy")?
+            .call_method1("astype", ("float64",))?
+            .call_method0("tolist")?
+            .extract()?;
+        let samples = obj
+            .getattr("columns")?
+            .call_method0("tolist")?
+            .try_iter()?
+            .map(|x| Ok(x?.str()?.to_string()))
+            .collect::<PyResult<Vec<String>>>()?;
+        let rows = analytes
+            .try_iter()?
+            .zip(values)
+            .map(|(analyte, values)| Ok((inputs::analyte_to_string(&analyte?)?, values)))
+            .collect::<PyResult<Vec<(String, Vec<f64>)>>>()?;
+        return Ok(ExpressionMatrix { samples, rows });
+    }
+    Err(PyValueError::new_err(
+        "Expression matrix must be a path, a file-like object, a DataFrame, or a dictionary!",
+    ))
+}
+
+/// Build a reference list of the analytes detected in an expression matrix, for use as the background of an ORA.
+///
+/// The reference list of an ORA should only contain analytes that could have been in the analyte list,
+/// which for expression data is the analytes detected in the experiment.
+///
+/// # Parameters
+/// - `expression` - `String` of the path to an expression matrix file, a file-like object, a DataFrame with analytes as the index
+///   and a column for each sample, or a dictionary mapping each analyte to a list of its values. The file has a header row with the sample
+///   names, then a row for each analyte with the analyte and its values, separated by tabs (or commas if the header has no tabs).
+/// - `threshold` - Value an analyte must be above to be detected in a sample (default: `0.0`)
+/// - `min_samples` - Minimum number of samples where an analyte must be detected (default: `1`)
+///
+/// Missing values (empty, `NA`, or `NaN`) are never detected.
+///
+/// # Returns
+///
+/// Returns a list of the detected analytes, in the order of the matrix. Analytes listed more than once are kept once.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the matrix is malformed or can not be read.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// reference = webgestaltpy.expressed_background("counts.tsv", threshold=10, min_samples=3)
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", reference)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (expression, threshold=0.0, min_samples=1))]
+pub fn expressed_background(
+    expression: &Bound<'_, PyAny>,
+    threshold: f64,
+    min_samples: usize,
+) -> PyResult<Vec<String>> {
+    let mut seen: AHashSet<String> = AHashSet::default();
+    Ok(expression_matrix(expression)?
+        .rows
+        .into_iter()
+        .filter(|(_, values)| values.iter().filter(|x| **x > threshold).count() >= min_samples)
+        .map(|(analyte, _)| analyte)
+        .filter(|x| seen.insert(x.clone()))
+        .collect())
+}
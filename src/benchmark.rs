@@ -0,0 +1,177 @@
+use crate::compare::{jaccard, spearman};
+use crate::{config, gsea_stats, inputs};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::FxHashSet;
+use std::time::Instant;
+use webgestalt_lib::methods::gsea::{GSEAConfig, GSEAResult};
+
+/// Reads a configuration of [`benchmark`], starting from the default `GSEAConfig`
+fn read_config(dict: &Bound<'_, PyDict>, index: usize) -> PyResult<(String, GSEAConfig)> {
+    let mut config = config::gsea_config();
+    let mut name = format!("config_{}", index);
+    for (key, value) in dict.iter() {
+        match key.extract::<&str>()? {
+            "name" => name = value.extract()?,
+            "permutations" => config.permutations = value.extract()?,
+            "min_overlap" => config.min_overlap = value.extract()?,
+            "max_overlap" => config.max_overlap = value.extract()?,
+            "p" => config.p = value.extract()?,
+            key => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown key '{}' in configuration {}. Expected 'name', 'permutations', 'min_overlap', 'max_overlap', or 'p'.",
+                    key, index
+                )))
+            }
+        }
+    }
+    if config.permutations < 1 {
+        return Err(PyValueError::new_err(format!(
+            "Number of permutations must be at least 1, but is {} in configuration {}",
+            config.permutations, index
+        )));
+    }
+    Ok((name, config))
+}
+
+/// Run GSEA with several configurations on the same inputs, to compare their runtime and how closely their results agree,
+/// like to pick a number of permutations that balances accuracy and speed.
+///
+/// Every configuration uses the same `seed`, and is compared to the `reference` configuration.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_list` - The rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `configs` - List of dictionaries of the configurations, each with any of:
+///     - `name` - Name of the configuration (default: `"config_<index>"`)
+///     - `permutations` - Number of permutations (default: set with [`set_defaults`](./set_defaults.md))
+///     - `min_overlap` - Minimum overlap of a set with the rank list to be tested (default: `15`)
+///     - `max_overlap` - Maximum overlap of a set with the rank list to be tested (default: `500`)
+///     - `p` - Weight of the ranks in the enrichment score (default: `1`)
+/// - `reference` - Index in `configs` of the configuration the others are compared to.
+///   If not provided, the configuration with the most permutations is used (default: `None`)
+/// - `fdr` - FDR threshold for a set to be significant (default: `0.05`)
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each configuration in the order of `configs`, with:
+///
+/// - `name`, `permutations`, `min_overlap`, `max_overlap`, and `p` - The configuration
+/// - `seconds` - Runtime of GSEA, without reading the inputs
+/// - `tested` - Number of sets tested
+/// - `significant` - Number of sets with an FDR below `fdr`
+/// - `spearman` - Spearman correlation of the NES with the reference, over the sets tested by both. `None` if it can not be computed.
+/// - `jaccard` - Jaccard index of the significant sets with those of the reference. `None` if neither has a significant set.
+/// - `max_fdr_difference` - Largest absolute difference of the FDR with the reference, over the sets tested by both. `None` if there are none.
+/// - `is_reference` - Whether this is the reference configuration
+/// - `seed` - Seed used for the permutations
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, if `configs` is empty or has an unknown key,
+/// or if `reference` is not an index of `configs`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// configs = [{"permutations": x} for x in [100, 250, 500, 1000, 5000]]
+/// res = webgestaltpy.benchmark("data/kegg.gmt", "data/test.rnk", configs)
+/// for x in res:
+///     print(x["permutations"], x["seconds"], x["spearman"], x["jaccard"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_list, configs, reference=None, fdr=0.05, seed=None))]
+pub fn benchmark<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_list: &Bound<'_, PyAny>,
+    configs: Vec<Bound<'_, PyDict>>,
+    reference: Option<usize>,
+    fdr: f64,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if configs.is_empty() {
+        return Err(PyValueError::new_err("configs must not be empty!"));
+    }
+    let configs: Vec<(String, GSEAConfig)> = configs
+        .into_iter()
+        .enumerate()
+        .map(|(i, x)| read_config(&x, i))
+        .collect::<PyResult<_>>()?;
+    let reference = match reference {
+        Some(i) if i >= configs.len() => {
+            return Err(PyValueError::new_err(format!(
+                "reference must be an index of configs, but is {}",
+                i
+            )))
+        }
+        Some(i) => i,
+        // first configuration with the most permutations
+        None => (0..configs.len())
+            .rev()
+            .max_by_key(|i| configs[*i].1.permutations)
+            .unwrap(),
+    };
+    let gmt_vec = inputs::gmt(gmt)?;
+    let rank_list = inputs::rank_list(rank_list)?;
+    let seed = gsea_stats::seed_or_random(seed);
+    let mut runs: Vec<(Vec<GSEAResult>, f64)> = Vec::with_capacity(configs.len());
+    for (_, gsea_config) in configs.iter() {
+        let start = Instant::now();
+        let permutations =
+            gsea_stats::make_permutations(gsea_config.permutations, rank_list.len(), seed);
+        let res = config::install(py, || {
+            gsea_stats::gsea(&rank_list, &gmt_vec, gsea_config, &permutations)
+        })?;
+        runs.push((res, start.elapsed().as_secs_f64()));
+    }
+    // every run has a result for every set, in GMT order
+    let tested = |res: &[GSEAResult]| -> Vec<bool> {
+        res.iter().map(|x| !x.running_sum.is_empty()).collect()
+    };
+    let significant = |res: &[GSEAResult]| -> FxHashSet<usize> {
+        (0..res.len())
+            .filter(|i| !res[*i].running_sum.is_empty() && res[*i].fdr < fdr)
+            .collect()
+    };
+    let reference_res = &runs[reference].0;
+    let reference_tested = tested(reference_res);
+    let reference_significant = significant(reference_res);
+    configs
+        .iter()
+        .zip(runs.iter())
+        .enumerate()
+        .map(|(i, ((name, gsea_config), (res, seconds)))| {
+            let run_tested = tested(res);
+            let shared: Vec<usize> = (0..res.len())
+                .filter(|x| run_tested[*x] && reference_tested[*x])
+                .collect();
+            let nes: Vec<f64> = shared.iter().map(|x| res[*x].nes).collect();
+            let reference_nes: Vec<f64> = shared.iter().map(|x| reference_res[*x].nes).collect();
+            let max_fdr_difference = shared
+                .iter()
+                .map(|x| (res[*x].fdr - reference_res[*x].fdr).abs())
+                .reduce(f64::max);
+            let run_significant = significant(res);
+            let dict = PyDict::new(py);
+            dict.set_item("name", name)?;
+            dict.set_item("permutations", gsea_config.permutations)?;
+            dict.set_item("min_overlap", gsea_config.min_overlap)?;
+            dict.set_item("max_overlap", gsea_config.max_overlap)?;
+            dict.set_item("p", gsea_config.p)?;
+            dict.set_item("seconds", seconds)?;
+            dict.set_item("tested", run_tested.iter().filter(|x| **x).count())?;
+            dict.set_item("significant", run_significant.len())?;
+            dict.set_item("spearman", spearman(&nes, &reference_nes))?;
+            dict.set_item("jaccard", jaccard(&run_significant, &reference_significant))?;
+            dict.set_item("max_fdr_difference", max_fdr_difference)?;
+            dict.set_item("is_reference", i == reference)?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
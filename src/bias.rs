@@ -0,0 +1,297 @@
+use ahash::AHashSet;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use statrs::function::factorial::ln_binomial;
+use webgestalt_lib::readers::utils::Item;
+
+/// Number of bins of the bias covariate used to estimate the probability weighting function
+const BIAS_BINS: usize = 20;
+
+/// Pool-adjacent-violators fit of a non-decreasing sequence to `values`, weighted by `weights`
+fn isotonic(values: &[f64], weights: &[f64]) -> Vec<f64> {
+    // (mean, weight, number of values) of each pooled block
+    let mut blocks: Vec<(f64, f64, usize)> = Vec::new();
+    for (value, weight) in values.iter().zip(weights) {
+        blocks.push((*value, *weight, 1));
+        while blocks.len() > 1 && blocks[blocks.len() - 2].0 > blocks[blocks.len() - 1].0 {
+            let (b_mean, b_weight, b_count) = blocks.pop().unwrap();
+            let a = blocks.last_mut().unwrap();
+            let total = a.1 + b_weight;
+            a.0 = (a.0 * a.1 + b_mean * b_weight) / total;
+            a.1 = total;
+            a.2 += b_count;
+        }
+    }
+    blocks
+        .into_iter()
+        .flat_map(|(mean, _, count)| std::iter::repeat_n(mean, count))
+        .collect()
+}
+
+/// Number of bins and the bin of each of `genes`, sorted by increasing bias, into bins of equal size. Genes with the same bias are
+/// kept in the same bin.
+fn sorted_bins(genes: &[&String], bias: &FxHashMap<String, f64>) -> (usize, Vec<usize>) {
+    let bins = BIAS_BINS.min(genes.len()).max(1);
+    let mut gene_bins: Vec<usize> = Vec::with_capacity(genes.len());
+    for (i, gene) in genes.iter().enumerate() {
+        let bin = match i.checked_sub(1) {
+            Some(j) if bias[genes[j]] == bias[*gene] => gene_bins[j],
+            _ => i * bins / genes.len(),
+        };
+        gene_bins.push(bin);
+    }
+    (bins, gene_bins)
+}
+
+/// Bins of `genes` by bias, like those of [`probability_weights`]: the genes of each bin, in the order of `genes`
+pub fn bias_bins(genes: &[&String], bias: &FxHashMap<String, f64>) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..genes.len()).collect();
+    order.sort_by(|a, b| {
+        bias[genes[*a]]
+            .partial_cmp(&bias[genes[*b]])
+            .unwrap()
+            .then(genes[*a].cmp(genes[*b]))
+    });
+    let sorted: Vec<&String> = order.iter().map(|i| genes[*i]).collect();
+    let (bins, gene_bins) = sorted_bins(&sorted, bias);
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); bins];
+    for (i, bin) in order.into_iter().zip(gene_bins) {
+        members[bin].push(i);
+    }
+    for bin in members.iter_mut() {
+        bin.sort_unstable();
+    }
+    members
+}
+
+/// Probability weighting function like [goseq](https://doi.org/10.1186/gb-2010-11-2-r14): the probability
+/// of each gene of `reference` being in `analyte_list` given its `bias`.
+///
+/// Genes are split into bins of equal size by their bias, and the fraction of genes in the list in each bin
+/// is fit with a monotonic function, either increasing or decreasing, whichever fits better.
+pub fn probability_weights(
+    analyte_list: &AHashSet<String>,
+    reference: &[String],
+    bias: &FxHashMap<String, f64>,
+) -> FxHashMap<String, f64> {
+    let mut genes: Vec<&String> = reference.iter().collect();
+    genes.sort_by(|a, b| bias[*a].partial_cmp(&bias[*b]).unwrap().then(a.cmp(b)));
+    let (bins, gene_bins) = sorted_bins(&genes, bias);
+    let mut counts = vec![0.0; bins];
+    let mut hits = vec![0.0; bins];
+    for (gene, bin) in genes.iter().zip(&gene_bins) {
+        counts[*bin] += 1.0;
+        if analyte_list.contains(*gene) {
+            hits[*bin] += 1.0;
+        }
+    }
+    // half a pseudo-count keeps every weight above zero, since a weight of zero would make any overlap impossible
+    let fractions: Vec<f64> = hits
+        .iter()
+        .zip(&counts)
+        .map(|(h, c)| (h + 0.5) / (c + 1.0))
+        .collect();
+    let increasing = isotonic(&fractions, &counts);
+    let reversed: Vec<f64> = fractions.iter().rev().map(|x| -x).collect();
+    let reversed_counts: Vec<f64> = counts.iter().rev().copied().collect();
+    let decreasing: Vec<f64> = isotonic(&reversed, &reversed_counts)
+        .into_iter()
+        .rev()
+        .map(|x| -x)
+        .collect();
+    let error = |fit: &[f64]| -> f64 {
+        fit.iter()
+            .zip(&fractions)
+            .zip(&counts)
+            .map(|((f, x), c)| c * (f - x).powi(2))
+            .sum()
+    };
+    let fit = if error(&decreasing) < error(&increasing) {
+        decreasing
+    } else {
+        increasing
+    };
+    genes
+        .into_iter()
+        .zip(gene_bins)
+        .map(|(gene, bin)| (gene.clone(), fit[bin]))
+        .collect()
+}
+
+/// `ln(1 - e^(-a))` for `a > 0`
+fn ln_one_minus_exp(a: f64) -> f64 {
+    (-(-a).exp_m1()).ln()
+}
+
+/// Natural log of the probability mass of Wallenius' noncentral hypergeometric distribution at `x`, for `n` draws
+/// from `m1` items of weight `odds` and `m2` items of weight `1`. Not normalized, since the integral is approximated.
+fn wallenius_ln_pmf(x: u64, m1: u64, m2: u64, n: u64, odds: f64) -> f64 {
+    let d = odds * (m1 - x) as f64 + (m2 - (n - x)) as f64;
+    let (x, y) = (x as f64, (n - x) as f64);
+    // integral of (1 - t^(odds/d))^x (1 - t^(1/d))^y over [0, 1], with t = e^(-d z)
+    let g = |z: f64| {
+        let mut value = d.ln() - d * z;
+        if x > 0.0 {
+            value += x * ln_one_minus_exp(odds * z);
+        }
+        if y > 0.0 {
+            value += y * ln_one_minus_exp(z);
+        }
+        value
+    };
+    // g is concave, so its maximum is where the derivative changes sign
+    let slope = |z: f64| {
+        let mut value = -d;
+        if x > 0.0 {
+            value += x * odds / (odds * z).exp_m1();
+        }
+        if y > 0.0 {
+            value += y / z.exp_m1();
+        }
+        value
+    };
+    let mut high = 1.0;
+    while slope(high) > 0.0 {
+        high *= 2.0;
+    }
+    let mut low = 0.0;
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if slope(mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let mode = (low + high) / 2.0;
+    let peak = g(mode);
+    // integrate where the integrand is within e^-40 of its maximum
+    let cutoff = peak - 40.0;
+    let mut start = 0.0;
+    let mut inside = mode;
+    for _ in 0..100 {
+        let mid = (start + inside) / 2.0;
+        if g(mid) < cutoff {
+            start = mid;
+        } else {
+            inside = mid;
+        }
+    }
+    let mut end = mode + (mode - start).max(f64::EPSILON);
+    while g(end) > cutoff {
+        end += end - mode;
+    }
+    const STEPS: usize = 512;
+    let h = (end - start) / STEPS as f64;
+    let integrand = |z: f64| {
+        if z <= 0.0 && x + y > 0.0 {
+            0.0
+        } else {
+            (g(z) - peak).exp()
+        }
+    };
+    let mut sum = integrand(start) + integrand(end);
+    for i in 1..STEPS {
+        let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+        sum += weight * integrand(start + i as f64 * h);
+    }
+    ln_binomial(m1, x as u64) + ln_binomial(m2, y as u64) + peak + (sum * h / 3.0).ln()
+}
+
+/// Probability of an overlap of at least `k` under Wallenius' noncentral hypergeometric distribution,
+/// for `n` draws from `m1` items of weight `odds` and `m2` items of weight `1`
+pub fn wallenius_sf(k: u64, m1: u64, m2: u64, n: u64, odds: f64) -> f64 {
+    let lowest = n.saturating_sub(m2);
+    let highest = n.min(m1);
+    if k <= lowest {
+        return 1.0;
+    }
+    if k > highest {
+        return 0.0;
+    }
+    let ln_pmf: Vec<f64> = (lowest..=highest)
+        .map(|x| wallenius_ln_pmf(x, m1, m2, n, odds))
+        .collect();
+    let max = ln_pmf.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let total: f64 = ln_pmf.iter().map(|x| (x - max).exp()).sum();
+    let tail: f64 = ln_pmf[(k - lowest) as usize..]
+        .iter()
+        .map(|x| (x - max).exp())
+        .sum();
+    (tail / total).min(1.0)
+}
+
+/// Bias-corrected p-value and odds of each set, in the order of `gmt`. The odds of a set is the mean
+/// probability weight of its genes over the mean weight of the other genes of the reference.
+pub fn bias_corrected_p(
+    gmt: &[Item],
+    analyte_list: &AHashSet<String>,
+    weights: &FxHashMap<String, f64>,
+) -> Vec<(f64, f64)> {
+    let total_weight: f64 = weights.values().sum();
+    let reference_size = weights.len() as u64;
+    let list_size = analyte_list.len() as u64;
+    gmt.par_iter()
+        .map(|set| {
+            let genes: AHashSet<&String> = set
+                .parts
+                .iter()
+                .filter(|x| weights.contains_key(*x))
+                .collect();
+            let set_size = genes.len() as u64;
+            let overlap = genes.iter().filter(|x| analyte_list.contains(**x)).count() as u64;
+            if set_size == 0 || set_size == reference_size {
+                return (1.0, 1.0);
+            }
+            let set_weight: f64 = genes.iter().map(|x| weights[*x]).sum();
+            let odds = (set_weight / set_size as f64)
+                / ((total_weight - set_weight) / (reference_size - set_size) as f64);
+            let p = if overlap == 0 {
+                1.0
+            } else {
+                wallenius_sf(
+                    overlap,
+                    set_size,
+                    reference_size - set_size,
+                    list_size,
+                    odds,
+                )
+            };
+            (p, odds)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Survival function of the central hypergeometric distribution, which Wallenius' distribution is with an odds of 1
+    fn hypergeometric_sf(k: u64, m1: u64, m2: u64, n: u64) -> f64 {
+        let ln_total = ln_binomial(m1 + m2, n);
+        (k..=n.min(m1))
+            .filter(|x| n - x <= m2)
+            .map(|x| (ln_binomial(m1, x) + ln_binomial(m2, n - x) - ln_total).exp())
+            .sum()
+    }
+
+    #[test]
+    fn wallenius_sf_without_bias_is_hypergeometric() {
+        for (k, m1, m2, n) in [(3, 20, 80, 10), (1, 5, 50, 12), (8, 30, 30, 15)] {
+            let expected = hypergeometric_sf(k, m1, m2, n);
+            assert!((wallenius_sf(k, m1, m2, n, 1.0) - expected).abs() < 1e-6 * expected.max(1e-3));
+        }
+    }
+
+    #[test]
+    fn wallenius_sf_bounds_and_odds() {
+        assert_eq!(wallenius_sf(0, 20, 80, 10, 2.0), 1.0);
+        assert_eq!(wallenius_sf(11, 20, 80, 10, 2.0), 0.0);
+        // items of more weight are drawn more often
+        let sf: Vec<f64> = [0.5, 1.0, 2.0, 4.0]
+            .iter()
+            .map(|odds| wallenius_sf(4, 20, 80, 10, *odds))
+            .collect();
+        assert!(sf.windows(2).all(|x| x[0] < x[1]));
+    }
+}
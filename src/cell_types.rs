@@ -0,0 +1,251 @@
+use crate::background::expression_matrix;
+use crate::meta::average_ranks;
+use crate::{config, inputs};
+use ahash::AHashMap;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+
+/// How a cell type is scored from the expression of its markers in a sample
+#[derive(Clone, Copy)]
+enum CellTypeMethod {
+    /// Single-sample GSEA, a random walk over the analytes of the sample ranked by expression
+    SsGsea,
+    /// Mean of the z-scores of the markers across samples, weighted by the weights of the markers
+    WeightedMean,
+}
+
+impl CellTypeMethod {
+    fn parse(name: &str) -> PyResult<CellTypeMethod> {
+        match name {
+            "ssgsea" => Ok(CellTypeMethod::SsGsea),
+            "weighted_mean" => Ok(CellTypeMethod::WeightedMean),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown method '{}'. Expected 'ssgsea' or 'weighted_mean'.",
+                name
+            ))),
+        }
+    }
+}
+
+/// ssGSEA score of each cell type in a sample, from the expression `values` of every analyte (`NaN` if missing) and the markers of
+/// each cell type as the rows of their analytes with their weights
+fn ssgsea_scores(
+    values: &[f64],
+    markers: &[Vec<(usize, f64)>],
+    alpha: f64,
+    min_size: usize,
+) -> Vec<Option<f64>> {
+    let measured: Vec<usize> = (0..values.len()).filter(|i| !values[*i].is_nan()).collect();
+    let ranks = average_ranks(&measured.iter().map(|i| values[*i]).collect::<Vec<f64>>());
+    let mut rank_of = vec![f64::NAN; values.len()];
+    for (i, rank) in measured.iter().zip(ranks) {
+        rank_of[*i] = rank;
+    }
+    // decreasing expression, with ties in the order of the matrix
+    let mut order = measured.clone();
+    order.sort_by(|a, b| values[*b].total_cmp(&values[*a]));
+    let n = order.len();
+    let mut in_set = vec![0.0; values.len()];
+    markers
+        .iter()
+        .map(|set| {
+            let present: Vec<&(usize, f64)> =
+                set.iter().filter(|(i, _)| !values[*i].is_nan()).collect();
+            if present.len() < min_size.max(1) || present.len() == n {
+                return None;
+            }
+            for (i, w) in present.iter() {
+                in_set[*i] = *w;
+            }
+            let total: f64 = present
+                .iter()
+                .map(|(i, w)| w * rank_of[*i].powf(alpha))
+                .sum();
+            let miss_step = 1.0 / (n - present.len()) as f64;
+            let (mut hits, mut misses, mut score) = (0.0, 0.0, 0.0);
+            for i in order.iter() {
+                if in_set[*i] != 0.0 {
+                    hits += in_set[*i] * rank_of[*i].powf(alpha) / total;
+                } else {
+                    misses += miss_step;
+                }
+                score += hits - misses;
+            }
+            for (i, _) in present.iter() {
+                in_set[*i] = 0.0;
+            }
+            Some(score)
+        })
+        .collect()
+}
+
+/// z-score of each value of each row across samples, ignoring missing values. Rows without variance have z-scores of `0`.
+fn z_scores(rows: &[&Vec<f64>]) -> Vec<Vec<f64>> {
+    rows.iter()
+        .map(|values| {
+            let measured: Vec<f64> = values.iter().filter(|x| !x.is_nan()).cloned().collect();
+            let mean = measured.iter().sum::<f64>() / measured.len().max(1) as f64;
+            let sd = if measured.len() > 1 {
+                (measured.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+                    / (measured.len() - 1) as f64)
+                    .sqrt()
+            } else {
+                0.0
+            };
+            values
+                .iter()
+                .map(|x| {
+                    if x.is_nan() {
+                        f64::NAN
+                    } else if sd > 0.0 {
+                        (x - mean) / sd
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Score the cell types of every sample of an expression matrix from the expression of their markers, like for the deconvolution of
+/// immune infiltration.
+///
+/// With `method="ssgsea"`, the score is the single-sample GSEA of [Barbie et al. 2009](https://doi.org/10.1038/nature08460), like `GSVA` with
+/// `method="ssgsea"`: the analytes of each sample are ranked by expression, and the score is the sum of the running difference between the
+/// markers, stepping by their rank to the power of `alpha` times their weight, and the other analytes. Tied expression values share their
+/// average rank. With `normalize=True`, the scores are divided by the range of every score of the matrix, like `ssgsea.norm` of `GSVA`.
+///
+/// With `method="weighted_mean"`, the expression of each analyte is converted to z-scores across samples, and the score is the mean of the
+/// z-scores of the markers, weighted by their weights. Scores are comparable between cell types, but depend on the other samples of the matrix.
+///
+/// # Parameters
+/// - `expression` - The expression matrix in any form accepted by [`expressed_background`](./expressed_background.md), with an analyte on each row
+///   and a sample on each column. Samples of a dictionary are named by their position, starting at `"1"`.
+/// - `markers` - The markers of each cell type as a weighted GMT in any form accepted by [`weighted_gmt_ora`](./weighted_gmt_ora.md). Markers
+///   without weights have a weight of `1`.
+/// - `method` - Either `"ssgsea"` or `"weighted_mean"` (default: `"ssgsea"`)
+/// - `alpha` - Exponent of the ranks of the markers for `method="ssgsea"` (default: `0.25`)
+/// - `normalize` - If `True`, ssGSEA scores are divided by the range of the scores of the matrix (default: `True`)
+/// - `min_size` - Minimum number of markers measured in a sample for a cell type to be scored (default: `1`)
+///
+/// Missing values (empty, `NA`, or `NaN`) are left out of their sample. Analytes listed more than once in the matrix keep their first row.
+///
+/// # Returns
+///
+/// Returns a dictionary with the row labels in `samples`, the column labels in `cell_types` (in the order of `markers`), the number of markers
+/// of each cell type in the matrix in `sizes`, and the `matrix` of scores as a list of rows, where `matrix[i][j]` is the score of `cell_types[j]`
+/// in `samples[i]`. Scores are `None` for cell types with fewer than `min_size` markers measured in the sample.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, if `method` is unknown, or if a weight is not positive.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// markers = {"T cell": {"CD3E": 2.5, "CD3D": 1.8, "CD2": 0.6}, "B cell": ["CD19", "MS4A1", "CD79A"]}
+/// scores = webgestaltpy.score_cell_types("expression.tsv", markers)
+/// for sample, row in zip(scores["samples"], scores["matrix"]):
+///     print(sample, dict(zip(scores["cell_types"], row)))
+/// ```
+#[pyfunction]
+#[pyo3(signature = (expression, markers, method="ssgsea", alpha=0.25, normalize=true, min_size=1))]
+pub fn score_cell_types<'a>(
+    py: Python<'a>,
+    expression: &Bound<'_, PyAny>,
+    markers: &Bound<'_, PyAny>,
+    method: &str,
+    alpha: f64,
+    normalize: bool,
+    min_size: usize,
+) -> PyResult<Bound<'a, PyDict>> {
+    let method = CellTypeMethod::parse(method)?;
+    let gmt = inputs::weighted_gmt(markers)?;
+    gmt.check_positive()?;
+    let matrix = expression_matrix(expression)?;
+    let mut rows: AHashMap<&String, usize> = AHashMap::default();
+    for (i, (analyte, _)) in matrix.rows.iter().enumerate() {
+        rows.entry(analyte).or_insert(i);
+    }
+    let mut kept: Vec<usize> = rows.values().copied().collect();
+    kept.sort_unstable();
+    let position: AHashMap<usize, usize> = kept.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+    let set_markers: Vec<Vec<(usize, f64)>> = gmt
+        .items
+        .iter()
+        .zip(gmt.weights.iter())
+        .map(|(item, weights)| {
+            item.parts
+                .iter()
+                .zip(weights.iter())
+                .filter_map(|(x, w)| rows.get(x).map(|row| (position[row], *w)))
+                .collect()
+        })
+        .collect();
+    let values: Vec<&Vec<f64>> = kept.iter().map(|i| &matrix.rows[*i].1).collect();
+    let value = |row: &Vec<f64>, sample: usize| row.get(sample).copied().unwrap_or(f64::NAN);
+    let mut scores: Vec<Vec<Option<f64>>> = config::install(py, || match method {
+        CellTypeMethod::SsGsea => (0..matrix.samples.len())
+            .into_par_iter()
+            .map(|sample| {
+                let sample_values: Vec<f64> = values.iter().map(|x| value(x, sample)).collect();
+                ssgsea_scores(&sample_values, &set_markers, alpha, min_size)
+            })
+            .collect(),
+        CellTypeMethod::WeightedMean => {
+            let z = z_scores(&values);
+            (0..matrix.samples.len())
+                .into_par_iter()
+                .map(|sample| {
+                    set_markers
+                        .iter()
+                        .map(|set| {
+                            let present: Vec<(f64, f64)> = set
+                                .iter()
+                                .map(|(i, w)| (value(&z[*i], sample), *w))
+                                .filter(|(x, _)| !x.is_nan())
+                                .collect();
+                            if present.len() < min_size.max(1) {
+                                return None;
+                            }
+                            Some(
+                                present.iter().map(|(x, w)| x * w).sum::<f64>()
+                                    / present.iter().map(|(_, w)| w).sum::<f64>(),
+                            )
+                        })
+                        .collect()
+                })
+                .collect()
+        }
+    })?;
+    if normalize && matches!(method, CellTypeMethod::SsGsea) {
+        let all = scores.iter().flatten().flatten();
+        let range = all.clone().fold(f64::NEG_INFINITY, |a, b| a.max(*b))
+            - all.fold(f64::INFINITY, |a, b| a.min(*b));
+        if range > 0.0 {
+            for score in scores.iter_mut().flatten().flatten() {
+                *score /= range;
+            }
+        }
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("samples", matrix.samples)?;
+    dict.set_item(
+        "cell_types",
+        gmt.items
+            .iter()
+            .map(|x| x.id.clone())
+            .collect::<Vec<String>>(),
+    )?;
+    dict.set_item(
+        "sizes",
+        set_markers.iter().map(|x| x.len()).collect::<Vec<usize>>(),
+    )?;
+    dict.set_item("matrix", scores)?;
+    Ok(dict)
+}
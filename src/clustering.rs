@@ -0,0 +1,300 @@
+use crate::compare::jaccard;
+use crate::inputs;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// A significant set of a list of results, with its analytes from the GMT
+pub struct SignificantSet {
+    pub set: String,
+    pub p: f64,
+    pub fdr: f64,
+    pub url: String,
+    pub analytes: Vec<String>,
+}
+
+/// Gets the sets of `results` with an FDR below `fdr`, ordered by FDR and then p-value.
+///
+/// Raises an error if a significant set is not in `gmt`.
+pub fn significant_sets(
+    results: &[Bound<'_, PyDict>],
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+) -> PyResult<Vec<SignificantSet>> {
+    let items = inputs::gmt(gmt)?;
+    let index: FxHashMap<&String, usize> =
+        items.iter().enumerate().map(|(i, x)| (&x.id, i)).collect();
+    let mut sets: Vec<SignificantSet> = Vec::new();
+    for row in results {
+        let get = |key: &str| {
+            row.get_item(key)?
+                .ok_or_else(|| PyValueError::new_err(format!("Result is missing '{}'!", key)))
+        };
+        let row_fdr: f64 = get("fdr")?.extract()?;
+        // a missing (NaN) FDR is not significant
+        if row_fdr.is_nan() || row_fdr >= fdr {
+            continue;
+        }
+        let set: String = get("set")?.extract()?;
+        let item = index
+            .get(&set)
+            .map(|i| &items[*i])
+            .ok_or_else(|| PyValueError::new_err(format!("Set '{}' not found in GMT!", set)))?;
+        sets.push(SignificantSet {
+            p: get("p")?.extract()?,
+            fdr: row_fdr,
+            url: item.url.clone(),
+            analytes: item.parts.clone(),
+            set,
+        });
+    }
+    sets.sort_by(|a, b| a.fdr.total_cmp(&b.fdr).then(a.p.total_cmp(&b.p)));
+    Ok(sets)
+}
+
+/// Cohen's kappa of the membership of two sets over `total` analytes
+fn kappa(a: &FxHashSet<&String>, b: &FxHashSet<&String>, total: usize) -> f64 {
+    let n = total as f64;
+    let both = a.intersection(b).count() as f64;
+    let only_a = a.len() as f64 - both;
+    let only_b = b.len() as f64 - both;
+    let neither = n - both - only_a - only_b;
+    let observed = (both + neither) / n;
+    let expected =
+        ((both + only_a) * (both + only_b) + (only_b + neither) * (only_a + neither)) / (n * n);
+    if expected == 1.0 {
+        1.0
+    } else {
+        (observed - expected) / (1.0 - expected)
+    }
+}
+
+/// Cluster the significant sets of an enrichment analysis by their shared analytes, like the functional annotation clustering of DAVID.
+///
+/// The similarity of two sets is the kappa statistic of their membership over all analytes in the significant sets.
+/// Each set with at least `min_size - 1` similar sets (kappa of at least `kappa`), where most pairs in the group are also similar, seeds a group.
+/// Groups that share more than `linkage` of the members of the smaller group are merged until no groups can be merged.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+/// - `gmt` - `String` of the path to the GMT file used for the analysis, or a [`Gmt`](./Gmt.md) object
+/// - `fdr` - FDR threshold for a set to be clustered (default: `0.05`)
+/// - `kappa` - Minimum kappa for two sets to be similar (default: `0.35`)
+/// - `min_size` - Minimum number of sets in a cluster (default: `3`)
+/// - `linkage` - Share of members two groups need in common to be merged (default: `0.5`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each cluster, ordered by `enrichment_score`. Each cluster has:
+///
+/// - `representative` - The set in the cluster with the lowest FDR
+/// - `sets` - The sets in the cluster, ordered by FDR
+/// - `enrichment_score` - The mean of the `-log10(p)` of the sets in the cluster
+///
+/// Significant sets not similar to enough other sets are not in any cluster, and a set can be in more than one cluster.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// clusters = webgestaltpy.kappa_clusters(res, "data/kegg.gmt", kappa=0.3)
+/// print([x["representative"] for x in clusters])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, fdr=0.05, kappa=0.35, min_size=3, linkage=0.5))]
+pub fn kappa_clusters<'a>(
+    py: Python<'a>,
+    results: Vec<Bound<'_, PyDict>>,
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+    kappa: f64,
+    min_size: usize,
+    linkage: f64,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let sets = significant_sets(&results, gmt, fdr)?;
+    let members: Vec<FxHashSet<&String>> =
+        sets.iter().map(|x| x.analytes.iter().collect()).collect();
+    let total = members.iter().flatten().collect::<FxHashSet<_>>().len();
+    let n = sets.len();
+    let mut similar = vec![vec![false; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let k = self::kappa(&members[i], &members[j], total);
+            similar[i][j] = k >= kappa;
+            similar[j][i] = k >= kappa;
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, row) in similar.iter().enumerate() {
+        let group: Vec<usize> = (0..n).filter(|j| *j == i || row[*j]).collect();
+        if group.len() < min_size.max(2) || groups.contains(&group) {
+            continue;
+        }
+        let mut pairs = 0;
+        let mut similar_pairs = 0;
+        for (x, a) in group.iter().enumerate() {
+            for b in group.iter().skip(x + 1) {
+                pairs += 1;
+                if similar[*a][*b] {
+                    similar_pairs += 1;
+                }
+            }
+        }
+        if similar_pairs as f64 / pairs as f64 > 0.5 {
+            groups.push(group);
+        }
+    }
+    // merge groups until none share enough members
+    let mut merged = true;
+    while merged {
+        merged = false;
+        'outer: for a in 0..groups.len() {
+            for b in (a + 1)..groups.len() {
+                let shared = groups[a].iter().filter(|x| groups[b].contains(x)).count();
+                let smaller = groups[a].len().min(groups[b].len());
+                if shared as f64 > linkage * smaller as f64 {
+                    let other = groups.remove(b);
+                    for x in other {
+                        if !groups[a].contains(&x) {
+                            groups[a].push(x);
+                        }
+                    }
+                    groups[a].sort();
+                    merged = true;
+                    break 'outer;
+                }
+            }
+        }
+    }
+    let mut clusters: Vec<(f64, Vec<usize>)> = groups
+        .into_iter()
+        .map(|group| {
+            let score = group
+                .iter()
+                .map(|x| -sets[*x].p.max(f64::MIN_POSITIVE).log10())
+                .sum::<f64>()
+                / group.len() as f64;
+            (score, group)
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1[0].cmp(&b.1[0])));
+    let mut res: Vec<Bound<'_, PyDict>> = Vec::new();
+    for (score, group) in clusters {
+        let names: Vec<&String> = group.iter().map(|x| &sets[*x].set).collect();
+        let dict = PyDict::new(py);
+        dict.set_item("representative", names[0])?;
+        dict.set_item("sets", names)?;
+        dict.set_item("enrichment_score", score)?;
+        res.push(dict);
+    }
+    Ok(res)
+}
+
+/// Select the top sets of an enrichment analysis while avoiding sets that share most of their analytes, for a summary table
+/// without several versions of the same pathway.
+///
+/// Sets are picked one at a time by maximal marginal relevance. The relevance of a set is its `-log10(p)`, divided by the largest
+/// among the significant sets so it is between `0` and `1`. Its redundancy is its largest Jaccard index with an already selected set.
+/// Each step picks the set with the highest `(1 - diversity) * relevance - diversity * redundancy`, with ties going to the lower FDR.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+/// - `gmt` - `String` of the path to the GMT file used for the analysis, or a [`Gmt`](./Gmt.md) object
+/// - `n` - Number of sets to select (default: `10`)
+/// - `diversity` - Weight of the redundancy, between `0` and `1`. `0` selects the sets with the lowest p-values, and larger values
+///   select sets that share fewer analytes (default: `0.5`)
+/// - `fdr` - FDR threshold for a set to be selected (default: `0.05`)
+///
+/// # Returns
+///
+/// Returns a list of at most `n` result dictionaries, in the order they were selected. Each is a copy of the row of `results` with:
+///
+/// - `selection_rank` - Position of the set in the selection, starting at `1`
+/// - `mmr_score` - Score of the set when it was selected
+/// - `most_similar` - The previously selected set sharing the most analytes with the set (`None` if it shares none, like for the first set)
+/// - `max_jaccard` - Jaccard index of the analytes of the set and of `most_similar` (`0` if it shares none)
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `diversity` is not between `0` and `1`, or if a significant set is not in `gmt`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// top = webgestaltpy.select_top(res, "data/kegg.gmt", n=5, diversity=0.5, fdr=0.2)
+/// for x in top:
+///     print(x["set"], x["fdr"], x["most_similar"], x["max_jaccard"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, n=10, diversity=0.5, fdr=0.05))]
+pub fn select_top<'a>(
+    results: Vec<Bound<'a, PyDict>>,
+    gmt: &Bound<'_, PyAny>,
+    n: usize,
+    diversity: f64,
+    fdr: f64,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if !(0.0..=1.0).contains(&diversity) {
+        return Err(PyValueError::new_err("diversity must be between 0 and 1!"));
+    }
+    let sets = significant_sets(&results, gmt, fdr)?;
+    let mut rows: FxHashMap<String, Bound<'_, PyDict>> = FxHashMap::default();
+    for row in results.iter() {
+        if let Some(set) = row.get_item("set")? {
+            rows.entry(set.extract()?).or_insert(row.clone());
+        }
+    }
+    let members: Vec<FxHashSet<&String>> =
+        sets.iter().map(|x| x.analytes.iter().collect()).collect();
+    let scores: Vec<f64> = sets
+        .iter()
+        .map(|x| -x.p.max(f64::MIN_POSITIVE).log10())
+        .collect();
+    let max_score = scores.iter().copied().fold(0.0, f64::max);
+    let relevance: Vec<f64> = scores
+        .iter()
+        .map(|x| if max_score > 0.0 { x / max_score } else { 0.0 })
+        .collect();
+    // largest Jaccard index of each set with a selected set, and that set
+    let mut redundancy: Vec<(f64, Option<usize>)> = vec![(0.0, None); sets.len()];
+    let mut selected: Vec<bool> = vec![false; sets.len()];
+    let mut res: Vec<Bound<'_, PyDict>> = Vec::new();
+    while res.len() < n {
+        // sets are ordered by FDR, so the first of tied sets has the lower FDR
+        let best = (0..sets.len())
+            .filter(|i| !selected[*i])
+            .map(|i| {
+                (
+                    i,
+                    (1.0 - diversity) * relevance[i] - diversity * redundancy[i].0,
+                )
+            })
+            .fold(None, |best: Option<(usize, f64)>, x| match best {
+                Some(b) if b.1 >= x.1 => Some(b),
+                _ => Some(x),
+            });
+        let Some((i, score)) = best else {
+            break;
+        };
+        selected[i] = true;
+        let row = rows[&sets[i].set].copy()?;
+        row.set_item("selection_rank", res.len() + 1)?;
+        row.set_item("mmr_score", score)?;
+        row.set_item("most_similar", redundancy[i].1.map(|x| &sets[x].set))?;
+        row.set_item("max_jaccard", redundancy[i].0)?;
+        res.push(row);
+        for j in 0..sets.len() {
+            let similarity = jaccard(&members[i], &members[j]).unwrap_or(0.0);
+            if !selected[j] && similarity > redundancy[j].0 {
+                redundancy[j] = (similarity, Some(i));
+            }
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,159 @@
+use crate::meta::average_ranks;
+use crate::{config, gsea_stats, inputs};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::methods::gsea::RankListItem;
+use webgestalt_lib::methods::ora::get_ora;
+
+/// Analytes of `rank_list` kept for ORA, either those with an absolute score of at least `threshold`,
+/// or the `top` analytes with the largest absolute scores
+fn thresholded_list(
+    rank_list: &[RankListItem],
+    threshold: Option<f64>,
+    top: Option<usize>,
+) -> PyResult<Vec<String>> {
+    match (threshold, top) {
+        (Some(threshold), None) => Ok(rank_list
+            .iter()
+            .filter(|x| x.rank.abs() >= threshold)
+            .map(|x| x.analyte.clone())
+            .collect()),
+        (None, Some(top)) => {
+            let mut sorted: Vec<&RankListItem> = rank_list.iter().collect();
+            sorted.sort_by(|a, b| b.rank.abs().total_cmp(&a.rank.abs()));
+            Ok(sorted
+                .into_iter()
+                .take(top)
+                .map(|x| x.analyte.clone())
+                .collect())
+        }
+        (Some(_), Some(_)) => Err(PyValueError::new_err("threshold can not be used with top!")),
+        (None, None) => Err(PyValueError::new_err(
+            "Either threshold or top must be provided!",
+        )),
+    }
+}
+
+/// Run ORA and GSEA on the same rank list, and merge the results of each set with a combined rank.
+///
+/// GSEA runs on the full rank list. ORA runs on the analytes selected by `threshold` or `top`, against every analyte of the rank list
+/// as the reference. Both directions are selected, since the absolute score is used.
+///
+/// Sets are ranked by their p-value in each analysis, with tied sets sharing their average rank and sets not tested by an analysis
+/// ranked last. The combined rank orders the sets by the mean of their two ranks.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_list` - The rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `threshold` - If provided, ORA uses the analytes with an absolute score of at least `threshold` (default: `None`)
+/// - `top` - If provided, ORA uses the `top` analytes with the largest absolute scores (default: `None`)
+/// - `seed` - Seed for the random permutations of GSEA. If not provided, a new seed is generated (default: `None`)
+///
+/// Exactly one of `threshold` or `top` must be provided.
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set, sorted by `combined_rank`, with:
+///
+/// - `set` - Name of the set
+/// - `ora_p`, `ora_fdr`, `overlap`, `enrichment_ratio` - ORA results of the set (`None` if the set was not tested)
+/// - `gsea_p`, `gsea_fdr`, `nes` - GSEA results of the set (`None` if the set was not tested)
+/// - `ora_rank`, `gsea_rank` - Rank of the set in each analysis, with `1` as the most significant
+/// - `combined_rank` - Rank of the mean of `ora_rank` and `gsea_rank`, with tied sets sharing their average rank
+/// - `seed` - Seed used for the permutations
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, or if neither or both of `threshold` and `top` are provided.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora_gsea("data/kegg.gmt", "data/test.rnk", top=200)
+/// for x in res[:5]:
+///     print(x["set"], x["ora_fdr"], x["gsea_fdr"], x["combined_rank"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_list, threshold=None, top=None, seed=None))]
+pub fn ora_gsea<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_list: &Bound<'_, PyAny>,
+    threshold: Option<f64>,
+    top: Option<usize>,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let gmt_vec = inputs::gmt(gmt)?;
+    let rank_list = inputs::rank_list(rank_list)?;
+    let analyte_list = thresholded_list(&rank_list, threshold, top)?;
+    let reference_list: Vec<String> = rank_list.iter().map(|x| x.analyte.clone()).collect();
+    let (ora_gmt, analytes, reference) =
+        inputs::filter_ora_inputs(gmt_vec.clone(), analyte_list, reference_list);
+    let ora_res = config::install(py, || {
+        get_ora(&analytes, &reference, ora_gmt, config::ora_config())
+    })?;
+    let gsea_config = config::gsea_config();
+    let seed = gsea_stats::seed_or_random(seed);
+    let permutations =
+        gsea_stats::make_permutations(gsea_config.permutations, rank_list.len(), seed);
+    let gsea_res = config::install(py, || {
+        gsea_stats::gsea(&rank_list, &gmt_vec, &gsea_config, &permutations)
+    })?;
+    let ora: FxHashMap<&String, usize> = ora_res
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.expected > 0.0)
+        .map(|(i, x)| (&x.set, i))
+        .collect();
+    let gsea: FxHashMap<&String, usize> = gsea_res
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !x.running_sum.is_empty())
+        .map(|(i, x)| (&x.set, i))
+        .collect();
+    // untested sets are ranked last
+    let ora_p: Vec<f64> = gmt_vec
+        .iter()
+        .map(|x| ora.get(&x.id).map_or(f64::INFINITY, |i| ora_res[*i].p))
+        .collect();
+    let gsea_p: Vec<f64> = gmt_vec
+        .iter()
+        .map(|x| gsea.get(&x.id).map_or(f64::INFINITY, |i| gsea_res[*i].p))
+        .collect();
+    let ora_rank = average_ranks(&ora_p);
+    let gsea_rank = average_ranks(&gsea_p);
+    let mean_rank: Vec<f64> = ora_rank
+        .iter()
+        .zip(gsea_rank.iter())
+        .map(|(a, b)| (a + b) / 2.0)
+        .collect();
+    let combined_rank = average_ranks(&mean_rank);
+    let mut order: Vec<usize> = (0..gmt_vec.len()).collect();
+    order.sort_by(|a, b| combined_rank[*a].total_cmp(&combined_rank[*b]));
+    order
+        .into_iter()
+        .map(|i| {
+            let set = &gmt_vec[i].id;
+            let ora_row = ora.get(set).map(|x| &ora_res[*x]);
+            let gsea_row = gsea.get(set).map(|x| &gsea_res[*x]);
+            let dict = PyDict::new(py);
+            dict.set_item("set", set)?;
+            dict.set_item("ora_p", ora_row.map(|x| x.p))?;
+            dict.set_item("ora_fdr", ora_row.map(|x| x.fdr))?;
+            dict.set_item("overlap", ora_row.map(|x| x.overlap))?;
+            dict.set_item("enrichment_ratio", ora_row.map(|x| x.enrichment_ratio))?;
+            dict.set_item("gsea_p", gsea_row.map(|x| x.p))?;
+            dict.set_item("gsea_fdr", gsea_row.map(|x| x.fdr))?;
+            dict.set_item("nes", gsea_row.map(|x| x.nes))?;
+            dict.set_item("ora_rank", ora_rank[i])?;
+            dict.set_item("gsea_rank", gsea_rank[i])?;
+            dict.set_item("combined_rank", combined_rank[i])?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
@@ -0,0 +1,255 @@
+use crate::inputs;
+use crate::meta::average_ranks;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The values of a single set in a list of results
+struct SetResult {
+    value: f64,
+    fdr: f64,
+}
+
+/// Reads the `set`, `fdr`, and `metric` of each row of a list of results
+fn read_results(
+    results: Vec<Bound<'_, PyDict>>,
+    metric: &str,
+) -> PyResult<(Vec<String>, FxHashMap<String, SetResult>)> {
+    let mut sets: Vec<String> = Vec::new();
+    let mut values: FxHashMap<String, SetResult> = FxHashMap::default();
+    for row in results {
+        let get = |key: &str| {
+            row.get_item(key)?
+                .ok_or_else(|| PyValueError::new_err(format!("Result is missing '{}'!", key)))
+        };
+        let set: String = get("set")?.extract()?;
+        let result = SetResult {
+            value: get(metric)?.extract()?,
+            fdr: get("fdr")?.extract()?,
+        };
+        if values.insert(set.clone(), result).is_none() {
+            sets.push(set);
+        }
+    }
+    Ok((sets, values))
+}
+
+/// Spearman correlation of paired values. `None` if there are fewer than two pairs or either side is constant.
+pub fn spearman(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 {
+        return None;
+    }
+    let (a, b) = (average_ranks(a), average_ranks(b));
+    let mean = (a.len() as f64 + 1.0) / 2.0;
+    let mut cov = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        cov += (x - mean) * (y - mean);
+        var_a += (x - mean).powi(2);
+        var_b += (y - mean).powi(2);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        None
+    } else {
+        Some(cov / (var_a * var_b).sqrt())
+    }
+}
+
+/// Compare two sets of results of the same analysis, such as before and after changing the preprocessing.
+///
+/// # Parameters
+/// - `res_a` - List of dictionaries of the results of an analysis, like the output of [`gsea`](./gsea.md) or [`ora`](./ora.md)
+/// - `res_b` - List of dictionaries of the results to compare `res_a` to
+/// - `fdr` - FDR threshold for a set to be significant (default: `0.05`)
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `metric` - The value that was correlated. `"nes"` for GSEA results and `"enrichment_ratio"` for ORA results.
+/// - `spearman` - Spearman correlation of `metric` over the sets in both results. `None` if it can not be computed.
+/// - `shared_sets` - Number of sets in both results
+/// - `jaccard` - Jaccard index of the significant sets of each result. `None` if neither result has a significant set.
+/// - `changed` - List of dictionaries of the sets that are significant in only one of the results, with the `set`,
+///   its `fdr_a` and `fdr_b` (`None` if the set is missing from that result), and whether it is `significant_a` or `significant_b`.
+///   Sets are in the order of `res_a`, followed by sets only in `res_b`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// a = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// b = webgestaltpy.ora("data/kegg.gmt", "data/second_genelist.txt", "data/reference.txt")
+/// diff = webgestaltpy.compare_results(a, b)
+/// print(diff["spearman"], diff["jaccard"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (res_a, res_b, fdr=0.05))]
+pub fn compare_results<'a>(
+    py: Python<'a>,
+    res_a: Vec<Bound<'_, PyDict>>,
+    res_b: Vec<Bound<'_, PyDict>>,
+    fdr: f64,
+) -> PyResult<Bound<'a, PyDict>> {
+    let is_gsea = res_a
+        .iter()
+        .chain(res_b.iter())
+        .next()
+        .map(|x| x.contains("nes"))
+        .transpose()?
+        .unwrap_or(true);
+    let metric = if is_gsea { "nes" } else { "enrichment_ratio" };
+    let (sets_a, values_a) = read_results(res_a, metric)?;
+    let (sets_b, values_b) = read_results(res_b, metric)?;
+    let mut paired_a: Vec<f64> = Vec::new();
+    let mut paired_b: Vec<f64> = Vec::new();
+    for set in sets_a.iter() {
+        if let Some(b) = values_b.get(set) {
+            paired_a.push(values_a[set].value);
+            paired_b.push(b.value);
+        }
+    }
+    let mut intersection = 0;
+    let mut union = 0;
+    let mut changed: Vec<Bound<'_, PyDict>> = Vec::new();
+    let only_b = sets_b.iter().filter(|x| !values_a.contains_key(*x));
+    for set in sets_a.iter().chain(only_b) {
+        let fdr_a = values_a.get(set).map(|x| x.fdr);
+        let fdr_b = values_b.get(set).map(|x| x.fdr);
+        let significant_a = fdr_a.is_some_and(|x| x < fdr);
+        let significant_b = fdr_b.is_some_and(|x| x < fdr);
+        if significant_a && significant_b {
+            intersection += 1;
+        }
+        if significant_a || significant_b {
+            union += 1;
+        }
+        if significant_a != significant_b {
+            let row = PyDict::new(py);
+            row.set_item("set", set)?;
+            row.set_item("fdr_a", fdr_a)?;
+            row.set_item("fdr_b", fdr_b)?;
+            row.set_item("significant_a", significant_a)?;
+            row.set_item("significant_b", significant_b)?;
+            changed.push(row);
+        }
+    }
+    let jaccard = if union > 0 {
+        Some(intersection as f64 / union as f64)
+    } else {
+        None
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("metric", metric)?;
+    dict.set_item("spearman", spearman(&paired_a, &paired_b))?;
+    dict.set_item("shared_sets", paired_a.len())?;
+    dict.set_item("jaccard", jaccard)?;
+    dict.set_item("changed", changed)?;
+    Ok(dict)
+}
+
+/// Jaccard index of two sets. `None` if both are empty.
+pub fn jaccard<T: std::hash::Hash + Eq>(a: &FxHashSet<T>, b: &FxHashSet<T>) -> Option<f64> {
+    let shared = a.intersection(b).count();
+    let union = a.len() + b.len() - shared;
+    if union > 0 {
+        Some(shared as f64 / union as f64)
+    } else {
+        None
+    }
+}
+
+/// Compare two GMT collections, such as two releases of the same database, before switching an analysis to the new one.
+///
+/// # Parameters
+/// - `a` - The first GMT in any form accepted by [`ora`](./ora.md)
+/// - `b` - The GMT to compare `a` to
+///
+/// If a set name is listed more than once in a GMT, only its first set is compared.
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `shared` - List of dictionaries of the sets in both GMTs, in the order of `a`, with the `set`, its `size_a` and `size_b`,
+///   the `jaccard` index of its analytes in each GMT (`None` if both are empty), and the number of analytes `added` in `b` and `removed` from `a`
+/// - `only_a` - List of the names of the sets only in `a`
+/// - `only_b` - List of the names of the sets only in `b`
+/// - `changed` - Number of shared sets whose analytes differ
+/// - `universe_a` and `universe_b` - Number of distinct analytes in each GMT
+/// - `universe_jaccard` - Jaccard index of the analytes of each GMT (`None` if both are empty)
+///
+/// # Errors
+///
+/// Raises a `ValueError` if either GMT is malformed or can not be read.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// diff = webgestaltpy.compare_gmts("kegg_2023.gmt", "kegg_2024.gmt")
+/// print(len(diff["only_a"]), len(diff["only_b"]), diff["changed"])
+/// low = [x["set"] for x in diff["shared"] if x["jaccard"] is not None and x["jaccard"] < 0.5]
+/// ```
+#[pyfunction]
+pub fn compare_gmts<'a>(
+    py: Python<'a>,
+    a: &Bound<'_, PyAny>,
+    b: &Bound<'_, PyAny>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let (gmt_a, gmt_b) = (inputs::gmt(a)?, inputs::gmt(b)?);
+    let mut sets_b: FxHashMap<&String, FxHashSet<&String>> = FxHashMap::default();
+    for item in gmt_b.iter() {
+        sets_b
+            .entry(&item.id)
+            .or_insert_with(|| item.parts.iter().collect());
+    }
+    let mut seen: FxHashSet<&String> = FxHashSet::default();
+    let mut shared: Vec<Bound<'_, PyDict>> = Vec::new();
+    let mut only_a: Vec<&String> = Vec::new();
+    let mut changed = 0;
+    for item in gmt_a.iter() {
+        if !seen.insert(&item.id) {
+            continue;
+        }
+        let Some(members_b) = sets_b.get(&item.id) else {
+            only_a.push(&item.id);
+            continue;
+        };
+        let members_a: FxHashSet<&String> = item.parts.iter().collect();
+        let added = members_b.difference(&members_a).count();
+        let removed = members_a.difference(members_b).count();
+        if added + removed > 0 {
+            changed += 1;
+        }
+        let row = PyDict::new(py);
+        row.set_item("set", &item.id)?;
+        row.set_item("size_a", members_a.len())?;
+        row.set_item("size_b", members_b.len())?;
+        row.set_item("jaccard", jaccard(&members_a, members_b))?;
+        row.set_item("added", added)?;
+        row.set_item("removed", removed)?;
+        shared.push(row);
+    }
+    let mut seen_b: FxHashSet<&String> = FxHashSet::default();
+    let only_b: Vec<&String> = gmt_b
+        .iter()
+        .map(|x| &x.id)
+        .filter(|x| !seen.contains(x) && seen_b.insert(x))
+        .collect();
+    let universe_a: FxHashSet<&String> = gmt_a.iter().flat_map(|x| x.parts.iter()).collect();
+    let universe_b: FxHashSet<&String> = gmt_b.iter().flat_map(|x| x.parts.iter()).collect();
+    let dict = PyDict::new(py);
+    dict.set_item("shared", shared)?;
+    dict.set_item("only_a", only_a)?;
+    dict.set_item("only_b", only_b)?;
+    dict.set_item("changed", changed)?;
+    dict.set_item("universe_a", universe_a.len())?;
+    dict.set_item("universe_b", universe_b.len())?;
+    dict.set_item("universe_jaccard", jaccard(&universe_a, &universe_b))?;
+    Ok(dict)
+}
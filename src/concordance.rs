@@ -0,0 +1,143 @@
+use crate::{config, gsea_stats, inputs};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use statrs::distribution::{ContinuousCDF, Normal};
+use webgestalt_lib::methods::gsea::GSEAResult;
+use webgestalt_lib::readers::utils::Item;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// Signed z-score of a two-sided GSEA p-value, positive for a positive NES. `p` is bounded below by
+/// `min_p`, since permutation p-values can be `0`.
+fn signed_z(p: f64, nes: f64, min_p: f64, normal: &Normal) -> f64 {
+    let z = normal.inverse_cdf(1.0 - p.clamp(min_p, 1.0) / 2.0);
+    if nes < 0.0 {
+        -z
+    } else {
+        z
+    }
+}
+
+/// Run GSEA on two rank lists, like the proteome and transcriptome of the same samples, and classify
+/// each set as concordantly or discordantly enriched.
+///
+/// The p-value of each set in each list is converted to a z-score with the sign of its NES. The evidence that
+/// the set moves the same way in both lists is the Stouffer combination `(z_a + z_b) / sqrt(2)`, and the evidence
+/// that it moves in opposite ways is `(z_a - z_b) / sqrt(2)`. Both are tested two-sided and adjusted with BH.
+/// Since GSEA p-values can be `0`, they are bounded below by `1 / (permutations + 1)`.
+///
+/// The lists are analyzed like in [`meta_gsea`](./meta_gsea.md), with `rank_file_a` using `seed` and `rank_file_b` using `seed + 1`.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_file_a` - The first rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_file_b` - The second rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `fdr` - FDR threshold for the classification (default: `0.05`)
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set in the order of the GMT, with:
+///
+/// - `set` - Name of the set
+/// - `nes_a`, `p_a`, `fdr_a`, `nes_b`, `p_b`, `fdr_b` - GSEA results of the set in each list
+/// - `concordance_p`, `concordance_fdr` - Significance of the set moving the same way in both lists
+/// - `discordance_p`, `discordance_fdr` - Significance of the set moving in opposite ways
+/// - `class` - `"concordant_up"` or `"concordant_down"` if the NES has the same sign in both lists and `concordance_fdr` is below `fdr`,
+///   `"discordant"` if the signs differ and `discordance_fdr` is below `fdr`, and `"not_significant"` otherwise.
+///   Sets that were not tested in both lists are `"not_significant"`.
+/// - `seed` - Seed used for `rank_file_a`
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.concordance("data/kegg.gmt", "proteome.rnk", "transcriptome.rnk")
+/// discordant = [x["set"] for x in res if x["class"] == "discordant"]
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_file_a, rank_file_b, fdr=0.05, seed=None))]
+pub fn concordance<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_file_a: &Bound<'_, PyAny>,
+    rank_file_b: &Bound<'_, PyAny>,
+    fdr: f64,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let gmt_vec: Vec<Item> = inputs::gmt(gmt)?;
+    let rank_lists = [
+        inputs::rank_list(rank_file_a)?,
+        inputs::rank_list(rank_file_b)?,
+    ];
+    let seed = gsea_stats::seed_or_random(seed);
+    let gsea_config = config::gsea_config();
+    let min_p = 1.0 / (gsea_config.permutations.max(0) as f64 + 1.0);
+    let mut results: Vec<Vec<GSEAResult>> = Vec::new();
+    for (i, rank_list) in rank_lists.into_iter().enumerate() {
+        let permutations = gsea_stats::make_permutations(
+            gsea_config.permutations,
+            rank_list.len(),
+            seed.wrapping_add(i as u64),
+        );
+        results.push(config::install(py, || {
+            gsea_stats::gsea(&rank_list, &gmt_vec, &gsea_config, &permutations)
+        })?);
+    }
+    let normal = Normal::new(0.0, 1.0).unwrap();
+    let two_sided = |z: f64| 2.0 * (1.0 - normal.cdf(z.abs()));
+    // every list has a result for every set, in GMT order
+    let (a, b) = (&results[0], &results[1]);
+    let (concordance_p, discordance_p): (Vec<f64>, Vec<f64>) = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| {
+            let z_a = signed_z(x.p, x.nes, min_p, &normal);
+            let z_b = signed_z(y.p, y.nes, min_p, &normal);
+            (
+                two_sided((z_a + z_b) / 2_f64.sqrt()),
+                two_sided((z_a - z_b) / 2_f64.sqrt()),
+            )
+        })
+        .unzip();
+    let concordance_fdr = adjust(&concordance_p, AdjustmentMethod::BH);
+    let discordance_fdr = adjust(&discordance_p, AdjustmentMethod::BH);
+    (0..gmt_vec.len())
+        .map(|i| {
+            let (x, y) = (&a[i], &b[i]);
+            let tested = x.es != 0.0 && y.es != 0.0;
+            let class = if !tested {
+                "not_significant"
+            } else if x.nes.signum() == y.nes.signum() && concordance_fdr[i] < fdr {
+                if x.nes > 0.0 {
+                    "concordant_up"
+                } else {
+                    "concordant_down"
+                }
+            } else if x.nes.signum() != y.nes.signum() && discordance_fdr[i] < fdr {
+                "discordant"
+            } else {
+                "not_significant"
+            };
+            let dict = PyDict::new(py);
+            dict.set_item("set", &x.set)?;
+            dict.set_item("nes_a", x.nes)?;
+            dict.set_item("p_a", x.p)?;
+            dict.set_item("fdr_a", x.fdr)?;
+            dict.set_item("nes_b", y.nes)?;
+            dict.set_item("p_b", y.p)?;
+            dict.set_item("fdr_b", y.fdr)?;
+            dict.set_item("concordance_p", concordance_p[i])?;
+            dict.set_item("concordance_fdr", concordance_fdr[i])?;
+            dict.set_item("discordance_p", discordance_p[i])?;
+            dict.set_item("discordance_fdr", discordance_fdr[i])?;
+            dict.set_item("class", class)?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
@@ -0,0 +1,358 @@
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use webgestalt_lib::methods::gsea::GSEAConfig;
+use webgestalt_lib::methods::ora::ORAConfig;
+
+/// Minimum and maximum set sizes of WebGestaltR (`minNum` and `maxNum`)
+const WEBGESTALTR_SET_SIZES: (usize, usize) = (10, 500);
+/// Number of permutations of GSEA in WebGestaltR (`perNum`)
+const WEBGESTALTR_PERMUTATIONS: i32 = 1000;
+
+/// Which defaults the analyses follow
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compat {
+    /// The defaults of `webgestalt_lib`
+    Default,
+    /// The defaults of WebGestaltR, to reproduce its results
+    WebGestaltR,
+}
+
+impl Compat {
+    pub fn parse(name: &str) -> PyResult<Compat> {
+        match name {
+            "default" => Ok(Compat::Default),
+            "webgestaltr" => Ok(Compat::WebGestaltR),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown compat value '{}'. Expected 'default' or 'webgestaltr'.",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compat::Default => "default",
+            Compat::WebGestaltR => "webgestaltr",
+        }
+    }
+}
+
+/// Package-wide defaults, set with `set_defaults` or the `WEBGESTALTPY_*` environment variables
+#[derive(Clone)]
+pub struct Defaults {
+    /// Number of threads for parallel work. `None` uses every core.
+    pub threads: Option<usize>,
+    pub cache_dir: Option<String>,
+    pub permutations: i32,
+    pub compat: Compat,
+    /// Whether the identifiers of every input are normalized
+    pub normalize_ids: bool,
+    /// Whether the version suffixes of Ensembl identifiers are removed from every input
+    pub ignore_id_versions: bool,
+    /// Memory in MB that an analysis should stay within, switching to slower methods that use less memory if needed
+    pub max_memory_mb: Option<usize>,
+    /// Whether the results of an analysis have the `link` of each set
+    pub set_links: bool,
+    /// FDR thresholds of the significance flags of the results of an analysis
+    pub significance_thresholds: Vec<f64>,
+    /// URL of the proxy for downloads. `None` uses the `HTTP_PROXY` and `HTTPS_PROXY` environment variables, if any.
+    pub proxy: Option<String>,
+}
+
+impl Defaults {
+    fn from_env() -> Defaults {
+        Defaults {
+            threads: std::env::var("WEBGESTALTPY_THREADS")
+                .ok()
+                .and_then(|x| x.trim().parse::<usize>().ok())
+                .filter(|x| *x > 0),
+            cache_dir: std::env::var("WEBGESTALTPY_CACHE_DIR")
+                .ok()
+                .filter(|x| !x.is_empty()),
+            permutations: GSEAConfig::default().permutations,
+            compat: Compat::Default,
+            normalize_ids: false,
+            ignore_id_versions: false,
+            max_memory_mb: std::env::var("WEBGESTALTPY_MAX_MEMORY_MB")
+                .ok()
+                .and_then(|x| x.trim().parse::<usize>().ok())
+                .filter(|x| *x > 0),
+            set_links: false,
+            significance_thresholds: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+// The defaults and the thread pools are the only state shared between calls. Both are behind locks
+// that are never held while an analysis runs, so concurrent calls from Python threads are safe.
+fn lock() -> &'static RwLock<Defaults> {
+    static DEFAULTS: OnceLock<RwLock<Defaults>> = OnceLock::new();
+    DEFAULTS.get_or_init(|| RwLock::new(Defaults::from_env()))
+}
+
+pub fn defaults() -> Defaults {
+    lock().read().unwrap().clone()
+}
+
+/// `GSEAConfig` with the default number of permutations, and the set size limits of WebGestaltR in compatibility mode
+pub fn gsea_config() -> GSEAConfig {
+    let defaults = defaults();
+    let config = GSEAConfig {
+        permutations: defaults.permutations,
+        ..Default::default()
+    };
+    match defaults.compat {
+        Compat::Default => config,
+        Compat::WebGestaltR => GSEAConfig {
+            min_overlap: WEBGESTALTR_SET_SIZES.0 as i32,
+            max_overlap: WEBGESTALTR_SET_SIZES.1 as i32,
+            ..config
+        },
+    }
+}
+
+/// `ORAConfig` with the set size limits of WebGestaltR in compatibility mode
+pub fn ora_config() -> ORAConfig {
+    match defaults().compat {
+        Compat::Default => ORAConfig::default(),
+        Compat::WebGestaltR => ORAConfig {
+            min_set_size: WEBGESTALTR_SET_SIZES.0,
+            max_set_size: WEBGESTALTR_SET_SIZES.1,
+            ..Default::default()
+        },
+    }
+}
+
+/// Whether duplicated analytes of a rank list are averaged, like WebGestaltR
+pub fn average_duplicate_ranks() -> bool {
+    defaults().compat == Compat::WebGestaltR
+}
+
+/// Runs `f` in a thread pool with the default number of threads.
+///
+/// The GIL is released while `f` runs, so calls from several Python threads run in parallel.
+pub fn install<R: Send>(py: Python<'_>, f: impl FnOnce() -> R + Send) -> PyResult<R> {
+    install_threads(py, None, f)
+}
+
+/// [`install`] with `threads` threads, or the default number of threads if `None`
+pub fn install_threads<R: Send>(
+    py: Python<'_>,
+    threads: Option<usize>,
+    f: impl FnOnce() -> R + Send,
+) -> PyResult<R> {
+    let pool = threads.or(defaults().threads).map(pool).transpose()?;
+    Ok(py.allow_threads(|| match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }))
+}
+
+/// Checks the `threads` argument of a function, where `0` is not a number of threads
+pub fn check_threads(threads: Option<usize>) -> PyResult<()> {
+    if threads == Some(0) {
+        Err(PyValueError::new_err("threads must be at least 1!"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Thread pool with `threads` threads. A pool is built the first time a number of threads is used and kept for the session, so calls
+/// with different `threads` from several Python threads each get their own pool instead of rebuilding a shared one.
+fn pool(threads: usize) -> PyResult<Arc<ThreadPool>> {
+    static POOLS: Mutex<Vec<(usize, Arc<ThreadPool>)>> = Mutex::new(Vec::new());
+    let mut pools = POOLS.lock().unwrap();
+    if let Some((_, pool)) = pools.iter().find(|(n, _)| *n == threads) {
+        return Ok(pool.clone());
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map(Arc::new)
+        .map_err(|e| {
+            PyRuntimeError::new_err(format!(
+                "Could not start a thread pool of {} threads. {}",
+                threads, e
+            ))
+        })?;
+    pools.push((threads, pool.clone()));
+    Ok(pool)
+}
+
+/// Set the defaults used by every function of the package.
+///
+/// The defaults start from the environment variables `WEBGESTALTPY_THREADS`, `WEBGESTALTPY_CACHE_DIR`, and `WEBGESTALTPY_MAX_MEMORY_MB`,
+/// so they can be tuned without changing any code. Only the provided parameters are changed.
+///
+/// # Parameters
+/// - `threads` - Number of threads used for parallel work, or `0` to use every core (default: every core)
+/// - `cache_dir` - `String` of the path to the directory for cached files, like the ortholog tables downloaded by [`map_orthologs`](./map_orthologs.md).
+///   If `None`, downloads are only kept for the session (default: `None`)
+/// - `permutations` - Number of permutations used by [`gsea`](./gsea.md) and [`meta_gsea`](./meta_gsea.md), and of random lists used by
+///   [`ora`](./ora.md) with `method="permutation"` (default: `1000`)
+/// - `compat` - Either `"webgestaltr"` to follow the defaults of WebGestaltR, to reproduce published results, or `"default"` for the defaults of this package.
+///   See below for details. (default: `"default"`)
+/// - `normalize_ids` - If `True`, the identifiers of every analyte list, rank list, GMT, and network are normalized before matching, like with
+///   [`normalize_ids`](./normalize_ids.md): trimmed of whitespace and quotes, in upper case, and without Ensembl version suffixes.
+///   Results use the normalized identifiers. (default: `False`)
+/// - `ignore_id_versions` - If `True`, only the version suffixes of Ensembl identifiers are removed from every input, so
+///   `ENSG00000141510.17` in an RNA-seq rank list matches `ENSG00000141510` in a GMT. Always done with `normalize_ids=True`. (default: `False`)
+/// - `max_memory_mb` - Memory in MB that an analysis should stay within, or `0` for no limit. See below for details. (default: no limit)
+/// - `set_links` - If `True`, every result of [`ora`](./ora.md), [`gsea`](./gsea.md), and their meta-analyses has the `link` of its set.
+///   See below for details. (default: `False`)
+/// - `significance_thresholds` - FDR thresholds of the significance flags of every result of [`ora`](./ora.md), [`gsea`](./gsea.md), and their
+///   meta-analyses, like `[0.05, 0.25]`, or an empty list for no flags. See below for details. (default: `[]`)
+/// - `proxy` - `String` of the URL of the proxy for downloads, like `"http://proxy.example.org:3128"`, or an empty string to remove it.
+///   Without a proxy, downloads use the `HTTP_PROXY` and `HTTPS_PROXY` environment variables, if set. (default: `None`)
+///
+/// # WebGestaltR Compatibility
+///
+/// With `compat="webgestaltr"`:
+///
+/// - The number of permutations is set to `1000`, unless `permutations` is also provided
+/// - GSEA only tests sets with between `10` and `500` analytes in the rank list (`minNum` and `maxNum`), instead of `15` and `500`
+/// - ORA only tests sets with between `10` and `500` analytes in the reference list, and the FDR is adjusted with BH over those sets only
+/// - Analytes listed more than once in a rank list get the mean of their ranks. Tied ranks keep the order of the rank list, like the stable sort of R.
+///
+/// # Low-Memory Mode
+///
+/// [`gsea`](./gsea.md) keeps the running sum and the null enrichment scores of every set in memory, which can take several GB for large
+/// collections like GO. If its estimated memory is above `max_memory_mb`, GSEA scores one set at a time without keeping the running sums,
+/// and computes the null enrichment scores again for the FDR instead of keeping them. The results are the same, but since every permutation
+/// is scored twice, it takes about twice as long.
+///
+/// The limit is a hint: the permutations, the rank list, the GMT, and the results are always kept.
+///
+/// # Set Links
+///
+/// With `set_links=True`, each result has the `link` to the page of its set, from the ID of the set: KEGG for KEGG pathways (like `hsa04110`),
+/// Reactome for Reactome pathways (like `R-HSA-109581`), and AmiGO for GO terms (like `GO:0006915`). Other sets have a `link` of `None`.
+/// The link is kept by every export of the results, and [`generate_markdown`](./generate_markdown.md) and [`enrichment_map_html`](./enrichment_map_html.md)
+/// link each set to it.
+///
+/// # Significance Flags
+///
+/// With `significance_thresholds`, each result has a boolean column for each threshold, named after it like `sig_fdr_0_05` for `0.05`,
+/// which is `True` if the FDR of the set is below the threshold. Tables of the results can then be filtered on a single column, like in Excel.
+/// A single call of [`ora`](./ora.md) or [`gsea`](./gsea.md) can use other thresholds with its own `significance_thresholds`.
+///
+/// ```python
+/// webgestaltpy.set_defaults(significance_thresholds=[0.05, 0.25])
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// print(sum(x["sig_fdr_0_25"] for x in res))
+/// ```
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// webgestaltpy.set_defaults(threads=4, permutations=2000)
+/// print(webgestaltpy.get_defaults())
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// {'threads': 4, 'cache_dir': None, 'permutations': 2000, 'compat': 'default', 'normalize_ids': False, 'ignore_id_versions': False, 'max_memory_mb': None, 'set_links': False, 'significance_thresholds': [], 'proxy': None}
+/// ```
+#[pyfunction]
+#[pyo3(signature = (threads=None, cache_dir=None, permutations=None, compat=None, normalize_ids=None, ignore_id_versions=None, max_memory_mb=None, set_links=None, significance_thresholds=None, proxy=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn set_defaults(
+    threads: Option<usize>,
+    cache_dir: Option<String>,
+    permutations: Option<i32>,
+    compat: Option<&str>,
+    normalize_ids: Option<bool>,
+    ignore_id_versions: Option<bool>,
+    max_memory_mb: Option<usize>,
+    set_links: Option<bool>,
+    significance_thresholds: Option<Vec<f64>>,
+    proxy: Option<String>,
+) -> PyResult<()> {
+    if permutations.is_some_and(|x| x < 1) {
+        return Err(PyValueError::new_err(
+            "Number of permutations must be at least 1!",
+        ));
+    }
+    if let Some(significance_thresholds) = &significance_thresholds {
+        check_significance_thresholds(significance_thresholds)?;
+    }
+    let compat = compat.map(Compat::parse).transpose()?;
+    let mut defaults = lock().write().unwrap();
+    if let Some(compat) = compat {
+        defaults.compat = compat;
+        if compat == Compat::WebGestaltR {
+            defaults.permutations = WEBGESTALTR_PERMUTATIONS;
+        }
+    }
+    if let Some(threads) = threads {
+        defaults.threads = if threads == 0 { None } else { Some(threads) };
+    }
+    if let Some(cache_dir) = cache_dir {
+        defaults.cache_dir = Some(cache_dir);
+    }
+    if let Some(permutations) = permutations {
+        defaults.permutations = permutations;
+    }
+    if let Some(normalize_ids) = normalize_ids {
+        defaults.normalize_ids = normalize_ids;
+    }
+    if let Some(ignore_id_versions) = ignore_id_versions {
+        defaults.ignore_id_versions = ignore_id_versions;
+    }
+    if let Some(max_memory_mb) = max_memory_mb {
+        defaults.max_memory_mb = if max_memory_mb == 0 {
+            None
+        } else {
+            Some(max_memory_mb)
+        };
+    }
+    if let Some(set_links) = set_links {
+        defaults.set_links = set_links;
+    }
+    if let Some(significance_thresholds) = significance_thresholds {
+        defaults.significance_thresholds = significance_thresholds;
+    }
+    if let Some(proxy) = proxy {
+        defaults.proxy = if proxy.is_empty() { None } else { Some(proxy) };
+    }
+    Ok(())
+}
+
+/// Checks that every FDR threshold of the significance flags is above 0 and at most 1
+pub fn check_significance_thresholds(thresholds: &[f64]) -> PyResult<()> {
+    if thresholds.iter().any(|t| !(*t > 0.0 && *t <= 1.0)) {
+        return Err(PyValueError::new_err(
+            "Significance thresholds must be above 0 and at most 1!",
+        ));
+    }
+    Ok(())
+}
+
+/// Get the current defaults of the package. See [`set_defaults`](./set_defaults.md).
+///
+/// # Returns
+///
+/// Returns a dictionary with the `threads` (`None` if using every core), `cache_dir`, `permutations`, `compat`, `normalize_ids`, `ignore_id_versions`, `max_memory_mb` (`None` if there is no limit), `set_links`, `significance_thresholds`, and `proxy` (`None` if not set).
+#[pyfunction]
+pub fn get_defaults(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let defaults = defaults();
+    let dict = PyDict::new(py);
+    dict.set_item("threads", defaults.threads)?;
+    dict.set_item("cache_dir", defaults.cache_dir)?;
+    dict.set_item("permutations", defaults.permutations)?;
+    dict.set_item("compat", defaults.compat.name())?;
+    dict.set_item("normalize_ids", defaults.normalize_ids)?;
+    dict.set_item("ignore_id_versions", defaults.ignore_id_versions)?;
+    dict.set_item("max_memory_mb", defaults.max_memory_mb)?;
+    dict.set_item("set_links", defaults.set_links)?;
+    dict.set_item("significance_thresholds", defaults.significance_thresholds)?;
+    dict.set_item("proxy", defaults.proxy)?;
+    Ok(dict)
+}
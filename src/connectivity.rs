@@ -0,0 +1,261 @@
+use crate::{config, gsea_stats, inputs, warnings};
+use ahash::{AHashMap, AHashSet};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// Ranked signature of a perturbation over the analytes of the library. Analytes of the up set are at the top in their
+/// order, analytes of the down set at the bottom in reverse order, and the other analytes share the average position between them.
+struct Signature {
+    name: String,
+    positions: AHashMap<usize, f64>,
+    middle: f64,
+}
+
+impl Signature {
+    fn new(name: String, up: &[usize], down: &[usize], n: usize) -> Signature {
+        let mut positions = AHashMap::default();
+        for (i, x) in up.iter().enumerate() {
+            positions.entry(*x).or_insert((i + 1) as f64);
+        }
+        for (i, x) in down.iter().enumerate() {
+            positions.entry(*x).or_insert((n - i) as f64);
+        }
+        let middle = (up.len() + 1 + n - down.len()) as f64 / 2.0;
+        Signature {
+            name,
+            positions,
+            middle,
+        }
+    }
+
+    /// Kolmogorov-Smirnov enrichment score of a query set in the signature, like the connectivity map of
+    /// [Lamb et al. 2006](https://doi.org/10.1126/science.1132939)
+    fn enrichment_score(&self, query: &[usize], n: usize) -> f64 {
+        if query.is_empty() {
+            return 0.0;
+        }
+        let mut positions: Vec<f64> = query
+            .iter()
+            .map(|x| *self.positions.get(x).unwrap_or(&self.middle))
+            .collect();
+        positions.sort_by(|a, b| a.total_cmp(b));
+        let (t, n) = (positions.len() as f64, n as f64);
+        let (mut a, mut b) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for (j, v) in positions.iter().enumerate() {
+            a = a.max((j + 1) as f64 / t - v / n);
+            b = b.max(v / n - j as f64 / t);
+        }
+        if a > b {
+            a
+        } else {
+            -b
+        }
+    }
+
+    /// Enrichment scores of the up and down queries, and the connectivity score
+    fn scores(&self, up: &[usize], down: &[usize], n: usize) -> (f64, f64, f64) {
+        let up_es = self.enrichment_score(up, n);
+        let down_es = self.enrichment_score(down, n);
+        let score = if up.is_empty() || down.is_empty() {
+            (up_es - down_es) / 2.0
+        } else if up_es.signum() == down_es.signum() {
+            0.0
+        } else {
+            (up_es - down_es) / 2.0
+        };
+        (up_es, down_es, score)
+    }
+}
+
+/// Score the connectivity of a signature with each perturbation of a library of drug signatures, like the
+/// [connectivity map](https://doi.org/10.1126/science.1132939).
+///
+/// Each perturbation of the library has an up set and a down set, with their analytes in order of rank, like the LINCS L1000
+/// libraries of Enrichr. The signature of the perturbation ranks the analytes of the library with its up set at the top and its down set
+/// at the bottom, and the other analytes share the average rank between them. The enrichment of the `up` and `down` queries in
+/// the signature of each perturbation is a Kolmogorov-Smirnov statistic, and the connectivity score is half of their difference,
+/// from `-1` to `1`. The score is `0` if both enrichment scores have the same sign. A positive score means the perturbation mimics
+/// the signature, and a negative score means it reverses it.
+///
+/// The p-value of each perturbation is the fraction of random queries, with the same number of analytes drawn from the library without
+/// replacement, with an absolute score at least as large, with a pseudo-count of one: `(1 + hits) / (1 + permutations)`.
+///
+/// # Parameters
+/// - `up` - The analytes up-regulated in the signature, in any form accepted by the `analyte_list` of [`ora`](./ora.md)
+/// - `down` - The analytes down-regulated in the signature, in any form accepted by the `analyte_list` of [`ora`](./ora.md)
+/// - `library` - The library as a GMT in any form accepted by [`ora`](./ora.md), with a set named `<perturbation><up_suffix>` and
+///   a set named `<perturbation><down_suffix>` for each perturbation. A perturbation can have only one of the two.
+/// - `permutations` - Number of random queries (default: `1000`)
+/// - `seed` - Seed for the random queries. If not provided, a new seed is generated (default: `None`)
+/// - `up_suffix` - Suffix of the name of the up sets (default: `"_up"`)
+/// - `down_suffix` - Suffix of the name of the down sets (default: `"_down"`)
+///
+/// Analytes of the queries that are not in the library are left out with a warning. Analytes in both queries are left out of both.
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each perturbation in the order of the library, with the `perturbation`, the connectivity
+/// `score`, the enrichment scores `up_es` and `down_es` of the queries, `p`, `fdr` (BH-adjusted), and the `seed`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, if a set of the library ends with neither suffix, if a perturbation
+/// has more than one up or down set, or if no analyte of the queries is in the library.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.connectivity_scores("up.txt", "down.txt", "data/lincs_l1000.gmt", seed=1)
+/// res.sort(key=lambda x: x["score"])
+/// for row in res[:5]:
+///     print(row["perturbation"], row["score"], row["fdr"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (up, down, library, permutations=1000, seed=None, up_suffix="_up", down_suffix="_down"))]
+#[allow(clippy::too_many_arguments)]
+pub fn connectivity_scores<'a>(
+    py: Python<'a>,
+    up: &Bound<'_, PyAny>,
+    down: &Bound<'_, PyAny>,
+    library: &Bound<'_, PyAny>,
+    permutations: usize,
+    seed: Option<u64>,
+    up_suffix: &str,
+    down_suffix: &str,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let items = inputs::gmt(library)?;
+    let mut universe: Vec<&String> = Vec::new();
+    let mut indices: AHashMap<&String, usize> = AHashMap::default();
+    for analyte in items.iter().flat_map(|x| x.parts.iter()) {
+        indices.entry(analyte).or_insert_with(|| {
+            universe.push(analyte);
+            universe.len() - 1
+        });
+    }
+    let mut names: Vec<String> = Vec::new();
+    let mut sets: AHashMap<(String, bool), Vec<usize>> = AHashMap::default();
+    for item in items.iter() {
+        // the longer suffix first, in case one ends with the other
+        let (is_up, name) = match (
+            item.id.strip_suffix(up_suffix),
+            item.id.strip_suffix(down_suffix),
+        ) {
+            (Some(a), Some(b)) if a.len() < b.len() => (true, a),
+            (_, Some(b)) => (false, b),
+            (Some(a), None) => (true, a),
+            (None, None) => {
+                return Err(PyValueError::new_err(format!(
+                    "Set '{}' of the library ends with neither '{}' nor '{}'!",
+                    item.id, up_suffix, down_suffix
+                )))
+            }
+        };
+        if !sets.contains_key(&(name.to_string(), !is_up))
+            && !sets.contains_key(&(name.to_string(), is_up))
+        {
+            names.push(name.to_string());
+        }
+        if sets
+            .insert(
+                (name.to_string(), is_up),
+                item.parts.iter().map(|x| indices[x]).collect(),
+            )
+            .is_some()
+        {
+            return Err(PyValueError::new_err(format!(
+                "Perturbation '{}' has more than one {} set!",
+                name,
+                if is_up { "up" } else { "down" }
+            )));
+        }
+    }
+    let n = universe.len();
+    let signatures: Vec<Signature> = names
+        .into_iter()
+        .map(|name| {
+            let set = |is_up| sets.get(&(name.clone(), is_up)).map_or(&[][..], |x| &x[..]);
+            Signature::new(name.clone(), set(true), set(false), n)
+        })
+        .collect();
+    let up_list = inputs::unique_analyte_list(up)?;
+    let down_list = inputs::unique_analyte_list(down)?;
+    let both: AHashSet<&String> = up_list.iter().filter(|x| down_list.contains(x)).collect();
+    let query = |list: &[String]| -> Vec<usize> {
+        list.iter()
+            .filter(|x| !both.contains(x))
+            .filter_map(|x| indices.get(x).copied())
+            .collect()
+    };
+    let (up_query, down_query) = (query(&up_list), query(&down_list));
+    let total = up_list.len() + down_list.len() - 2 * both.len();
+    warnings::warn_left_out(
+        py,
+        total - up_query.len() - down_query.len(),
+        total,
+        "analytes of the queries",
+        "are not in the library",
+    )?;
+    if up_query.is_empty() && down_query.is_empty() {
+        return Err(PyValueError::new_err(
+            "No analyte of the queries is in the library!",
+        ));
+    }
+    let seed = gsea_stats::seed_or_random(seed);
+    let (observed, hits) = config::install(py, || {
+        let observed: Vec<(f64, f64, f64)> = signatures
+            .par_iter()
+            .map(|x| x.scores(&up_query, &down_query, n))
+            .collect();
+        let hits = (0..permutations)
+            .into_par_iter()
+            .map(|b| {
+                let mut rng = rand::rngs::SmallRng::seed_from_u64(seed.wrapping_add(b as u64));
+                let random =
+                    rand::seq::index::sample(&mut rng, n, up_query.len() + down_query.len())
+                        .into_vec();
+                let (random_up, random_down) = random.split_at(up_query.len());
+                signatures
+                    .iter()
+                    .zip(observed.iter())
+                    .map(|(x, (_, _, score))| {
+                        (x.scores(random_up, random_down, n).2.abs() >= score.abs()) as usize
+                    })
+                    .collect::<Vec<usize>>()
+            })
+            .reduce(
+                || vec![0; signatures.len()],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(x, y)| *x += y);
+                    a
+                },
+            );
+        (observed, hits)
+    })?;
+    let p: Vec<f64> = hits
+        .iter()
+        .map(|hit| (1 + hit) as f64 / (1 + permutations) as f64)
+        .collect();
+    let fdr = adjust(&p, AdjustmentMethod::BH);
+    signatures
+        .iter()
+        .enumerate()
+        .map(|(i, signature)| {
+            let (up_es, down_es, score) = observed[i];
+            let dict = PyDict::new(py);
+            dict.set_item("perturbation", &signature.name)?;
+            dict.set_item("score", score)?;
+            dict.set_item("up_es", up_es)?;
+            dict.set_item("down_es", down_es)?;
+            dict.set_item("p", p[i])?;
+            dict.set_item("fdr", fdr[i])?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
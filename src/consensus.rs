@@ -0,0 +1,102 @@
+use crate::meta;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// `(set, value)` pairs of a list of results
+type SetValues = Vec<(String, f64)>;
+
+/// Reads the `set`, `p`, and `fdr` of each row of a list of results
+fn read_results(results: &[Bound<'_, PyDict>]) -> PyResult<(SetValues, SetValues)> {
+    let mut p: SetValues = Vec::new();
+    let mut fdr: SetValues = Vec::new();
+    for row in results {
+        let get = |key: &str| {
+            row.get_item(key)?
+                .ok_or_else(|| PyValueError::new_err(format!("Result is missing '{}'!", key)))
+        };
+        let set: String = get("set")?.extract()?;
+        let probability = |key: &str| -> PyResult<f64> {
+            let value: f64 = get(key)?.extract()?;
+            if !(0.0..=1.0).contains(&value) {
+                return Err(PyValueError::new_err(format!(
+                    "The {} of set '{}' must be between 0 and 1, but is {}",
+                    key, set, value
+                )));
+            }
+            Ok(value)
+        };
+        let (set_p, set_fdr) = (probability("p")?, probability("fdr")?);
+        p.push((set.clone(), set_p));
+        fdr.push((set, set_fdr));
+    }
+    Ok((p, fdr))
+}
+
+/// Find the sets significant in at least `k` of the results of replicate lists.
+///
+/// A simpler alternative to [`meta_gsea`](./meta_gsea.md) and [`meta_ora`](./meta_ora.md) for quality control
+/// of technical replicates. The p-values of each set are combined with Stouffer's method.
+///
+/// # Parameters
+/// - `results` - List of the results of each replicate, like the outputs of [`gsea`](./gsea.md) or [`ora`](./ora.md)
+/// - `k` - Minimum number of replicates where a set must be significant
+/// - `fdr` - FDR threshold for a set to be significant in a replicate (default: `0.05`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries of the sets significant in at least `k` replicates, with the `set`,
+/// the number of replicates where it is `significant`, the number of replicates it was `found` in,
+/// and the combined `p` and `fdr` (BH-adjusted over every set). Sets are sorted by `p`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `k` is out of range, or if a result is missing its `set`, `p`, or `fdr`, or has a `p` or `fdr` that is not between `0` and `1`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// replicates = [webgestaltpy.ora("data/kegg.gmt", x, "data/reference.txt") for x in ["rep1.txt", "rep2.txt", "rep3.txt"]]
+/// res = webgestaltpy.consensus(replicates, k=2)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, k, fdr=0.05))]
+pub fn consensus<'a>(
+    py: Python<'a>,
+    results: Vec<Vec<Bound<'_, PyDict>>>,
+    k: usize,
+    fdr: f64,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if k == 0 || k > results.len() {
+        return Err(PyValueError::new_err(format!(
+            "k must be between 1 and the number of replicates ({}), but is {}",
+            results.len(),
+            k
+        )));
+    }
+    let (p_lists, fdr_lists): (Vec<_>, Vec<_>) = results
+        .iter()
+        .map(|x| read_results(x))
+        .collect::<PyResult<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    let (sets, meta_p, meta_fdr) = meta::meta_analysis(&p_lists, &meta::MetaMethod::Stouffer, None);
+    let votes = meta::vote_counts(&fdr_lists, &sets, fdr);
+    let found = meta::vote_counts(&fdr_lists, &sets, f64::INFINITY);
+    let mut order: Vec<usize> = (0..sets.len()).filter(|i| votes[*i] >= k).collect();
+    order.sort_by(|a, b| meta_p[*a].total_cmp(&meta_p[*b]));
+    order
+        .into_iter()
+        .map(|i| {
+            let dict = PyDict::new(py);
+            dict.set_item("set", &sets[i])?;
+            dict.set_item("significant", votes[i])?;
+            dict.set_item("found", found[i])?;
+            dict.set_item("p", meta_p[i])?;
+            dict.set_item("fdr", meta_fdr[i])?;
+            Ok(dict)
+        })
+        .collect()
+}
@@ -0,0 +1,521 @@
+use crate::clustering::{significant_sets, SignificantSet};
+use crate::gsea_stats::{leading_edge_analytes, SortedRankList};
+use crate::{inputs, links, manifest, warnings};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::IntoPyObjectExt;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::Value;
+use webgestalt_lib::readers::utils::Item;
+
+/// Gets the significant sets of `results`, with their analytes limited to `genes` and the leading edge of `rank_file` if provided
+pub fn restricted_sets(
+    results: &[Bound<'_, PyDict>],
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+    genes: Option<&[String]>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Vec<SignificantSet>> {
+    let mut sets = significant_sets(results, gmt, fdr)?;
+    if let Some(rank_file) = rank_file {
+        let rank_list = SortedRankList::new(&inputs::rank_list(rank_file)?);
+        for set in sets.iter_mut() {
+            set.analytes = leading_edge_analytes(
+                &rank_list,
+                &Item {
+                    id: set.set.clone(),
+                    url: set.url.clone(),
+                    parts: set.analytes.clone(),
+                },
+            );
+        }
+    }
+    if let Some(genes) = genes {
+        let genes: FxHashSet<&String> = genes.iter().collect();
+        for set in sets.iter_mut() {
+            set.analytes.retain(|x| genes.contains(x));
+        }
+    }
+    Ok(sets)
+}
+
+/// Create a matrix of which analytes are in which significant sets, like for a clustered heatmap of the analytes driving each enrichment.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+/// - `gmt` - `String` of the path to the GMT file used for the analysis, or a [`Gmt`](./Gmt.md) object
+/// - `fdr` - FDR threshold for a set to be included (default: `0.05`)
+/// - `genes` - If provided, a list of the analytes to use as the rows, as `String`s or integers, such as the analyte list of an ORA. Otherwise, uses every analyte in at least one significant set (default: `None`)
+/// - `rank_file` - If provided, the rank list of a GSEA, as the `String` of the path to the rank file or in any form accepted by [`gsea`](./gsea.md). Only the analytes in the leading edge of each set are counted as members (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary with the row labels in `genes`, the column labels in `sets` (ordered by FDR),
+/// and the `matrix` as a list of rows, where `matrix[i][j]` is `True` if `genes[i]` is in `sets[j]`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+/// import pandas as pd
+///
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// m = webgestaltpy.membership_matrix(res, "data/kegg.gmt", rank_file="data/test.rnk")
+/// df = pd.DataFrame(m["matrix"], index=m["genes"], columns=m["sets"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, fdr=0.05, genes=None, rank_file=None))]
+pub fn membership_matrix<'a>(
+    py: Python<'a>,
+    results: Vec<Bound<'_, PyDict>>,
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+    genes: Option<&Bound<'_, PyAny>>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let genes = genes.map(inputs::analyte_ids).transpose()?;
+    let sets = restricted_sets(&results, gmt, fdr, genes.as_deref(), rank_file)?;
+    let genes: Vec<String> = match genes {
+        Some(genes) => genes,
+        None => {
+            let mut seen: FxHashSet<&String> = FxHashSet::default();
+            sets.iter()
+                .flat_map(|x| x.analytes.iter())
+                .filter(|x| seen.insert(x))
+                .cloned()
+                .collect()
+        }
+    };
+    let rows: FxHashMap<&String, usize> = genes.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    let mut matrix = vec![vec![false; sets.len()]; genes.len()];
+    for (j, set) in sets.iter().enumerate() {
+        for analyte in set.analytes.iter() {
+            if let Some(i) = rows.get(analyte) {
+                matrix[*i][j] = true;
+            }
+        }
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("genes", genes)?;
+    dict.set_item("sets", sets.iter().map(|x| &x.set).collect::<Vec<_>>())?;
+    dict.set_item("matrix", matrix)?;
+    Ok(dict)
+}
+
+/// Write the significant sets of an analysis to a new GMT file, like for a focused follow-up analysis.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+/// - `gmt` - `String` of the path to the GMT file used for the analysis, or a [`Gmt`](./Gmt.md) object
+/// - `path` - `String` of the path to write the new GMT file to
+/// - `fdr` - FDR threshold for a set to be written (default: `0.05`)
+/// - `genes` - If provided, a list of analytes as `String`s or integers, such as the analyte list of an ORA. Each set only keeps the analytes in this list (default: `None`)
+/// - `rank_file` - If provided, the rank list of a GSEA, as the `String` of the path to the rank file or in any form accepted by [`gsea`](./gsea.md). Each set only keeps the analytes in its leading edge (default: `None`)
+///
+/// # Returns
+///
+/// Returns the number of sets written. Sets are written in order of FDR, and sets left with no analytes are not written.
+/// Sets keep their URL from the GMT, unless it is not a web link and their result has a `link` (see [`set_defaults`](./set_defaults.md)).
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// genes = open("data/genelist.txt").read().split()
+/// webgestaltpy.results_to_gmt(res, "data/kegg.gmt", "significant.gmt", genes=genes)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, path, fdr=0.05, genes=None, rank_file=None))]
+pub fn results_to_gmt(
+    results: Vec<Bound<'_, PyDict>>,
+    gmt: &Bound<'_, PyAny>,
+    path: String,
+    fdr: f64,
+    genes: Option<&Bound<'_, PyAny>>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+) -> PyResult<usize> {
+    let genes = genes.map(inputs::analyte_ids).transpose()?;
+    let sets = restricted_sets(&results, gmt, fdr, genes.as_deref(), rank_file)?;
+    let mut result_links: FxHashMap<String, String> = FxHashMap::default();
+    for row in results.iter() {
+        if let (Some(set), Some(link)) = (row.get_item("set")?, links::result_link(row)?) {
+            result_links.insert(inputs::analyte_to_string(&set)?, link);
+        }
+    }
+    let mut content = String::new();
+    let mut written = 0;
+    for set in sets.iter().filter(|x| !x.analytes.is_empty()) {
+        let url = match result_links.get(&set.set) {
+            Some(link) if !links::is_web_link(&set.url) => link,
+            _ => &set.url,
+        };
+        content.push_str(&format!(
+            "{}\t{}\t{}\n",
+            set.set,
+            url,
+            set.analytes.join("\t")
+        ));
+        written += 1;
+    }
+    inputs::write_file(&path, &content).map_err(|e| {
+        PyValueError::new_err(format!("Error when writing GMT file at: {}. {}", path, e))
+    })?;
+    Ok(written)
+}
+
+/// Score the analytes driving the significant sets of an analysis, to prioritize analytes for follow-up.
+///
+/// Each analyte gets the significant sets it is a member of, or in the leading edge of with `rank_file`, and its contribution, which adds up a score
+/// of each of these sets. For GSEA results, the score of a set is its NES, so analytes driving sets in opposite directions cancel out.
+/// For ORA results, which have no NES, the score of a set is its `-log10(p)`.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+/// - `gmt` - `String` of the path to the GMT file used for the analysis, or a [`Gmt`](./Gmt.md) object
+/// - `fdr` - FDR threshold for a set to be counted (default: `0.05`)
+/// - `genes` - If provided, a list of the analytes to score, as `String`s or integers, such as the analyte list of an ORA. Otherwise, scores every analyte in at least one significant set (default: `None`)
+/// - `rank_file` - If provided, the rank list of a GSEA, as the `String` of the path to the rank file or in any form accepted by [`gsea`](./gsea.md). Only the analytes in the leading edge of each set are counted as members (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each analyte in at least one significant set, ordered by decreasing `set_count` and then absolute `contribution`, with:
+///
+/// - `analyte` - The analyte
+/// - `set_count` - Number of significant sets with the analyte
+/// - `sets` - The significant sets with the analyte, ordered by FDR
+/// - `contribution` - Sum of the NES (GSEA) or `-log10(p)` (ORA) of `sets`
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// genes = webgestaltpy.gene_attribution(res, "data/kegg.gmt", rank_file="data/test.rnk")
+/// for x in genes[:5]:
+///     print(x["analyte"], x["set_count"], x["contribution"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, fdr=0.05, genes=None, rank_file=None))]
+pub fn gene_attribution<'a>(
+    py: Python<'a>,
+    results: Vec<Bound<'_, PyDict>>,
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+    genes: Option<&Bound<'_, PyAny>>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let genes = genes.map(inputs::analyte_ids).transpose()?;
+    let sets = restricted_sets(&results, gmt, fdr, genes.as_deref(), rank_file)?;
+    let mut nes: FxHashMap<String, f64> = FxHashMap::default();
+    for row in results.iter() {
+        if let (Some(set), Some(value)) = (row.get_item("set")?, row.get_item("nes")?) {
+            nes.insert(set.extract()?, value.extract()?);
+        }
+    }
+    // analytes in the order they are first found, with the indices of their sets
+    let mut index: FxHashMap<&String, usize> = FxHashMap::default();
+    let mut analytes: Vec<(&String, Vec<usize>)> = Vec::new();
+    for (i, set) in sets.iter().enumerate() {
+        for analyte in set.analytes.iter() {
+            let j = *index.entry(analyte).or_insert_with(|| {
+                analytes.push((analyte, Vec::new()));
+                analytes.len() - 1
+            });
+            analytes[j].1.push(i);
+        }
+    }
+    let score = |i: usize| match nes.get(&sets[i].set) {
+        Some(nes) => *nes,
+        None => -sets[i].p.max(f64::MIN_POSITIVE).log10(),
+    };
+    let mut scored: Vec<(&String, Vec<usize>, f64)> = analytes
+        .into_iter()
+        .map(|(analyte, members)| {
+            let contribution = members.iter().map(|i| score(*i)).sum();
+            (analyte, members, contribution)
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        b.1.len()
+            .cmp(&a.1.len())
+            .then(b.2.abs().total_cmp(&a.2.abs()))
+    });
+    scored
+        .into_iter()
+        .map(|(analyte, members, contribution)| {
+            let dict = PyDict::new(py);
+            dict.set_item("analyte", analyte)?;
+            dict.set_item("set_count", members.len())?;
+            dict.set_item(
+                "sets",
+                members.iter().map(|i| &sets[*i].set).collect::<Vec<_>>(),
+            )?;
+            dict.set_item("contribution", contribution)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Format of saved results
+enum ResultsFormat {
+    Json,
+    Tsv,
+    Parquet,
+}
+
+impl ResultsFormat {
+    fn parse(name: &str) -> PyResult<ResultsFormat> {
+        match name {
+            "json" => Ok(ResultsFormat::Json),
+            "tsv" => Ok(ResultsFormat::Tsv),
+            "parquet" => Ok(ResultsFormat::Parquet),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown results format '{}'. Expected 'json', 'tsv', or 'parquet'.",
+                name
+            ))),
+        }
+    }
+
+    /// Format of a path from its extension, or `None` for file-like objects and unknown extensions
+    fn from_path(obj: &Bound<'_, PyAny>) -> Option<ResultsFormat> {
+        let path = obj.extract::<&str>().ok()?.to_lowercase();
+        if path.ends_with(".json") {
+            Some(ResultsFormat::Json)
+        } else if path.ends_with(".parquet") || path.ends_with(".pq") {
+            Some(ResultsFormat::Parquet)
+        } else if path.ends_with(".tsv") || path.ends_with(".txt") {
+            Some(ResultsFormat::Tsv)
+        } else {
+            None
+        }
+    }
+}
+
+/// Text of saved results, before the values are converted to Python objects
+enum SavedResults {
+    Json(Value),
+    /// Header and rows of a tab-separated table
+    Table(Vec<String>, Vec<Vec<String>>),
+}
+
+/// Parses saved results as `format`, or as JSON if the text starts with `[` or `{` and as a table otherwise
+fn parse_results(content: &str, format: Option<&ResultsFormat>) -> Result<SavedResults, String> {
+    let is_json = match format {
+        Some(ResultsFormat::Json) => true,
+        Some(_) => false,
+        None => content.trim_start().starts_with(['[', '{']),
+    };
+    if is_json {
+        let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        return match value {
+            Value::Array(_) | Value::Object(_) => Ok(SavedResults::Json(value)),
+            _ => Err("Results must be a list or a dictionary.".to_owned()),
+        };
+    }
+    let mut lines = content.lines().filter(|x| !x.is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some(line) => line.split('\t').map(|x| x.to_owned()).collect(),
+        None => return Err("Results have no header.".to_owned()),
+    };
+    let rows = lines
+        .enumerate()
+        .map(|(i, line)| {
+            let row: Vec<String> = line.split('\t').map(|x| x.to_owned()).collect();
+            if row.len() == header.len() {
+                Ok(row)
+            } else {
+                Err(format!(
+                    "Row {} has {} columns, but the header has {}.",
+                    i + 1,
+                    row.len(),
+                    header.len()
+                ))
+            }
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(SavedResults::Table(header, rows))
+}
+
+/// Columns of results that are always integers, like the overlap of ORA
+const INTEGER_COLUMNS: [&str; 7] = [
+    "overlap",
+    "list_only",
+    "set_only",
+    "neither",
+    "size",
+    "leading_edge",
+    "rank_at_max",
+];
+
+/// Columns of results that are always floats, even with a whole value, like the expected overlap of ORA
+const FLOAT_COLUMNS: [&str; 10] = [
+    "p",
+    "fdr",
+    "expected",
+    "enrichment_ratio",
+    "expected_sd",
+    "z",
+    "es",
+    "nes",
+    "nes_ci_low",
+    "nes_ci_high",
+];
+
+/// Value of a cell of a results table, written as the `str` of a Python value by `ResultStream.write_tsv`
+fn cell_to_python(py: Python<'_>, column: &str, cell: &str) -> PyResult<PyObject> {
+    // set names are kept as text, even if they look like numbers
+    if column == "set" {
+        return cell.into_py_any(py);
+    }
+    // known numeric columns keep their type whichever way the tool that wrote the table formatted them
+    if INTEGER_COLUMNS.contains(&column) {
+        if let Ok(x) = cell.parse::<f64>() {
+            if x.fract() == 0.0 {
+                return (x as i64).into_py_any(py);
+            }
+        }
+    }
+    if FLOAT_COLUMNS.contains(&column) {
+        if let Ok(x) = cell.parse::<f64>() {
+            return x.into_py_any(py);
+        }
+    }
+    if let Ok(x) = cell.parse::<i64>() {
+        return x.into_py_any(py);
+    }
+    if let Ok(x) = cell.parse::<f64>() {
+        return x.into_py_any(py);
+    }
+    match cell {
+        "None" => return Ok(py.None()),
+        "True" => return true.into_py_any(py),
+        "False" => return false.into_py_any(py),
+        _ => {}
+    }
+    if cell.starts_with(['[', '{', '(']) {
+        if let Ok(value) = py.import("ast")?.call_method1("literal_eval", (cell,)) {
+            return value.into_py_any(py);
+        }
+    }
+    cell.into_py_any(py)
+}
+
+/// Rows of a Parquet file, read with pandas, with NumPy values converted to Python values
+fn parquet_results<'a>(py: Python<'a>, obj: &Bound<'a, PyAny>) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let pandas = py.import("pandas").map_err(|_| {
+        PyValueError::new_err("pandas (with pyarrow) must be installed to load Parquet results!")
+    })?;
+    let rows: Vec<Bound<'_, PyDict>> = pandas
+        .call_method1("read_parquet", (obj,))?
+        .call_method1("to_dict", ("records",))?
+        .extract()?;
+    for row in rows.iter() {
+        for (key, value) in row.iter() {
+            if value.hasattr("tolist")? {
+                row.set_item(key, value.call_method0("tolist")?)?;
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Load results saved to disk, to run post-processing like [`kappa_clusters`](./kappa_clusters.md),
+/// [`generate_markdown`](./generate_markdown.md), or [`compare_results`](./compare_results.md) without running the analysis again.
+///
+/// Results can be saved as:
+///
+/// - JSON, like with `json.dump(res, f)`, including the output of `with_metadata=True` and of the meta-analyses
+/// - A tab-separated table with a header, like from `ResultStream.write_tsv` or `pandas.DataFrame(res).to_csv(path, sep="\t", index=False)`
+/// - Parquet, like from `pandas.DataFrame(res).to_parquet(path)`. Reading Parquet needs pandas and pyarrow.
+///
+/// # Parameters
+/// - `path` - `String` of the path to the saved results, or a file-like object
+/// - `format` - Either `"json"`, `"tsv"`, or `"parquet"`. If not provided, the format is found from the extension of `path`
+///   (`.json`, `.tsv` or `.txt`, and `.parquet` or `.pq`), or from the content, with text starting with `[` or `{` read as JSON (default: `None`)
+/// - `is_content` - If `True`, `path` is the text of the results instead of its path (default: `False`)
+///
+/// # Returns
+///
+/// Returns the results in the same form they were saved, usually a list of result dictionaries.
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if the metadata of the results has a newer major `schema_version` (see [`schema_version`](./schema_version.md)).
+/// In a table, numbers, `None`, `True`, `False`, and lists written by Python are converted back to their values,
+/// and the `set` column is always kept as text.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the file can not be read or is malformed, or if pandas is not installed for Parquet results.
+///
+/// # Example
+///
+/// ```python
+/// import json
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// with open("ora_results.json", "w") as f:
+///     json.dump(res, f)
+///
+/// # later, without running the ORA again
+/// res = webgestaltpy.load_results("ora_results.json")
+/// webgestaltpy.generate_markdown(res, "report.md")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (path, format=None, is_content=false))]
+pub fn load_results(
+    py: Python<'_>,
+    path: &Bound<'_, PyAny>,
+    format: Option<&str>,
+    is_content: bool,
+) -> PyResult<PyObject> {
+    let format = match format {
+        Some(format) => Some(ResultsFormat::parse(format)?),
+        None if is_content => None,
+        None => ResultsFormat::from_path(path),
+    };
+    if let Some(ResultsFormat::Parquet) = format {
+        return parquet_results(py, path)?.into_py_any(py);
+    }
+    let path = inputs::content(path, is_content)?;
+    let saved = inputs::parse_text_input(&path, "results", |x| parse_results(x, format.as_ref()))?
+        .ok_or_else(|| PyValueError::new_err("Results must be a path or a file-like object!"))?;
+    match saved {
+        SavedResults::Json(value) => {
+            let saved_major = value
+                .pointer("/metadata/schema_version")
+                .and_then(|x| x.as_str())
+                .and_then(manifest::schema_major);
+            let major = manifest::schema_major(manifest::SCHEMA_VERSION);
+            if saved_major > major {
+                warnings::warn(
+                    py,
+                    &format!(
+                        "Results were saved with schema version {}, which is newer than {}. Some fields may have changed.",
+                        value["metadata"]["schema_version"].as_str().unwrap_or_default(),
+                        manifest::SCHEMA_VERSION
+                    ),
+                )?;
+            }
+            manifest::to_python(py, &value)
+        }
+        SavedResults::Table(header, rows) => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    let dict = PyDict::new(py);
+                    for (column, cell) in header.iter().zip(row) {
+                        // missing values are left out, like the keys missing from a result
+                        if !cell.is_empty() {
+                            dict.set_item(column, cell_to_python(py, column, cell)?)?;
+                        }
+                    }
+                    Ok(dict)
+                })
+                .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()?;
+            PyList::new(py, rows)?.into_py_any(py)
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{config, inputs};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::{FxHashMap, FxHashSet};
+use webgestalt_lib::readers::utils::Item;
+
+/// Default upper edges of the bins of the set size histogram
+const SIZE_BINS: [usize; 8] = [5, 10, 15, 25, 50, 100, 200, 500];
+
+/// A collection of analyte sets read from a GMT file.
+///
+/// # Parameters
+/// - `path` - `String` of the path to the GMT file of interest, a file-like object, or a dictionary mapping each set name to a list of analytes
+/// - `is_content` - If `True`, `path` is the text of the GMT file instead of its path (default: `False`)
+///
+/// # Methods
+///
+/// - `sets_containing(genes)` - Finds the sets that contain one or more analytes
+/// - `summary(reference=None, bins=None)` - Counts the sets and analytes, with a histogram of the set sizes
+/// - `restrict_to(genes)` - Keeps only the analytes of a universe, like the genes of a targeted panel
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// gmt = webgestaltpy.Gmt("data/kegg.gmt")
+/// print(len(gmt))
+/// ```
+#[pyclass]
+pub struct Gmt {
+    pub items: Vec<Item>,
+    /// Maps each analyte to the indices of the sets that contain it
+    index: FxHashMap<String, Vec<usize>>,
+}
+
+impl Gmt {
+    pub fn from_items(items: Vec<Item>) -> Gmt {
+        let mut index: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+        for (i, item) in items.iter().enumerate() {
+            for analyte in item.parts.iter() {
+                let sets = index.entry(analyte.clone()).or_default();
+                if sets.last() != Some(&i) {
+                    sets.push(i);
+                }
+            }
+        }
+        Gmt { items, index }
+    }
+}
+
+#[pymethods]
+impl Gmt {
+    #[new]
+    #[pyo3(signature = (path, is_content=false))]
+    fn new(path: &Bound<'_, PyAny>, is_content: bool) -> PyResult<Self> {
+        Ok(Gmt::from_items(inputs::gmt(&inputs::content(
+            path, is_content,
+        )?)?))
+    }
+
+    fn __len__(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Find the sets that contain the provided analyte(s).
+    ///
+    /// # Parameters
+    /// - `genes` - A single analyte, or a list of analytes, as `String`s or integers
+    ///
+    /// # Returns
+    ///
+    /// Returns a list of dictionaries with the `set`, its `size`, and the queried `genes` found in the set.
+    /// Sets containing more of the queried analytes are listed first.
+    ///
+    /// # Example
+    ///
+    /// ```python
+    /// import webgestaltpy
+    ///
+    /// gmt = webgestaltpy.Gmt("data/kegg.gmt")
+    /// res = gmt.sets_containing(["7157", "1956"])
+    /// ```
+    fn sets_containing<'a>(
+        &self,
+        py: Python<'a>,
+        genes: &Bound<'_, PyAny>,
+    ) -> PyResult<Vec<Bound<'a, PyDict>>> {
+        let genes = inputs::analyte_ids(genes)?;
+        let mut matches: FxHashMap<usize, Vec<String>> = FxHashMap::default();
+        for gene in genes {
+            if let Some(sets) = self.index.get(&gene) {
+                for set in sets {
+                    let found = matches.entry(*set).or_default();
+                    if !found.contains(&gene) {
+                        found.push(gene.clone());
+                    }
+                }
+            }
+        }
+        let mut matches: Vec<(usize, Vec<String>)> = matches.into_iter().collect();
+        matches.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(&b.0)));
+        let mut res: Vec<Bound<'_, PyDict>> = Vec::new();
+        for (i, found) in matches {
+            let item = &self.items[i];
+            let dict = PyDict::new(py);
+            dict.set_item("set", &item.id)?;
+            dict.set_item("size", item.parts.len())?;
+            dict.set_item("genes", found)?;
+            res.push(dict);
+        }
+        Ok(res)
+    }
+
+    /// Restrict every set to the analytes of a universe, like the genes measured by a targeted panel, before an analysis.
+    ///
+    /// Sets keep their order, URL, and the order of their analytes. Sets with no analytes in the universe are left out.
+    ///
+    /// # Parameters
+    /// - `genes` - The universe, in any form accepted as a reference list by [`ora`](./ora.md)
+    ///
+    /// # Returns
+    ///
+    /// Returns a new `Gmt` with the restricted sets. The original `Gmt` is not changed.
+    ///
+    /// # Errors
+    ///
+    /// Raises a `ValueError` if `genes` can not be read.
+    ///
+    /// # Example
+    ///
+    /// ```python
+    /// import webgestaltpy
+    ///
+    /// gmt = webgestaltpy.Gmt("data/kegg.gmt")
+    /// panel = gmt.restrict_to("data/reference.txt")
+    /// res = webgestaltpy.ora(panel, "data/genelist.txt", "data/reference.txt")
+    /// ```
+    fn restrict_to(&self, genes: &Bound<'_, PyAny>) -> PyResult<Gmt> {
+        let universe: FxHashSet<String> = inputs::analyte_list(genes)?.into_iter().collect();
+        Ok(Gmt::from_items(
+            self.items
+                .iter()
+                .map(|x| Item {
+                    id: x.id.clone(),
+                    url: x.url.clone(),
+                    parts: x
+                        .parts
+                        .iter()
+                        .filter(|a| universe.contains(*a))
+                        .cloned()
+                        .collect(),
+                })
+                .filter(|x| !x.parts.is_empty())
+                .collect(),
+        ))
+    }
+
+    /// Summarize the sets, like for quality control before an analysis or a table of the databases used.
+    ///
+    /// # Parameters
+    /// - `reference` - If provided, a reference list in any form accepted by [`ora`](./ora.md), to count how much of it is annotated (default: `None`)
+    /// - `bins` - Upper edges of the bins of the size histogram, positive and in increasing order (default: `[5, 10, 15, 25, 50, 100, 200, 500]`)
+    ///
+    /// # Returns
+    ///
+    /// Returns a dictionary with:
+    ///
+    /// - `sets` - Number of sets
+    /// - `universe` - Number of distinct analytes in any set
+    /// - `min_size`, `median_size`, `mean_size`, `max_size` - Statistics of the number of distinct analytes in each set (`None` if there are no sets)
+    /// - `histogram` - List of dictionaries of the bins of the set sizes, each with the `min` and `max` size in the bin (`None` for the last bin) and its `count`
+    ///
+    /// If `reference` is provided, also contains:
+    ///
+    /// - `reference_size` - Number of distinct analytes in the reference list
+    /// - `annotated` - Number of analytes of the reference list in at least one set
+    /// - `reference_coverage` - Fraction of the reference list in at least one set (`None` if the reference list is empty)
+    /// - `universe_coverage` - Fraction of the analytes of the sets in the reference list (`None` if there are no analytes)
+    /// - `testable_sets` - Number of sets whose size within the reference list is between the default `min_set_size` and `max_set_size` of ORA
+    ///
+    /// # Errors
+    ///
+    /// Raises a `ValueError` if the reference list can not be read, or if `bins` is not positive and increasing.
+    ///
+    /// # Example
+    ///
+    /// ```python
+    /// import webgestaltpy
+    ///
+    /// gmt = webgestaltpy.Gmt("data/kegg.gmt")
+    /// summary = gmt.summary("data/reference.txt")
+    /// print(summary["sets"], summary["universe"], summary["reference_coverage"])
+    /// ```
+    #[pyo3(signature = (reference=None, bins=None))]
+    fn summary<'a>(
+        &self,
+        py: Python<'a>,
+        reference: Option<&Bound<'_, PyAny>>,
+        bins: Option<Vec<usize>>,
+    ) -> PyResult<Bound<'a, PyDict>> {
+        let bins = bins.unwrap_or_else(|| SIZE_BINS.to_vec());
+        if bins.first() == Some(&0) || bins.windows(2).any(|x| x[0] >= x[1]) {
+            return Err(PyValueError::new_err(
+                "bins must be positive and increasing!",
+            ));
+        }
+        let members: Vec<FxHashSet<&String>> = self
+            .items
+            .iter()
+            .map(|x| x.parts.iter().collect())
+            .collect();
+        let mut sizes: Vec<usize> = members.iter().map(|x| x.len()).collect();
+        sizes.sort_unstable();
+        let mut counts = vec![0_usize; bins.len() + 1];
+        for size in sizes.iter() {
+            counts[bins.partition_point(|x| x <= size)] += 1;
+        }
+        let histogram: Vec<Bound<'_, PyDict>> = counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let min = if i == 0 { 0 } else { bins[i - 1] };
+                let max = bins.get(i).map(|x| x - 1);
+                let dict = PyDict::new(py);
+                dict.set_item("min", min)?;
+                dict.set_item("max", max)?;
+                dict.set_item("count", count)?;
+                Ok(dict)
+            })
+            .collect::<PyResult<_>>()?;
+        let median = match sizes.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(sizes[n / 2] as f64),
+            n => Some((sizes[n / 2 - 1] + sizes[n / 2]) as f64 / 2.0),
+        };
+        let mean = if sizes.is_empty() {
+            None
+        } else {
+            Some(sizes.iter().sum::<usize>() as f64 / sizes.len() as f64)
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("sets", self.items.len())?;
+        dict.set_item("universe", self.index.len())?;
+        dict.set_item("min_size", sizes.first())?;
+        dict.set_item("median_size", median)?;
+        dict.set_item("mean_size", mean)?;
+        dict.set_item("max_size", sizes.last())?;
+        dict.set_item("histogram", histogram)?;
+        if let Some(reference) = reference {
+            let reference: FxHashSet<String> =
+                inputs::analyte_list(reference)?.into_iter().collect();
+            let annotated = reference
+                .iter()
+                .filter(|x| self.index.contains_key(*x))
+                .count();
+            let fraction = |count: usize, total: usize| {
+                if total > 0 {
+                    Some(count as f64 / total as f64)
+                } else {
+                    None
+                }
+            };
+            let config = config::ora_config();
+            let testable = members
+                .iter()
+                .map(|x| x.iter().filter(|a| reference.contains(**a)).count())
+                .filter(|x| *x >= config.min_set_size && *x <= config.max_set_size)
+                .count();
+            dict.set_item("reference_size", reference.len())?;
+            dict.set_item("annotated", annotated)?;
+            dict.set_item("reference_coverage", fraction(annotated, reference.len()))?;
+            dict.set_item("universe_coverage", fraction(annotated, self.index.len()))?;
+            dict.set_item("testable_sets", testable)?;
+        }
+        Ok(dict)
+    }
+}
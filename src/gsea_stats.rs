@@ -0,0 +1,821 @@
+use crate::{config, inputs};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::prelude::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use webgestalt_lib::methods::gsea::{GSEAConfig, GSEAResult, RankListItem};
+use webgestalt_lib::readers::utils::Item;
+
+/// Rank list sorted the same way as `webgestalt_lib` does before scoring, split into analytes and
+/// the absolute rank values used as weights.
+pub struct SortedRankList {
+    pub analytes: Vec<String>,
+    pub weights: Vec<f64>,
+}
+
+impl SortedRankList {
+    pub fn new(rank_list: &[RankListItem]) -> SortedRankList {
+        let mut rank_list = rank_list.to_vec();
+        rank_list.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+        SortedRankList {
+            analytes: rank_list.iter().map(|x| x.analyte.clone()).collect(),
+            weights: rank_list.iter().map(|x| x.rank.abs()).collect(),
+        }
+    }
+}
+
+/// Creates random index permutations for GSEA from `seed`. Equivalent to
+/// [`webgestalt_lib::methods::gsea::make_permutations`], which is not seeded.
+pub fn make_permutations(permutations: i32, max: usize, seed: u64) -> Vec<Vec<usize>> {
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+    (0..permutations)
+        .map(|_| {
+            let mut order: Vec<usize> = (0..max).collect();
+            order.shuffle(&mut rng);
+            order
+        })
+        .collect()
+}
+
+/// Permutations of GSEA from `obj`, either a matrix with a permutation of the positions of `analytes` on each row, or a callable that
+/// returns one from `analytes`, the number of permutations, and `seed`
+pub fn custom_permutations(
+    obj: &Bound<'_, PyAny>,
+    analytes: &[String],
+    permutations: i32,
+    seed: u64,
+) -> PyResult<Vec<Vec<usize>>> {
+    let matrix = if obj.is_callable() {
+        obj.call1((analytes.to_vec(), permutations, seed))?
+    } else {
+        obj.clone()
+    };
+    // NumPy arrays of any integer type are converted at once, instead of element by element
+    let matrix = if matrix.hasattr("tolist")? {
+        matrix.call_method0("tolist")?
+    } else {
+        matrix
+    };
+    let matrix: Vec<Vec<usize>> = matrix.extract().map_err(|_| {
+        PyValueError::new_err(
+            "permutations must be a matrix of indices or a callable returning one!",
+        )
+    })?;
+    if matrix.is_empty() {
+        return Err(PyValueError::new_err(
+            "permutations must have at least one row!",
+        ));
+    }
+    for (i, order) in matrix.iter().enumerate() {
+        let mut seen = vec![false; analytes.len()];
+        let is_permutation = order.len() == analytes.len()
+            && order
+                .iter()
+                .all(|x| *x < seen.len() && !std::mem::replace(&mut seen[*x], true));
+        if !is_permutation {
+            return Err(PyValueError::new_err(format!(
+                "Row {} of permutations is not a permutation of the positions of the {} analytes of the rank list!",
+                i,
+                analytes.len()
+            )));
+        }
+    }
+    Ok(matrix)
+}
+
+/// Enrichment score of the set where `in_set[order[i]]` is the weight in the set of the analyte at position `i`, `1` for the
+/// analytes of an unweighted set and `0` for the analytes outside the set
+fn enrichment_score(
+    in_set: &[f64],
+    weights: &[f64],
+    order: &[usize],
+    inverse_size_dif: f64,
+) -> f64 {
+    let mut n_r: f64 = 0.0;
+    for (i, w) in weights.iter().enumerate() {
+        let member = in_set[order[i]];
+        if member != 0.0 {
+            n_r += w * member;
+        }
+    }
+    let inverse_nr = 1.0 / n_r;
+    let (mut sum_hits, mut sum_miss, mut max_score) = (0.0, 0.0, 0.0_f64);
+    for (i, w) in weights.iter().enumerate() {
+        let member = in_set[order[i]];
+        if member != 0.0 {
+            sum_hits += w * member;
+        } else {
+            sum_miss += 1.0;
+        }
+        let es = sum_hits * inverse_nr - sum_miss * inverse_size_dif;
+        if es.abs() > max_score.abs() {
+            max_score = es;
+        }
+    }
+    max_score
+}
+
+/// Checks the `permute` argument of GSEA. Only the analytes of the rank list can be permuted, since phenotype permutation
+/// needs an expression matrix with the sample labels.
+pub fn check_permute(permute: &str) -> PyResult<()> {
+    match permute {
+        "gene" => Ok(()),
+        "phenotype" => Err(PyValueError::new_err(
+            "Phenotype permutation needs an expression matrix, which is not supported yet. Use 'gene'.",
+        )),
+        _ => Err(PyValueError::new_err(format!(
+            "Unknown permute value '{}'. Expected 'gene' or 'phenotype'.",
+            permute
+        ))),
+    }
+}
+
+/// Number of analytes of the rank list with the same rank as another analyte
+pub fn tied_count(rank_list: &[RankListItem]) -> usize {
+    let mut ranks: Vec<f64> = rank_list.iter().map(|x| x.rank).collect();
+    ranks.sort_by(|a, b| a.total_cmp(b));
+    ranks
+        .iter()
+        .enumerate()
+        .filter(|(i, x)| {
+            (*i > 0 && ranks[i - 1] == **x) || ranks.get(i + 1).is_some_and(|y| y == *x)
+        })
+        .count()
+}
+
+/// Seed to use for the permutations, generating a new one if none is provided
+pub fn seed_or_random(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| rand::random::<u32>() as u64)
+}
+
+/// Running enrichment score of `set` along the rank list, with the same weighting as `webgestalt_lib`
+pub fn running_sum(rank_list: &SortedRankList, set: &Item) -> Vec<f64> {
+    let members: FxHashSet<&String> = set.parts.iter().collect();
+    let in_set: Vec<bool> = rank_list
+        .analytes
+        .iter()
+        .map(|x| members.contains(x))
+        .collect();
+    let n_r: f64 = rank_list
+        .weights
+        .iter()
+        .zip(in_set.iter())
+        .filter(|(_, x)| **x)
+        .map(|(w, _)| w)
+        .sum();
+    let inverse_size_dif = 1.0 / ((rank_list.analytes.len() - members.len()) as f64);
+    let inverse_nr = 1.0 / n_r;
+    let (mut sum_hits, mut sum_miss) = (0.0, 0.0);
+    let mut sums: Vec<f64> = Vec::with_capacity(in_set.len());
+    for (w, hit) in rank_list.weights.iter().zip(in_set.iter()) {
+        if *hit {
+            sum_hits += w;
+        } else {
+            sum_miss += 1.0;
+        }
+        sums.push(sum_hits * inverse_nr - sum_miss * inverse_size_dif);
+    }
+    sums
+}
+
+/// Position of the maximum deviation of a running sum from zero, which is the enrichment score.
+/// The first position is used if tied, like `webgestalt_lib`. `None` if the running sum is empty.
+pub fn peak(sums: &[f64]) -> Option<usize> {
+    let mut peak = 0;
+    for (i, x) in sums.iter().enumerate() {
+        if x.abs() > sums[peak].abs() {
+            peak = i;
+        }
+    }
+    if sums.is_empty() {
+        None
+    } else {
+        Some(peak)
+    }
+}
+
+/// Analytes of `set` in the leading edge: the members ranked before the maximum deviation of the
+/// running sum for a positive enrichment score, or after it for a negative one.
+pub fn leading_edge_analytes(rank_list: &SortedRankList, set: &Item) -> Vec<String> {
+    let sums = running_sum(rank_list, set);
+    let members: FxHashSet<&String> = set.parts.iter().collect();
+    let range = match peak(&sums) {
+        Some(i) if sums[i] < 0.0 => i..sums.len(),
+        Some(i) => 0..i + 1,
+        None => 0..0,
+    };
+    rank_list.analytes[range]
+        .iter()
+        .filter(|x| members.contains(x))
+        .cloned()
+        .collect()
+}
+
+/// Enrichment score of `set` in each permutation, in the order of `permutations`.
+///
+/// Uses the same scoring as `webgestalt_lib`, so passing the same `permutations` to
+/// [`webgestalt_lib::methods::gsea::gsea`] gives the null behind its results.
+/// Returns an empty `Vec` if the set is outside the overlap limits and was not tested.
+pub fn null_es(
+    rank_list: &SortedRankList,
+    set: &Item,
+    permutations: &[Vec<usize>],
+    min_overlap: i32,
+    max_overlap: i32,
+) -> Vec<f64> {
+    match membership(rank_list, set, None, min_overlap, max_overlap) {
+        Some((in_set, inverse_size_dif)) => permutations
+            .par_iter()
+            .map(|order| enrichment_score(&in_set, &rank_list.weights, order, inverse_size_dif))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Weight in `set` of each analyte of the rank list, from `weights` in the order of the analytes of the set or `1` without weights,
+/// and `0` for analytes outside the set. Returned with the inverse of the number of analytes outside the set, the same way
+/// as `webgestalt_lib`. `None` if the set is outside the overlap limits and is not tested.
+fn membership(
+    rank_list: &SortedRankList,
+    set: &Item,
+    weights: Option<&[f64]>,
+    min_overlap: i32,
+    max_overlap: i32,
+) -> Option<(Vec<f64>, f64)> {
+    let members: FxHashMap<&String, f64> = match weights {
+        Some(weights) => set.parts.iter().zip(weights.iter().copied()).collect(),
+        None => set.parts.iter().map(|x| (x, 1.0)).collect(),
+    };
+    let in_set: Vec<f64> = rank_list
+        .analytes
+        .iter()
+        .map(|x| members.get(x).copied().unwrap_or(0.0))
+        .collect();
+    let overlap = in_set.iter().filter(|x| **x != 0.0).count() as i32;
+    if overlap < min_overlap || overlap > max_overlap {
+        return None;
+    }
+    let inverse_size_dif = 1.0 / ((rank_list.analytes.len() - members.len()) as f64);
+    Some((in_set, inverse_size_dif))
+}
+
+/// Estimated memory in bytes used by [`gsea`], which keeps the permutations, and the running sum and null enrichment scores of every set
+fn gsea_memory(analytes: usize, sets: usize, permutations: usize) -> usize {
+    8 * (permutations * analytes + sets * (analytes + permutations))
+}
+
+/// Whether GSEA should run with [`low_memory_gsea`], since its estimated memory is above `max_memory_mb`
+pub fn low_memory(analytes: usize, sets: usize, permutations: usize) -> bool {
+    config::defaults()
+        .max_memory_mb
+        .is_some_and(|mb| gsea_memory(analytes, sets, permutations) > mb * 1024 * 1024)
+}
+
+/// Observed scores of a tested set, kept until the FDR is computed. `null` and `running_sum` are empty in low-memory mode.
+struct SetScore {
+    es: f64,
+    nes: f64,
+    p: f64,
+    leading_edge: i32,
+    peak: usize,
+    null: Vec<f64>,
+    null_means: (f64, f64),
+    running_sum: Vec<f64>,
+}
+
+/// Enrichment score of the set along the rank list, with the leading edge count of `webgestalt_lib`, the position of the
+/// maximum deviation, and the running sum if `keep_running_sum` is `true`
+fn observed_score(
+    in_set: &[f64],
+    weights: &[f64],
+    inverse_size_dif: f64,
+    keep_running_sum: bool,
+) -> (f64, i32, usize, Vec<f64>) {
+    let n_r: f64 = weights
+        .iter()
+        .zip(in_set.iter())
+        .filter(|(_, x)| **x != 0.0)
+        .map(|(w, x)| w * x)
+        .sum();
+    let inverse_nr = 1.0 / n_r;
+    let (mut sum_hits, mut sum_miss, mut max_score) = (0.0, 0.0, 0.0_f64);
+    let (mut hits, mut max_hits, mut peak) = (0, 0, 0);
+    let mut running_sum: Vec<f64> = Vec::new();
+    for (i, (w, member)) in weights.iter().zip(in_set.iter()).enumerate() {
+        if *member != 0.0 {
+            sum_hits += w * member;
+            hits += 1;
+        } else {
+            sum_miss += 1.0;
+        }
+        let es = sum_hits * inverse_nr - sum_miss * inverse_size_dif;
+        if keep_running_sum {
+            running_sum.push(es);
+        }
+        if es.abs() > max_score.abs() {
+            max_score = es;
+            max_hits = hits;
+            peak = i;
+        }
+    }
+    let leading_edge = if max_score > 0.0 {
+        max_hits
+    } else {
+        in_set.len() as i32 - max_hits
+    };
+    (max_score, leading_edge, peak, running_sum)
+}
+
+/// Null NES of each permutation enrichment score, normalized by `null_means`. Like `webgestalt_lib`, the positive scores come
+/// first, then the negative ones, with scores of `0` in both.
+fn nes_values(es: &[f64], null_means: (f64, f64)) -> impl Iterator<Item = f64> + '_ {
+    let (up_avg, down_avg) = null_means;
+    es.iter()
+        .filter(|x| **x >= 0.0)
+        .map(move |x| x / up_avg)
+        .chain(es.iter().filter(|x| **x <= 0.0).map(move |x| -x / down_avg))
+}
+
+/// Enrichment score of a set in each permutation, scored one permutation after the other
+fn permutation_scores(
+    rank_list: &SortedRankList,
+    in_set: &[f64],
+    inverse_size_dif: f64,
+    permutations: &[Vec<usize>],
+) -> Vec<f64> {
+    permutations
+        .iter()
+        .map(|order| enrichment_score(in_set, &rank_list.weights, order, inverse_size_dif))
+        .collect()
+}
+
+/// Scores a tested set, with the p-value and NES computed like `webgestalt_lib`
+fn score_set(
+    rank_list: &SortedRankList,
+    in_set: &[f64],
+    inverse_size_dif: f64,
+    permutations: &[Vec<usize>],
+    low_memory: bool,
+) -> SetScore {
+    let (es, leading_edge, peak, running_sum) =
+        observed_score(in_set, &rank_list.weights, inverse_size_dif, !low_memory);
+    let null = permutation_scores(rank_list, in_set, inverse_size_dif, permutations);
+    let side: Vec<&f64> = if es >= 0.0 {
+        null.iter().filter(|x| **x >= 0.0).collect()
+    } else {
+        null.iter().filter(|x| **x < 0.0).collect()
+    };
+    let p = if side.is_empty() {
+        0.0
+    } else {
+        side.iter().filter(|x| x.abs() >= es.abs()).count() as f64 / side.len() as f64
+    };
+    let null_means = null_means(&null);
+    let nes = if es >= 0.0 {
+        if null.iter().any(|x| *x >= 0.0) {
+            es / null_means.0
+        } else {
+            0.0
+        }
+    } else if null.iter().any(|x| *x <= 0.0) {
+        -es / null_means.1
+    } else {
+        0.0
+    };
+    SetScore {
+        es,
+        nes,
+        p,
+        leading_edge,
+        peak,
+        null: if low_memory { Vec::new() } else { null },
+        null_means,
+        running_sum,
+    }
+}
+
+/// Number of values counted in `histogram` at least as large as each of the sorted `thresholds`, where `histogram[k]` is the
+/// number of values with exactly `k` thresholds at most as large
+fn counts_at_least(thresholds: &[f64], histogram: &[usize]) -> Vec<usize> {
+    let mut above = vec![0; histogram.len() + 1];
+    for k in (0..histogram.len()).rev() {
+        above[k] = above[k + 1] + histogram[k];
+    }
+    thresholds
+        .iter()
+        .map(|t| above[thresholds.partition_point(|x| x < t) + 1])
+        .collect()
+}
+
+/// Runs GSEA, keeping the null enrichment scores of every set or, in low-memory mode, scoring them again for the FDR.
+/// With `set_weights`, the analytes of each set are weighted by their weight in the set.
+fn run_gsea(
+    rank_list: &[RankListItem],
+    gmt: &[Item],
+    set_weights: Option<&[Vec<f64>]>,
+    config: &GSEAConfig,
+    permutations: &[Vec<usize>],
+    low_memory: bool,
+) -> (Vec<GSEAResult>, Vec<Option<usize>>) {
+    let sorted = SortedRankList::new(rank_list);
+    let set_membership = |i: usize| {
+        membership(
+            &sorted,
+            &gmt[i],
+            set_weights.map(|x| x[i].as_slice()),
+            config.min_overlap,
+            config.max_overlap,
+        )
+    };
+    let scores: Vec<Option<SetScore>> = (0..gmt.len())
+        .into_par_iter()
+        .map(|i| {
+            set_membership(i).map(|(in_set, inverse_size_dif)| {
+                score_set(&sorted, &in_set, inverse_size_dif, permutations, low_memory)
+            })
+        })
+        .collect();
+    let observed: Vec<f64> = scores
+        .iter()
+        .map(|x| x.as_ref().map_or(0.0, |x| x.nes))
+        .collect();
+    // the null NES of every set are compared with the observed NES on their side: above 0 for the positive
+    // side, and at most 0 for the negative one. Counts are added up, so the order of the sets does not matter.
+    let mut positive: Vec<f64> = observed.iter().filter(|x| **x > 0.0).cloned().collect();
+    let mut negative: Vec<f64> = observed
+        .iter()
+        .filter(|x| **x <= 0.0)
+        .map(|x| x.abs())
+        .collect();
+    positive.sort_by(|a, b| a.total_cmp(b));
+    negative.sort_by(|a, b| a.total_cmp(b));
+    let empty = || (vec![0; positive.len() + 1], vec![0; negative.len() + 1]);
+    let (positive_histogram, negative_histogram) = scores
+        .par_iter()
+        .enumerate()
+        .filter_map(|(i, score)| score.as_ref().map(|x| (i, x)))
+        .fold(empty, |(mut up, mut down), (i, score)| {
+            let recomputed: Vec<f64>;
+            let null = if low_memory {
+                let (in_set, inverse_size_dif) = set_membership(i).unwrap();
+                recomputed = permutation_scores(&sorted, &in_set, inverse_size_dif, permutations);
+                &recomputed
+            } else {
+                &score.null
+            };
+            for x in nes_values(null, score.null_means) {
+                if x >= 0.0 {
+                    up[positive.partition_point(|t| *t <= x)] += 1;
+                } else {
+                    down[negative.partition_point(|t| *t <= x.abs())] += 1;
+                }
+            }
+            (up, down)
+        })
+        .reduce(empty, |(mut up, mut down), (other_up, other_down)| {
+            up.iter_mut().zip(other_up).for_each(|(x, y)| *x += y);
+            down.iter_mut().zip(other_down).for_each(|(x, y)| *x += y);
+            (up, down)
+        });
+    let positive_counts = counts_at_least(&positive, &positive_histogram);
+    let negative_counts = counts_at_least(&negative, &negative_histogram);
+    let positive_len = positive_histogram.iter().sum::<usize>();
+    let negative_len = negative_histogram.iter().sum::<usize>();
+    let mut observed_positive: Vec<f64> = observed.iter().filter(|x| **x >= 0.0).cloned().collect();
+    let mut observed_negative: Vec<f64> = observed
+        .iter()
+        .filter(|x| **x < 0.0)
+        .map(|x| x.abs())
+        .collect();
+    observed_positive.sort_by(|a, b| a.total_cmp(b));
+    observed_negative.sort_by(|a, b| a.total_cmp(b));
+    gmt.iter()
+        .zip(scores)
+        .zip(observed)
+        .map(|((set, score), nes)| {
+            let nes_abs = nes.abs();
+            let (top_val, top_len) = if nes > 0.0 {
+                let i = positive.partition_point(|t| *t < nes_abs);
+                (positive_counts[i], positive_len)
+            } else {
+                let i = negative.partition_point(|t| *t < nes_abs);
+                (negative_counts[i], negative_len)
+            };
+            let bottom_side = if nes >= 0.0 {
+                &observed_positive
+            } else {
+                &observed_negative
+            };
+            let bottom_val = bottom_side.len() - bottom_side.partition_point(|t| *t < nes_abs);
+            let or_tiny = |len: usize| if len == 0 { 0.000001 } else { len as f64 };
+            let fdr = (top_val as f64 * or_tiny(bottom_side.len()))
+                / (bottom_val as f64 * or_tiny(top_len));
+            let peak = score.as_ref().map(|x| x.peak);
+            let result = match score {
+                Some(score) => GSEAResult {
+                    set: set.id.clone(),
+                    p: score.p,
+                    fdr,
+                    es: score.es,
+                    nes,
+                    leading_edge: score.leading_edge,
+                    running_sum: score.running_sum,
+                },
+                None => GSEAResult {
+                    set: set.id.clone(),
+                    p: 1.0,
+                    fdr,
+                    es: 0.0,
+                    nes,
+                    leading_edge: 0,
+                    running_sum: Vec::new(),
+                },
+            };
+            (result, peak)
+        })
+        .unzip()
+}
+
+/// GSEA with the same scoring, p-values, and FDR as [`webgestalt_lib::methods::gsea::gsea`], in the order of `gmt`.
+///
+/// Unlike `webgestalt_lib`, which sums the null enrichment scores in parallel, every sum is taken in order and the FDR is
+/// computed from counts, so the results are the same for any number of threads. The NES can differ from `webgestalt_lib`
+/// in the last digits. Sets outside the overlap limits have an empty running sum, like `webgestalt_lib`.
+pub fn gsea(
+    rank_list: &[RankListItem],
+    gmt: &[Item],
+    config: &GSEAConfig,
+    permutations: &[Vec<usize>],
+) -> Vec<GSEAResult> {
+    run_gsea(rank_list, gmt, None, config, permutations, false).0
+}
+
+/// [`gsea`] with the analytes of each set weighted by their weight in the set, like in a weighted GMT. The step of the running sum
+/// at an analyte of the set is its absolute rank times its weight, so a set with a weight of `1` for every analyte scores like [`gsea`].
+pub fn weighted_gsea(
+    rank_list: &[RankListItem],
+    gmt: &inputs::WeightedGmt,
+    config: &GSEAConfig,
+    permutations: &[Vec<usize>],
+) -> Vec<GSEAResult> {
+    run_gsea(
+        rank_list,
+        &gmt.items,
+        Some(&gmt.weights),
+        config,
+        permutations,
+        false,
+    )
+    .0
+}
+
+/// [`gsea`] in much less memory. The running sums are never kept, and the null enrichment scores are scored again in a second
+/// pass for the FDR instead of being kept for every set, at the cost of scoring the permutations twice. The results are the same.
+///
+/// Returns the results with empty running sums, and the position of the maximum deviation of the running sum of each set. The
+/// position is `None` if the set is outside the overlap limits and was not tested.
+pub fn low_memory_gsea(
+    rank_list: &[RankListItem],
+    gmt: &[Item],
+    config: &GSEAConfig,
+    permutations: &[Vec<usize>],
+) -> (Vec<GSEAResult>, Vec<Option<usize>>) {
+    run_gsea(rank_list, gmt, None, config, permutations, true)
+}
+
+/// Mean of the positive and of the negative permutation enrichment scores, which `webgestalt_lib` divides by to normalize them
+fn null_means(es: &[f64]) -> (f64, f64) {
+    let up: Vec<f64> = es.iter().filter(|x| **x >= 0.0).cloned().collect();
+    let down: Vec<f64> = es.iter().filter(|x| **x <= 0.0).cloned().collect();
+    let up_avg = if up.is_empty() {
+        0.000001
+    } else {
+        up.iter().sum::<f64>() / (up.len() as f64 + 0.000001) + 0.000001
+    };
+    let down_avg = if down.is_empty() {
+        -0.000001
+    } else {
+        down.iter().sum::<f64>() / (down.len() as f64 - 0.000001) - 0.000001
+    };
+    (up_avg, down_avg)
+}
+
+/// Normalized enrichment score of each permutation enrichment score, in the same order
+pub fn normalize_null(es: &[f64]) -> Vec<f64> {
+    let (up_avg, down_avg) = null_means(es);
+    es.iter()
+        .map(|x| if *x >= 0.0 { x / up_avg } else { -x / down_avg })
+        .collect()
+}
+
+/// Permutation null distribution of the normalized enrichment score of `set`.
+///
+/// Like `webgestalt_lib`, the positive scores come first, then the negative ones, with scores of `0` in both.
+/// Returns an empty `Vec` if the set is outside the overlap limits and was not tested.
+pub fn null_nes(
+    rank_list: &SortedRankList,
+    set: &Item,
+    permutations: &[Vec<usize>],
+    min_overlap: i32,
+    max_overlap: i32,
+) -> Vec<f64> {
+    let es = null_es(rank_list, set, permutations, min_overlap, max_overlap);
+    nes_values(&es, null_means(&es)).collect()
+}
+
+/// Standard error of an observed NES, estimated as the standard deviation of the permutation
+/// null NES values on the same side (sign) as `nes`.
+pub fn nes_standard_error(nes: f64, null: &[f64]) -> Option<f64> {
+    let side: Vec<f64> = null
+        .iter()
+        .filter(|x| if nes >= 0.0 { **x >= 0.0 } else { **x < 0.0 })
+        .cloned()
+        .collect();
+    if side.len() < 2 {
+        return None;
+    }
+    let mean = side.iter().sum::<f64>() / side.len() as f64;
+    let var = side.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (side.len() - 1) as f64;
+    if var > 0.0 {
+        Some(var.sqrt())
+    } else {
+        None
+    }
+}
+
+/// Rank lists resampled from the analytes of a rank list with replacement. Each resample is kept
+/// as the indices of the sampled analytes sorted by rank, with their weights.
+pub struct Bootstrap {
+    analytes: Vec<String>,
+    resamples: Vec<(Vec<usize>, Vec<f64>)>,
+}
+
+impl Bootstrap {
+    pub fn new(rank_list: &[RankListItem], resamples: usize, seed: u64) -> Bootstrap {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        let n = rank_list.len();
+        let resamples = (0..resamples)
+            .map(|_| {
+                let mut order: Vec<usize> = (0..n).map(|_| rng.gen_range(0..n)).collect();
+                order.sort_by(|a, b| rank_list[*b].rank.total_cmp(&rank_list[*a].rank));
+                let weights: Vec<f64> = order.iter().map(|x| rank_list[*x].rank.abs()).collect();
+                (order, weights)
+            })
+            .collect();
+        Bootstrap {
+            analytes: rank_list.iter().map(|x| x.analyte.clone()).collect(),
+            resamples,
+        }
+    }
+
+    /// Enrichment score of `set` in each resample, scored the same way as `webgestalt_lib`
+    pub fn enrichment_scores(&self, set: &Item) -> Vec<f64> {
+        let members: FxHashSet<&String> = set.parts.iter().collect();
+        let in_set: Vec<f64> = self
+            .analytes
+            .iter()
+            .map(|x| if members.contains(x) { 1.0 } else { 0.0 })
+            .collect();
+        let inverse_size_dif = 1.0 / ((self.analytes.len() - members.len()) as f64);
+        self.resamples
+            .par_iter()
+            .map(|(order, weights)| enrichment_score(&in_set, weights, order, inverse_size_dif))
+            .collect()
+    }
+}
+
+/// Value at quantile `q` of `values`, interpolating linearly between the closest values. Returns `None` if `values` is empty.
+pub fn quantile(values: &[f64], q: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let position = q * (sorted.len() - 1) as f64;
+    let (low, high) = (position.floor() as usize, position.ceil() as usize);
+    Some(sorted[low] + (sorted[high] - sorted[low]) * (position - low as f64))
+}
+
+/// Create the permutations of a rank list used by [`gsea`](./gsea.md) for a seed, to save the null distribution and reuse the exact same
+/// permutations across runs, or in other tools for a head-to-head comparison of methods.
+///
+/// # Parameters
+/// - `rank_list` - The rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `permutations` - Number of permutations. If not provided, uses the default number of permutations (see [`set_defaults`](./set_defaults.md)) (default: `None`)
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `analytes` - The analytes of the rank list sorted by decreasing rank, which the indices of `permutations` refer to
+/// - `permutations` - Matrix with a permutation of the positions of `analytes` on each row, as a list of lists
+/// - `seed` - Seed used for the permutations
+///
+/// Passing `permutations` (or the same matrix read back from a file) as the `permutations` of [`gsea`](./gsea.md) gives the same results
+/// as passing `seed`. A NumPy array of any integer type, like `int32`, can also be passed.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the rank list is malformed or can not be read, or if `permutations` is `0`.
+///
+/// # Example
+///
+/// ```python
+/// import numpy as np
+/// import webgestaltpy
+///
+/// m = webgestaltpy.permutation_matrix("data/test.rnk", seed=1)
+/// np.save("null.npy", np.array(m["permutations"], dtype=np.int32))
+///
+/// # later, or with another method
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk", permutations=np.load("null.npy"))
+/// ```
+#[pyfunction]
+#[pyo3(signature = (rank_list, permutations=None, seed=None))]
+pub fn permutation_matrix<'a>(
+    py: Python<'a>,
+    rank_list: &Bound<'_, PyAny>,
+    permutations: Option<i32>,
+    seed: Option<u64>,
+) -> PyResult<Bound<'a, PyDict>> {
+    if permutations.is_some_and(|x| x < 1) {
+        return Err(PyValueError::new_err(
+            "Number of permutations must be at least 1!",
+        ));
+    }
+    let rank_list = inputs::rank_list(rank_list)?;
+    let permutations = permutations.unwrap_or(config::defaults().permutations);
+    let seed = seed_or_random(seed);
+    let matrix = make_permutations(permutations, rank_list.len(), seed);
+    let dict = PyDict::new(py);
+    dict.set_item("analytes", SortedRankList::new(&rank_list).analytes)?;
+    dict.set_item("permutations", matrix)?;
+    dict.set_item("seed", seed)?;
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_data(name: &str) -> String {
+        std::fs::read_to_string(format!("{}/data/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+    }
+
+    /// The first sets of the KEGG GMT and the test rank list of `data/`, with few permutations to keep the tests fast
+    fn kegg_inputs() -> (Vec<RankListItem>, Vec<Item>, GSEAConfig, Vec<Vec<usize>>) {
+        let rank_list = inputs::parse_rank_list(&read_data("test.rnk")).unwrap();
+        let mut gmt = inputs::parse_gmt(&read_data("kegg.gmt")).unwrap();
+        gmt.truncate(40);
+        let config = GSEAConfig {
+            permutations: 100,
+            ..Default::default()
+        };
+        let permutations = make_permutations(config.permutations, rank_list.len(), 1);
+        (rank_list, gmt, config, permutations)
+    }
+
+    fn in_pool<R: Send>(threads: usize, f: impl FnOnce() -> R + Send) -> R {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(f)
+    }
+
+    #[test]
+    fn gsea_matches_webgestalt_lib() {
+        let (rank_list, gmt, config, permutations) = kegg_inputs();
+        let res = gsea(&rank_list, &gmt, &config, &permutations);
+        let expected =
+            webgestalt_lib::methods::gsea::gsea(rank_list, gmt, config, Some(permutations));
+        assert_eq!(res.len(), expected.len());
+        for (x, y) in res.iter().zip(expected.iter()) {
+            assert_eq!(x.set, y.set);
+            assert_eq!(x.p, y.p);
+            assert_eq!(x.es, y.es);
+            assert_eq!(x.leading_edge, y.leading_edge);
+            assert!((x.nes - y.nes).abs() <= 1e-9 * y.nes.abs().max(1.0));
+            assert!((x.fdr - y.fdr).abs() <= 1e-9);
+        }
+    }
+
+    #[test]
+    fn gsea_does_not_depend_on_the_thread_count() {
+        let (rank_list, gmt, config, permutations) = kegg_inputs();
+        let serial = in_pool(1, || gsea(&rank_list, &gmt, &config, &permutations));
+        let parallel = in_pool(4, || gsea(&rank_list, &gmt, &config, &permutations));
+        for (x, y) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(x.set, y.set);
+            assert_eq!(
+                [x.p, x.fdr, x.es, x.nes].map(f64::to_bits),
+                [y.p, y.fdr, y.es, y.nes].map(f64::to_bits)
+            );
+            assert_eq!(x.leading_edge, y.leading_edge);
+        }
+    }
+}
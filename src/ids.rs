@@ -0,0 +1,239 @@
+use crate::{config, inputs};
+use ahash::AHashSet;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// `id` without the version suffix of an Ensembl identifier, like `ENSG00000141510` for `ENSG00000141510.17`.
+/// Other identifiers are returned unchanged.
+pub fn strip_version(id: &str) -> &str {
+    if !id.starts_with("ENS") {
+        return id;
+    }
+    match id.rsplit_once('.') {
+        Some((base, version))
+            if !version.is_empty()
+                && version.bytes().all(|x| x.is_ascii_digit())
+                && base.bytes().all(|x| x.is_ascii_alphanumeric())
+                && base.ends_with(|x: char| x.is_ascii_digit()) =>
+        {
+            base
+        }
+        _ => id,
+    }
+}
+
+/// Normalized form of an identifier: without surrounding whitespace and quotes, in upper case, and without
+/// the version suffix of an Ensembl identifier
+pub fn normalize(id: &str) -> String {
+    let id = id
+        .trim()
+        .trim_matches(|x| x == '"' || x == '\'')
+        .trim()
+        .to_uppercase();
+    strip_version(&id).to_owned()
+}
+
+/// How the identifiers of the inputs are changed before matching
+#[derive(PartialEq)]
+enum Normalization {
+    None,
+    /// Only the version suffixes of Ensembl identifiers are removed
+    Versions,
+    /// Full normalization with [`normalize`]
+    Full,
+}
+
+/// Applies the default identifier normalization to analytes, if it is enabled with `set_defaults`
+pub struct Normalizer {
+    normalization: Normalization,
+}
+
+impl Normalizer {
+    pub fn from_defaults() -> Normalizer {
+        let defaults = config::defaults();
+        let normalization = if defaults.normalize_ids {
+            Normalization::Full
+        } else if defaults.ignore_id_versions {
+            Normalization::Versions
+        } else {
+            Normalization::None
+        };
+        Normalizer { normalization }
+    }
+
+    pub fn id(&self, id: String) -> String {
+        match self.normalization {
+            Normalization::None => id,
+            Normalization::Versions => match strip_version(&id) {
+                base if base.len() < id.len() => base.to_owned(),
+                _ => id,
+            },
+            Normalization::Full => normalize(&id),
+        }
+    }
+
+    pub fn ids(&self, ids: Vec<String>) -> Vec<String> {
+        if self.normalization == Normalization::None {
+            return ids;
+        }
+        ids.into_iter().map(|x| self.id(x)).collect()
+    }
+
+    /// [`Normalizer::ids`] for the members of a set, keeping analytes that become duplicates once
+    pub fn members(&self, ids: Vec<String>) -> Vec<String> {
+        if self.normalization == Normalization::None {
+            return ids;
+        }
+        let mut seen: AHashSet<String> = AHashSet::default();
+        ids.into_iter()
+            .map(|x| self.id(x))
+            .filter(|x| seen.insert(x.clone()))
+            .collect()
+    }
+}
+
+/// Normalize identifiers the same way as `set_defaults(normalize_ids=True)`, to preview how an input will be matched.
+///
+/// Each identifier is:
+///
+/// - Trimmed of surrounding whitespace and quotes
+/// - Converted to upper case, so gene symbols match regardless of case
+/// - Stripped of the version suffix if it is an Ensembl identifier, like `ENSG00000141510.17` to `ENSG00000141510`
+///
+/// # Parameters
+/// - `ids` - An analyte list in any form accepted by [`ora`](./ora.md)
+///
+/// # Returns
+///
+/// Returns a list of the normalized identifiers, in the order of `ids`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// print(webgestaltpy.normalize_ids([" tp53", '"BRCA1"', "ENSG00000141510.17"]))
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// ['TP53', 'BRCA1', 'ENSG00000141510']
+/// ```
+#[pyfunction]
+pub fn normalize_ids(ids: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    Ok(inputs::analyte_list(ids)?
+        .iter()
+        .map(|x| normalize(x))
+        .collect())
+}
+
+/// Genes whose symbols Excel converts to dates, with the month Excel reads, their current symbol prefix, the prefix of
+/// their previous symbols, and the numbers of the family members
+const EXCEL_DATE_GENES: [(&str, &str, &str, &[u32]); 3] = [
+    (
+        "MAR",
+        "MARCHF",
+        "MARCH",
+        &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+    ),
+    (
+        "SEP",
+        "SEPTIN",
+        "SEPT",
+        &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14],
+    ),
+    ("DEC", "DELEC", "DEC", &[1]),
+];
+
+/// Current symbol of a gene symbol converted to a date by Excel (like `1-Mar` or `Sep-07`), or of a previous symbol
+/// of the same genes (like `SEPT7`). `None` if `id` is neither.
+pub fn repair_excel_symbol(id: &str) -> Option<String> {
+    let upper = id.trim().to_uppercase();
+    let parse_number = |x: &str| {
+        if x.is_empty() || x.len() > 2 || !x.bytes().all(|x| x.is_ascii_digit()) {
+            None
+        } else {
+            x.parse::<u32>().ok()
+        }
+    };
+    let (month, number) = match upper.split_once('-') {
+        Some((a, b)) => match (parse_number(a), parse_number(b)) {
+            (Some(n), None) => (b, n),
+            (None, Some(n)) => (a, n),
+            _ => return None,
+        },
+        None => {
+            // previous symbols, which were changed to avoid the conversion
+            return EXCEL_DATE_GENES
+                .iter()
+                .find_map(|(_, current, previous, members)| {
+                    let number = upper.strip_prefix(previous).and_then(parse_number)?;
+                    members
+                        .contains(&number)
+                        .then(|| format!("{}{}", current, number))
+                });
+        }
+    };
+    // Excel also writes September as SEPT
+    let month = if month == "SEPT" { "SEP" } else { month };
+    let (_, current, _, members) = EXCEL_DATE_GENES.iter().find(|x| x.0 == month)?;
+    members
+        .contains(&number)
+        .then(|| format!("{}{}", current, number))
+}
+
+/// Repair gene symbols that Excel converted to dates, like `1-Mar` for `MARCHF1` or `7-Sep` for `SEPTIN7`.
+///
+/// Excel reads some gene symbols as dates when a file is opened, and the original symbols are lost when the file is saved.
+/// The affected genes are the `MARCHF`, `SEPTIN`, and `DELEC` families, which HGNC renamed from `MARCH`, `SEPT`, and `DEC`
+/// to avoid the conversion. Both the dates (in the `1-Mar` or `Mar-01` forms, in any case) and the previous symbols
+/// are repaired to the current symbols. Other identifiers are kept as they are.
+///
+/// # Parameters
+/// - `ids` - An analyte list in any form accepted by [`ora`](./ora.md). A dictionary of ranks can be passed as its keys.
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `ids` - List of the identifiers after the repair, in the order of `ids`
+/// - `repaired` - Dictionary mapping each repaired identifier to its current symbol
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.repair_excel_symbols(["TP53", "1-Mar", "Sep-07", "SEPT7"])
+/// print(res["ids"])
+/// print(res["repaired"])
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// ['TP53', 'MARCHF1', 'SEPTIN7', 'SEPTIN7']
+/// {'1-Mar': 'MARCHF1', 'Sep-07': 'SEPTIN7', 'SEPT7': 'SEPTIN7'}
+/// ```
+#[pyfunction]
+pub fn repair_excel_symbols<'a>(
+    py: Python<'a>,
+    ids: &Bound<'_, PyAny>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let repaired = PyDict::new(py);
+    let ids: Vec<String> = inputs::analyte_list(ids)?
+        .into_iter()
+        .map(|id| match repair_excel_symbol(&id) {
+            Some(symbol) => {
+                repaired.set_item(id, &symbol)?;
+                Ok(symbol)
+            }
+            None => Ok(id),
+        })
+        .collect::<PyResult<_>>()?;
+    let dict = PyDict::new(py);
+    dict.set_item("ids", ids)?;
+    dict.set_item("repaired", repaired)?;
+    Ok(dict)
+}
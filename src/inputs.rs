@@ -0,0 +1,990 @@
+use crate::gmt::Gmt;
+use crate::{config, ids};
+use ahash::{AHashMap, AHashSet};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBool, PyBytes, PyDict, PyString};
+use std::borrow::Cow;
+use std::time::Duration;
+use webgestalt_lib::methods::gsea::RankListItem;
+use webgestalt_lib::readers::utils::Item;
+
+/// URI schemes read through [fsspec](https://filesystem-spec.readthedocs.io) instead of the local filesystem
+const REMOTE_SCHEMES: [&str; 4] = ["s3://", "gs://", "http://", "https://"];
+
+fn is_remote(path: &str) -> bool {
+    REMOTE_SCHEMES.iter().any(|x| path.starts_with(x))
+}
+
+/// Opens `path` with `fsspec.open`, which is only installed with the `cloud` extra
+fn open_remote<'a>(py: Python<'a>, path: &str, mode: &str) -> Result<Bound<'a, PyAny>, String> {
+    let fsspec = py.import("fsspec").map_err(|_| {
+        format!(
+            "Reading and writing {} requires fsspec. Install it with `pip install webgestaltpy[cloud]`.",
+            path
+        )
+    })?;
+    // compressed files, like `.gz` downloads, are decompressed by their extension
+    let open = || -> PyResult<Bound<'a, PyAny>> {
+        let kwargs = [("compression", "infer")].into_py_dict(py)?;
+        fsspec.call_method("open", (path, mode), Some(&kwargs))
+    };
+    open().map_err(|e| e.value(py).to_string())
+}
+
+/// Number of attempts of a download before giving up
+const DOWNLOAD_ATTEMPTS: u32 = 4;
+
+/// Keyword arguments of `fsspec.open` for a web URI. aiohttp ignores the `HTTP_PROXY` and `HTTPS_PROXY` environment variables
+/// unless `trust_env` is set, and the `proxy` of the defaults is passed with every request.
+fn http_options(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("client_kwargs", [("trust_env", true)].into_py_dict(py)?)?;
+    if let Some(proxy) = config::defaults().proxy {
+        kwargs.set_item("proxy", proxy)?;
+    }
+    Ok(kwargs)
+}
+
+/// Error of a download attempt, and whether another attempt could succeed
+struct DownloadError {
+    message: String,
+    transient: bool,
+}
+
+/// Downloads `url` once. The number of bytes received is checked against the size reported by the server, and compressed
+/// files, like `.gz` downloads, are checked by their own checksum when decompressed.
+fn download_once(py: Python<'_>, url: &str) -> Result<String, DownloadError> {
+    let fsspec = py.import("fsspec").map_err(|_| DownloadError {
+        message: format!(
+            "Downloading {} requires fsspec. Install it with `pip install webgestaltpy[cloud]`.",
+            url
+        ),
+        transient: false,
+    })?;
+    let download = || -> PyResult<(Vec<u8>, Option<usize>)> {
+        let file = fsspec.call_method("open", (url, "rb"), Some(&http_options(py)?))?;
+        let size = file
+            .getattr("fs")?
+            .call_method1("size", (file.getattr("path")?,))
+            .ok()
+            .and_then(|x| x.extract::<usize>().ok());
+        let f = file.call_method0("__enter__")?;
+        let data = f
+            .call_method0("read")?
+            .downcast::<PyBytes>()?
+            .as_bytes()
+            .to_vec();
+        file.call_method1("__exit__", (py.None(), py.None(), py.None()))?;
+        Ok((data, size))
+    };
+    let (data, size) = download().map_err(|e| {
+        // a missing file is not retried
+        if e.is_instance_of::<pyo3::exceptions::PyFileNotFoundError>(py) {
+            DownloadError {
+                message: "The server has no file at this URL.".to_owned(),
+                transient: false,
+            }
+        } else {
+            DownloadError {
+                message: e.value(py).to_string(),
+                transient: true,
+            }
+        }
+    })?;
+    if let Some(size) = size.filter(|x| data.len() < *x) {
+        return Err(DownloadError {
+            message: format!("Received {} of the {} bytes of the file.", data.len(), size),
+            transient: true,
+        });
+    }
+    let decompress = || -> PyResult<Vec<u8>> {
+        let compression = py
+            .import("fsspec.utils")?
+            .call_method1("infer_compression", (url,))?;
+        if compression.is_none() {
+            return Ok(data.clone());
+        }
+        let buffer = py
+            .import("io")?
+            .call_method1("BytesIO", (PyBytes::new(py, &data),))?;
+        let kwargs = [("mode", "rb")].into_py_dict(py)?;
+        let f = py
+            .import("fsspec.compression")?
+            .getattr("compr")?
+            .get_item(compression)?
+            .call((buffer,), Some(&kwargs))?;
+        Ok(f.call_method0("read")?
+            .downcast::<PyBytes>()?
+            .as_bytes()
+            .to_vec())
+    };
+    let content = decompress().map_err(|e| DownloadError {
+        message: format!("The downloaded file is corrupted. {}", e.value(py)),
+        transient: true,
+    })?;
+    String::from_utf8(content).map_err(|_| DownloadError {
+        message: "The downloaded file is not text.".to_owned(),
+        transient: false,
+    })
+}
+
+/// Downloads the content of a web URI through fsspec, retrying failed or incomplete downloads after 1, 2, then 4 seconds.
+/// Proxies are read from the `proxy` of the defaults, or from the `HTTP_PROXY` and `HTTPS_PROXY` environment variables.
+pub fn download(url: &str) -> Result<String, String> {
+    let mut attempt = 0;
+    loop {
+        let error = match Python::with_gil(|py| download_once(py, url)) {
+            Ok(content) => return Ok(content),
+            Err(error) => error,
+        };
+        attempt += 1;
+        if !error.transient || attempt == DOWNLOAD_ATTEMPTS {
+            return Err(if error.transient {
+                format!(
+                    "Download failed after {} attempts. {}",
+                    attempt, error.message
+                )
+            } else {
+                error.message
+            });
+        }
+        std::thread::sleep(Duration::from_secs(1 << (attempt - 1)));
+    }
+}
+
+/// Reads the content of an object store or web URI. Web URIs are downloaded with the retries and checks of [`download`], while
+/// the fsspec backends of object stores retry on their own.
+fn read_remote(path: &str) -> Result<String, String> {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return download(path);
+    }
+    Python::with_gil(|py| {
+        let file = open_remote(py, path, "r")?;
+        let read = || -> PyResult<String> {
+            let f = file.call_method0("__enter__")?;
+            let content = f.call_method0("read")?.extract();
+            file.call_method1("__exit__", (py.None(), py.None(), py.None()))?;
+            content
+        };
+        read().map_err(|e| e.value(py).to_string())
+    })
+}
+
+/// Writes `content` to an object store URI
+fn write_remote(path: &str, content: &str) -> Result<(), String> {
+    Python::with_gil(|py| {
+        let file = open_remote(py, path, "w")?;
+        let write = || -> PyResult<()> {
+            let f = file.call_method0("__enter__")?;
+            f.call_method1("write", (content,))?;
+            file.call_method1("__exit__", (py.None(), py.None(), py.None()))?;
+            Ok(())
+        };
+        write().map_err(|e| e.value(py).to_string())
+    })
+}
+
+/// Reads the content of a local file
+#[cfg(feature = "fs")]
+fn read_local_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+/// Reads the content of a local file. Always fails, since this build has no file access.
+#[cfg(not(feature = "fs"))]
+fn read_local_file(_path: &str) -> Result<String, String> {
+    Err(
+        "webgestaltpy was built without file access. Pass the input as a Python object instead."
+            .to_owned(),
+    )
+}
+
+/// Writes `content` to a local file
+#[cfg(feature = "fs")]
+fn write_local_file(path: &str, content: &str) -> Result<(), String> {
+    std::fs::write(path, content).map_err(|e| e.to_string())
+}
+
+/// Writes `content` to a local file. Always fails, since this build has no file access.
+#[cfg(not(feature = "fs"))]
+fn write_local_file(_path: &str, _content: &str) -> Result<(), String> {
+    Err("webgestaltpy was built without file access.".to_owned())
+}
+
+/// Creates a local file to write in pieces
+#[cfg(feature = "fs")]
+fn create_local_file(path: &str) -> Result<OutputFile, String> {
+    std::fs::File::create(path)
+        .map(|file| OutputFile::Local(std::io::BufWriter::new(file)))
+        .map_err(|e| e.to_string())
+}
+
+/// Creates a local file to write in pieces. Always fails, since this build has no file access.
+#[cfg(not(feature = "fs"))]
+fn create_local_file(_path: &str) -> Result<OutputFile, String> {
+    Err("webgestaltpy was built without file access.".to_owned())
+}
+
+/// Creates a local directory and its parents if they do not exist
+#[cfg(feature = "fs")]
+fn create_local_dir(path: &str) -> Result<(), String> {
+    std::fs::create_dir_all(path).map_err(|e| e.to_string())
+}
+
+/// Creates a local directory. Always fails, since this build has no file access.
+#[cfg(not(feature = "fs"))]
+fn create_local_dir(_path: &str) -> Result<(), String> {
+    Err("webgestaltpy was built without file access.".to_owned())
+}
+
+/// Reads the content of a file, which can also be an `s3://`, `gs://`, or `http(s)://` URI
+pub fn read_file(path: &str) -> Result<String, String> {
+    if is_remote(path) {
+        read_remote(path)
+    } else {
+        read_local_file(path)
+    }
+}
+
+/// Creates a directory for output files. Object stores have no directories, so `s3://` and `gs://` URIs are left as they are.
+pub fn create_dir(path: &str) -> Result<(), String> {
+    if is_remote(path) {
+        Ok(())
+    } else {
+        create_local_dir(path)
+    }
+}
+
+/// Writes `content` to a file, which can also be an `s3://` or `gs://` URI
+pub fn write_file(path: &str, content: &str) -> Result<(), String> {
+    if is_remote(path) {
+        write_remote(path, content)
+    } else {
+        write_local_file(path, content)
+    }
+}
+
+/// File open for writing in pieces, like a table written one chunk of rows at a time, so the whole content is never held in memory
+pub enum OutputFile {
+    #[cfg(feature = "fs")]
+    Local(std::io::BufWriter<std::fs::File>),
+    /// The `fsspec` file, and the file object returned by its `__enter__`
+    Remote(PyObject, PyObject),
+}
+
+impl OutputFile {
+    /// Opens a file for writing, which can also be an `s3://` or `gs://` URI
+    pub fn create(py: Python<'_>, path: &str) -> Result<OutputFile, String> {
+        if is_remote(path) {
+            let file = open_remote(py, path, "w")?;
+            let f = file
+                .call_method0("__enter__")
+                .map_err(|e| e.value(py).to_string())?;
+            Ok(OutputFile::Remote(file.into(), f.into()))
+        } else {
+            create_local_file(path)
+        }
+    }
+
+    /// Appends `content` to the file
+    pub fn write(&mut self, py: Python<'_>, content: &str) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "fs")]
+            OutputFile::Local(file) => {
+                std::io::Write::write_all(file, content.as_bytes()).map_err(|e| e.to_string())
+            }
+            OutputFile::Remote(_, f) => f
+                .call_method1(py, "write", (content,))
+                .map(|_| ())
+                .map_err(|e| e.value(py).to_string()),
+        }
+    }
+
+    /// Flushes and closes the file. Object stores only upload the file when it is closed.
+    pub fn close(self, py: Python<'_>) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "fs")]
+            OutputFile::Local(mut file) => {
+                std::io::Write::flush(&mut file).map_err(|e| e.to_string())
+            }
+            OutputFile::Remote(file, _) => file
+                .call_method1(py, "__exit__", (py.None(), py.None(), py.None()))
+                .map(|_| ())
+                .map_err(|e| e.value(py).to_string()),
+        }
+    }
+}
+
+/// Largest float that is an exact integer, above which a float ID can not be written back as the integer it came from
+const MAX_EXACT_FLOAT: f64 = 9007199254740992.0;
+
+/// Converts a Python analyte ID to a `String`. Integers (like Entrez IDs), including NumPy integers, are written without
+/// decimals. So are floats with a whole value, since pandas stores a column of integer IDs with missing values as floats.
+pub fn analyte_to_string(obj: &Bound<'_, PyAny>) -> PyResult<String> {
+    if let Ok(s) = obj.extract::<String>() {
+        return Ok(s);
+    }
+    if !obj.is_instance_of::<PyBool>() {
+        if let Ok(x) = obj.extract::<i64>() {
+            return Ok(x.to_string());
+        }
+        if let Ok(x) = obj.extract::<u64>() {
+            return Ok(x.to_string());
+        }
+        if let Ok(x) = obj.extract::<f64>() {
+            if x.fract() == 0.0 && x.abs() <= MAX_EXACT_FLOAT {
+                return Ok((x as i64).to_string());
+            }
+        }
+    }
+    Ok(obj.str()?.to_string())
+}
+
+/// Gets analyte IDs passed as a parameter, either a single ID or a list of IDs, as strings or integers.
+/// The IDs are normalized like every input (see `set_defaults`).
+pub fn analyte_ids(obj: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    let ids: Vec<String> = if obj.is_instance_of::<PyString>() || obj.try_iter().is_err() {
+        vec![analyte_to_string(obj)?]
+    } else {
+        obj.try_iter()?
+            .map(|x| analyte_to_string(&x?))
+            .collect::<PyResult<_>>()?
+    };
+    Ok(ids::Normalizer::from_defaults().ids(ids))
+}
+
+/// Gets the values of column `name` from a DataFrame (or any object that returns a sequence for `obj[name]`)
+pub fn column<'a>(obj: &Bound<'a, PyAny>, name: &str) -> PyResult<Vec<Bound<'a, PyAny>>> {
+    let col = obj
+        .get_item(name)
+        .map_err(|_| PyValueError::new_err(format!("Column '{}' not found!", name)))?;
+    let col = if col.hasattr("tolist")? {
+        col.call_method0("tolist")?
+    } else {
+        col
+    };
+    col.try_iter()?.collect()
+}
+
+/// Sets of a GMT file, one set per line with the name, the URL, and the analytes separated by tabs
+pub fn parse_gmt(content: &str) -> Result<Vec<Item>, String> {
+    let mut items: Vec<Item> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let id = columns.next().unwrap_or_default().to_owned();
+        let url = columns
+            .next()
+            .ok_or_else(|| format!("Line {} has no URL column.", i + 1))?
+            .to_owned();
+        items.push(Item {
+            id,
+            url,
+            parts: columns
+                .filter(|x| !x.is_empty())
+                .map(|x| x.to_owned())
+                .collect(),
+        });
+    }
+    Ok(items)
+}
+
+/// Sets of a weighted GMT, where each analyte of a set has a weight, like the specificity of a cell-type marker
+pub struct WeightedGmt {
+    pub items: Vec<Item>,
+    /// Weight of each analyte of each set, in the order of its `parts`
+    pub weights: Vec<Vec<f64>>,
+}
+
+impl WeightedGmt {
+    /// Weighted GMT where every analyte has a weight of `1`
+    fn unweighted(items: Vec<Item>) -> WeightedGmt {
+        let weights = items.iter().map(|x| vec![1.0; x.parts.len()]).collect();
+        WeightedGmt { items, weights }
+    }
+
+    /// Checks that every weight is positive, for the weighted statistics that add up the weights of the analytes of a set
+    pub fn check_positive(&self) -> PyResult<()> {
+        for (item, weights) in self.items.iter().zip(self.weights.iter()) {
+            if let Some((analyte, weight)) = item
+                .parts
+                .iter()
+                .zip(weights.iter())
+                .find(|(_, x)| **x <= 0.0)
+            {
+                return Err(PyValueError::new_err(format!(
+                    "Weight of '{}' in set '{}' must be positive ({})!",
+                    analyte, item.id, weight
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that no weight is `0`, for signed sets where the sign of each weight is the direction of the analyte in the set
+    pub fn check_signed(&self) -> PyResult<()> {
+        for (item, weights) in self.items.iter().zip(self.weights.iter()) {
+            if let Some(analyte) = item
+                .parts
+                .iter()
+                .zip(weights.iter())
+                .find(|(_, x)| **x == 0.0)
+                .map(|(x, _)| x)
+            {
+                return Err(PyValueError::new_err(format!(
+                    "Weight of '{}' in set '{}' must not be 0, since its sign is the mode of regulation!",
+                    analyte, item.id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the weight of `analyte` on line `line` of a weighted GMT
+fn parse_weight(weight: &str, analyte: &str, line: usize) -> Result<f64, String> {
+    weight
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| x.is_finite())
+        .ok_or_else(|| {
+            format!(
+                "Could not parse weight '{}' of '{}' on line {}.",
+                weight, analyte, line
+            )
+        })
+}
+
+/// Weighted GMT with the set name and URL columns of a GMT, followed by the analytes of the set, each with its weight either in the next
+/// column (`gene\tweight`) or after a comma in the same column (`gene,weight`, like the weighted libraries of Enrichr).
+/// Analytes listed more than once in a set keep their first weight.
+pub fn parse_weighted_gmt(content: &str) -> Result<WeightedGmt, String> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut weights: Vec<Vec<f64>> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let id = columns.next().unwrap_or_default().to_owned();
+        let url = columns
+            .next()
+            .ok_or_else(|| format!("Line {} has no URL column.", i + 1))?
+            .to_owned();
+        let mut parts: Vec<String> = Vec::new();
+        let mut set_weights: Vec<f64> = Vec::new();
+        let mut columns = columns.filter(|x| !x.is_empty());
+        while let Some(column) = columns.next() {
+            let (analyte, weight) = match column.rsplit_once(',') {
+                Some((analyte, weight)) if weight.trim().parse::<f64>().is_ok() => {
+                    (analyte, parse_weight(weight, analyte, i + 1)?)
+                }
+                _ => {
+                    let weight = columns
+                        .next()
+                        .ok_or_else(|| format!("Line {} has no weight for '{}'.", i + 1, column))?;
+                    (column, parse_weight(weight, column, i + 1)?)
+                }
+            };
+            if !parts.iter().any(|x| x == analyte) {
+                parts.push(analyte.to_owned());
+                set_weights.push(weight);
+            }
+        }
+        items.push(Item { id, url, parts });
+        weights.push(set_weights);
+    }
+    Ok(WeightedGmt { items, weights })
+}
+
+/// Rank list with the analyte in the first column and its rank in the second, separated by a tab
+///
+/// Unlike `webgestalt_lib::readers::read_rank_file`, malformed lines are returned as an error instead of panicking.
+pub fn parse_rank_list(content: &str) -> Result<Vec<RankListItem>, String> {
+    let mut items: Vec<RankListItem> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let analyte = columns.next().unwrap_or_default();
+        let rank = columns
+            .next()
+            .ok_or_else(|| format!("Line {} has no rank column.", i + 1))?;
+        let rank = rank
+            .parse::<f64>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .ok_or_else(|| format!("Could not parse rank '{}' on line {}.", rank, i + 1))?;
+        items.push(RankListItem {
+            analyte: analyte.to_owned(),
+            rank,
+        });
+    }
+    Ok(items)
+}
+
+/// List of analytes separated by new lines, skipping empty lines
+pub fn parse_analyte_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_owned())
+        .collect()
+}
+
+/// Edge list with an edge on each line, with the nodes and an optional weight separated by whitespace
+pub fn parse_edge_list(content: &str) -> Vec<Vec<String>> {
+    content
+        .lines()
+        .map(|x| x.split_whitespace().map(|x| x.to_owned()).collect())
+        .collect()
+}
+
+/// Text without a leading UTF-8 byte order mark, and with Windows (`\r\n`) and stray carriage return (`\r`) line endings
+/// replaced by `\n`, so files saved on any platform parse the same way
+fn clean_text(content: &str) -> Cow<'_, str> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    if content.contains('\r') {
+        Cow::Owned(content.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(content)
+    }
+}
+
+/// Reads the file at `path` with `parse`, adding the kind of input and the path to any error
+fn read_and_parse<T>(
+    path: &str,
+    kind: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> PyResult<T> {
+    read_file(path)
+        .and_then(|x| parse(&clean_text(&x)))
+        .map_err(|e| {
+            PyValueError::new_err(format!("Error when reading {} at: {}. {}", kind, path, e))
+        })
+}
+
+/// Parses a text input with `parse`. Returns `None` if `obj` is neither a path, the bytes of the text (`bytes`, `bytearray`,
+/// `memoryview`, or any other buffer of bytes), nor a file-like object (anything with a `read` method, like an open file or
+/// `io.StringIO`).
+pub fn parse_text_input<T>(
+    obj: &Bound<'_, PyAny>,
+    kind: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> PyResult<Option<T>> {
+    if let Ok(path) = obj.extract::<&str>() {
+        return read_and_parse(path, kind, parse).map(Some);
+    }
+    // the bytes are parsed here, so a large list read from object storage never becomes Python strings
+    if let Ok(buffer) = PyBuffer::<u8>::get(obj) {
+        let bytes = buffer.to_vec(obj.py())?;
+        return std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|x| parse(&clean_text(x)))
+            .map(Some)
+            .map_err(|e| {
+                PyValueError::new_err(format!("Error when reading {} from bytes. {}", kind, e))
+            });
+    }
+    if !obj.hasattr("read")? {
+        return Ok(None);
+    }
+    let content = obj.call_method0("read")?;
+    let content = match content.extract::<&[u8]>() {
+        Ok(bytes) => std::str::from_utf8(bytes)
+            .map_err(|e| e.to_string())
+            .map(|x| x.to_owned()),
+        Err(_) => content.extract::<String>().map_err(|e| e.to_string()),
+    };
+    content
+        .and_then(|x| parse(&clean_text(&x)))
+        .map(Some)
+        .map_err(|e| {
+            PyValueError::new_err(format!(
+                "Error when reading {} from file object. {}",
+                kind, e
+            ))
+        })
+}
+
+/// Wraps `obj` in an `io.StringIO` if it is the text of an input rather than a path
+pub fn content<'py>(obj: &Bound<'py, PyAny>, is_content: bool) -> PyResult<Bound<'py, PyAny>> {
+    if is_content && obj.is_instance_of::<PyString>() {
+        obj.py().import("io")?.getattr("StringIO")?.call1((obj,))
+    } else {
+        Ok(obj.clone())
+    }
+}
+
+/// [`content`] for each of a list of inputs
+pub fn contents(objs: Vec<Bound<'_, PyAny>>, is_content: bool) -> PyResult<Vec<Bound<'_, PyAny>>> {
+    objs.into_iter().map(|x| content(&x, is_content)).collect()
+}
+
+/// Gets the sets of a GMT input, which can be a path to a GMT file, a file-like object, a `Gmt` object,
+/// or a dictionary mapping each set name to a list of analytes
+pub fn gmt(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Item>> {
+    let normalizer = ids::Normalizer::from_defaults();
+    Ok(read_gmt(obj)?
+        .into_iter()
+        .map(|x| Item {
+            parts: normalizer.members(x.parts),
+            ..x
+        })
+        .collect())
+}
+
+fn read_gmt(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Item>> {
+    if let Some(items) = parse_text_input(obj, "GMT file", parse_gmt)? {
+        return Ok(items);
+    }
+    if let Ok(gmt) = obj.extract::<PyRef<Gmt>>() {
+        return Ok(gmt.items.clone());
+    }
+    if let Ok(dict) = obj.downcast::<PyDict>() {
+        return dict
+            .iter()
+            .map(|(set, analytes)| {
+                Ok(Item {
+                    id: analyte_to_string(&set)?,
+                    url: String::new(),
+                    parts: analyte_list(&analytes)?,
+                })
+            })
+            .collect();
+    }
+    Err(PyValueError::new_err(
+        "GMT must be a path, a Gmt object, or a dictionary of sets!",
+    ))
+}
+
+/// Gets the sets of a weighted GMT input, which can be a path to a weighted GMT file (see [`parse_weighted_gmt`]), a file-like object,
+/// a `Gmt` object, or a dictionary mapping each set name to a dictionary of the weight of each analyte. Sets without weights, from a
+/// `Gmt` or a list of analytes, have a weight of `1` for every analyte.
+pub fn weighted_gmt(obj: &Bound<'_, PyAny>) -> PyResult<WeightedGmt> {
+    let gmt = if let Some(gmt) = parse_text_input(obj, "weighted GMT file", parse_weighted_gmt)? {
+        gmt
+    } else if let Ok(gmt) = obj.extract::<PyRef<Gmt>>() {
+        WeightedGmt::unweighted(gmt.items.clone())
+    } else if let Ok(dict) = obj.downcast::<PyDict>() {
+        let mut items: Vec<Item> = Vec::with_capacity(dict.len());
+        let mut weights: Vec<Vec<f64>> = Vec::with_capacity(dict.len());
+        for (set, analytes) in dict.iter() {
+            let (parts, set_weights) = match analytes.downcast::<PyDict>() {
+                Ok(analytes) => analytes
+                    .iter()
+                    .map(|(analyte, weight)| Ok((analyte_to_string(&analyte)?, weight.extract()?)))
+                    .collect::<PyResult<Vec<(String, f64)>>>()?
+                    .into_iter()
+                    .unzip(),
+                Err(_) => {
+                    let parts = analyte_list(&analytes)?;
+                    let set_weights = vec![1.0; parts.len()];
+                    (parts, set_weights)
+                }
+            };
+            items.push(Item {
+                id: analyte_to_string(&set)?,
+                url: String::new(),
+                parts,
+            });
+            weights.push(set_weights);
+        }
+        WeightedGmt { items, weights }
+    } else {
+        return Err(PyValueError::new_err(
+            "Weighted GMT must be a path, a Gmt object, or a dictionary of sets!",
+        ));
+    };
+    // normalized analytes that become duplicates keep their first weight, like in a weighted GMT file
+    let normalizer = ids::Normalizer::from_defaults();
+    let mut weighted = WeightedGmt {
+        items: Vec::with_capacity(gmt.items.len()),
+        weights: Vec::with_capacity(gmt.items.len()),
+    };
+    for (item, set_weights) in gmt.items.into_iter().zip(gmt.weights) {
+        let mut seen: AHashSet<String> = AHashSet::default();
+        let (parts, set_weights): (Vec<String>, Vec<f64>) = normalizer
+            .ids(item.parts)
+            .into_iter()
+            .zip(set_weights)
+            .filter(|(x, _)| seen.insert(x.clone()))
+            .unzip();
+        weighted.items.push(Item { parts, ..item });
+        weighted.weights.push(set_weights);
+    }
+    Ok(weighted)
+}
+
+/// Gets an `(analyte, rank)` pair from a tuple or a list of two items
+fn rank_pair<'py>(obj: &Bound<'py, PyAny>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+    if let Ok(pair) = obj.extract::<(Bound<'py, PyAny>, Bound<'py, PyAny>)>() {
+        return Ok(pair);
+    }
+    match obj.extract::<Vec<Bound<'py, PyAny>>>()?.as_slice() {
+        [analyte, rank] => Ok((analyte.clone(), rank.clone())),
+        _ => Err(PyValueError::new_err("Rank list pair must have two items!")),
+    }
+}
+
+/// Gets a rank list input, which can be a path to a rank file, a file-like object, a dictionary mapping each analyte
+/// to its rank, a pandas Series of ranks with the analytes as the index, or a list of `(analyte, rank)` pairs (as tuples or lists)
+pub fn rank_list(obj: &Bound<'_, PyAny>) -> PyResult<Vec<RankListItem>> {
+    let normalizer = ids::Normalizer::from_defaults();
+    let items: Vec<RankListItem> = read_rank_list(obj)?
+        .into_iter()
+        .map(|x| RankListItem {
+            analyte: normalizer.id(x.analyte),
+            ..x
+        })
+        .collect();
+    if config::average_duplicate_ranks() {
+        Ok(average_duplicates(items))
+    } else {
+        Ok(items)
+    }
+}
+
+/// Replaces the analytes listed more than once with a single item at their first position, with the mean of their ranks
+fn average_duplicates(items: Vec<RankListItem>) -> Vec<RankListItem> {
+    let mut positions: AHashMap<String, usize> = AHashMap::default();
+    let mut sums: Vec<(RankListItem, usize)> = Vec::with_capacity(items.len());
+    for item in items {
+        match positions.get(&item.analyte) {
+            Some(i) => {
+                sums[*i].0.rank += item.rank;
+                sums[*i].1 += 1;
+            }
+            None => {
+                positions.insert(item.analyte.clone(), sums.len());
+                sums.push((item, 1));
+            }
+        }
+    }
+    sums.into_iter()
+        .map(|(item, count)| RankListItem {
+            rank: item.rank / count as f64,
+            ..item
+        })
+        .collect()
+}
+
+fn read_rank_list(obj: &Bound<'_, PyAny>) -> PyResult<Vec<RankListItem>> {
+    if let Some(items) = parse_text_input(obj, "rank file", parse_rank_list)? {
+        return Ok(items);
+    }
+    let pairs: Vec<(Bound<'_, PyAny>, Bound<'_, PyAny>)> = match obj.downcast::<PyDict>() {
+        Ok(dict) => dict.iter().collect(),
+        Err(_) => {
+            // a Series iterates over its values, so the pairs come from `items`
+            let pairs = if obj.hasattr("index")? && obj.hasattr("items")? {
+                obj.call_method0("items")?
+            } else {
+                obj.clone()
+            };
+            pairs
+                .try_iter()?
+                .map(|x| rank_pair(&x?))
+                .collect::<PyResult<_>>()
+                .map_err(|_| {
+                    PyValueError::new_err(
+                        "Rank list must be a path, a dictionary, a Series, or a list of (analyte, rank) pairs! Use read_rank_file to select the columns of a DataFrame.",
+                    )
+                })?
+        }
+    };
+    pairs
+        .into_iter()
+        .map(|(analyte, rank)| {
+            let analyte = analyte_to_string(&analyte)?;
+            let rank: f64 = rank.extract()?;
+            if !rank.is_finite() {
+                return Err(PyValueError::new_err(format!(
+                    "Rank of '{}' must be finite, but is {}",
+                    analyte, rank
+                )));
+            }
+            Ok(RankListItem { analyte, rank })
+        })
+        .collect()
+}
+
+/// Gets an analyte list input, which can be a path to (or file-like object of) a file with an analyte on each line, or a list of analytes
+pub fn analyte_list(obj: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    let analytes = if let Some(analytes) =
+        parse_text_input(obj, "analyte list", |x| Ok(parse_analyte_list(x)))?
+    {
+        analytes
+    } else {
+        obj.try_iter()?
+            .map(|x| analyte_to_string(&x?))
+            .collect::<PyResult<_>>()?
+    };
+    Ok(ids::Normalizer::from_defaults().ids(analytes))
+}
+
+/// Gets an analyte list input without duplicates, in order of first appearance
+pub fn unique_analyte_list(obj: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    let mut seen: AHashSet<String> = AHashSet::default();
+    Ok(analyte_list(obj)?
+        .into_iter()
+        .filter(|x| seen.insert(x.clone()))
+        .collect())
+}
+
+/// Reads an edge list from either a path to an edge list file, a file-like object, or a DataFrame with a row for each edge.
+///
+/// Edges from a DataFrame contain the weight as the third element if `weight_column` is provided.
+pub fn read_edge_list(
+    edge_list: &Bound<'_, PyAny>,
+    source_column: &str,
+    target_column: &str,
+    weight_column: Option<&str>,
+) -> PyResult<Vec<Vec<String>>> {
+    let normalizer = ids::Normalizer::from_defaults();
+    Ok(
+        read_edges(edge_list, source_column, target_column, weight_column)?
+            .into_iter()
+            .map(|edge| {
+                // only the nodes are identifiers, not the weight
+                edge.into_iter()
+                    .enumerate()
+                    .map(|(i, x)| if i < 2 { normalizer.id(x) } else { x })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+fn read_edges(
+    edge_list: &Bound<'_, PyAny>,
+    source_column: &str,
+    target_column: &str,
+    weight_column: Option<&str>,
+) -> PyResult<Vec<Vec<String>>> {
+    if let Some(edges) = parse_text_input(edge_list, "edge list", |x| Ok(parse_edge_list(x)))? {
+        return Ok(edges);
+    }
+    let sources = column(edge_list, source_column)?;
+    let targets = column(edge_list, target_column)?;
+    let weights = match weight_column {
+        Some(name) => Some(
+            column(edge_list, name)?
+                .into_iter()
+                .map(|x| x.extract::<f64>())
+                .collect::<PyResult<Vec<f64>>>()?,
+        ),
+        None => None,
+    };
+    let mut edges: Vec<Vec<String>> = Vec::with_capacity(sources.len());
+    for (i, (source, target)) in sources.iter().zip(targets.iter()).enumerate() {
+        let mut edge = vec![analyte_to_string(source)?, analyte_to_string(target)?];
+        if let Some(weights) = &weights {
+            edge.push(weights[i].to_string());
+        }
+        edges.push(edge);
+    }
+    Ok(edges)
+}
+
+/// Edge list input with the default column names
+pub fn edge_list(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Vec<String>>> {
+    read_edge_list(obj, "source", "target", None)
+}
+
+/// The sets, analyte list, and reference list of an ORA
+pub type ORAInputs = (Vec<Item>, AHashSet<String>, AHashSet<String>);
+
+/// Gets the inputs of an ORA. Like `webgestalt_lib::readers::read_ora_files`, the reference list
+/// is limited to the analytes in the GMT, and the analyte list to the reference list.
+pub fn ora_inputs(
+    gmt: Vec<Item>,
+    analyte_list: &Bound<'_, PyAny>,
+    reference_list: &Bound<'_, PyAny>,
+) -> PyResult<ORAInputs> {
+    Ok(filter_ora_inputs(
+        gmt,
+        self::analyte_list(analyte_list)?,
+        self::analyte_list(reference_list)?,
+    ))
+}
+
+/// Drops the `excluded` analytes from the analyte list, the reference list, and every set of an ORA
+pub fn exclude_analytes(
+    gmt: Vec<Item>,
+    analyte_list: Vec<String>,
+    reference_list: Vec<String>,
+    excluded: &AHashSet<String>,
+) -> (Vec<Item>, Vec<String>, Vec<String>) {
+    let keep = |x: &String| !excluded.contains(x);
+    let gmt = gmt
+        .into_iter()
+        .map(|x| Item {
+            parts: x.parts.into_iter().filter(keep).collect(),
+            ..x
+        })
+        .collect();
+    (
+        gmt,
+        analyte_list.into_iter().filter(keep).collect(),
+        reference_list.into_iter().filter(keep).collect(),
+    )
+}
+
+/// Like WebGestaltR, in compatibility mode the sets are filtered by their size in the reference list before testing
+fn compat_set_sizes(gmt: Vec<Item>, reference_list: &[String]) -> Vec<Item> {
+    match config::defaults().compat {
+        config::Compat::WebGestaltR => {
+            let reference: AHashSet<&String> = reference_list.iter().collect();
+            let limits = config::ora_config();
+            gmt.into_iter()
+                .filter(|x| {
+                    let size = x.parts.iter().filter(|a| reference.contains(a)).count();
+                    size >= limits.min_set_size && size <= limits.max_set_size
+                })
+                .collect()
+        }
+        config::Compat::Default => gmt,
+    }
+}
+
+/// Inputs of an ORA against an explicit universe. Unlike [`filter_ora_inputs`], the universe is kept as it is, even the
+/// analytes that are not in any set, and only the analyte list is limited to it.
+pub fn universe_ora_inputs(
+    gmt: Vec<Item>,
+    analyte_list: Vec<String>,
+    universe: Vec<String>,
+) -> ORAInputs {
+    let gmt = compat_set_sizes(gmt, &universe);
+    let universe: AHashSet<String> = universe.into_iter().collect();
+    let analytes: AHashSet<String> = analyte_list
+        .into_iter()
+        .filter(|x| universe.contains(x))
+        .collect();
+    (gmt, analytes, universe)
+}
+
+/// [`ora_inputs`] for lists that were already read
+pub fn filter_ora_inputs(
+    gmt: Vec<Item>,
+    analyte_list: Vec<String>,
+    reference_list: Vec<String>,
+) -> ORAInputs {
+    let gmt = compat_set_sizes(gmt, &reference_list);
+    let annotated: AHashSet<&String> = gmt.iter().flat_map(|x| x.parts.iter()).collect();
+    let reference: AHashSet<String> = reference_list
+        .into_iter()
+        .filter(|x| annotated.contains(x))
+        .collect();
+    let analytes: AHashSet<String> = analyte_list
+        .into_iter()
+        .filter(|x| reference.contains(x))
+        .collect();
+    (gmt, analytes, reference)
+}
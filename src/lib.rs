@@ -1,11 +1,52 @@
+// pyo3 0.20 expands `#[new]` into impls that newer compilers flag as non-local
+#![allow(non_local_definitions)]
+use ahash::AHashSet;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
-use webgestalt_lib::methods::gsea::{GSEAConfig, GSEAResult};
-use webgestalt_lib::methods::multilist::{multilist_gsea, multilist_ora, GSEAJob, ORAJob};
-use webgestalt_lib::methods::nta::{NTAConfig, NTAResult};
-use webgestalt_lib::methods::ora::{ORAConfig, ORAResult};
+use pyo3::types::{IntoPyDict, PyDict};
+use pyo3::IntoPyObjectExt;
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use webgestalt_lib::methods::gsea::{GSEAConfig, GSEAResult, RankListItem};
+use webgestalt_lib::methods::nta::NTAResult;
+use webgestalt_lib::methods::ora::{get_ora, ORAConfig, ORAResult};
 use webgestalt_lib::readers::utils::Item;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+mod adjust;
+mod aliases;
+mod background;
+mod benchmark;
+mod bias;
+mod cell_types;
+mod clustering;
+mod combined;
+mod compare;
+mod concordance;
+mod config;
+mod connectivity;
+mod consensus;
+mod export;
+mod gmt;
+mod gsea_stats;
+mod ids;
+mod inputs;
+mod links;
+mod manifest;
+mod meta;
+mod network;
+mod ora_stats;
+mod orthologs;
+mod plots;
+mod rank_file;
+mod regions;
+mod report;
+mod set_weights;
+mod stability;
+mod stream;
+mod trend;
+mod warnings;
+mod weighted_ora;
 
 /// Enum of the NTA Methods supported by WebGestalt
 ///
@@ -21,7 +62,8 @@ use webgestalt_lib::readers::utils::Item;
 ///  
 /// method = webgestaltpy.NTAMethod.Expansion
 /// ```
-#[pyclass]
+#[pyclass(eq, eq_int)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum NTAMethod {
     /// Finds the N seeds (input analytes) that are most likely to be encountered with a random walk
     Prioritization,
@@ -29,54 +71,253 @@ pub enum NTAMethod {
     Expansion,
 }
 
-fn gsea_result_to_dict(obj: GSEAResult, py: Python) -> Result<&PyDict, PyErr> {
+impl NTAMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NTAMethod::Expansion => "Expansion",
+            NTAMethod::Prioritization => "Prioritization",
+        }
+    }
+}
+
+/// Adds a flag of whether `fdr` is below each of the significance thresholds, like `sig_fdr_0_05` for `0.05`
+fn add_significance_flags(dict: &Bound<'_, PyDict>, fdr: f64, thresholds: &[f64]) -> PyResult<()> {
+    for threshold in thresholds.iter().copied() {
+        dict.set_item(
+            format!("sig_fdr_{}", threshold.to_string().replace('.', "_")),
+            fdr < threshold,
+        )?;
+    }
+    Ok(())
+}
+
+/// Columns added to every result that depend on the defaults, which are read once for each call instead of for each result
+#[derive(Clone)]
+struct RowOptions {
+    /// Whether each result has the `link` of its set
+    set_links: bool,
+    /// FDR thresholds of the significance flags of each result
+    significance_thresholds: Vec<f64>,
+}
+
+impl RowOptions {
+    fn from_defaults() -> RowOptions {
+        let defaults = config::defaults();
+        RowOptions {
+            set_links: defaults.set_links,
+            significance_thresholds: defaults.significance_thresholds,
+        }
+    }
+
+    /// Options from the defaults, with the significance thresholds of a call instead if provided
+    fn with_thresholds(significance_thresholds: Option<Vec<f64>>) -> PyResult<RowOptions> {
+        let mut options = RowOptions::from_defaults();
+        if let Some(significance_thresholds) = significance_thresholds {
+            config::check_significance_thresholds(&significance_thresholds)?;
+            options.significance_thresholds = significance_thresholds;
+        }
+        Ok(options)
+    }
+}
+
+fn gsea_result_to_dict<'a>(
+    obj: GSEAResult,
+    options: &RowOptions,
+    py: Python<'a>,
+) -> Result<Bound<'a, PyDict>, PyErr> {
+    let rank_at_max = gsea_stats::peak(&obj.running_sum);
+    gsea_row_to_dict(obj, rank_at_max, options, py)
+}
+
+/// [`gsea_result_to_dict`] for results without a running sum, with the position of its maximum deviation
+fn gsea_row_to_dict<'a>(
+    obj: GSEAResult,
+    rank_at_max: Option<usize>,
+    options: &RowOptions,
+    py: Python<'a>,
+) -> Result<Bound<'a, PyDict>, PyErr> {
     let dict = PyDict::new(py);
-    dict.set_item("set".to_object(py), obj.set.to_object(py))?;
-    dict.set_item("p".to_object(py), obj.p.to_object(py))?;
-    dict.set_item("fdr".to_object(py), obj.fdr.to_object(py))?;
-    dict.set_item("es".to_object(py), obj.es.to_object(py))?;
-    dict.set_item("nes".to_object(py), obj.nes.to_object(py))?;
-    dict.set_item("leading_edge".to_object(py), obj.leading_edge.to_object(py))?;
+    dict.set_item("set", &obj.set)?;
+    links::add_link(&dict, &obj.set, options.set_links)?;
+    dict.set_item("p", obj.p)?;
+    dict.set_item("fdr", obj.fdr)?;
+    add_significance_flags(&dict, obj.fdr, &options.significance_thresholds)?;
+    dict.set_item("es", obj.es)?;
+    dict.set_item("nes", obj.nes)?;
+    dict.set_item("leading_edge", obj.leading_edge)?;
+    let direction = if obj.es > 0.0 {
+        Some("up")
+    } else if obj.es < 0.0 {
+        Some("down")
+    } else {
+        None
+    };
+    dict.set_item("rank_at_max", rank_at_max)?;
+    dict.set_item("direction", direction)?;
     Ok(dict)
 }
 
-fn ora_result_to_dict(obj: ORAResult, py: Python) -> Result<&PyDict, PyErr> {
+fn ora_result_to_dict<'a>(
+    obj: ORAResult,
+    options: &RowOptions,
+    py: Python<'a>,
+) -> Result<Bound<'a, PyDict>, PyErr> {
     let dict = PyDict::new(py);
-    dict.set_item("set".to_object(py), obj.set.to_object(py))?;
-    dict.set_item("p".to_object(py), obj.p.to_object(py))?;
-    dict.set_item("fdr".to_object(py), obj.fdr.to_object(py))?;
-    dict.set_item("overlap".to_object(py), obj.overlap.to_object(py))?;
-    dict.set_item("expected".to_object(py), obj.expected.to_object(py))?;
-    dict.set_item(
-        "enrichment_ratio".to_object(py),
-        obj.enrichment_ratio.to_object(py),
-    )?;
+    dict.set_item("set", &obj.set)?;
+    links::add_link(&dict, &obj.set, options.set_links)?;
+    dict.set_item("p", obj.p)?;
+    dict.set_item("fdr", obj.fdr)?;
+    add_significance_flags(&dict, obj.fdr, &options.significance_thresholds)?;
+    dict.set_item("overlap", obj.overlap)?;
+    dict.set_item("expected", obj.expected)?;
+    dict.set_item("enrichment_ratio", obj.enrichment_ratio)?;
+    Ok(dict)
+}
+
+/// Converts an ORA result, adding the contingency table of the set and the z-score of its overlap
+fn ora_result_with_table<'a>(
+    obj: ORAResult,
+    set_size: usize,
+    sizes: &ora_stats::ORASizes,
+    options: &RowOptions,
+    py: Python<'a>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let table = ora_stats::ContingencyTable::new(
+        obj.overlap as usize,
+        set_size,
+        sizes.list_size,
+        sizes.reference_size,
+    );
+    let expected = obj.expected;
+    let dict = ora_result_to_dict(obj, options, py)?;
+    table.add_to_dict(&dict, expected)?;
     Ok(dict)
 }
 
-fn nta_result_to_dict(obj: NTAResult, py: Python) -> Result<&PyDict, PyErr> {
+/// Converts the results of an ORA in the order of the GMT, adding the contingency table of each set
+fn ora_results_with_tables<'a>(
+    res: Vec<ORAResult>,
+    sizes: &ora_stats::ORASizes,
+    py: Python<'a>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let options = RowOptions::from_defaults();
+    res.into_iter()
+        .zip(sizes.set_sizes.iter())
+        .map(|(x, set_size)| ora_result_with_table(x, *set_size, sizes, &options, py))
+        .collect()
+}
+
+fn nta_result_to_dict(obj: NTAResult, py: Python<'_>) -> Result<Bound<'_, PyDict>, PyErr> {
     let dict = PyDict::new(py);
-    dict.set_item("candidates".to_object(py), obj.candidates.to_object(py))?;
-    dict.set_item("scores".to_object(py), obj.scores.to_object(py))?;
-    dict.set_item("neighborhood".to_object(py), obj.neighborhood.to_object(py))?;
+    dict.set_item("candidates", obj.candidates)?;
+    dict.set_item("scores", obj.scores)?;
+    dict.set_item("neighborhood", obj.neighborhood)?;
     Ok(dict)
 }
 
+fn add_gsea_parameters(run: &mut manifest::Manifest, config: &GSEAConfig) {
+    run.parameter("permutations", config.permutations);
+    run.parameter("min_overlap", config.min_overlap);
+    run.parameter("max_overlap", config.max_overlap);
+    run.parameter("p", config.p);
+}
+
+fn add_ora_parameters(run: &mut manifest::Manifest, config: &ORAConfig) {
+    run.parameter("min_overlap", config.min_overlap);
+    run.parameter("min_set_size", config.min_set_size);
+    run.parameter("max_set_size", config.max_set_size);
+    run.parameter(
+        "fdr_method",
+        match config.fdr_method {
+            AdjustmentMethod::BH => "BH",
+            AdjustmentMethod::None => "none",
+        },
+    );
+}
+
+/// Indices of the results to return. Without paging, every result in the order of the GMT. When paging, the results from `offset`,
+/// up to `limit` of them, sorted by increasing FDR, then p-value, then set name, so the pages of an analysis line up.
+fn page_order(keys: Vec<(f64, f64, &String)>, offset: usize, limit: Option<usize>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    if offset == 0 && limit.is_none() {
+        return order;
+    }
+    order.sort_by(|a, b| {
+        let (x, y) = (&keys[*a], &keys[*b]);
+        x.0.total_cmp(&y.0)
+            .then(x.1.total_cmp(&y.1))
+            .then(x.2.cmp(y.2))
+    });
+    order
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
 /// Run single-omic NTA (Network-topology based analysis) with files at the provided paths
 ///
 /// # Parameters
-/// - `edge_list_path` - `String` of the path to the edge list file of the network. See below for details.
-/// - `analyte_list_path` - `String` of the path to the rank file of interest, with analytes separated by new lines
-/// - `nta_method` - a `NTAMethod` object specifying the NTA method for the analysis.
-/// - `n` - the number of seeds or nodes to identify according to `nta_method`
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with a row for each edge. See below for details.
+/// - `analyte_list_path` - `String` of the path to the analyte list file of interest, with analytes separated by new lines, a file-like object, or a list of analytes
+/// - `nta_method` - a `NTAMethod` object specifying the NTA method for the analysis. Not used with `config`.
+/// - `n` - the number of seeds or nodes to identify according to `nta_method`. Not used with `config`.
+/// - `config` - If provided, a [`NTAConfig`](./NTAConfig.md) object with the method, `n`, and the parameters of the random walk, used instead of
+///   `nta_method` and `n` (default: `None`)
+/// - `background_seeds` - If provided, a second analyte list in any form accepted by `analyte_list_path`, like the analytes of the controls.
+///   The random walk is also run from these seeds, and the score of each node is contrasted with its score from the background with `contrast`.
+///   This prioritizes the nodes close to the seeds but not to the background (default: `None`)
+/// - `contrast` - How the scores are contrasted with the scores from `background_seeds`. Either `"difference"` to subtract them, or `"log_ratio"` for the
+///   log2 ratio of the scores, each plus one over the number of nodes. Only used with `background_seeds` (default: `"difference"`)
+/// - `contributions` - If `True`, also reports how much of the score of each node comes from each seed. Can not be used with `background_seeds` (default: `False`)
+/// - `scores_path` - If provided, the `String` of a path to write the score of every node in the network to, as a tab-separated file with the `id`, `score`, `rank`,
+///   `is_seed`, and `degree` of each node, ordered by decreasing score. The table is written directly, so large networks do not need to be passed to Python (default: `None`)
+/// - `depth` - Also include the nodes within this many edges of the nodes selected by `nta_method` in the neighborhood (default: `0`)
+/// - `include_seeds` - If `True`, also include every seed found in the network in the neighborhood (default: `False`)
+/// - `max_size` - If provided, the neighborhood is cut to this many nodes with the highest scores, after applying `depth` and `include_seeds` (default: `None`)
+/// - `min_weight` - If provided, edges with a weight (third column of the edge list) below this value are removed before the random walk. Edges without a weight have a weight of `1.0`.
+/// - `remove_self_loops` - If `True`, removes edges from a node to itself before the random walk (default: `False`)
+/// - `remove_duplicates` - If `True`, removes repeated edges between the same nodes before the random walk (default: `False`)
+/// - `largest_component` - If `True`, only keeps the largest connected component of the network for the random walk (default: `False`)
+/// - `source_column` - Name of the column with the source node of each edge when `edge_list_path` is a DataFrame (default: `"source"`)
+/// - `target_column` - Name of the column with the target node of each edge when `edge_list_path` is a DataFrame (default: `"target"`)
+/// - `weight_column` - Name of the column with the weight of each edge when `edge_list_path` is a DataFrame. Edges are unweighted if not provided.
+/// - `edge_list_is_content` - If `True`, `edge_list_path` is the text of the edge list file instead of its path (default: `False`)
+/// - `analyte_list_is_content` - If `True`, `analyte_list_path` is the text of the analyte list file instead of its path (default: `False`)
+/// - `with_metadata` - If `True`, returns a dictionary with the `results` and the `metadata` of the run, which has the same fields as the manifest
+///   with the number of `seconds` it took and the `sizes` of the inputs, so results saved to disk describe how they were made (default: `False`)
+/// - `manifest` - If provided, the `String` of a path to write a JSON manifest of the run to, with the SHA-256 of each input file, the parameters, the versions, and the start and end times (default: `None`)
+/// - `on_complete` - If provided, a function called with the `metadata` of the run when it finishes, with the same fields as with `with_metadata=True`,
+///   to start downstream steps or send a notification. It is called after the manifest is written. Errors raised by the function are emitted as a
+///   [`WebGestaltWarning`](./WebGestaltWarning.md), so the results are still returned (default: `None`)
+///
+/// # Network Format
+///
+/// The edge list file has an edge on each line, with the two nodes separated by whitespace and an optional weight as the third column.
+/// Networks exported as tables (e.g. from STRING or BioGRID) can be passed directly as a pandas DataFrame, with the columns selected by `source_column`, `target_column`, and `weight_column`.
 ///
 /// # Returns
 ///
 /// Returns a dictionary object containing the `candidates` (seed nodes when using prioritization), `scores` (random-walk probabilities), and `neighborhood` (identified nodes)
 ///
-/// # Panics
+/// The dictionary also contains `nodes`, the same neighborhood as a list of records that can be passed directly to `pandas.DataFrame`.
+/// Each node has its `id`, its `score`, whether it `is_seed`, its `rank` in the neighborhood (starting at `1`), and its `degree`,
+/// the number of neighbors of the node in the network after preprocessing.
+///
+/// When `depth`, `include_seeds`, or `max_size` is used, the `neighborhood` is ordered by decreasing score and `candidates` is unchanged.
+///
+/// If `contributions` is `True`, the dictionary also contains `contributions`, a list with a dictionary for each node in `neighborhood`
+/// that maps each seed to the part of the node's score that comes from random walks restarting at that seed. Seeds that do not reach the node are left out.
+///
+/// If any of the network preprocessing options are used, the dictionary also contains `preprocessing`, with the number of edges removed by each step
+/// (`below_min_weight`, `self_loops`, `duplicates`, and `outside_largest_component`) and the `removed_nodes` that no longer have any edges.
 ///
-/// Panics if the network or the analyte file is malformed or not at specified path. Will also panic if `nta_method` is not specified correctly
+/// # Errors
+///
+/// Raises a `ValueError` if the network or an analyte file is malformed or can not be read, if `config` is not provided
+/// instead of both `nta_method` and `n`, if a weight is not positive and finite with `weighted`, if `contributions` is used with `background_seeds`, or if `scores_path` can not be written.
+///
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if some seeds are not in the network.
 ///
 /// # Example
 ///
@@ -111,46 +352,300 @@ fn nta_result_to_dict(obj: NTAResult, py: Python) -> Result<&PyDict, PyErr> {
 ///     'ACTB',
 ///     'ACTG1',
 ///     'ACTG2'
+///   ],
+///   'nodes': [
+///     {'id': 'ACTA1', 'score': 0.015611545101449542, 'is_seed': True, 'rank': 1, 'degree': 8},
+///     {'id': 'ACTA2', 'score': 0.015611545101449542, 'is_seed': True, 'rank': 2, 'degree': 8},
+///     ...
 ///   ]
 /// }
 /// ```
 #[pyfunction]
-fn nta<'a>(
-    py: Python<'a>,
-    edge_list_path: String,
-    analyte_list_path: String,
-    nta_method: &'a NTAMethod,
-    n: usize,
-) -> PyResult<&'a PyDict> {
-    let net_file = webgestalt_lib::readers::read_edge_list(edge_list_path);
-    let analytes = webgestalt_lib::readers::read_single_list(analyte_list_path);
-    let method = match nta_method {
-        NTAMethod::Expansion => webgestalt_lib::methods::nta::NTAMethod::Expand(n),
-        NTAMethod::Prioritization => webgestalt_lib::methods::nta::NTAMethod::Prioritize(n),
+#[pyo3(signature = (
+    edge_list_path,
+    analyte_list_path,
+    nta_method=None,
+    n=None,
+    config=None,
+    background_seeds=None,
+    contrast="difference",
+    contributions=false,
+    scores_path=None,
+    depth=0,
+    include_seeds=false,
+    max_size=None,
+    min_weight=None,
+    remove_self_loops=false,
+    remove_duplicates=false,
+    largest_component=false,
+    source_column="source",
+    target_column="target",
+    weight_column=None,
+    edge_list_is_content=false,
+    analyte_list_is_content=false,
+    with_metadata=false,
+    manifest=None,
+    on_complete=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn nta(
+    py: Python<'_>,
+    edge_list_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    nta_method: Option<NTAMethod>,
+    n: Option<usize>,
+    config: Option<&network::NTAConfig>,
+    background_seeds: Option<&Bound<'_, PyAny>>,
+    contrast: &str,
+    contributions: bool,
+    scores_path: Option<String>,
+    depth: usize,
+    include_seeds: bool,
+    max_size: Option<usize>,
+    min_weight: Option<f64>,
+    remove_self_loops: bool,
+    remove_duplicates: bool,
+    largest_component: bool,
+    source_column: &str,
+    target_column: &str,
+    weight_column: Option<&str>,
+    edge_list_is_content: bool,
+    analyte_list_is_content: bool,
+    with_metadata: bool,
+    manifest: Option<String>,
+    on_complete: Option<&Bound<'_, PyAny>>,
+) -> PyResult<PyObject> {
+    manifest::check_on_complete(on_complete)?;
+    let nta_config = network::NTAConfig::resolve(config, nta_method, n)?;
+    let contrast_method = network::Contrast::parse(contrast)?;
+    if contributions && background_seeds.is_some() {
+        return Err(PyValueError::new_err(
+            "contributions can not be used with background_seeds!",
+        ));
+    }
+    let edge_list_path = inputs::content(edge_list_path, edge_list_is_content)?;
+    let analyte_list_path = inputs::content(analyte_list_path, analyte_list_is_content)?;
+    let mut run = manifest::Manifest::new("nta");
+    run.input("edge_list", &edge_list_path);
+    run.input("analyte_list", &analyte_list_path);
+    if let Some(background_seeds) = background_seeds {
+        run.input("background_seeds", background_seeds);
+    }
+    let filter = network::EdgeFilter {
+        min_weight,
+        remove_self_loops,
+        remove_duplicates,
+        largest_component,
     };
-    let res = webgestalt_lib::methods::nta::get_nta(NTAConfig {
-        edge_list: net_file,
-        seeds: analytes.into_iter().collect(),
-        method: Option::Some(method),
-        ..Default::default()
-    });
+    let edge_list =
+        inputs::read_edge_list(&edge_list_path, source_column, target_column, weight_column)?;
+    let (edges, report) = filter.apply(edge_list);
+    let seeds = inputs::unique_analyte_list(&analyte_list_path)?;
+    let background = background_seeds
+        .map(inputs::unique_analyte_list)
+        .transpose()?;
+    run.size("edges", edges.len());
+    run.size("analyte_list", seeds.len());
+    if let Some(background) = &background {
+        run.size("background_seeds", background.len());
+    }
+    let neighborhood_options = network::NeighborhoodOptions {
+        depth,
+        include_seeds,
+        max_size,
+    };
+    let network = network::Network::for_walk(&edges, nta_config.weighted)?;
+    let seed_set: FxHashSet<&String> = seeds.iter().collect();
+    warnings::warn_left_out(
+        py,
+        seeds
+            .iter()
+            .filter(|x| !network.node_map.contains_key(*x))
+            .count(),
+        seeds.len(),
+        "seeds",
+        "are not in the network",
+    )?;
+    let (reset_probability, tolerance) = (nta_config.reset_probability, nta_config.tolerance);
+    run.parameter("nta_method", nta_config.method.name());
+    run.parameter("n", nta_config.n);
+    run.parameter("reset_probability", reset_probability);
+    run.parameter("tolerance", tolerance);
+    run.parameter("weighted", nta_config.weighted);
+    run.parameter("normalization", nta_config.normalization.name());
+    run.parameter("contrast", background.as_ref().map(|_| contrast));
+    run.parameter("depth", depth);
+    run.parameter("include_seeds", include_seeds);
+    run.parameter("max_size", max_size);
+    run.parameter("min_weight", min_weight);
+    run.parameter("remove_self_loops", remove_self_loops);
+    run.parameter("remove_duplicates", remove_duplicates);
+    run.parameter("largest_component", largest_component);
+    let scores = config::install(py, || {
+        let walk = |seeds: &[String]| {
+            network.normalized_walk(
+                &network.seed_vector(seeds),
+                reset_probability,
+                tolerance,
+                nta_config.normalization,
+            )
+        };
+        let scores = walk(&seeds);
+        match &background {
+            Some(background) => contrast_method.apply(&scores, &walk(background)),
+            None => scores,
+        }
+    })?;
+    let mut res = config::install(py, || {
+        let is_seed: Vec<bool> = network.nodes.iter().map(|x| seed_set.contains(x)).collect();
+        let (neighborhood, candidates) =
+            network::select_nodes(&scores, &is_seed, &nta_config.method, nta_config.n);
+        let names =
+            |x: &[usize]| -> Vec<String> { x.iter().map(|i| network.nodes[*i].clone()).collect() };
+        NTAResult {
+            scores: neighborhood.iter().map(|i| scores[*i]).collect(),
+            neighborhood: names(&neighborhood),
+            candidates: names(&candidates),
+        }
+    })?;
+    if neighborhood_options.is_active() {
+        let selected: Vec<usize> = res
+            .neighborhood
+            .iter()
+            .map(|x| network.node_map[x])
+            .collect();
+        let nodes = neighborhood_options.apply(&network, &scores, &seeds, &selected);
+        res.scores = nodes.iter().map(|x| scores[*x]).collect();
+        res.neighborhood = nodes
+            .into_iter()
+            .map(|x| network.nodes[x].clone())
+            .collect();
+    }
+    let neighborhood = res.neighborhood.clone();
+    let records = network::node_records(py, &network, &res.neighborhood, &res.scores, &seed_set)?;
     let new_res = nta_result_to_dict(res, py)?;
-    Ok(new_res)
+    new_res.set_item("nodes", records)?;
+    if contributions {
+        let nodes: Vec<usize> = neighborhood.iter().map(|x| network.node_map[x]).collect();
+        let breakdown = config::install(py, || {
+            network::seed_contributions(
+                &network,
+                &seeds,
+                &nodes,
+                reset_probability,
+                tolerance,
+                nta_config.normalization,
+            )
+        })?;
+        let breakdown = breakdown
+            .into_iter()
+            .map(|x| x.into_py_dict(py))
+            .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()?;
+        new_res.set_item("contributions", breakdown)?;
+    }
+    if filter.is_active() {
+        new_res.set_item("preprocessing", report.to_dict(py)?)?;
+    }
+    if let Some(path) = scores_path {
+        inputs::write_file(&path, &network::score_table(&network, &scores, &seed_set)).map_err(
+            |e| PyValueError::new_err(format!("Error when writing NTA scores at: {}. {}", path, e)),
+        )?;
+    }
+    if let Some(path) = manifest {
+        run.write(py, &path)?;
+    }
+    run.wrap(py, new_res.into_py_any(py)?, with_metadata, on_complete)
 }
 
 /// Run single-omic GSEA with files at provided paths.
 ///
 /// # Parameters
-/// - `gmt_path` - `String` of the path to the gmt file of interest
-/// - `rank_file_path` - `String` of the path to the rank file of interest. Tab separated.
+/// - `gmt_path` - `String` of the path to the gmt file of interest, a file-like object (like an open file or `io.StringIO`), a [`Gmt`](./Gmt.md) object, or a dictionary mapping each set name to a list of analytes
+/// - `rank_file_path` - `String` of the path to the rank file of interest. Tab separated. Can also be a file-like object, a dictionary mapping each analyte to its rank, a pandas Series of ranks with the analytes as the index, or a list of `(analyte, rank)` pairs as tuples or lists.
+///   Analytes can be `String`s or integers.
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated. With the same seed, the results are identical
+///   for any number of threads. (default: `None`)
+/// - `bootstrap` - If provided, the number of times to resample the analytes of the rank list with replacement to get a
+///   95% confidence interval of the NES of each set. The seed of the resampling is `seed` (default: `None`)
+/// - `aliases` - If provided, an alias table that lets analytes match the GMT through aliases, like previous gene symbols, in any form accepted by
+///   [`liftover_gmt`](./liftover_gmt.md). The GMT and the rank list are both mapped to current identifiers before matching. If an analyte is
+///   ranked under more than one alias, only its current identifier (or else its first alias) is kept. (default: `None`)
+/// - `null_sets` - If provided, a list of the names of the sets to return the permutation null distribution of (default: `None`)
+/// - `null_path` - If provided, the `String` of a path to write the permutation null distribution of the sets in `null_sets` (or of every set if
+///   `null_sets` is not provided) to, as a tab-separated file with the `set`, the `permutation` (starting at `1`), and its `es` and `nes` (default: `None`)
+/// - `permute` - What is permuted to build the null distribution. Only `"gene"`, which shuffles the analytes of the rank list, is supported.
+///   `"phenotype"`, which shuffles the sample labels, will be supported once expression matrices can be passed. It is recorded in the manifest (default: `"gene"`)
+/// - `permutations` - If provided, the permutations of the rank list to use instead of random shuffles, as a matrix of indices or a callable returning one.
+///   See below for details. (default: `None`)
+/// - `offset` - Number of results to skip, to get a page of the results. See below for details. (default: `0`)
+/// - `limit` - If provided, the maximum number of results to return (default: `None`)
+/// - `gmt_is_content` - If `True`, `gmt_path` is the text of the GMT file instead of its path (default: `False`)
+/// - `rank_file_is_content` - If `True`, `rank_file_path` is the text of the rank file instead of its path (default: `False`)
+/// - `with_metadata` - If `True`, returns a dictionary with the `results` and the `metadata` of the run, which has the same fields as the manifest
+///   with the number of `seconds` it took and the `sizes` of the inputs, so results saved to disk describe how they were made (default: `False`)
+/// - `manifest` - If provided, the `String` of a path to write a JSON manifest of the run to, with the SHA-256 of each input file, the parameters, the versions, and the start and end times (default: `None`)
+/// - `on_complete` - If provided, a function called with the `metadata` of the run when it finishes, with the same fields as with `with_metadata=True`,
+///   to start downstream steps or send a notification. It is called after the manifest is written. Errors raised by the function are emitted as a
+///   [`WebGestaltWarning`](./WebGestaltWarning.md), so the results are still returned (default: `None`)
+/// - `significance_thresholds` - If provided, the FDR thresholds of the significance flags of the results, like `[0.05, 0.25]`, instead of the
+///   `significance_thresholds` of [`set_defaults`](./set_defaults.md), where the flags are described (default: `None`)
 ///
 /// # Returns
 ///
 /// Returns a list containing the GSEA results for every set.
 ///
-/// # Panics
+/// Like the `RANK AT MAX` column of GSEA desktop, `rank_at_max` is the position (starting at `0`) in the rank list, sorted by decreasing rank,
+/// where the running enrichment score reaches its maximum deviation from zero. `direction` is `"up"` for a positive enrichment score, and `"down"`
+/// for a negative one. Both are `None` for sets that were not tested.
+///
+/// Every result also has the `seed` used for the permutations, so the run can be reproduced by passing it as `seed`.
+///
+/// When `bootstrap` is provided, every result also has `nes_ci_low` and `nes_ci_high`, the 2.5% and 97.5% quantiles of the NES of the set
+/// over the resampled rank lists. The resampled enrichment scores are normalized with the same factor as the observed NES. A wide interval,
+/// especially one that crosses `0`, means the enrichment depends on a handful of analytes. Both are `None` for sets that were not tested.
+///
+/// When `aliases` is provided, every result also has `alias_matches`, the number of analytes of the set in the rank list that only match through an alias.
+///
+/// When `offset` or `limit` is provided, the results are sorted by increasing `fdr`, then `p`, then set name before taking the page, so that
+/// consecutive pages of the same analysis line up. Pass the same `seed` for every page, since the permutations change the p-values.
+/// Without them, the results are in the order of the GMT.
+///
+/// The results of the sets in `null_sets` also have `null_es` and `null_nes`, the enrichment score of the set in each permutation of the rank list
+/// and its normalized score, in the order of the permutations. Positive scores are normalized by the mean of the positive scores, and negative
+/// scores by the mean of the negative scores, the same way as the observed NES. Both are empty for sets that were not tested.
+///
+/// # Custom Permutations
+///
+/// By default, each permutation shuffles the analytes of the rank list at random, which ignores the correlation between analytes. With `permutations`,
+/// the null distribution can come from any scheme, like shuffling blocks of correlated genes together. Each row of the matrix is a permutation
+/// of the positions `0` to `n - 1` of the `n` analytes of the rank list sorted by decreasing rank: `row[i]` is the position of the analyte that takes
+/// the `i`-th rank in that permutation. The number of rows is the number of permutations. `permutations` can also be a callable, which is called
+/// with the list of the analytes sorted by decreasing rank, the default number of permutations, and `seed`, and returns the matrix.
+/// The matrix can be a list of lists or a NumPy array of any integer type, like one saved from [`permutation_matrix`](./permutation_matrix.md)
+/// to reuse the exact same null distribution across runs.
+///
+/// ```python
+/// def block_shuffle(analytes, n, seed):
+///     rng = random.Random(seed)
+///     blocks = [list(range(i, min(i + 50, len(analytes)))) for i in range(0, len(analytes), 50)]
+///     return [[x for block in rng.sample(blocks, len(blocks)) for x in block] for _ in range(n)]
+///
+/// res = webgestaltpy.gsea("kegg.gmt", "test.rnk", permutations=block_shuffle)
+/// ```
+///
+/// # Memory
+///
+/// For large collections like GO, the running sums and null distributions of every set can take several GB. With `max_memory_mb` set with
+/// [`set_defaults`](./set_defaults.md), GSEA switches to a low-memory mode when its estimated memory is above the limit, which gives the same results
+/// but takes about twice as long. The metadata records `low_memory` when it is used.
+///
+/// # Errors
 ///
-/// Panics if the GMT or the rank file is malformed or not at specified path.
+/// Raises a `ValueError` if the GMT or the rank file is malformed or can not be read, if a set of `null_sets` is not in the GMT, if `null_path` can not be written,
+/// if `permute` is not `"gene"`, if a row of `permutations` is not a permutation of the positions of the rank list, or if a significance threshold
+/// is not above `0` and at most `1`.
+///
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if analytes of the rank list have tied ranks, or if some sets were not tested.
 ///
 /// # Example
 ///
@@ -185,27 +680,301 @@ fn nta<'a>(
 /// ]
 /// ```
 #[pyfunction]
-fn gsea(py: Python, gmt_path: String, rank_file_path: String) -> PyResult<Vec<&PyDict>> {
-    let analyte_list = webgestalt_lib::readers::read_rank_file(rank_file_path);
-    let gmt = webgestalt_lib::readers::read_gmt_file(gmt_path);
-    let res: Vec<GSEAResult> = webgestalt_lib::methods::gsea::gsea(
-        analyte_list.unwrap(),
-        gmt.unwrap(),
-        GSEAConfig::default(),
-        None,
+#[pyo3(signature = (
+    gmt_path,
+    rank_file_path,
+    seed=None,
+    bootstrap=None,
+    aliases=None,
+    null_sets=None,
+    null_path=None,
+    permute="gene",
+    permutations=None,
+    offset=0,
+    limit=None,
+    gmt_is_content=false,
+    rank_file_is_content=false,
+    with_metadata=false,
+    manifest=None,
+    on_complete=None,
+    significance_thresholds=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn gsea(
+    py: Python<'_>,
+    gmt_path: &Bound<'_, PyAny>,
+    rank_file_path: &Bound<'_, PyAny>,
+    seed: Option<u64>,
+    bootstrap: Option<usize>,
+    aliases: Option<&Bound<'_, PyAny>>,
+    null_sets: Option<Vec<String>>,
+    null_path: Option<String>,
+    permute: &str,
+    permutations: Option<&Bound<'_, PyAny>>,
+    offset: usize,
+    limit: Option<usize>,
+    gmt_is_content: bool,
+    rank_file_is_content: bool,
+    with_metadata: bool,
+    manifest: Option<String>,
+    on_complete: Option<&Bound<'_, PyAny>>,
+    significance_thresholds: Option<Vec<f64>>,
+) -> PyResult<PyObject> {
+    manifest::check_on_complete(on_complete)?;
+    let options = RowOptions::with_thresholds(significance_thresholds)?;
+    let gmt_path = inputs::content(gmt_path, gmt_is_content)?;
+    let rank_file_path = inputs::content(rank_file_path, rank_file_is_content)?;
+    if bootstrap == Some(0) {
+        return Err(PyValueError::new_err("bootstrap must be at least 1!"));
+    }
+    gsea_stats::check_permute(permute)?;
+    let mut run = manifest::Manifest::new("gsea");
+    run.input("gmt", &gmt_path);
+    run.input("rank_file", &rank_file_path);
+    if let Some(aliases) = aliases {
+        run.input("aliases", aliases);
+    }
+    let analyte_list = inputs::rank_list(&rank_file_path)?;
+    let gmt = inputs::gmt(&gmt_path)?;
+    let (alias_matches, gmt, analyte_list) = match aliases {
+        Some(aliases) => {
+            let (matching, gmt, analyte_list) = aliases::AliasMatching::new(
+                &aliases::alias_map(aliases)?,
+                gmt,
+                analyte_list,
+                |x| &mut x.analyte,
+            );
+            let ranked: FxHashSet<&String> = analyte_list.iter().map(|x| &x.analyte).collect();
+            let alias_matches: Vec<usize> = gmt
+                .iter()
+                .enumerate()
+                .map(|(i, set)| matching.alias_matches(i, set, |x| ranked.contains(x)))
+                .collect();
+            (Some(alias_matches), gmt, analyte_list)
+        }
+        None => (None, gmt, analyte_list),
+    };
+    run.size("sets", gmt.len());
+    run.size("rank_list", analyte_list.len());
+    let mut gsea_config = config::gsea_config();
+    let seed = gsea_stats::seed_or_random(seed);
+    run.parameter("custom_permutations", permutations.is_some());
+    let permutations = match permutations {
+        Some(permutations) => gsea_stats::custom_permutations(
+            permutations,
+            &gsea_stats::SortedRankList::new(&analyte_list).analytes,
+            gsea_config.permutations,
+            seed,
+        )?,
+        None => gsea_stats::make_permutations(gsea_config.permutations, analyte_list.len(), seed),
+    };
+    gsea_config.permutations = permutations.len() as i32;
+    add_gsea_parameters(&mut run, &gsea_config);
+    run.parameter("seed", seed);
+    run.parameter("permute", permute);
+    run.parameter("bootstrap", bootstrap);
+    let bootstrap = bootstrap.map(|resamples| {
+        (
+            gsea_stats::Bootstrap::new(&analyte_list, resamples, seed),
+            gmt.clone(),
+        )
+    });
+    let return_nulls = null_sets.is_some();
+    let null_indices: Option<Vec<usize>> = match (null_sets, &null_path) {
+        (Some(names), _) => Some(
+            names
+                .iter()
+                .map(|name| {
+                    gmt.iter().position(|x| &x.id == name).ok_or_else(|| {
+                        PyValueError::new_err(format!("Set '{}' is not in the GMT!", name))
+                    })
+                })
+                .collect::<PyResult<_>>()?,
+        ),
+        (None, Some(_)) => Some((0..gmt.len()).collect()),
+        (None, None) => None,
+    };
+    let nulls: Option<Vec<(usize, Vec<f64>)>> = null_indices
+        .map(|indices| {
+            let sorted = gsea_stats::SortedRankList::new(&analyte_list);
+            config::install(py, || {
+                indices
+                    .into_iter()
+                    .map(|i| {
+                        let es = gsea_stats::null_es(
+                            &sorted,
+                            &gmt[i],
+                            &permutations,
+                            gsea_config.min_overlap,
+                            gsea_config.max_overlap,
+                        );
+                        (i, es)
+                    })
+                    .collect()
+            })
+        })
+        .transpose()?;
+    let tied = gsea_stats::tied_count(&analyte_list);
+    if tied > 0 {
+        warnings::warn(
+            py,
+            &format!(
+                "{} analytes of the rank list have tied ranks. Tied analytes are kept in the order of the rank list.",
+                tied
+            ),
+        )?;
+    }
+    let low_memory = gsea_stats::low_memory(analyte_list.len(), gmt.len(), permutations.len());
+    run.parameter("low_memory", low_memory);
+    let (res, peaks): (Vec<GSEAResult>, Vec<Option<usize>>) = if low_memory {
+        config::install(py, || {
+            gsea_stats::low_memory_gsea(&analyte_list, &gmt, &gsea_config, &permutations)
+        })?
+    } else {
+        let res = config::install(py, || {
+            gsea_stats::gsea(&analyte_list, &gmt, &gsea_config, &permutations)
+        })?;
+        // the running sums are only needed for the position of the maximum
+        res.into_iter()
+            .map(|x| {
+                let peak = gsea_stats::peak(&x.running_sum);
+                (
+                    GSEAResult {
+                        running_sum: Vec::new(),
+                        ..x
+                    },
+                    peak,
+                )
+            })
+            .unzip()
+    };
+    let untested = peaks.iter().filter(|x| x.is_none()).count();
+    if untested > 0 {
+        warnings::warn(
+            py,
+            &format!(
+                "{} of {} sets were not tested, since their overlap with the rank list is below min_overlap or above max_overlap.",
+                untested,
+                res.len()
+            ),
+        )?;
+    }
+    let intervals: Option<Vec<Option<(f64, f64)>>> = bootstrap
+        .map(|(bootstrap, gmt)| {
+            let sets: FxHashMap<&String, &Item> = gmt.iter().map(|x| (&x.id, x)).collect();
+            config::install(py, || {
+                res.iter()
+                    .map(|row| nes_interval(row, &bootstrap, sets[&row.set]))
+                    .collect()
+            })
+        })
+        .transpose()?;
+    if let Some(path) = manifest {
+        run.write(py, &path)?;
+    }
+    let order = page_order(
+        res.iter().map(|x| (x.fdr, x.p, &x.set)).collect(),
+        offset,
+        limit,
     );
-    let new_res: Vec<&PyDict> = res
+    let new_res: Vec<Bound<'_, PyDict>> = res
         .into_iter()
-        .map(|x| gsea_result_to_dict(x, py).unwrap())
+        .zip(peaks)
+        .map(|(x, peak)| gsea_row_to_dict(x, peak, &options, py))
+        .collect::<PyResult<_>>()?;
+    for row in new_res.iter() {
+        row.set_item("seed", seed)?;
+    }
+    if let Some(nulls) = nulls {
+        let mut content = String::from("set\tpermutation\tes\tnes\n");
+        for (i, es) in nulls {
+            let nes = gsea_stats::normalize_null(&es);
+            let set: String = new_res[i].get_item("set")?.unwrap().extract()?;
+            for (j, (x, y)) in es.iter().zip(nes.iter()).enumerate() {
+                content.push_str(&format!("{}\t{}\t{}\t{}\n", set, j + 1, x, y));
+            }
+            if return_nulls {
+                new_res[i].set_item("null_es", es)?;
+                new_res[i].set_item("null_nes", nes)?;
+            }
+        }
+        if let Some(path) = null_path {
+            inputs::write_file(&path, &content).map_err(|e| {
+                PyValueError::new_err(format!(
+                    "Error when writing null distribution at: {}. {}",
+                    path, e
+                ))
+            })?;
+        }
+    }
+    if let Some(alias_matches) = alias_matches {
+        for (row, matches) in new_res.iter().zip(alias_matches) {
+            row.set_item("alias_matches", matches)?;
+        }
+    }
+    if let Some(intervals) = intervals {
+        for (row, interval) in new_res.iter().zip(intervals) {
+            row.set_item("nes_ci_low", interval.map(|x| x.0))?;
+            row.set_item("nes_ci_high", interval.map(|x| x.1))?;
+        }
+    }
+    let new_res: Vec<Bound<'_, PyDict>> = order.into_iter().map(|i| new_res[i].clone()).collect();
+    run.wrap(py, new_res.into_py_any(py)?, with_metadata, on_complete)
+}
+
+/// 95% bootstrap confidence interval of the NES of a GSEA result. The bootstrap enrichment scores
+/// are normalized with the same factor as the observed NES. `None` if the set was not tested.
+fn nes_interval(
+    row: &GSEAResult,
+    bootstrap: &gsea_stats::Bootstrap,
+    set: &Item,
+) -> Option<(f64, f64)> {
+    if row.es == 0.0 || row.nes == 0.0 {
+        return None;
+    }
+    let scale = row.nes / row.es;
+    let nes: Vec<f64> = bootstrap
+        .enrichment_scores(set)
+        .into_iter()
+        .filter(|x| x.is_finite())
+        .map(|x| x * scale)
         .collect();
-    Ok(new_res)
+    Some((
+        gsea_stats::quantile(&nes, 0.025)?,
+        gsea_stats::quantile(&nes, 0.975)?,
+    ))
 }
 
 /// Run a meta-analysis GSEA with files at the provided paths.
 ///
 /// # Parameters
-/// - `gmt_path` - `String` of the path to the gmt file of interest
-/// - `rank_files` -  Lists of `String`s of the paths to the rank files of interest. Tab separated.
+/// - `gmt_path` - `String` of the path to the gmt file of interest, a file-like object (like an open file or `io.StringIO`), a [`Gmt`](./Gmt.md) object, or a dictionary mapping each set name to a list of analytes
+/// - `rank_files` -  Lists of `String`s of the paths to the rank files of interest. Tab separated. Each rank list can also be passed in any form accepted by [`gsea`](./gsea.md),
+///   so paths and in-memory rank lists can be mixed. Can also be a dictionary mapping the name of each list to its rank list, to get the results by name.
+/// - `seed` - Seed for the random permutations. The list at index `i` of `rank_files` uses `seed + i`. If not provided, a new seed is generated (default: `None`)
+/// - `nes_combination` - If `"fixed"` or `"random"`, also pools the NES of each set across the lists with a fixed-effects or
+///   (DerSimonian-Laird) random-effects model. The standard error of each NES is estimated from the permutation null distribution. (default: `None`)
+/// - `meta_method` - How the p-values of each set are combined across lists. Either `"stouffer"` for Stouffer's method, or `"rra"` for robust rank aggregation
+///   of the rank of the set in each list, which is less affected by a single low-quality list (default: `"stouffer"`)
+/// - `weights` - If provided, the weight of each list for Stouffer's method, like a list in the same order as the lists, or a dictionary with the same names
+///   if the lists are named. The combined z-score is `sum(w * z) / sqrt(sum(w^2))`, so a list with a larger weight counts more. A common choice is
+///   the square root of the sample size of each cohort. Weights must be positive (default: `None`)
+/// - `vote_fdr` - If provided, the meta-analysis results also contain `votes`, the number of lists where the set has an FDR below this value (default: `None`)
+/// - `on_error` - Either `"raise"` to raise an error if any list can not be read, or `"skip"` to leave out the lists that can not be read and
+///   run the analysis on the rest (default: `"raise"`)
+/// - `names` - If provided, a list of the names of the lists in `rank_files`, to get the results by name (default: `None`)
+/// - `permute` - What is permuted to build the null distribution of each list, like in [`gsea`](./gsea.md). Only `"gene"` is supported (default: `"gene"`)
+/// - `threads` - Number of threads to run the lists on. The lists run in parallel, as well as the sets of each list. If not provided,
+///   uses the default number of threads (see [`set_defaults`](./set_defaults.md)). Each list running at the same time holds its own permutations,
+///   so fewer threads use less memory for many long rank lists (default: `None`)
+/// - `gmt_is_content` - If `True`, `gmt` is the text of the GMT file instead of its path (default: `False`)
+/// - `rank_files_are_content` - If `True`, each of `rank_files` passed as a `String` is the text of the rank file instead of its path (default: `False`)
+/// - `with_metadata` - If `True`, returns a dictionary with the `results` and the `metadata` of the run, which has the same fields as the manifest
+///   with the number of `seconds` it took and the `sizes` of the inputs, so results saved to disk describe how they were made (default: `False`)
+/// - `manifest` - If provided, the `String` of a path to write a JSON manifest of the run to, with the SHA-256 of each input file, the parameters, the versions, and the start and end times (default: `None`)
+/// - `on_complete` - If provided, a function called with the `metadata` of the run when it finishes, with the same fields as with `with_metadata=True`,
+///   to start downstream steps or send a notification. It is called after the manifest is written. Errors raised by the function are emitted as a
+///   [`WebGestaltWarning`](./WebGestaltWarning.md), so the results are still returned (default: `None`)
 ///
 /// # Returns
 ///
@@ -213,9 +982,21 @@ fn gsea(py: Python, gmt_path: String, rank_file_path: String) -> PyResult<Vec<&P
 ///
 /// The first list contains the results of the meta-analysis. The following lists are the results for each list individually.
 ///
-/// # Panics
+/// If the lists are named, returns a dictionary instead, with the results of the meta-analysis in `meta` and a dictionary mapping the name of each list
+/// to its results in `lists`.
+///
+/// If `on_error` is `"skip"`, returns a dictionary instead, with the list of lists (or the named results) in `results` and the lists that were left out in `skipped`.
+/// Each skipped list has its `index`, the `path` of the file (`None` if it was not a path), the `error` when reading it, and its `name` if the lists are named.
+///
+/// When using `nes_combination`, the meta-analysis results also contain the pooled `nes`,
+/// its standard error `nes_se`, the 95% confidence interval `nes_ci_low` and `nes_ci_high`, the between-list variance `tau2` (`0` for fixed effects), and the heterogeneity `i2`.
+/// These are `None` for sets that were not tested in any list.
+///
+/// Every result also has the `seed` used, so the run can be reproduced by passing the `seed` of the meta-analysis results.
+///
+/// # Errors
 ///
-/// Panics if the any file is malformed or not at specified path.
+/// Raises a `ValueError` if the GMT file is malformed or can not be read.
 ///
 /// # Example
 ///
@@ -229,61 +1010,343 @@ fn gsea(py: Python, gmt_path: String, rank_file_path: String) -> PyResult<Vec<&P
 /// individually. In this example, `res[0]` would look be the results of the meta-analysis.
 /// `res[1]` would be the results from `rank_list1.txt`, `res[2]` would be the results from `rank_list2.txt`, and so on.
 ///
+/// With named lists, each list can be found by name, even when other lists are skipped:
+///
+/// ```python
+/// res = webgestaltpy.meta_gsea("kegg.gmt", {"discovery": "rank_list1.txt", "validation": "rank_list2.txt"})
+/// validation = res["lists"]["validation"]
+/// ```
+///
 /// See the documentation for [`webgestaltpy.gsea`](./gsea.md) for specifics about the format of the results.
 #[pyfunction]
-fn meta_gsea(py: Python, gmt: String, rank_files: Vec<String>) -> PyResult<Vec<Vec<&PyDict>>> {
-    let mut jobs: Vec<GSEAJob> = Vec::new();
-    let gmt_vec: Vec<Item> = webgestalt_lib::readers::read_gmt_file(gmt).unwrap();
-    for rank_file in rank_files {
-        let analyte_list_result = webgestalt_lib::readers::read_rank_file(rank_file.clone());
-        if analyte_list_result.is_ok() {
-            let analyte_list = analyte_list_result.unwrap();
-            let new_job = GSEAJob {
-                gmt: gmt_vec.clone(),
-                rank_list: analyte_list.clone(),
-                config: GSEAConfig::default(),
-            };
-            jobs.push(new_job);
-        } else {
-            return Err(PyValueError::new_err(format!(
-                "Error when reading rank file at: {}",
-                rank_file
-            )));
+#[pyo3(signature = (
+    gmt,
+    rank_files,
+    seed=None,
+    nes_combination=None,
+    meta_method="stouffer",
+    weights=None,
+    vote_fdr=None,
+    on_error="raise",
+    names=None,
+    permute="gene",
+    threads=None,
+    gmt_is_content=false,
+    rank_files_are_content=false,
+    with_metadata=false,
+    manifest=None,
+    on_complete=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn meta_gsea(
+    py: Python<'_>,
+    gmt: &Bound<'_, PyAny>,
+    rank_files: &Bound<'_, PyAny>,
+    seed: Option<u64>,
+    nes_combination: Option<&str>,
+    meta_method: &str,
+    weights: Option<&Bound<'_, PyAny>>,
+    vote_fdr: Option<f64>,
+    on_error: &str,
+    names: Option<Vec<String>>,
+    permute: &str,
+    threads: Option<usize>,
+    gmt_is_content: bool,
+    rank_files_are_content: bool,
+    with_metadata: bool,
+    manifest: Option<String>,
+    on_complete: Option<&Bound<'_, PyAny>>,
+) -> PyResult<PyObject> {
+    manifest::check_on_complete(on_complete)?;
+    gsea_stats::check_permute(permute)?;
+    config::check_threads(threads)?;
+    let gmt = inputs::content(gmt, gmt_is_content)?;
+    let mut jobs = meta::Jobs::new(rank_files, names)?;
+    jobs.inputs = inputs::contents(jobs.inputs, rank_files_are_content)?;
+    let mut run = manifest::Manifest::new("meta_gsea");
+    run.input("gmt", &gmt);
+    for rank_file in jobs.inputs.iter() {
+        run.input("rank_file", rank_file);
+    }
+    run.parameter("nes_combination", nes_combination);
+    run.parameter("meta_method", meta_method);
+    run.parameter("vote_fdr", vote_fdr);
+    run.parameter("on_error", on_error);
+    run.parameter("threads", threads);
+    let meta_method = meta::MetaMethod::parse(meta_method)?;
+    let weights = weights.map(|x| jobs.weights(x, &meta_method)).transpose()?;
+    run.parameter("weights", weights.clone());
+    let nes_combination = nes_combination
+        .map(meta::NESCombination::parse)
+        .transpose()?;
+    let on_error = meta::OnError::parse(on_error)?;
+    let gmt_vec: Vec<Item> = inputs::gmt(&gmt)?;
+    let seed = gsea_stats::seed_or_random(seed);
+    // rank lists with the seed of their permutations
+    let mut rank_lists: Vec<(Vec<RankListItem>, u64)> = Vec::new();
+    let mut kept: Vec<usize> = Vec::new();
+    let mut skipped: Vec<(meta::SkippedInput, String)> = Vec::new();
+    for (i, rank_file) in jobs.inputs.iter().enumerate() {
+        match inputs::rank_list(rank_file) {
+            Ok(analyte_list) => {
+                rank_lists.push((analyte_list, seed.wrapping_add(i as u64)));
+                kept.push(i);
+            }
+            Err(e) => on_error.handle(py, jobs.skipped(i), e, &mut skipped)?,
         }
     }
-
-    let rust_result = multilist_gsea(
-        jobs,
-        webgestalt_lib::methods::multilist::MultiListMethod::Meta(
-            webgestalt_lib::methods::multilist::MetaAnalysisMethod::Stouffer,
-        ),
-        webgestalt_lib::stat::AdjustmentMethod::BH,
+    run.size("sets", gmt_vec.len());
+    run.size("rank_lists", rank_lists.len());
+    let config = config::gsea_config();
+    add_gsea_parameters(&mut run, &config);
+    run.parameter("seed", seed);
+    run.parameter("permute", permute);
+    let list_seeds: Vec<u64> = rank_lists.iter().map(|x| x.1).collect();
+    let with_standard_errors = nes_combination.is_some();
+    // each list with the standard errors of the NES of each set (in GMT order), run in parallel across lists
+    let (results, standard_errors): (Vec<Vec<GSEAResult>>, Vec<Vec<Option<f64>>>) =
+        config::install_threads(py, threads, || {
+            rank_lists
+                .into_par_iter()
+                .map(|(rank_list, list_seed)| {
+                    let permutations = gsea_stats::make_permutations(
+                        config.permutations,
+                        rank_list.len(),
+                        list_seed,
+                    );
+                    let sorted = gsea_stats::SortedRankList::new(&rank_list);
+                    let res = gsea_stats::gsea(&rank_list, &gmt_vec, &config, &permutations);
+                    if !with_standard_errors {
+                        return (res, Vec::new());
+                    }
+                    let standard_errors = gmt_vec
+                        .par_iter()
+                        .zip(res.par_iter())
+                        .map(|(set, row)| {
+                            let null = gsea_stats::null_nes(
+                                &sorted,
+                                set,
+                                &permutations,
+                                config.min_overlap,
+                                config.max_overlap,
+                            );
+                            gsea_stats::nes_standard_error(row.nes, &null)
+                        })
+                        .collect();
+                    (res, standard_errors)
+                })
+                .unzip()
+        })?;
+    let kept_weights: Option<Vec<f64>> = weights.map(|x| kept.iter().map(|i| x[*i]).collect());
+    let (sets, meta_p, meta_fdr) = meta::meta_analysis(
+        &results
+            .iter()
+            .map(|x| x.iter().map(|row| (row.set.clone(), row.p)).collect())
+            .collect::<Vec<Vec<(String, f64)>>>(),
+        &meta_method,
+        kept_weights.as_deref(),
     );
-    let mut final_results: Vec<Vec<&PyDict>> = Vec::new();
-    for res in rust_result {
-        let converted = res
+    let votes = vote_fdr.map(|threshold| {
+        meta::vote_counts(
+            &results
+                .iter()
+                .map(|x| x.iter().map(|row| (row.set.clone(), row.fdr)).collect())
+                .collect::<Vec<Vec<(String, f64)>>>(),
+            &sets,
+            threshold,
+        )
+    });
+    // the row of each set in each list, found by ID, since the GMT can list a set more than once
+    let positions: Vec<FxHashMap<&String, usize>> = results
+        .iter()
+        .map(|res| {
+            let mut positions: FxHashMap<&String, usize> = FxHashMap::default();
+            for (j, row) in res.iter().enumerate() {
+                positions.entry(&row.set).or_insert(j);
+            }
+            positions
+        })
+        .collect();
+    let options = RowOptions::from_defaults();
+    let mut meta_results: Vec<Bound<'_, PyDict>> = Vec::new();
+    for (i, set) in sets.into_iter().enumerate() {
+        let pooled = nes_combination.as_ref().map(|method| {
+            let studies: Vec<(f64, f64)> = results
+                .iter()
+                .zip(standard_errors.iter())
+                .zip(positions.iter())
+                .filter_map(|((res, se), positions)| {
+                    let j = *positions.get(&set)?;
+                    se[j].map(|se| (res[j].nes, se))
+                })
+                .collect();
+            meta::PooledNES::new(&studies, method)
+        });
+        let row = gsea_result_to_dict(
+            GSEAResult {
+                set,
+                p: meta_p[i],
+                fdr: meta_fdr[i],
+                es: 0.0,
+                nes: 0.0,
+                leading_edge: 0,
+                running_sum: Vec::new(),
+            },
+            &options,
+            py,
+        )?;
+        if let Some(pooled) = &pooled {
+            meta::PooledNES::add_to_dict(pooled, &row)?;
+        }
+        if let Some(votes) = &votes {
+            row.set_item("votes", votes[i])?;
+        }
+        row.set_item("seed", seed)?;
+        meta_results.push(row);
+    }
+    let mut final_results: Vec<Vec<Bound<'_, PyDict>>> = vec![meta_results];
+    for (res, list_seed) in results.into_iter().zip(list_seeds) {
+        let converted: Vec<Bound<'_, PyDict>> = res
             .into_iter()
-            .map(|x| gsea_result_to_dict(x, py).unwrap())
+            .map(|x| gsea_result_to_dict(x, &options, py).unwrap())
             .collect();
+        for row in converted.iter() {
+            row.set_item("seed", list_seed)?;
+        }
         final_results.push(converted);
     }
-    Ok(final_results)
+    if let Some(path) = manifest {
+        run.write(py, &path)?;
+    }
+    let final_results = jobs.label_results(py, final_results, &kept)?;
+    let final_results = on_error.wrap_results(py, final_results, skipped)?;
+    run.wrap(py, final_results, with_metadata, on_complete)
 }
 
 /// Run a single-omic ORA with files at the provided paths.
 ///
 /// # Parameters
-/// - `gmt_path` - `String` of the path to the gmt file of interest
-/// - `analyte_list_path` - `String` of the path to the analyte file of interest.
-/// - `reference_list_path`
+/// - `gmt_path` - `String` of the path to the gmt file of interest, a file-like object (like an open file or `io.StringIO`), a [`Gmt`](./Gmt.md) object, or a dictionary mapping each set name to a list of analytes
+/// - `analyte_list_path` - `String` of the path to the analyte file of interest, a file-like object, or a list of analytes.
+///   Analytes can be `String`s or integers, like Entrez IDs from a pandas column, which match the same IDs read from a file.
+///   The content of a file as `bytes` or a `memoryview`, with an analyte on each line, is also accepted, like a file read from object storage.
+/// - `reference_list_path` - `String` of the path to the reference file of interest, a file-like object, `bytes` or a `memoryview` of its content,
+///   or a list of analytes. For large reference lists, passing the bytes is much faster than a list of strings.
+///   Can only be `None` if `universe` is provided.
+/// - `bias` - If provided, a per-analyte bias covariate, like gene length or mean expression, in any form accepted as a rank list by [`gsea`](./gsea.md).
+///   The p-values are then corrected for the bias like [goseq](https://doi.org/10.1186/gb-2010-11-2-r14), or with `method="permutation"`, the random
+///   lists are matched to the analyte list by bins of the bias. See below for details. (default: `None`)
+/// - `aliases` - If provided, an alias table that lets analytes match the GMT through aliases, like previous gene symbols, in any form accepted by
+///   [`liftover_gmt`](./liftover_gmt.md). See below for details. (default: `None`)
+/// - `exclude` - If provided, an analyte list in any form accepted for `analyte_list_path` of analytes to drop from the analyte list, the reference list,
+///   and every set before testing, like the query gene of a locus expanded with [`nta`](./nta.md). See below for details. (default: `None`)
+/// - `universe` - If provided, an analyte list in any form accepted for `analyte_list_path` of every analyte the assay can measure, like the targets
+///   of a panel, used instead of the reference list. See below for details. (default: `None`)
+/// - `method` - Either `"hypergeometric"` for the hypergeometric test, or `"permutation"` for p-values from random analyte lists drawn from the
+///   reference list, for small universes like targeted panels. See below for details. (default: `"hypergeometric"`)
+/// - `seed` - Seed for the random lists of `method="permutation"`. If not provided, a new seed is generated (default: `None`)
+/// - `statistic` - If provided, a Python callable that computes the p-values from the 2×2 contingency tables of the sets, used instead of the
+///   hypergeometric test, like Barnard's test. See below for details. (default: `None`)
+/// - `statistic_batch_size` - Maximum number of tables passed to `statistic` in each call (default: `1000`)
+/// - `offset` - Number of results to skip, to get a page of the results. See below for details. (default: `0`)
+/// - `limit` - If provided, the maximum number of results to return (default: `None`)
+/// - `gmt_is_content` - If `True`, `gmt_path` is the text of the GMT file instead of its path (default: `False`)
+/// - `analyte_list_is_content` - If `True`, `analyte_list_path` is the text of the analyte file instead of its path (default: `False`)
+/// - `reference_list_is_content` - If `True`, `reference_list_path` is the text of the reference file instead of its path (default: `False`)
+/// - `with_metadata` - If `True`, returns a dictionary with the `results` and the `metadata` of the run, which has the same fields as the manifest
+///   with the number of `seconds` it took and the `sizes` of the inputs, so results saved to disk describe how they were made (default: `False`)
+/// - `manifest` - If provided, the `String` of a path to write a JSON manifest of the run to, with the SHA-256 of each input file, the parameters, the versions, and the start and end times (default: `None`)
+/// - `on_complete` - If provided, a function called with the `metadata` of the run when it finishes, with the same fields as with `with_metadata=True`,
+///   to start downstream steps or send a notification. It is called after the manifest is written. Errors raised by the function are emitted as a
+///   [`WebGestaltWarning`](./WebGestaltWarning.md), so the results are still returned (default: `None`)
+/// - `significance_thresholds` - If provided, the FDR thresholds of the significance flags of the results, like `[0.05, 0.25]`, instead of the
+///   `significance_thresholds` of [`set_defaults`](./set_defaults.md), where the flags are described (default: `None`)
 ///
 /// # Returns
 ///
 /// Returns a list of dictionaries with the results containing the ORA results for every set.
+/// Each result also contains the rest of the 2×2 contingency table of the set within the reference list:
+/// `list_only` (analytes in the list but not the set), `set_only` (in the set but not the list), and `neither`.
+/// `expected_sd` is the hypergeometric standard deviation of the overlap, and `z` is `(overlap - expected) / expected_sd`
+/// (`None` if `expected_sd` is `0`).
+///
+/// `overlap`, `list_only`, `set_only`, and `neither` are always integers, and `p`, `fdr`, `expected`, `enrichment_ratio`, and `expected_sd`
+/// are always floats, even with a whole value. For a set without analytes in the reference list, `expected` is `0.0` and `enrichment_ratio` is `nan`.
+/// [`load_results`](./load_results.md) keeps these types for results saved as a table.
+///
+/// # Pagination
+///
+/// When `offset` or `limit` is provided, the results are sorted by increasing `fdr`, then `p`, then set name, and only the results of the page
+/// are converted to Python, so a service can page through large databases like GO. Consecutive pages of the same analysis line up.
+/// Without them, every result is returned in the order of the GMT.
+///
+/// # Bias Correction
+///
+/// Differentially expressed genes from RNA-seq are more likely to be long or highly expressed genes, which biases standard ORA towards sets of such genes.
+/// When `bias` is provided, analytes without a bias value are left out of both lists. The reference is split into 20 bins by bias, and the fraction
+/// of each bin in the analyte list is fit with a monotonic function to give the probability weight of each analyte. The p-value of each set is then
+/// the upper tail of Wallenius' noncentral hypergeometric distribution, with the odds of the mean weight of the set over the mean weight of the rest
+/// of the reference, and `fdr` is adjusted with BH. The odds is reported as `bias_odds`.
 ///
-/// # Panics
+/// With `method="permutation"`, the bias is matched instead of modeled: the reference is split into the same 20 bins by bias, and each random list
+/// draws as many analytes from each bin as the analyte list has in it. Sets of analytes with a high bias, like housekeeping genes with a high mean
+/// expression, are then only enriched if the list has more of them than random lists with the same distribution of bias. `bias_odds` is not reported.
 ///
-/// Panics if the any file is malformed or not at specified path.
+/// # Alias Matching
+///
+/// When `aliases` is provided, the GMT, the analyte list, and the reference list (and the keys of `bias`) are all mapped to current identifiers
+/// before matching, so an analyte listed by a previous symbol still matches a set listing its current symbol, and the other way around.
+/// Ambiguous and unknown identifiers are kept as they are. Every result also has `alias_matches`, the number of analytes in the overlap
+/// that only match the set through an alias.
+///
+/// # Excluding Analytes
+///
+/// When the analyte list is built around a few query analytes, like a GWAS locus expanded to its network neighborhood, the sets containing
+/// the query analytes are enriched by construction. With `exclude`, those analytes are dropped from the analyte list, the reference list, and
+/// every set before testing, so the results only reflect the rest of the list. Sizes and contingency tables are computed without them.
+/// With `aliases`, the excluded analytes are mapped to current identifiers too.
+///
+/// # Explicit Universe
+///
+/// The reference list is limited to the analytes in at least one set, like in WebGestalt. For a targeted assay, the population is the set of
+/// analytes the assay measures, whether they are annotated or not. With `universe`, the analytes of `universe` are the population as they are:
+/// the analyte list is limited to the universe, the sets are counted within it, and every statistic (`expected`, the p-values, the contingency
+/// table, and the random lists of `method="permutation"`) is computed against it. `reference_list_path` must then be `None`.
+///
+/// # Permutation Test
+///
+/// With a universe of a few hundred analytes, like a targeted panel, and heavily overlapping sets, the hypergeometric p-values and their BH
+/// adjustment are unreliable. With `method="permutation"`, lists of the same size as the analyte list are drawn from the reference list, as many
+/// as the default number of permutations (see [`set_defaults`](./set_defaults.md)). The p-value of a set is `(1 + b) / (1 + permutations)`, where `b` is
+/// the number of random lists with an overlap at least as large as observed. The FDR is estimated from the same random lists: the mean number of sets
+/// per random list with a p-value at most as large as that of the set, over the number of sets with an observed p-value at most as large, made monotonic
+/// like BH. This accounts for the dependence between sets and the discreteness of small overlaps. Every result also has the `seed`.
+///
+/// # Custom Statistic
+///
+/// When the built-in tests do not fit, `statistic` computes the p-value of every tested set (`expected` above `0`) instead. It is called with a list
+/// of at most `statistic_batch_size` tables, each an `(overlap, list_only, set_only, neither)` tuple of the counts within the reference list,
+/// and must return a sequence of as many p-values, like a list or a NumPy array. Passing many tables in each call keeps the overhead of calling
+/// Python low. The other columns are unchanged, and `fdr` is adjusted with BH.
+///
+/// ```python
+/// from scipy.stats import barnard_exact
+///
+/// def barnard(tables):
+///     return [barnard_exact([[a, b], [c, d]], alternative="greater").pvalue for a, b, c, d in tables]
+///
+/// res = webgestaltpy.ora("kegg.gmt", "gene_list.txt", "reference.txt", statistic=barnard)
+/// ```
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, if no analytes of the list have a bias value,
+/// if not exactly one of `reference_list_path` and `universe` is provided, or if a significance threshold is not above `0` and at most `1`.
+/// Also raises a `ValueError` if `statistic` is used with `bias` or `method="permutation"`, or returns the wrong number of p-values
+/// or a p-value that is not between `0` and `1`. Errors raised by `statistic` are passed through.
+///
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if some analytes of the list are left out, since they are not in the reference list or in any set,
+/// or not in `universe`.
 ///
 /// # Example
 ///
@@ -305,7 +1368,12 @@ fn meta_gsea(py: Python, gmt: String, rank_files: Vec<String>) -> PyResult<Vec<V
 ///     'fdr': 1,
 ///     'overlap': 2,
 ///     'expected': 2.6840874707743088,
-///     'enrichment_ratio': 0.7451321992211519
+///     'enrichment_ratio': 0.7451321992211519,
+///     'expected_sd': 1.5982432443613253,
+///     'z': -0.4280246284085983,
+///     'list_only': 285,
+///     'set_only': 66,
+///     'neither': 6918
 ///   },
 ///   {
 ///     'set': 'has00020',
@@ -313,34 +1381,344 @@ fn meta_gsea(py: Python, gmt: String, rank_files: Vec<String>) -> PyResult<Vec<V
 ///     'fdr': 0.9981116297866582,
 ///     'overlap': 1,
 ///     'expected': 1.1841562371063128,
-///     'enrichment_ratio': 0.8444831591173054
+///     'enrichment_ratio': 0.8444831591173054,
+///     'expected_sd': 1.0643675236607986,
+///     'z': -0.1730194063728322,
+///     'list_only': 286,
+///     'set_only': 29,
+///     'neither': 6955
 ///   }
 /// ]
 /// ```
 #[pyfunction]
+#[pyo3(signature = (
+    gmt_path,
+    analyte_list_path,
+    reference_list_path,
+    bias=None,
+    aliases=None,
+    exclude=None,
+    universe=None,
+    method="hypergeometric",
+    seed=None,
+    statistic=None,
+    statistic_batch_size=1000,
+    offset=0,
+    limit=None,
+    gmt_is_content=false,
+    analyte_list_is_content=false,
+    reference_list_is_content=false,
+    with_metadata=false,
+    manifest=None,
+    on_complete=None,
+    significance_thresholds=None
+))]
+#[allow(clippy::too_many_arguments)]
 fn ora(
-    py: Python,
-    gmt_path: String,
-    analyte_list_path: String,
-    reference_list_path: String,
-) -> PyResult<Vec<&PyDict>> {
-    let (gmt, analyte_list, reference) =
-        webgestalt_lib::readers::read_ora_files(gmt_path, analyte_list_path, reference_list_path);
-    let res: Vec<ORAResult> =
-        webgestalt_lib::methods::ora::get_ora(&analyte_list, &reference, gmt, ORAConfig::default());
-    let new_res: Vec<&PyDict> = res
-        .into_iter()
-        .map(|x| ora_result_to_dict(x, py).unwrap())
-        .collect();
-    Ok(new_res)
+    py: Python<'_>,
+    gmt_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    reference_list_path: Option<&Bound<'_, PyAny>>,
+    bias: Option<&Bound<'_, PyAny>>,
+    aliases: Option<&Bound<'_, PyAny>>,
+    exclude: Option<&Bound<'_, PyAny>>,
+    universe: Option<&Bound<'_, PyAny>>,
+    method: &str,
+    seed: Option<u64>,
+    statistic: Option<&Bound<'_, PyAny>>,
+    statistic_batch_size: usize,
+    offset: usize,
+    limit: Option<usize>,
+    gmt_is_content: bool,
+    analyte_list_is_content: bool,
+    reference_list_is_content: bool,
+    with_metadata: bool,
+    manifest: Option<String>,
+    on_complete: Option<&Bound<'_, PyAny>>,
+    significance_thresholds: Option<Vec<f64>>,
+) -> PyResult<PyObject> {
+    manifest::check_on_complete(on_complete)?;
+    let options = RowOptions::with_thresholds(significance_thresholds)?;
+    let gmt_path = inputs::content(gmt_path, gmt_is_content)?;
+    let analyte_list_path = inputs::content(analyte_list_path, analyte_list_is_content)?;
+    let population_path = match (reference_list_path, universe) {
+        (Some(reference_list_path), None) => {
+            inputs::content(reference_list_path, reference_list_is_content)?
+        }
+        (None, Some(universe)) => universe.clone(),
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "universe can not be used with reference_list_path!",
+            ))
+        }
+        (None, None) => {
+            return Err(PyValueError::new_err(
+                "Either reference_list_path or universe must be provided!",
+            ))
+        }
+    };
+    let mut run = manifest::Manifest::new("ora");
+    run.input("gmt", &gmt_path);
+    run.input("analyte_list", &analyte_list_path);
+    run.input(
+        if universe.is_some() {
+            "universe"
+        } else {
+            "reference_list"
+        },
+        &population_path,
+    );
+    if let Some(bias) = bias {
+        run.input("bias", bias);
+    }
+    if let Some(aliases) = aliases {
+        run.input("aliases", aliases);
+    }
+    if let Some(exclude) = exclude {
+        run.input("exclude", exclude);
+    }
+    add_ora_parameters(&mut run, &config::ora_config());
+    run.parameter("method", method);
+    let seed = match ora_stats::ORAMethod::parse(method)? {
+        ora_stats::ORAMethod::Hypergeometric => None,
+        ora_stats::ORAMethod::Permutation => {
+            let seed = gsea_stats::seed_or_random(seed);
+            run.parameter("permutations", config::defaults().permutations);
+            run.parameter("seed", seed);
+            Some(seed)
+        }
+    };
+    if let Some(statistic) = statistic {
+        if bias.is_some() || seed.is_some() {
+            return Err(PyValueError::new_err(
+                "statistic can not be used with bias or method 'permutation'!",
+            ));
+        }
+        if !statistic.is_callable() {
+            return Err(PyValueError::new_err("statistic must be callable!"));
+        }
+        if statistic_batch_size == 0 {
+            return Err(PyValueError::new_err(
+                "statistic_batch_size must be at least 1!",
+            ));
+        }
+        run.parameter(
+            "statistic",
+            statistic
+                .getattr("__name__")
+                .and_then(|x| x.extract::<String>())
+                .unwrap_or_else(|_| "custom".to_owned()),
+        );
+    }
+    let gmt = inputs::gmt(&gmt_path)?;
+    let analyte_list = inputs::analyte_list(&analyte_list_path)?;
+    let reference_list = inputs::analyte_list(&population_path)?;
+    let aliases = aliases.map(aliases::alias_map).transpose()?;
+    let (matching, gmt, analyte_list, reference_list) = match &aliases {
+        Some(aliases) => {
+            let (matching, gmt, analyte_list) =
+                aliases::AliasMatching::new(aliases, gmt, analyte_list, |x| x);
+            let reference_list: Vec<String> = reference_list
+                .iter()
+                .map(|x| aliases.canonical(x).to_owned())
+                .collect();
+            (Some(matching), gmt, analyte_list, reference_list)
+        }
+        None => (None, gmt, analyte_list, reference_list),
+    };
+    let (gmt, analyte_list, reference_list) = match exclude {
+        Some(exclude) => {
+            let excluded: AHashSet<String> = inputs::analyte_list(exclude)?
+                .into_iter()
+                .map(|x| match &aliases {
+                    Some(aliases) => aliases.canonical(&x).to_owned(),
+                    None => x,
+                })
+                .collect();
+            inputs::exclude_analytes(gmt, analyte_list, reference_list, &excluded)
+        }
+        None => (gmt, analyte_list, reference_list),
+    };
+    let listed = analyte_list.iter().collect::<FxHashSet<&String>>().len();
+    let (gmt, mut analyte_list, mut reference) = if universe.is_some() {
+        inputs::universe_ora_inputs(gmt, analyte_list, reference_list)
+    } else {
+        inputs::filter_ora_inputs(gmt, analyte_list, reference_list)
+    };
+    run.size("sets", gmt.len());
+    run.size("analyte_list", analyte_list.len());
+    run.size(
+        if universe.is_some() {
+            "universe"
+        } else {
+            "reference_list"
+        },
+        reference.len(),
+    );
+    let bias: Option<FxHashMap<String, f64>> = match bias {
+        Some(bias) => {
+            let bias: FxHashMap<String, f64> = inputs::rank_list(bias)?
+                .into_iter()
+                .map(|x| match &aliases {
+                    Some(aliases) => (aliases.canonical(&x.analyte).to_owned(), x.rank),
+                    None => (x.analyte, x.rank),
+                })
+                .collect();
+            // like goseq, analytes without a bias value are left out
+            reference.retain(|x| bias.contains_key(x));
+            analyte_list.retain(|x| reference.contains(x));
+            if analyte_list.is_empty() {
+                return Err(PyValueError::new_err(
+                    "No analytes of the list have a bias value!",
+                ));
+            }
+            Some(bias)
+        }
+        None => None,
+    };
+    warnings::warn_left_out(
+        py,
+        listed - analyte_list.len(),
+        listed,
+        "analytes of the list",
+        if universe.is_some() {
+            "are not in the universe"
+        } else {
+            "are not in both the reference list and a set"
+        },
+    )?;
+    let sizes = ora_stats::ORASizes::new(&gmt, &analyte_list, &reference);
+    // with permutations, the bias is matched by the random lists instead
+    let corrected = bias
+        .as_ref()
+        .filter(|_| seed.is_none())
+        .map(|bias| {
+            let reference: Vec<String> = reference.iter().cloned().collect();
+            config::install(py, || {
+                let weights = bias::probability_weights(&analyte_list, &reference, bias);
+                bias::bias_corrected_p(&gmt, &analyte_list, &weights)
+            })
+        })
+        .transpose()?;
+    let alias_matches: Option<Vec<usize>> = matching.map(|matching| {
+        gmt.iter()
+            .enumerate()
+            .map(|(i, set)| matching.alias_matches(i, set, |x| analyte_list.contains(x)))
+            .collect()
+    });
+    let permuted = seed
+        .map(|seed| {
+            config::install(py, || {
+                ora_stats::permutation_p(
+                    &gmt,
+                    &analyte_list,
+                    &reference,
+                    bias.as_ref(),
+                    config::defaults().permutations as usize,
+                    seed,
+                )
+            })
+        })
+        .transpose()?;
+    let mut res: Vec<ORAResult> = config::install(py, || {
+        get_ora(&analyte_list, &reference, gmt, config::ora_config())
+    })?;
+    if let Some((p, fdr)) = permuted {
+        for (row, (p, fdr)) in res.iter_mut().zip(p.into_iter().zip(fdr)) {
+            row.p = p;
+            row.fdr = fdr;
+        }
+    }
+    if let Some(statistic) = statistic {
+        let tested: Vec<usize> = (0..res.len()).filter(|i| res[*i].expected > 0.0).collect();
+        let tables: Vec<ora_stats::ContingencyTable> = tested
+            .iter()
+            .map(|i| {
+                ora_stats::ContingencyTable::new(
+                    res[*i].overlap as usize,
+                    sizes.set_sizes[*i],
+                    sizes.list_size,
+                    sizes.reference_size,
+                )
+            })
+            .collect();
+        let p = ora_stats::statistic_p(statistic, &tables, statistic_batch_size)?;
+        for (i, p) in tested.into_iter().zip(p) {
+            res[i].p = p;
+        }
+        let p: Vec<f64> = res.iter().map(|x| x.p).collect();
+        for (row, fdr) in res.iter_mut().zip(adjust(&p, AdjustmentMethod::BH)) {
+            row.fdr = fdr;
+        }
+    }
+    if let Some(corrected) = &corrected {
+        let p: Vec<f64> = corrected.iter().map(|x| x.0).collect();
+        let fdr = adjust(&p, AdjustmentMethod::BH);
+        for (row, (p, fdr)) in res.iter_mut().zip(p.into_iter().zip(fdr)) {
+            row.p = p;
+            row.fdr = fdr;
+        }
+    }
+    if let Some(path) = manifest {
+        run.write(py, &path)?;
+    }
+    let order = page_order(
+        res.iter().map(|x| (x.fdr, x.p, &x.set)).collect(),
+        offset,
+        limit,
+    );
+    let mut res: Vec<Option<ORAResult>> = res.into_iter().map(Some).collect();
+    let mut rows: Vec<Bound<'_, PyDict>> = Vec::with_capacity(order.len());
+    for i in order {
+        let row = ora_result_with_table(
+            res[i].take().unwrap(),
+            sizes.set_sizes[i],
+            &sizes,
+            &options,
+            py,
+        )?;
+        if let Some(corrected) = &corrected {
+            row.set_item("bias_odds", corrected[i].1)?;
+        }
+        if let Some(alias_matches) = &alias_matches {
+            row.set_item("alias_matches", alias_matches[i])?;
+        }
+        if let Some(seed) = seed {
+            row.set_item("seed", seed)?;
+        }
+        rows.push(row);
+    }
+    run.wrap(py, rows.into_py_any(py)?, with_metadata, on_complete)
 }
 
 /// Run a meta-analysis ORA with files at the provided paths.
 ///
 /// # Parameters
-/// - `gmt_path` - `String` of the path to the gmt file of interest
-/// - `analyte_list_paths` -  Lists of `String`s of the path to the analyte files of interest.
-/// - `reference_list_paths` - Lists of `String`s of the paths to reference lists.
+/// - `gmt_path` - `String` of the path to the gmt file of interest, a file-like object (like an open file or `io.StringIO`), a [`Gmt`](./Gmt.md) object, or a dictionary mapping each set name to a list of analytes
+/// - `analyte_list_paths` -  Lists of `String`s of the path to the analyte files of interest, file-like objects, or lists (or tuples) of analytes. Paths and in-memory lists can be mixed.
+///   Can also be a dictionary mapping the name of each list to its analyte list, to get the results by name.
+/// - `reference_list_paths` - Lists of `String`s of the paths to reference lists, file-like objects, or lists (or tuples) of analytes. Paths and in-memory lists can be mixed.
+///   If the lists are named, can also be a dictionary mapping the name of each list to its reference list.
+/// - `meta_method` - How the p-values of each set are combined across lists. Either `"stouffer"` for Stouffer's method, or `"rra"` for robust rank aggregation
+///   of the rank of the set in each list, which is less affected by a single low-quality list (default: `"stouffer"`)
+/// - `weights` - If provided, the weight of each list for Stouffer's method, like a list in the same order as the lists, or a dictionary with the same names
+///   if the lists are named. The combined z-score is `sum(w * z) / sqrt(sum(w^2))`, so a list with a larger weight counts more. A common choice is
+///   the square root of the sample size of each cohort. Weights must be positive (default: `None`)
+/// - `vote_fdr` - If provided, the meta-analysis results also contain `votes`, the number of lists where the set has an FDR below this value (default: `None`)
+/// - `on_error` - Either `"raise"` to raise an error if any list can not be read, or `"skip"` to leave out the lists that can not be read and
+///   run the analysis on the rest (default: `"raise"`)
+/// - `names` - If provided, a list of the names of the lists in `analyte_list_paths`, to get the results by name (default: `None`)
+/// - `threads` - Number of threads to run the lists on. The lists are read one at a time, and then run in parallel. If not provided,
+///   uses the default number of threads (see [`set_defaults`](./set_defaults.md)) (default: `None`)
+/// - `gmt_is_content` - If `True`, `gmt_path` is the text of the GMT file instead of its path (default: `False`)
+/// - `analyte_lists_are_content` - If `True`, each of `analyte_list_paths` passed as a `String` is the text of the analyte file instead of its path (default: `False`)
+/// - `reference_lists_are_content` - If `True`, each of `reference_list_paths` passed as a `String` is the text of the reference file instead of its path (default: `False`)
+/// - `with_metadata` - If `True`, returns a dictionary with the `results` and the `metadata` of the run, which has the same fields as the manifest
+///   with the number of `seconds` it took and the `sizes` of the inputs, so results saved to disk describe how they were made (default: `False`)
+/// - `manifest` - If provided, the `String` of a path to write a JSON manifest of the run to, with the SHA-256 of each input file, the parameters, the versions, and the start and end times (default: `None`)
+/// - `on_complete` - If provided, a function called with the `metadata` of the run when it finishes, with the same fields as with `with_metadata=True`,
+///   to start downstream steps or send a notification. It is called after the manifest is written. Errors raised by the function are emitted as a
+///   [`WebGestaltWarning`](./WebGestaltWarning.md), so the results are still returned (default: `None`)
 ///
 /// # Returns
 ///
@@ -348,9 +1726,11 @@ fn ora(
 ///
 /// The first list contains the results of the meta-analysis. The following lists are the results for each list individually.
 ///
-/// # Panics
+/// If the lists are named, returns a dictionary instead, with the results of the meta-analysis in `meta` and a dictionary mapping the name of each list
+/// to its results in `lists`.
 ///
-/// Panics if the any file is malformed or not at specified path.
+/// If `on_error` is `"skip"`, returns a dictionary instead, with the list of lists (or the named results) in `results` and the lists that were left out in `skipped`.
+/// Each skipped list has its `index`, the `path` of the analyte file (`None` if it was not a path), the `error` when reading it, and its `name` if the lists are named.
 ///
 /// # Example
 ///
@@ -364,14 +1744,78 @@ fn ora(
 /// individually. In this example, `res[0]` would look be the results of the meta-analysis.
 /// `res[1]` would be the results from `gene_list1.txt`, `res[2]` would be the results from `gene_list2.txt`, and so on.
 ///
+/// With named lists, each list can be found by name, even when other lists are skipped:
+///
+/// ```python
+/// res = webgestaltpy.meta_ora("kegg.gmt", {"tumor": "gene_list1.txt", "normal": "gene_list2.txt"}, ["reference.txt", "reference.txt"])
+/// tumor = res["lists"]["tumor"]
+/// ```
+///
 /// See the documentation for [`webgestaltpy.ora`](./ora.md) for specifics about the format of the results.
 #[pyfunction]
+#[pyo3(signature = (
+    gmt_path,
+    analyte_list_paths,
+    reference_list_paths,
+    meta_method="stouffer",
+    weights=None,
+    vote_fdr=None,
+    on_error="raise",
+    names=None,
+    threads=None,
+    gmt_is_content=false,
+    analyte_lists_are_content=false,
+    reference_lists_are_content=false,
+    with_metadata=false,
+    manifest=None,
+    on_complete=None
+))]
+#[allow(clippy::too_many_arguments)]
 fn meta_ora(
-    py: Python,
-    gmt_path: String,
-    analyte_list_paths: Vec<String>,
-    reference_list_paths: Vec<String>,
-) -> PyResult<Vec<Vec<&PyDict>>> {
+    py: Python<'_>,
+    gmt_path: &Bound<'_, PyAny>,
+    analyte_list_paths: &Bound<'_, PyAny>,
+    reference_list_paths: &Bound<'_, PyAny>,
+    meta_method: &str,
+    weights: Option<&Bound<'_, PyAny>>,
+    vote_fdr: Option<f64>,
+    on_error: &str,
+    names: Option<Vec<String>>,
+    threads: Option<usize>,
+    gmt_is_content: bool,
+    analyte_lists_are_content: bool,
+    reference_lists_are_content: bool,
+    with_metadata: bool,
+    manifest: Option<String>,
+    on_complete: Option<&Bound<'_, PyAny>>,
+) -> PyResult<PyObject> {
+    manifest::check_on_complete(on_complete)?;
+    config::check_threads(threads)?;
+    let gmt_path = inputs::content(gmt_path, gmt_is_content)?;
+    let mut jobs = meta::Jobs::new(analyte_list_paths, names)?;
+    let reference_list_paths = inputs::contents(
+        jobs.paired(reference_list_paths, "reference list")?,
+        reference_lists_are_content,
+    )?;
+    jobs.inputs = inputs::contents(jobs.inputs, analyte_lists_are_content)?;
+    let analyte_list_paths = &jobs.inputs;
+    let mut run = manifest::Manifest::new("meta_ora");
+    run.input("gmt", &gmt_path);
+    for (analyte_list_path, reference_list_path) in
+        analyte_list_paths.iter().zip(reference_list_paths.iter())
+    {
+        run.input("analyte_list", analyte_list_path);
+        run.input("reference_list", reference_list_path);
+    }
+    run.parameter("meta_method", meta_method);
+    run.parameter("vote_fdr", vote_fdr);
+    run.parameter("on_error", on_error);
+    run.parameter("threads", threads);
+    add_ora_parameters(&mut run, &config::ora_config());
+    let meta_method = meta::MetaMethod::parse(meta_method)?;
+    let weights = weights.map(|x| jobs.weights(x, &meta_method)).transpose()?;
+    run.parameter("weights", weights.clone());
+    let on_error = meta::OnError::parse(on_error)?;
     if analyte_list_paths.len() != reference_list_paths.len() {
         // Verify list sizes
         Err(PyValueError::new_err(format!(
@@ -380,48 +1824,149 @@ fn meta_ora(
             reference_list_paths.len()
         )))
     } else {
-        let mut jobs: Vec<ORAJob> = Vec::new();
+        let gmt_vec = inputs::gmt(&gmt_path)?;
+        let mut list_inputs: Vec<inputs::ORAInputs> = Vec::new();
+        let mut list_sizes: Vec<ora_stats::ORASizes> = Vec::new();
+        let mut kept: Vec<usize> = Vec::new();
+        let mut skipped: Vec<(meta::SkippedInput, String)> = Vec::new();
+        // the lists are read with the GIL, and then run in parallel
         for (i, analyte_list_path) in analyte_list_paths.iter().enumerate() {
-            let (gmt, analyte_list, reference) = webgestalt_lib::readers::read_ora_files(
-                gmt_path.clone(),
-                analyte_list_path.clone(),
-                reference_list_paths[i].clone(),
-            );
-            let new_job: ORAJob = ORAJob {
-                gmt: gmt.clone(),
-                interest_list: analyte_list.clone(),
-                reference_list: reference.clone(),
-                config: ORAConfig::default(),
-            };
-            jobs.push(new_job);
-        }
-        let rust_result = multilist_ora(
-            jobs,
-            webgestalt_lib::methods::multilist::MultiListMethod::Meta(
-                webgestalt_lib::methods::multilist::MetaAnalysisMethod::Stouffer,
-            ),
-            webgestalt_lib::stat::AdjustmentMethod::BH,
+            match inputs::ora_inputs(gmt_vec.clone(), analyte_list_path, &reference_list_paths[i]) {
+                Ok((gmt, analyte_list, reference)) => {
+                    list_sizes.push(ora_stats::ORASizes::new(&gmt, &analyte_list, &reference));
+                    list_inputs.push((gmt, analyte_list, reference));
+                    kept.push(i);
+                }
+                Err(e) => on_error.handle(py, jobs.skipped(i), e, &mut skipped)?,
+            }
+        }
+        let ora_config = config::ora_config();
+        let results: Vec<Vec<ORAResult>> = config::install_threads(py, threads, || {
+            list_inputs
+                .into_par_iter()
+                .map(|(gmt, analyte_list, reference)| {
+                    get_ora(&analyte_list, &reference, gmt, ora_config.clone())
+                })
+                .collect()
+        })?;
+        let kept_weights: Option<Vec<f64>> = weights.map(|x| kept.iter().map(|i| x[*i]).collect());
+        let (sets, meta_p, meta_fdr) = meta::meta_analysis(
+            &results
+                .iter()
+                .map(|x| x.iter().map(|row| (row.set.clone(), row.p)).collect())
+                .collect::<Vec<Vec<(String, f64)>>>(),
+            &meta_method,
+            kept_weights.as_deref(),
         );
-        let mut final_results: Vec<Vec<&PyDict>> = Vec::new();
-        for res in rust_result {
-            let converted = res
-                .into_iter()
-                .map(|x| ora_result_to_dict(x, py).unwrap())
-                .collect();
-            final_results.push(converted);
+        let votes = vote_fdr.map(|threshold| {
+            meta::vote_counts(
+                &results
+                    .iter()
+                    .map(|x| x.iter().map(|row| (row.set.clone(), row.fdr)).collect())
+                    .collect::<Vec<Vec<(String, f64)>>>(),
+                &sets,
+                threshold,
+            )
+        });
+        let options = RowOptions::from_defaults();
+        let mut meta_results: Vec<Bound<'_, PyDict>> = Vec::new();
+        for (i, set) in sets.into_iter().enumerate() {
+            let row = ora_result_to_dict(
+                ORAResult {
+                    set,
+                    p: meta_p[i],
+                    fdr: meta_fdr[i],
+                    overlap: 0,
+                    expected: 0.0,
+                    enrichment_ratio: 0.0,
+                },
+                &options,
+                py,
+            )?;
+            if let Some(votes) = &votes {
+                row.set_item("votes", votes[i])?;
+            }
+            meta_results.push(row);
+        }
+        let mut final_results: Vec<Vec<Bound<'_, PyDict>>> = vec![meta_results];
+        for (res, sizes) in results.into_iter().zip(list_sizes.iter()) {
+            final_results.push(ora_results_with_tables(res, sizes, py)?);
         }
-        Ok(final_results)
+        run.size("sets", gmt_vec.len());
+        run.size("lists", kept.len());
+        if let Some(path) = manifest {
+            run.write(py, &path)?;
+        }
+        let final_results = jobs.label_results(py, final_results, &kept)?;
+        let final_results = on_error.wrap_results(py, final_results, skipped)?;
+        run.wrap(py, final_results, with_metadata, on_complete)
     }
 }
 
 /// High performance enrichment methods implemented in Rust, with Python bindings.
-#[pymodule]
-fn webgestaltpy(_py: Python, m: &PyModule) -> PyResult<()> {
+///
+/// The module does not need the GIL: its shared state is behind mutexes, so free-threaded builds of Python run calls in parallel.
+#[pymodule(gil_used = false)]
+fn webgestaltpy(py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(config::set_defaults, m)?)?;
+    m.add_function(wrap_pyfunction!(config::get_defaults, m)?)?;
     m.add_function(wrap_pyfunction!(gsea, m)?)?;
     m.add_function(wrap_pyfunction!(ora, m)?)?;
     m.add_function(wrap_pyfunction!(meta_gsea, m)?)?;
     m.add_function(wrap_pyfunction!(meta_ora, m)?)?;
+    m.add_function(wrap_pyfunction!(weighted_ora::weighted_ora, m)?)?;
+    m.add_function(wrap_pyfunction!(set_weights::read_weighted_gmt, m)?)?;
+    m.add_function(wrap_pyfunction!(set_weights::weighted_gmt_ora, m)?)?;
+    m.add_function(wrap_pyfunction!(set_weights::weighted_gmt_gsea, m)?)?;
+    m.add_function(wrap_pyfunction!(trend::gsea_trend, m)?)?;
+    m.add_function(wrap_pyfunction!(concordance::concordance, m)?)?;
+    m.add_function(wrap_pyfunction!(combined::ora_gsea, m)?)?;
+    m.add_function(wrap_pyfunction!(background::expressed_background, m)?)?;
+    m.add_function(wrap_pyfunction!(cell_types::score_cell_types, m)?)?;
+    m.add_function(wrap_pyfunction!(connectivity::connectivity_scores, m)?)?;
+    m.add_function(wrap_pyfunction!(regions::regions_to_genes, m)?)?;
+    m.add_function(wrap_pyfunction!(regions::region_ora, m)?)?;
+    m.add_function(wrap_pyfunction!(regions::region_gsea, m)?)?;
+    m.add_function(wrap_pyfunction!(stream::ora_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(stream::gsea_stream, m)?)?;
+    m.add_class::<stream::ResultStream>()?;
+    m.add(
+        "WebGestaltWarning",
+        py.get_type::<warnings::WebGestaltWarning>(),
+    )?;
     m.add_class::<NTAMethod>()?;
+    m.add_class::<network::NTAConfig>()?;
+    m.add_class::<gmt::Gmt>()?;
     m.add_function(wrap_pyfunction!(nta, m)?)?;
+    m.add_function(wrap_pyfunction!(network::multiplex_nta, m)?)?;
+    m.add_function(wrap_pyfunction!(network::steiner_subnetwork, m)?)?;
+    m.add_function(wrap_pyfunction!(network::pairwise_distances, m)?)?;
+    m.add_function(wrap_pyfunction!(network::nta_modules, m)?)?;
+    m.add_function(wrap_pyfunction!(network::nta_benchmark, m)?)?;
+    m.add_function(wrap_pyfunction!(network::nta_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(network::transition_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(adjust::adjust_grouped, m)?)?;
+    m.add_function(wrap_pyfunction!(compare::compare_results, m)?)?;
+    m.add_function(wrap_pyfunction!(compare::compare_gmts, m)?)?;
+    m.add_function(wrap_pyfunction!(consensus::consensus, m)?)?;
+    m.add_function(wrap_pyfunction!(stability::stability, m)?)?;
+    m.add_function(wrap_pyfunction!(benchmark::benchmark, m)?)?;
+    m.add_function(wrap_pyfunction!(clustering::kappa_clusters, m)?)?;
+    m.add_function(wrap_pyfunction!(clustering::select_top, m)?)?;
+    m.add_function(wrap_pyfunction!(export::membership_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(export::results_to_gmt, m)?)?;
+    m.add_function(wrap_pyfunction!(export::gene_attribution, m)?)?;
+    m.add_function(wrap_pyfunction!(export::load_results, m)?)?;
+    m.add_function(wrap_pyfunction!(aliases::liftover_gmt, m)?)?;
+    m.add_function(wrap_pyfunction!(rank_file::read_rank_file, m)?)?;
+    m.add_function(wrap_pyfunction!(gsea_stats::permutation_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(ids::normalize_ids, m)?)?;
+    m.add_function(wrap_pyfunction!(ids::repair_excel_symbols, m)?)?;
+    m.add_function(wrap_pyfunction!(orthologs::map_orthologs, m)?)?;
+    m.add_function(wrap_pyfunction!(report::enrichment_map_html, m)?)?;
+    m.add_function(wrap_pyfunction!(report::generate_markdown, m)?)?;
+    m.add_function(wrap_pyfunction!(manifest::schema_version, m)?)?;
+    m.add_function(wrap_pyfunction!(plots::gsea_curves, m)?)?;
+    m.add_function(wrap_pyfunction!(plots::write_gsea_plots, m)?)?;
     Ok(())
 }
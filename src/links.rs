@@ -0,0 +1,58 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Whether `text` after its first `prefix_len` bytes is only ASCII digits, at least `digits` of them
+fn digits_after(text: &str, prefix_len: usize, digits: usize) -> bool {
+    text.len() >= prefix_len + digits && text[prefix_len..].bytes().all(|x| x.is_ascii_digit())
+}
+
+/// Canonical page of a set from its ID, for the IDs of KEGG pathways (like `hsa04110`), Reactome pathways (like `R-HSA-109581`),
+/// and GO terms (like `GO:0006915`). Returns `None` for any other ID.
+pub fn set_link(set: &str) -> Option<String> {
+    let set = set.trim();
+    if !set.is_ascii() {
+        return None;
+    }
+    if set.starts_with("GO:") && set.len() == 10 && digits_after(set, 3, 7) {
+        return Some(format!("https://amigo.geneontology.org/amigo/term/{}", set));
+    }
+    let parts: Vec<&str> = set.split('-').collect();
+    if let ["R", species, id] = parts[..] {
+        if species.len() == 3
+            && species.bytes().all(|x| x.is_ascii_uppercase())
+            && digits_after(id, 0, 1)
+        {
+            return Some(format!("https://reactome.org/content/detail/{}", set));
+        }
+    }
+    // KEGG pathways are an organism code (or `map` and `ko`) followed by five digits
+    let code = set.len().saturating_sub(5);
+    if (2..=4).contains(&code)
+        && set[..code].bytes().all(|x| x.is_ascii_lowercase())
+        && digits_after(set, code, 5)
+    {
+        return Some(format!("https://www.kegg.jp/pathway/{}", set));
+    }
+    None
+}
+
+/// Adds the `link` of `set` to a result if `set_links`, like with `set_defaults(set_links=True)`. Sets without a canonical page get `None`.
+pub fn add_link(dict: &Bound<'_, PyDict>, set: &str, set_links: bool) -> PyResult<()> {
+    if set_links {
+        dict.set_item("link", set_link(set))?;
+    }
+    Ok(())
+}
+
+/// Web link of a result, from its `link`
+pub fn result_link(row: &Bound<'_, PyDict>) -> PyResult<Option<String>> {
+    Ok(row
+        .get_item("link")?
+        .and_then(|x| x.extract::<String>().ok())
+        .filter(|x| is_web_link(x)))
+}
+
+/// Whether `url` is a web link, unlike the `na` or description in the URL column of many GMT files
+pub fn is_web_link(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
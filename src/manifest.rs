@@ -0,0 +1,216 @@
+use crate::{inputs, warnings};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::IntoPyObjectExt;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of `webgestalt_lib` in `Cargo.toml`, which computes the statistics
+const WEBGESTALT_LIB_VERSION: &str = "0.3.1";
+
+/// Version of the schema of results, metadata, and reports, as `MAJOR.MINOR`. See [`schema_version`].
+pub const SCHEMA_VERSION: &str = "1.0";
+
+/// Major version of a schema version, which changes when fields are removed or change type
+pub fn schema_major(version: &str) -> Option<u64> {
+    version.split('.').next()?.parse().ok()
+}
+
+/// Provenance record of a run, written as JSON next to the results when a function gets a `manifest` path,
+/// or returned with the results with `with_metadata=True`
+pub struct Manifest {
+    function: &'static str,
+    started: SystemTime,
+    inputs: Vec<Value>,
+    parameters: Map<String, Value>,
+    sizes: Map<String, Value>,
+}
+
+impl Manifest {
+    pub fn new(function: &'static str) -> Manifest {
+        Manifest {
+            function,
+            started: SystemTime::now(),
+            inputs: Vec::new(),
+            parameters: Map::new(),
+            sizes: Map::new(),
+        }
+    }
+
+    /// Records an input with the SHA-256 of its file. The path and hash are `null` if the input
+    /// was passed as a Python object, and the hash is `null` if the file can not be read.
+    pub fn input(&mut self, role: &str, obj: &Bound<'_, PyAny>) {
+        let path = obj.extract::<String>().ok();
+        let sha256 = path
+            .as_ref()
+            .and_then(|x| inputs::read_file(x).ok())
+            .map(|x| format!("{:x}", Sha256::digest(x)));
+        self.inputs
+            .push(json!({"role": role, "path": path, "sha256": sha256}));
+    }
+
+    pub fn parameter(&mut self, name: &str, value: impl Into<Value>) {
+        self.parameters.insert(name.to_owned(), value.into());
+    }
+
+    /// Records the size of an input after it was read, like the number of sets of a GMT
+    pub fn size(&mut self, name: &str, size: usize) {
+        self.sizes.insert(name.to_owned(), size.into());
+    }
+
+    fn record(&self, py: Python<'_>) -> Value {
+        let finished = SystemTime::now();
+        json!({
+            "function": self.function,
+            "schema_version": SCHEMA_VERSION,
+            "webgestaltpy_version": env!("CARGO_PKG_VERSION"),
+            "webgestalt_lib_version": WEBGESTALT_LIB_VERSION,
+            "python_version": py.version(),
+            "started": timestamp(self.started),
+            "finished": timestamp(finished),
+            "seconds": finished.duration_since(self.started).map_or(0.0, |x| x.as_secs_f64()),
+            "inputs": self.inputs,
+            "sizes": self.sizes,
+            "parameters": self.parameters,
+        })
+    }
+
+    /// Writes the manifest to `path` as JSON
+    pub fn write(&self, py: Python<'_>, path: &str) -> PyResult<()> {
+        inputs::write_file(
+            path,
+            &serde_json::to_string_pretty(&self.record(py)).unwrap(),
+        )
+        .map_err(|e| {
+            PyValueError::new_err(format!("Error when writing manifest at: {}. {}", path, e))
+        })
+    }
+
+    /// Returns `results` as they are, or with `with_metadata` a dictionary with the `results` and the manifest as `metadata`.
+    ///
+    /// Calls `on_complete` with the manifest first, warning instead of raising if it fails, so the results are not lost.
+    pub fn wrap(
+        &self,
+        py: Python<'_>,
+        results: PyObject,
+        with_metadata: bool,
+        on_complete: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let record = self.record(py);
+        if let Some(on_complete) = on_complete {
+            if let Err(e) = on_complete.call1((to_python(py, &record)?,)) {
+                warnings::warn(
+                    py,
+                    &format!("Error in on_complete of {}. {}", self.function, e),
+                )?;
+            }
+        }
+        if !with_metadata {
+            return Ok(results);
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("results", results)?;
+        dict.set_item("metadata", to_python(py, &record)?)?;
+        dict.into_py_any(py)
+    }
+}
+
+/// Checks the `on_complete` argument of a function before the run starts, instead of failing at its end
+pub fn check_on_complete(on_complete: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+    match on_complete {
+        Some(x) if !x.is_callable() => Err(PyValueError::new_err("on_complete must be callable!")),
+        _ => Ok(()),
+    }
+}
+
+/// Get the version of the schema of the results, which is recorded as `schema_version` in the metadata of `with_metadata=True`, in manifests,
+/// in the data of [`enrichment_map_html`](./enrichment_map_html.md), and in the parameters of [`generate_markdown`](./generate_markdown.md),
+/// so parsers of saved results can check which fields to expect.
+///
+/// # Versioning Policy
+///
+/// The version is `"MAJOR.MINOR"`:
+///
+/// - The minor version goes up when fields are added to results, metadata, or reports. Parsers should ignore the fields they do not know.
+/// - The major version goes up when a field is removed or renamed, or when its type or meaning changes.
+///
+/// Within a major version, the fields documented for a function keep their name and type. The order of the fields of a result is not
+/// part of the schema. [`load_results`](./load_results.md) emits a [`WebGestaltWarning`](./WebGestaltWarning.md) for results saved with a newer major version.
+///
+/// # Returns
+///
+/// Returns the schema version as a `String`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt", with_metadata=True)
+/// print(webgestaltpy.schema_version(), res["metadata"]["schema_version"])
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// 1.0 1.0
+/// ```
+#[pyfunction]
+pub fn schema_version() -> &'static str {
+    SCHEMA_VERSION
+}
+
+/// Converts a JSON value to the matching Python object
+pub fn to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(x) => x.into_py_any(py)?,
+        Value::Number(x) => match (x.as_u64(), x.as_i64()) {
+            (Some(x), _) => x.into_py_any(py)?,
+            (None, Some(x)) => x.into_py_any(py)?,
+            _ => x.as_f64().into_py_any(py)?,
+        },
+        Value::String(x) => x.into_py_any(py)?,
+        Value::Array(x) => PyList::new(
+            py,
+            x.iter()
+                .map(|x| to_python(py, x))
+                .collect::<PyResult<Vec<PyObject>>>()?,
+        )?
+        .into_py_any(py)?,
+        Value::Object(x) => {
+            let dict = PyDict::new(py);
+            for (key, value) in x {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_py_any(py)?
+        }
+    })
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, like `2024-01-31T12:00:00Z`
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
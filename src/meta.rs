@@ -0,0 +1,520 @@
+use crate::inputs;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
+use rustc_hash::{FxHashMap, FxHashSet};
+use statrs::distribution::{Beta, ContinuousCDF, Normal};
+use webgestalt_lib::methods::multilist::{stouffer, stouffer_weighted};
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// What to do when one of the inputs of a multi-list analysis can not be read
+pub enum OnError {
+    Raise,
+    /// Leave out the input and record it in the `skipped` field of the results
+    Skip,
+}
+
+impl OnError {
+    pub fn parse(name: &str) -> PyResult<OnError> {
+        match name {
+            "raise" => Ok(OnError::Raise),
+            "skip" => Ok(OnError::Skip),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown on_error value '{}'. Expected 'raise' or 'skip'.",
+                name
+            ))),
+        }
+    }
+
+    /// Raises `error`, or records it in `skipped` when skipping.
+    pub fn handle(
+        &self,
+        py: Python<'_>,
+        input: SkippedInput,
+        error: PyErr,
+        skipped: &mut Vec<(SkippedInput, String)>,
+    ) -> PyResult<()> {
+        match self {
+            OnError::Raise => Err(error),
+            OnError::Skip => {
+                skipped.push((input, error.value(py).to_string()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns `results` as is when raising, or with the skipped inputs when skipping
+    pub fn wrap_results(
+        &self,
+        py: Python<'_>,
+        results: PyObject,
+        skipped: Vec<(SkippedInput, String)>,
+    ) -> PyResult<PyObject> {
+        match self {
+            OnError::Raise => Ok(results),
+            OnError::Skip => {
+                let dict = PyDict::new(py);
+                dict.set_item("results", results)?;
+                let skipped: Vec<Bound<'_, PyDict>> = skipped
+                    .into_iter()
+                    .map(|(input, error)| {
+                        let row = PyDict::new(py);
+                        row.set_item("index", input.index)?;
+                        row.set_item("path", input.path)?;
+                        if let Some(name) = input.name {
+                            row.set_item("name", name)?;
+                        }
+                        row.set_item("error", error)?;
+                        Ok(row)
+                    })
+                    .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()?;
+                dict.set_item("skipped", skipped)?;
+                dict.into_py_any(py)
+            }
+        }
+    }
+}
+
+/// Position of an input in its list, with its path if it was passed as one and its name if the lists are named
+pub struct SkippedInput {
+    pub index: usize,
+    pub path: Option<String>,
+    pub name: Option<String>,
+}
+
+/// The inputs of a multi-list analysis, with the name of each list if they are named
+pub struct Jobs<'a> {
+    pub inputs: Vec<Bound<'a, PyAny>>,
+    pub names: Option<Vec<String>>,
+}
+
+impl<'a> Jobs<'a> {
+    /// Gets the inputs from a list, named by `names` if provided, or from a dictionary mapping each name to its input
+    pub fn new(obj: &Bound<'a, PyAny>, names: Option<Vec<String>>) -> PyResult<Jobs<'a>> {
+        let (inputs, names) = match obj.downcast::<PyDict>() {
+            Ok(dict) => {
+                if names.is_some() {
+                    return Err(PyValueError::new_err(
+                        "names can not be used when the lists are passed as a dictionary!",
+                    ));
+                }
+                let names = dict
+                    .keys()
+                    .iter()
+                    .map(|x| inputs::analyte_to_string(&x))
+                    .collect::<PyResult<Vec<String>>>()?;
+                (dict.values().iter().collect(), Some(names))
+            }
+            Err(_) => (obj.extract::<Vec<Bound<'_, PyAny>>>()?, names),
+        };
+        if let Some(names) = &names {
+            if names.len() != inputs.len() {
+                return Err(PyValueError::new_err(format!(
+                    "Number of names ({0}) and lists ({1}) don't match!",
+                    names.len(),
+                    inputs.len()
+                )));
+            }
+            let mut seen: FxHashSet<&String> = FxHashSet::default();
+            if let Some(name) = names.iter().find(|x| !seen.insert(*x)) {
+                return Err(PyValueError::new_err(format!(
+                    "Name '{}' is used for more than one list!",
+                    name
+                )));
+            }
+        }
+        Ok(Jobs { inputs, names })
+    }
+
+    /// Gets the inputs that go with each list, like their reference lists, from a list in the same order,
+    /// or a dictionary with the same names if the lists are named
+    pub fn paired(&self, obj: &Bound<'a, PyAny>, kind: &str) -> PyResult<Vec<Bound<'a, PyAny>>> {
+        let Ok(dict) = obj.downcast::<PyDict>() else {
+            return obj.extract();
+        };
+        let Some(names) = &self.names else {
+            return Err(PyValueError::new_err(format!(
+                "The {}s can only be a dictionary when the lists are named!",
+                kind
+            )));
+        };
+        let paired: FxHashMap<String, Bound<'_, PyAny>> = dict
+            .iter()
+            .map(|(name, x)| Ok((inputs::analyte_to_string(&name)?, x)))
+            .collect::<PyResult<_>>()?;
+        names
+            .iter()
+            .map(|name| {
+                paired.get(name).cloned().ok_or_else(|| {
+                    PyValueError::new_err(format!("No {} for list '{}'!", kind, name))
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the weight of each list for Stouffer's method, from a list in the same order or a dictionary with the same names
+    pub fn weights(&self, obj: &Bound<'a, PyAny>, method: &MetaMethod) -> PyResult<Vec<f64>> {
+        if let MetaMethod::RankAggregation = method {
+            return Err(PyValueError::new_err(
+                "weights can only be used with the 'stouffer' meta_method!",
+            ));
+        }
+        let weights = self
+            .paired(obj, "weight")?
+            .into_iter()
+            .map(|x| x.extract::<f64>())
+            .collect::<PyResult<Vec<f64>>>()?;
+        if weights.len() != self.inputs.len() {
+            return Err(PyValueError::new_err(format!(
+                "Number of weights ({0}) and lists ({1}) don't match!",
+                weights.len(),
+                self.inputs.len()
+            )));
+        }
+        if let Some(weight) = weights.iter().find(|x| !(x.is_finite() && **x > 0.0)) {
+            return Err(PyValueError::new_err(format!(
+                "Weights must be positive, but got {}!",
+                weight
+            )));
+        }
+        Ok(weights)
+    }
+
+    /// Describes the input at `index` for the `skipped` field of the results
+    pub fn skipped(&self, index: usize) -> SkippedInput {
+        SkippedInput {
+            index,
+            path: self.inputs[index].extract().ok(),
+            name: self.names.as_ref().map(|x| x[index].clone()),
+        }
+    }
+
+    /// Labels the results of the lists at `kept` (the lists that were not skipped), after the results of the meta-analysis.
+    /// Unnamed results stay a list, with the meta-analysis first. Named results are a dictionary with the meta-analysis
+    /// in `meta`, and a dictionary mapping each name to the results of its list in `lists`.
+    pub fn label_results(
+        &self,
+        py: Python<'_>,
+        results: Vec<Vec<Bound<'_, PyDict>>>,
+        kept: &[usize],
+    ) -> PyResult<PyObject> {
+        let Some(names) = &self.names else {
+            return results.into_py_any(py);
+        };
+        let mut results = results.into_iter();
+        let dict = PyDict::new(py);
+        dict.set_item("meta", results.next())?;
+        let lists = PyDict::new(py);
+        for (i, res) in kept.iter().zip(results) {
+            lists.set_item(&names[*i], res)?;
+        }
+        dict.set_item("lists", lists)?;
+        dict.into_py_any(py)
+    }
+}
+
+/// How the results of each set are combined across lists
+pub enum MetaMethod {
+    /// Stouffer's method on the p-values
+    Stouffer,
+    /// Robust rank aggregation of the rank of each set in every list
+    RankAggregation,
+}
+
+impl MetaMethod {
+    pub fn parse(name: &str) -> PyResult<MetaMethod> {
+        match name {
+            "stouffer" => Ok(MetaMethod::Stouffer),
+            "rra" => Ok(MetaMethod::RankAggregation),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown meta-analysis method '{}'. Expected 'stouffer' or 'rra'.",
+                name
+            ))),
+        }
+    }
+}
+
+/// Combines the p-values of each set across lists and adjusts them with BH.
+///
+/// `lists` contains the `(set, p)` pairs of each list, and `weights` the weight of each list for Stouffer's method.
+/// Returns the sets in order of first appearance, with their meta p-values and FDRs.
+pub fn meta_analysis(
+    lists: &[Vec<(String, f64)>],
+    method: &MetaMethod,
+    weights: Option<&[f64]>,
+) -> (Vec<String>, Vec<f64>, Vec<f64>) {
+    let mut sets: Vec<String> = Vec::new();
+    let mut values: FxHashMap<String, Vec<f64>> = FxHashMap::default();
+    // lists with a value for each set, to match the values to their weights
+    let mut list_indices: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+    for (i, list) in lists.iter().enumerate() {
+        let list_values: Vec<f64> = match method {
+            // p-values of 0 or 1, like a GSEA set with no permutation on its side or an untested set, would be an infinite z-score
+            MetaMethod::Stouffer => list
+                .iter()
+                .map(|(_, p)| p.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON))
+                .collect(),
+            MetaMethod::RankAggregation => normalized_ranks(list),
+        };
+        for ((set, _), value) in list.iter().zip(list_values) {
+            if !values.contains_key(set) {
+                sets.push(set.clone());
+            }
+            values.entry(set.clone()).or_default().push(value);
+            list_indices.entry(set.clone()).or_default().push(i);
+        }
+    }
+    let meta_p: Vec<f64> = match (method, weights) {
+        (MetaMethod::Stouffer, None) => sets.iter().map(|x| stouffer(&values[x])).collect(),
+        (MetaMethod::Stouffer, Some(weights)) => sets
+            .iter()
+            .map(|x| {
+                stouffer_weighted(
+                    values[x].clone(),
+                    list_indices[x].iter().map(|i| weights[*i]).collect(),
+                )
+            })
+            .collect(),
+        (MetaMethod::RankAggregation, _) => sets
+            .iter()
+            .map(|x| {
+                // sets missing from a list are ranked last
+                let mut ranks = values[x].clone();
+                ranks.resize(lists.len(), 1.0);
+                rra(&ranks)
+            })
+            .collect(),
+    };
+    let meta_fdr = adjust(&meta_p, AdjustmentMethod::BH);
+    (sets, meta_p, meta_fdr)
+}
+
+/// Number of lists where each of `sets` has an FDR below `threshold`.
+///
+/// `lists` contains the `(set, fdr)` pairs of each list.
+pub fn vote_counts(lists: &[Vec<(String, f64)>], sets: &[String], threshold: f64) -> Vec<usize> {
+    let mut votes: FxHashMap<&String, usize> = FxHashMap::default();
+    for list in lists {
+        for (set, fdr) in list {
+            if *fdr < threshold {
+                *votes.entry(set).or_default() += 1;
+            }
+        }
+    }
+    sets.iter()
+        .map(|x| votes.get(x).cloned().unwrap_or(0))
+        .collect()
+}
+
+/// Rank of each set by p-value divided by the number of sets. Tied sets share their average rank.
+fn normalized_ranks(list: &[(String, f64)]) -> Vec<f64> {
+    let values: Vec<f64> = list.iter().map(|(_, p)| *p).collect();
+    average_ranks(&values)
+        .into_iter()
+        .map(|x| x / list.len() as f64)
+        .collect()
+}
+
+/// One-based rank of each value in ascending order. Tied values share their average rank.
+pub fn average_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && values[order[end + 1]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end) as f64 / 2.0 + 1.0;
+        for i in order[start..=end].iter() {
+            ranks[*i] = rank;
+        }
+        start = end + 1;
+    }
+    ranks
+}
+
+/// Robust rank aggregation ([Kolde et al. 2012](https://doi.org/10.1093/bioinformatics/btr709)) of
+/// the normalized ranks of a set in each list, with the Bonferroni-corrected rho score as the p-value.
+fn rra(ranks: &[f64]) -> f64 {
+    let mut ranks = ranks.to_vec();
+    ranks.sort_by(|a, b| a.total_cmp(b));
+    let n = ranks.len();
+    let rho = ranks
+        .iter()
+        .enumerate()
+        .map(|(k, r)| Beta::new((k + 1) as f64, (n - k) as f64).unwrap().cdf(*r))
+        .fold(1.0, f64::min);
+    (rho * n as f64).min(1.0)
+}
+
+/// How the NES of a set is pooled across lists
+pub enum NESCombination {
+    /// Inverse-variance weighted mean
+    Fixed,
+    /// DerSimonian-Laird random effects
+    Random,
+}
+
+impl NESCombination {
+    pub fn parse(name: &str) -> PyResult<NESCombination> {
+        match name {
+            "fixed" => Ok(NESCombination::Fixed),
+            "random" => Ok(NESCombination::Random),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown NES combination '{}'. Expected 'fixed' or 'random'.",
+                name
+            ))),
+        }
+    }
+}
+
+/// Pooled NES of a set with its 95% confidence interval
+pub struct PooledNES {
+    pub nes: f64,
+    pub se: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+    /// Between-list variance. Always `0.0` for fixed effects.
+    pub tau2: f64,
+    /// Share of the variation across lists due to heterogeneity
+    pub i2: f64,
+}
+
+impl PooledNES {
+    /// Pools the `(nes, se)` pairs of each list. Returns `None` if there are no lists to pool.
+    pub fn new(studies: &[(f64, f64)], method: &NESCombination) -> Option<PooledNES> {
+        if studies.is_empty() {
+            return None;
+        }
+        let weights: Vec<f64> = studies.iter().map(|(_, se)| 1.0 / (se * se)).collect();
+        let sum_w: f64 = weights.iter().sum();
+        let fixed: f64 = studies
+            .iter()
+            .zip(weights.iter())
+            .map(|((nes, _), w)| nes * w)
+            .sum::<f64>()
+            / sum_w;
+        let q: f64 = studies
+            .iter()
+            .zip(weights.iter())
+            .map(|((nes, _), w)| w * (nes - fixed).powi(2))
+            .sum();
+        let df = (studies.len() - 1) as f64;
+        let i2 = if q > 0.0 {
+            ((q - df) / q).max(0.0)
+        } else {
+            0.0
+        };
+        let tau2 = match method {
+            NESCombination::Fixed => 0.0,
+            NESCombination::Random => {
+                let c = sum_w - weights.iter().map(|w| w * w).sum::<f64>() / sum_w;
+                if c > 0.0 {
+                    ((q - df) / c).max(0.0)
+                } else {
+                    0.0
+                }
+            }
+        };
+        let weights: Vec<f64> = studies
+            .iter()
+            .map(|(_, se)| 1.0 / (se * se + tau2))
+            .collect();
+        let sum_w: f64 = weights.iter().sum();
+        let nes = studies
+            .iter()
+            .zip(weights.iter())
+            .map(|((nes, _), w)| nes * w)
+            .sum::<f64>()
+            / sum_w;
+        let se = (1.0 / sum_w).sqrt();
+        let z = Normal::new(0.0, 1.0).unwrap().inverse_cdf(0.975);
+        Some(PooledNES {
+            nes,
+            se,
+            ci_low: nes - z * se,
+            ci_high: nes + z * se,
+            tau2,
+            i2,
+        })
+    }
+
+    /// Adds the pooled values to a meta-analysis result. Values are `None` if nothing was pooled.
+    pub fn add_to_dict(pooled: &Option<PooledNES>, dict: &Bound<'_, PyDict>) -> PyResult<()> {
+        let get = |f: fn(&PooledNES) -> f64| pooled.as_ref().map(f);
+        dict.set_item("nes", get(|x| x.nes).unwrap_or(0.0))?;
+        dict.set_item("nes_se", get(|x| x.se))?;
+        dict.set_item("nes_ci_low", get(|x| x.ci_low))?;
+        dict.set_item("nes_ci_high", get(|x| x.ci_high))?;
+        dict.set_item("tau2", get(|x| x.tau2))?;
+        dict.set_item("i2", get(|x| x.i2))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lists(p: &[&[f64]]) -> Vec<Vec<(String, f64)>> {
+        p.iter()
+            .map(|list| {
+                list.iter()
+                    .enumerate()
+                    .map(|(i, p)| (format!("set_{}", i), *p))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stouffer_handles_p_values_of_zero_and_one() {
+        let (sets, meta_p, meta_fdr) = meta_analysis(
+            &lists(&[&[0.0, 1.0, 0.5], &[1.0, 1.0, 0.0]]),
+            &MetaMethod::Stouffer,
+            None,
+        );
+        assert_eq!(sets.len(), 3);
+        assert!(meta_p.iter().chain(meta_fdr.iter()).all(|x| x.is_finite()));
+        assert!(meta_p[1] > 0.99);
+        assert!(meta_p[2] < 0.05);
+        let (_, weighted, _) = meta_analysis(
+            &lists(&[&[0.0], &[1.0]]),
+            &MetaMethod::Stouffer,
+            Some(&[1.0, 2.0]),
+        );
+        assert!(weighted[0].is_finite());
+    }
+
+    #[test]
+    fn rra_is_the_corrected_minimum_of_the_order_statistics() {
+        assert!((rra(&[0.3]) - 0.3).abs() < 1e-12);
+        // Beta(2, 1) at 0.1 is 0.01, below the 0.19 of Beta(1, 2), times 2 lists
+        assert!((rra(&[0.1, 0.1]) - 0.02).abs() < 1e-12);
+        assert_eq!(rra(&[0.9, 0.2, 0.5]), rra(&[0.2, 0.5, 0.9]));
+        assert_eq!(rra(&[1.0, 1.0, 1.0]), 1.0);
+    }
+
+    #[test]
+    fn pooled_nes() {
+        assert!(PooledNES::new(&[], &NESCombination::Fixed).is_none());
+        let single = PooledNES::new(&[(1.5, 0.5)], &NESCombination::Random).unwrap();
+        assert!((single.nes - 1.5).abs() < 1e-12 && (single.se - 0.5).abs() < 1e-12);
+        assert!((single.ci_high - single.nes - 1.959964 * 0.5).abs() < 1e-6);
+        assert_eq!((single.tau2, single.i2), (0.0, 0.0));
+        // the fixed-effects mean is weighted by the inverse variances
+        let fixed = PooledNES::new(&[(1.0, 0.5), (2.0, 1.0)], &NESCombination::Fixed).unwrap();
+        assert!((fixed.nes - 1.2).abs() < 1e-12);
+        assert!((fixed.se - 0.2_f64.sqrt()).abs() < 1e-12);
+        assert_eq!(fixed.tau2, 0.0);
+        // lists that disagree more than their standard errors allow widen the random-effects interval
+        let studies = [(-2.0, 0.2), (2.0, 0.2), (0.5, 0.3)];
+        let fixed = PooledNES::new(&studies, &NESCombination::Fixed).unwrap();
+        let random = PooledNES::new(&studies, &NESCombination::Random).unwrap();
+        assert!(random.tau2 > 0.0 && random.i2 > 0.9);
+        assert!(random.se > fixed.se);
+    }
+}
@@ -0,0 +1,1656 @@
+use crate::config;
+use crate::inputs;
+use crate::NTAMethod;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Undirected, weighted network stored as an adjacency list.
+///
+/// Nodes are indexed in the order they are first encountered.
+#[derive(Clone, Default)]
+pub struct Network {
+    pub nodes: Vec<String>,
+    pub node_map: FxHashMap<String, usize>,
+    /// For every node, the neighboring node indices and the weight of the connecting edge
+    pub adjacency: Vec<Vec<(usize, f64)>>,
+}
+
+impl Network {
+    /// Creates a network with the provided nodes and no edges
+    pub fn with_nodes(nodes: &[String]) -> Network {
+        let mut network = Network::default();
+        for node in nodes {
+            network.node_index(node);
+        }
+        network
+    }
+
+    pub fn from_edge_list(edge_list: &[Vec<String>]) -> Network {
+        let mut network = Network::default();
+        network.add_edges(edge_list);
+        network
+    }
+
+    /// Builds the network of a random walk. If `weighted`, the third column of the edge list is the weight of each edge,
+    /// which must be positive and finite. Otherwise, every edge has a weight of `1.0`.
+    pub fn for_walk(edge_list: &[Vec<String>], weighted: bool) -> PyResult<Network> {
+        let mut network = Network::default();
+        network.add_walk_edges(edge_list, weighted)?;
+        Ok(network)
+    }
+
+    /// Adds the edges of an edge list to the network of a random walk, with their weights like [`Network::for_walk`]
+    pub fn add_walk_edges(&mut self, edge_list: &[Vec<String>], weighted: bool) -> PyResult<()> {
+        if !weighted {
+            let edges: Vec<Vec<String>> = edge_list
+                .iter()
+                .map(|x| x.iter().take(2).cloned().collect())
+                .collect();
+            self.add_edges(&edges);
+            return Ok(());
+        }
+        for (i, edge) in edge_list.iter().enumerate().filter(|(_, x)| x.len() >= 2) {
+            if let Some(weight) = edge.get(2).and_then(|x| x.parse::<f64>().ok()) {
+                if !weight.is_finite() || weight <= 0.0 {
+                    return Err(PyValueError::new_err(format!(
+                        "Weight of line {} of the edge list ('{}', '{}') must be positive and finite, but is {}",
+                        i + 1,
+                        edge[0],
+                        edge[1],
+                        weight
+                    )));
+                }
+            }
+        }
+        self.add_edges(edge_list);
+        Ok(())
+    }
+
+    /// Returns the index of `node`, adding it to the network if it does not exist
+    pub fn node_index(&mut self, node: &str) -> usize {
+        if let Some(i) = self.node_map.get(node) {
+            return *i;
+        }
+        let i = self.nodes.len();
+        self.nodes.push(node.to_owned());
+        self.node_map.insert(node.to_owned(), i);
+        self.adjacency.push(Vec::new());
+        i
+    }
+
+    /// Adds the edges of an edge list, where each edge is `[source, target]` with an optional
+    /// weight as the third column. Edges without a valid weight are given a weight of `1.0`.
+    pub fn add_edges(&mut self, edge_list: &[Vec<String>]) {
+        for edge in edge_list.iter().filter(|x| x.len() >= 2) {
+            let weight = edge
+                .get(2)
+                .and_then(|x| x.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            let a = self.node_index(&edge[0]);
+            let b = self.node_index(&edge[1]);
+            self.add_edge(a, b, weight);
+        }
+    }
+
+    /// Adds an undirected edge. Repeated edges keep the first weight seen.
+    pub fn add_edge(&mut self, a: usize, b: usize, weight: f64) {
+        if self.adjacency[a].iter().any(|(x, _)| *x == b) {
+            return;
+        }
+        self.adjacency[a].push((b, weight));
+        if a != b {
+            self.adjacency[b].push((a, weight));
+        }
+    }
+
+    /// Sum of the weights of the edges connected to each node
+    pub fn strengths(&self) -> Vec<f64> {
+        self.adjacency
+            .iter()
+            .map(|x| x.iter().map(|(_, w)| w).sum())
+            .collect()
+    }
+
+    /// Restart vector with the probability spread evenly over the seeds found in the network
+    pub fn seed_vector(&self, seeds: &[String]) -> Vec<f64> {
+        let indices: Vec<usize> = seeds
+            .iter()
+            .filter_map(|x| self.node_map.get(x).cloned())
+            .collect();
+        let mut p0 = vec![0.0; self.nodes.len()];
+        for i in indices.iter() {
+            p0[*i] = 1.0 / indices.len() as f64;
+        }
+        p0
+    }
+
+    /// Multiplies `p` by the column-normalized transition matrix of the network
+    pub fn transition(&self, p: &[f64], strengths: &[f64]) -> Vec<f64> {
+        let mut next = vec![0.0; self.nodes.len()];
+        for (j, neighbors) in self.adjacency.iter().enumerate() {
+            if p[j] == 0.0 || strengths[j] == 0.0 {
+                continue;
+            }
+            let mass = p[j] / strengths[j];
+            for (i, w) in neighbors {
+                next[*i] += mass * w;
+            }
+        }
+        next
+    }
+
+    /// Multiplies `p` by the transition matrix of the network with the chosen normalization
+    pub fn normalized_transition(
+        &self,
+        p: &[f64],
+        strengths: &[f64],
+        normalization: Normalization,
+    ) -> Vec<f64> {
+        if normalization == Normalization::Column {
+            return self.transition(p, strengths);
+        }
+        let mut next = vec![0.0; self.nodes.len()];
+        for (j, neighbors) in self.adjacency.iter().enumerate() {
+            if p[j] == 0.0 || strengths[j] == 0.0 {
+                continue;
+            }
+            for (i, w) in neighbors {
+                next[*i] += p[j] * w * normalization.factor(strengths[j], strengths[*i]);
+            }
+        }
+        next
+    }
+
+    /// Transition matrix of the network in compressed sparse row format, as the values, the column indices, and the
+    /// start of each row. The value at row `i` and column `j` is the probability of moving from node `j` to node `i`.
+    pub fn transition_matrix(
+        &self,
+        normalization: Normalization,
+    ) -> (Vec<f64>, Vec<usize>, Vec<usize>) {
+        let strengths = self.strengths();
+        let mut data: Vec<f64> = Vec::new();
+        let mut indices: Vec<usize> = Vec::new();
+        let mut indptr: Vec<usize> = vec![0];
+        for (i, neighbors) in self.adjacency.iter().enumerate() {
+            let mut row: Vec<(usize, f64)> = neighbors
+                .iter()
+                .filter(|(j, _)| strengths[*j] != 0.0)
+                .map(|(j, w)| (*j, w * normalization.factor(strengths[*j], strengths[i])))
+                .collect();
+            row.sort_by_key(|x| x.0);
+            indices.extend(row.iter().map(|x| x.0));
+            data.extend(row.iter().map(|x| x.1));
+            indptr.push(indices.len());
+        }
+        (data, indices, indptr)
+    }
+
+    /// Random walk with restart from the restart vector `p0`, using the same update as WebGestaltR with the chosen
+    /// normalization of the transition matrix.
+    ///
+    /// Iterates until the L1 change between steps is at most `tolerance`.
+    pub fn normalized_walk(
+        &self,
+        p0: &[f64],
+        r: f64,
+        tolerance: f64,
+        normalization: Normalization,
+    ) -> Vec<f64> {
+        let strengths = self.strengths();
+        iterate_walk(p0, r, tolerance, |p| {
+            self.normalized_transition(p, &strengths, normalization)
+        })
+    }
+
+    /// Connected components of the subgraph induced by `nodes`, each in the order of `nodes`
+    pub fn components(&self, nodes: &[usize]) -> Vec<Vec<usize>> {
+        let position: FxHashMap<usize, usize> =
+            nodes.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+        let mut sets = UnionFind::new(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            for (u, _) in self.adjacency[*node].iter() {
+                if let Some(j) = position.get(u) {
+                    sets.union(i, *j);
+                }
+            }
+        }
+        let mut components: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+        let mut roots: Vec<usize> = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let root = sets.find(i);
+            if !components.contains_key(&root) {
+                roots.push(root);
+            }
+            components.entry(root).or_default().push(*node);
+        }
+        roots
+            .into_iter()
+            .map(|x| components.remove(&x).unwrap())
+            .collect()
+    }
+
+    /// Breadth-first search from `source`.
+    ///
+    /// Returns the number of hops to every node (`None` if unreachable) and the parent of each
+    /// node on a shortest path back to `source`.
+    pub fn bfs(&self, source: usize) -> (Vec<Option<usize>>, Vec<usize>) {
+        let mut dist: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut parent: Vec<usize> = (0..self.nodes.len()).collect();
+        let mut queue = std::collections::VecDeque::from([source]);
+        dist[source] = Some(0);
+        while let Some(v) = queue.pop_front() {
+            for (u, _) in self.adjacency[v].iter() {
+                if dist[*u].is_none() {
+                    dist[*u] = Some(dist[v].unwrap() + 1);
+                    parent[*u] = v;
+                    queue.push_back(*u);
+                }
+            }
+        }
+        (dist, parent)
+    }
+
+    /// Dijkstra shortest paths from `source`, using the edge weights as lengths.
+    ///
+    /// Unreachable nodes have a distance of infinity.
+    pub fn dijkstra(&self, source: usize) -> Vec<f64> {
+        let mut dist: Vec<f64> = vec![f64::INFINITY; self.nodes.len()];
+        let mut heap = BinaryHeap::from([HeapEntry {
+            cost: 0.0,
+            node: source,
+        }]);
+        dist[source] = 0.0;
+        while let Some(HeapEntry { cost, node }) = heap.pop() {
+            if cost > dist[node] {
+                continue;
+            }
+            for (u, w) in self.adjacency[node].iter() {
+                let next = cost + w;
+                if next < dist[*u] {
+                    dist[*u] = next;
+                    heap.push(HeapEntry {
+                        cost: next,
+                        node: *u,
+                    });
+                }
+            }
+        }
+        dist
+    }
+
+    /// Approximate minimal subnetwork connecting the `terminals`, using the
+    /// Kou-Markowsky-Berman 2-approximation of the Steiner tree with hop distances.
+    ///
+    /// Terminals in different connected components are connected within their own component, so
+    /// the result is a forest. Returns the edges of the forest.
+    pub fn steiner_tree(&self, terminals: &[usize]) -> Vec<(usize, usize)> {
+        // metric closure between terminals
+        let searches: Vec<(Vec<Option<usize>>, Vec<usize>)> =
+            terminals.iter().map(|x| self.bfs(*x)).collect();
+        let mut closure: Vec<(usize, usize, usize)> = Vec::new();
+        for (i, (dist, _)) in searches.iter().enumerate() {
+            for (j, terminal) in terminals.iter().enumerate().skip(i + 1) {
+                if let Some(d) = dist[*terminal] {
+                    closure.push((d, i, j));
+                }
+            }
+        }
+        // minimum spanning forest of the closure, expanded into the shortest paths
+        closure.sort();
+        let mut components = UnionFind::new(terminals.len());
+        let mut sub_edges: FxHashSet<(usize, usize)> = FxHashSet::default();
+        for (_, i, j) in closure {
+            if components.union(i, j) {
+                let parent = &searches[i].1;
+                let mut v = terminals[j];
+                while v != terminals[i] {
+                    sub_edges.insert((v.min(parent[v]), v.max(parent[v])));
+                    v = parent[v];
+                }
+            }
+        }
+        // spanning forest of the expanded subgraph
+        let mut sub_edges: Vec<(usize, usize)> = sub_edges.into_iter().collect();
+        sub_edges.sort();
+        let mut components = UnionFind::new(self.nodes.len());
+        let mut tree: Vec<(usize, usize)> = sub_edges
+            .into_iter()
+            .filter(|(a, b)| components.union(*a, *b))
+            .collect();
+        // remove leaves that are not terminals
+        let is_terminal: FxHashSet<usize> = terminals.iter().cloned().collect();
+        loop {
+            let mut degree: FxHashMap<usize, usize> = FxHashMap::default();
+            for (a, b) in tree.iter() {
+                *degree.entry(*a).or_default() += 1;
+                *degree.entry(*b).or_default() += 1;
+            }
+            let before = tree.len();
+            let is_kept = |x: &usize| degree[x] > 1 || is_terminal.contains(x);
+            tree.retain(|(a, b)| is_kept(a) && is_kept(b));
+            if tree.len() == before {
+                return tree;
+            }
+        }
+    }
+}
+
+/// Entry of the Dijkstra priority queue, ordered so the lowest cost is popped first
+struct HeapEntry {
+    cost: f64,
+    node: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Disjoint sets used to build spanning forests
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> UnionFind {
+        UnionFind {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    /// Joins the sets of `a` and `b`. Returns `false` if they were already joined.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        self.parent[a] = b;
+        true
+    }
+}
+
+/// Options to clean up an edge list before running a random walk
+#[derive(Default)]
+pub struct EdgeFilter {
+    /// Drop edges with a weight below this value. Edges without a weight have a weight of `1.0`.
+    pub min_weight: Option<f64>,
+    pub remove_self_loops: bool,
+    /// Drop repeated edges between the same two nodes, in either direction
+    pub remove_duplicates: bool,
+    /// Only keep the edges of the largest connected component
+    pub largest_component: bool,
+}
+
+/// Number of edges removed by each step of an [`EdgeFilter`]
+#[derive(Default)]
+pub struct FilterReport {
+    pub below_min_weight: usize,
+    pub self_loops: usize,
+    pub duplicates: usize,
+    pub outside_largest_component: usize,
+    /// Nodes that no longer have any edges
+    pub removed_nodes: Vec<String>,
+}
+
+impl FilterReport {
+    pub fn to_dict<'a>(&self, py: Python<'a>) -> PyResult<Bound<'a, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("below_min_weight", self.below_min_weight)?;
+        dict.set_item("self_loops", self.self_loops)?;
+        dict.set_item("duplicates", self.duplicates)?;
+        dict.set_item("outside_largest_component", self.outside_largest_component)?;
+        dict.set_item("removed_nodes", &self.removed_nodes)?;
+        Ok(dict)
+    }
+}
+
+impl EdgeFilter {
+    pub fn is_active(&self) -> bool {
+        self.min_weight.is_some()
+            || self.remove_self_loops
+            || self.remove_duplicates
+            || self.largest_component
+    }
+
+    /// Applies the filter to an edge list, returning the remaining edges and what was removed
+    pub fn apply(&self, edge_list: Vec<Vec<String>>) -> (Vec<Vec<String>>, FilterReport) {
+        let mut report = FilterReport::default();
+        let mut seen: FxHashSet<(String, String)> = FxHashSet::default();
+        let original_nodes: Vec<String> = Network::from_edge_list(&edge_list).nodes;
+        let mut edges: Vec<Vec<String>> = Vec::new();
+        for edge in edge_list.into_iter().filter(|x| x.len() >= 2) {
+            if let Some(min_weight) = self.min_weight {
+                let weight = edge
+                    .get(2)
+                    .and_then(|x| x.parse::<f64>().ok())
+                    .unwrap_or(1.0);
+                if weight < min_weight {
+                    report.below_min_weight += 1;
+                    continue;
+                }
+            }
+            if self.remove_self_loops && edge[0] == edge[1] {
+                report.self_loops += 1;
+                continue;
+            }
+            if self.remove_duplicates {
+                let key = if edge[0] <= edge[1] {
+                    (edge[0].clone(), edge[1].clone())
+                } else {
+                    (edge[1].clone(), edge[0].clone())
+                };
+                if !seen.insert(key) {
+                    report.duplicates += 1;
+                    continue;
+                }
+            }
+            edges.push(edge);
+        }
+        if self.largest_component {
+            let network = Network::from_edge_list(&edges);
+            let all: Vec<usize> = (0..network.nodes.len()).collect();
+            let largest: FxHashSet<&String> = network
+                .components(&all)
+                .into_iter()
+                .max_by_key(|x| x.len())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|x| &network.nodes[x])
+                .collect();
+            let before = edges.len();
+            let kept: Vec<Vec<String>> = edges
+                .iter()
+                .filter(|x| largest.contains(&x[0]))
+                .cloned()
+                .collect();
+            report.outside_largest_component = before - kept.len();
+            edges = kept;
+        }
+        let remaining = Network::from_edge_list(&edges);
+        report.removed_nodes = original_nodes
+            .into_iter()
+            .filter(|x| !remaining.node_map.contains_key(x))
+            .collect();
+        (edges, report)
+    }
+}
+
+/// Options for how the neighborhood of an NTA is built from the nodes selected by the method
+#[derive(Default)]
+pub struct NeighborhoodOptions {
+    /// Also include the nodes within this many edges of the selected nodes
+    pub depth: usize,
+    /// Also include every seed in the network
+    pub include_seeds: bool,
+    /// Keep at most this many nodes, with the highest scores
+    pub max_size: Option<usize>,
+}
+
+impl NeighborhoodOptions {
+    pub fn is_active(&self) -> bool {
+        self.depth > 0 || self.include_seeds || self.max_size.is_some()
+    }
+
+    /// Builds the neighborhood from the `selected` nodes, ordered by decreasing score
+    pub fn apply(
+        &self,
+        network: &Network,
+        scores: &[f64],
+        seeds: &[String],
+        selected: &[usize],
+    ) -> Vec<usize> {
+        let mut included: FxHashSet<usize> = selected.iter().copied().collect();
+        let mut neighborhood: Vec<usize> = selected.to_vec();
+        let mut frontier: Vec<usize> = selected.to_vec();
+        for _ in 0..self.depth {
+            let mut next: Vec<usize> = Vec::new();
+            for node in frontier {
+                for (u, _) in network.adjacency[node].iter() {
+                    if included.insert(*u) {
+                        next.push(*u);
+                    }
+                }
+            }
+            neighborhood.extend(next.iter());
+            frontier = next;
+        }
+        if self.include_seeds {
+            for seed in seeds.iter().filter_map(|x| network.node_map.get(x)) {
+                if included.insert(*seed) {
+                    neighborhood.push(*seed);
+                }
+            }
+        }
+        // stable, so ties keep the order of the selected nodes
+        neighborhood.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+        if let Some(max_size) = self.max_size {
+            neighborhood.truncate(max_size);
+        }
+        neighborhood
+    }
+}
+
+/// How the weights of the network are normalized into the transition matrix of the random walk,
+/// where `W` is the weighted adjacency matrix and `D` is the diagonal matrix of the node strengths
+#[derive(Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// `W D^-1`, where each node spreads its score over its edges, like WebGestaltR. Favors hubs.
+    Column,
+    /// `D^-1 W`, where each node takes the average score of its neighbors. Favors nodes with few edges.
+    Row,
+    /// `D^-1/2 W D^-1/2`, which is between the two
+    Symmetric,
+}
+
+impl Normalization {
+    pub fn parse(name: &str) -> PyResult<Normalization> {
+        match name {
+            "column" => Ok(Normalization::Column),
+            "row" => Ok(Normalization::Row),
+            "symmetric" => Ok(Normalization::Symmetric),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown normalization '{}'. Expected 'column', 'row', or 'symmetric'.",
+                name
+            ))),
+        }
+    }
+
+    /// Factor of the weight of the edge from node `j` to node `i` in the transition matrix, given their strengths.
+    /// The factor is `0` if a strength it divides by is `0`, so a node without edges never gets an infinite or NaN score.
+    pub fn factor(&self, s_j: f64, s_i: f64) -> f64 {
+        let strength = match self {
+            Normalization::Column => s_j,
+            Normalization::Row => s_i,
+            Normalization::Symmetric => (s_i * s_j).sqrt(),
+        };
+        if strength > 0.0 {
+            1.0 / strength
+        } else {
+            0.0
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalization::Column => "column",
+            Normalization::Row => "row",
+            Normalization::Symmetric => "symmetric",
+        }
+    }
+}
+
+/// How the scores of the random walk from the seeds are contrasted with the scores from background seeds
+pub enum Contrast {
+    /// Seed scores minus background scores
+    Difference,
+    /// Log2 ratio of the seed scores to the background scores
+    LogRatio,
+}
+
+impl Contrast {
+    pub fn parse(name: &str) -> PyResult<Contrast> {
+        match name {
+            "difference" => Ok(Contrast::Difference),
+            "log_ratio" => Ok(Contrast::LogRatio),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown contrast '{}'. Expected 'difference' or 'log_ratio'.",
+                name
+            ))),
+        }
+    }
+
+    /// Contrasts the scores of each node. The log ratio adds the score of a uniform walk (one over the number of nodes)
+    /// to both scores, so nodes that neither walk reaches have a ratio of `0`.
+    pub fn apply(&self, scores: &[f64], background: &[f64]) -> Vec<f64> {
+        let pseudocount = 1.0 / scores.len().max(1) as f64;
+        scores
+            .iter()
+            .zip(background.iter())
+            .map(|(x, y)| match self {
+                Contrast::Difference => x - y,
+                Contrast::LogRatio => ((x + pseudocount) / (y + pseudocount)).log2(),
+            })
+            .collect()
+    }
+}
+
+/// Parameters of the random walk of NTA, which can be passed to [`nta`](./nta.md) as `config` instead of `nta_method` and `n`.
+///
+/// # Parameters
+/// - `method` - a `NTAMethod` object specifying the NTA method for the analysis
+/// - `n` - the number of seeds or nodes to identify according to `method`
+/// - `reset_probability` - Probability of the random walk restarting at the seeds at each step, greater than `0` and at most `1` (default: `0.5`)
+/// - `tolerance` - The random walk stops when the total change of the probabilities between steps is at most this value (default: `0.000001`)
+/// - `weighted` - If `True`, the random walk follows the edges in proportion to their weight (third column of the edge list), which must be
+///   positive and finite. Otherwise, every edge has the same weight, like WebGestaltR (default: `False`)
+/// - `normalization` - How the network is normalized into the transition matrix of the walk, with `W` the adjacency matrix and `D` the diagonal
+///   matrix of the degrees (or the sums of the weights). Either `"column"` (`W D^-1`, like WebGestaltR), where a node spreads its score over its edges,
+///   which favors hubs, `"row"` (`D^-1 W`), where a node takes the average score of its neighbors, which favors nodes with few edges,
+///   or `"symmetric"` (`D^-1/2 W D^-1/2`), which is between the two. The scores only sum to `1` with `"column"` (default: `"column"`)
+///
+/// Every parameter is also an attribute that can be changed after the object is created.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `reset_probability` or `tolerance` is out of range, or if `normalization` is unknown.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// config = webgestaltpy.NTAConfig(webgestaltpy.NTAMethod.Expansion, 10, reset_probability=0.3, weighted=True)
+/// y = webgestaltpy.nta("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", "data/net_genes.txt", config=config)
+/// ```
+#[pyclass]
+#[derive(Clone)]
+pub struct NTAConfig {
+    #[pyo3(get, set)]
+    pub method: NTAMethod,
+    #[pyo3(get, set)]
+    pub n: usize,
+    #[pyo3(get, set)]
+    pub reset_probability: f64,
+    #[pyo3(get, set)]
+    pub tolerance: f64,
+    #[pyo3(get, set)]
+    pub weighted: bool,
+    pub normalization: Normalization,
+}
+
+impl NTAConfig {
+    /// Checks the parameters, which can be changed from Python after the object is created
+    pub fn validate(&self) -> PyResult<()> {
+        if !(self.reset_probability > 0.0 && self.reset_probability <= 1.0) {
+            return Err(PyValueError::new_err(format!(
+                "reset_probability must be greater than 0 and at most 1, but is {}",
+                self.reset_probability
+            )));
+        }
+        if self.tolerance.is_nan() || self.tolerance <= 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "tolerance must be positive, but is {}",
+                self.tolerance
+            )));
+        }
+        Ok(())
+    }
+
+    /// Gets the configuration of an NTA from either `config`, or `nta_method` and `n` with the default parameters
+    pub fn resolve(
+        config: Option<&NTAConfig>,
+        nta_method: Option<NTAMethod>,
+        n: Option<usize>,
+    ) -> PyResult<NTAConfig> {
+        let config = match (config, nta_method, n) {
+            (Some(config), None, None) => config.clone(),
+            (None, Some(method), Some(n)) => {
+                let defaults = webgestalt_lib::methods::nta::NTAConfig::default();
+                NTAConfig {
+                    method,
+                    n,
+                    reset_probability: defaults.reset_probability,
+                    tolerance: defaults.tolerance,
+                    weighted: false,
+                    normalization: Normalization::Column,
+                }
+            }
+            (Some(_), _, _) => {
+                return Err(PyValueError::new_err(
+                    "config can not be used with nta_method or n!",
+                ))
+            }
+            (None, _, _) => {
+                return Err(PyValueError::new_err(
+                    "Either nta_method and n, or config, must be provided!",
+                ))
+            }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Gets the parameters of a random walk from `config`, or the default parameters, for functions that do not use its method and `n`
+    pub fn walk_parameters(config: Option<&NTAConfig>) -> PyResult<NTAConfig> {
+        match config {
+            Some(config) => {
+                config.validate()?;
+                Ok(config.clone())
+            }
+            None => NTAConfig::resolve(None, Some(NTAMethod::Prioritization), Some(0)),
+        }
+    }
+}
+
+#[pymethods]
+impl NTAConfig {
+    #[new]
+    #[pyo3(signature = (method, n, reset_probability=0.5, tolerance=0.000001, weighted=false, normalization="column"))]
+    fn new(
+        method: NTAMethod,
+        n: usize,
+        reset_probability: f64,
+        tolerance: f64,
+        weighted: bool,
+        normalization: &str,
+    ) -> PyResult<Self> {
+        let config = NTAConfig {
+            method,
+            n,
+            reset_probability,
+            tolerance,
+            weighted,
+            normalization: Normalization::parse(normalization)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[getter]
+    fn get_normalization(&self) -> &'static str {
+        self.normalization.name()
+    }
+
+    #[setter]
+    fn set_normalization(&mut self, normalization: &str) -> PyResult<()> {
+        self.normalization = Normalization::parse(normalization)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "NTAConfig(method=NTAMethod.{}, n={}, reset_probability={}, tolerance={}, weighted={}, normalization='{}')",
+            self.method.name(),
+            self.n,
+            self.reset_probability,
+            self.tolerance,
+            if self.weighted { "True" } else { "False" },
+            self.normalization.name()
+        )
+    }
+}
+
+/// Records of the nodes of an NTA neighborhood with their score, whether they are a seed, their rank, and their degree
+pub fn node_records<'a>(
+    py: Python<'a>,
+    network: &Network,
+    neighborhood: &[String],
+    scores: &[f64],
+    seeds: &FxHashSet<&String>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    neighborhood
+        .iter()
+        .zip(scores.iter())
+        .enumerate()
+        .map(|(i, (node, score))| {
+            let record = PyDict::new(py);
+            record.set_item("id", node)?;
+            record.set_item("score", score)?;
+            record.set_item("is_seed", seeds.contains(node))?;
+            record.set_item("rank", i + 1)?;
+            record.set_item("degree", network.adjacency[network.node_map[node]].len())?;
+            Ok(record)
+        })
+        .collect()
+}
+
+/// Tab-separated table of the random-walk score of every node of `network`, ordered by decreasing score
+pub fn score_table(network: &Network, scores: &[f64], seeds: &FxHashSet<&String>) -> String {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    let mut content = String::from("id\tscore\trank\tis_seed\tdegree\n");
+    for (rank, i) in order.into_iter().enumerate() {
+        let node = &network.nodes[i];
+        content.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            node,
+            scores[i],
+            rank + 1,
+            seeds.contains(node),
+            network.adjacency[i].len()
+        ));
+    }
+    content
+}
+
+/// Splits the random-walk scores of `nodes` into the part that comes from each seed.
+///
+/// The walk is linear in the restart vector, so the score of a node is the sum of the walks
+/// restarting from each single seed, weighted by one over the number of seeds.
+pub fn seed_contributions(
+    network: &Network,
+    seeds: &[String],
+    nodes: &[usize],
+    r: f64,
+    tolerance: f64,
+    normalization: Normalization,
+) -> Vec<Vec<(String, f64)>> {
+    let found: Vec<&String> = seeds
+        .iter()
+        .filter(|x| network.node_map.contains_key(*x))
+        .collect();
+    let strengths = network.strengths();
+    let walks: Vec<Vec<f64>> = found
+        .par_iter()
+        .map(|seed| {
+            let mut p0 = vec![0.0; network.nodes.len()];
+            p0[network.node_map[*seed]] = 1.0;
+            iterate_walk(&p0, r, tolerance, |p| {
+                network.normalized_transition(p, &strengths, normalization)
+            })
+        })
+        .collect();
+    nodes
+        .iter()
+        .map(|node| {
+            found
+                .iter()
+                .zip(walks.iter())
+                .map(|(seed, walk)| ((*seed).clone(), walk[*node] / found.len() as f64))
+                .filter(|(_, x)| *x > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// ROC and precision-recall curves of a ranking by `scores` against the true `labels`
+pub struct RankingCurves {
+    pub fpr: Vec<f64>,
+    pub tpr: Vec<f64>,
+    pub precision: Vec<f64>,
+    pub recall: Vec<f64>,
+    pub auroc: f64,
+    /// Average precision
+    pub auprc: f64,
+}
+
+impl RankingCurves {
+    /// Computes the curves, with one point for every distinct score (ties are ranked together)
+    pub fn new(scores: &[f64], labels: &[bool]) -> RankingCurves {
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+        let positives = labels.iter().filter(|x| **x).count() as f64;
+        let negatives = labels.len() as f64 - positives;
+        let mut curves = RankingCurves {
+            fpr: vec![0.0],
+            tpr: vec![0.0],
+            precision: Vec::new(),
+            recall: Vec::new(),
+            auroc: 0.0,
+            auprc: 0.0,
+        };
+        let (mut tp, mut fp) = (0.0, 0.0);
+        for (i, node) in order.iter().enumerate() {
+            if labels[*node] {
+                tp += 1.0;
+            } else {
+                fp += 1.0;
+            }
+            if order
+                .get(i + 1)
+                .is_some_and(|next| scores[*next] == scores[*node])
+            {
+                continue;
+            }
+            let tpr = if positives > 0.0 { tp / positives } else { 0.0 };
+            let fpr = if negatives > 0.0 { fp / negatives } else { 0.0 };
+            let last_tpr = *curves.tpr.last().unwrap();
+            let last_fpr = *curves.fpr.last().unwrap();
+            let precision = tp / (tp + fp);
+            curves.auroc += (fpr - last_fpr) * (tpr + last_tpr) / 2.0;
+            curves.auprc += (tpr - last_tpr) * precision;
+            curves.fpr.push(fpr);
+            curves.tpr.push(tpr);
+            curves.precision.push(precision);
+            curves.recall.push(tpr);
+        }
+        curves
+    }
+}
+
+/// Maximum number of steps of a random walk, in case it does not converge
+const MAX_WALK_STEPS: usize = 100_000;
+
+/// Repeats `p_{t+1} = (1 - r) * step(p_t) + r * p0` until converged, or until the change is no longer finite
+/// or [`MAX_WALK_STEPS`] is reached
+pub fn iterate_walk<F>(p0: &[f64], r: f64, tolerance: f64, step: F) -> Vec<f64>
+where
+    F: Fn(&[f64]) -> Vec<f64>,
+{
+    let mut pt = p0.to_vec();
+    for _ in 0..MAX_WALK_STEPS {
+        let pt1: Vec<f64> = step(&pt)
+            .into_iter()
+            .zip(p0.iter())
+            .map(|(x, p)| (1.0 - r) * x + r * p)
+            .collect();
+        let change: f64 = pt1.iter().zip(pt.iter()).map(|(a, b)| (a - b).abs()).sum();
+        pt = pt1;
+        if change <= tolerance || !change.is_finite() {
+            break;
+        }
+    }
+    pt
+}
+
+/// Selects the nodes to report for `method`, ordered by decreasing score.
+///
+/// Returns the indices of the neighborhood and the candidates. Candidates are only reported for
+/// prioritization, where they are the same as the neighborhood.
+pub fn select_nodes(
+    scores: &[f64],
+    is_seed: &[bool],
+    method: &NTAMethod,
+    n: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    match method {
+        NTAMethod::Prioritization => {
+            let neighborhood: Vec<usize> =
+                order.into_iter().filter(|x| is_seed[*x]).take(n).collect();
+            (neighborhood.clone(), neighborhood)
+        }
+        NTAMethod::Expansion => {
+            let neighborhood: Vec<usize> =
+                order.into_iter().filter(|x| !is_seed[*x]).take(n).collect();
+            (neighborhood, Vec::new())
+        }
+    }
+}
+
+/// Network with several layers that share the same nodes
+pub struct Multiplex {
+    pub nodes: Vec<String>,
+    pub layers: Vec<Network>,
+}
+
+impl Multiplex {
+    /// Builds a layer from each edge list, with the edge weights like [`Network::for_walk`]
+    pub fn from_edge_lists(edge_lists: &[Vec<Vec<String>>], weighted: bool) -> PyResult<Multiplex> {
+        let mut union = Network::default();
+        for edge_list in edge_lists {
+            for edge in edge_list.iter().filter(|x| x.len() >= 2) {
+                union.node_index(&edge[0]);
+                union.node_index(&edge[1]);
+            }
+        }
+        let layers = edge_lists
+            .iter()
+            .map(|edge_list| {
+                let mut layer = Network::with_nodes(&union.nodes);
+                layer.add_walk_edges(edge_list, weighted)?;
+                Ok(layer)
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(Multiplex {
+            nodes: union.nodes,
+            layers,
+        })
+    }
+
+    /// Random walk with restart on the supra-graph of all layers.
+    ///
+    /// At each step the walker jumps to the same node in another layer with probability `delta`
+    /// (split evenly between the other layers), and otherwise moves within its current layer.
+    /// Nodes without edges in a layer always jump. The restart is spread evenly over the seeds in
+    /// every layer. Moves within a layer follow its transition matrix with the chosen normalization.
+    ///
+    /// Returns the stationary probabilities of every node in each layer, indexed by `[layer][node]`.
+    pub fn random_walk(
+        &self,
+        seeds: &[String],
+        delta: f64,
+        r: f64,
+        tolerance: f64,
+        normalization: Normalization,
+    ) -> Vec<Vec<f64>> {
+        let n = self.nodes.len();
+        let l = self.layers.len();
+        let strengths: Vec<Vec<f64>> = self.layers.iter().map(|x| x.strengths()).collect();
+        let mut p0: Vec<f64> = Vec::with_capacity(n * l);
+        for layer in self.layers.iter() {
+            p0.extend(layer.seed_vector(seeds).into_iter().map(|x| x / l as f64));
+        }
+        let walk = iterate_walk(&p0, r, tolerance, |p| {
+            let mut next = vec![0.0; n * l];
+            for (a, layer) in self.layers.iter().enumerate() {
+                let intra = layer.normalized_transition(
+                    &p[a * n..(a + 1) * n],
+                    &strengths[a],
+                    normalization,
+                );
+                for v in 0..n {
+                    let mass = p[a * n + v];
+                    if mass == 0.0 || l == 1 {
+                        continue;
+                    }
+                    let jump = if strengths[a][v] == 0.0 { 1.0 } else { delta };
+                    for b in (0..l).filter(|b| *b != a) {
+                        next[b * n + v] += mass * jump / (l - 1) as f64;
+                    }
+                }
+                let stay = if l == 1 { 1.0 } else { 1.0 - delta };
+                for (v, x) in intra.into_iter().enumerate() {
+                    next[a * n + v] += stay * x;
+                }
+            }
+            next
+        });
+        walk.chunks(n).map(|x| x.to_vec()).collect()
+    }
+}
+
+/// Run NTA over several networks (layers) at once with a multiplex random walk.
+///
+/// The networks are joined into a multiplex network where each analyte is present in every
+/// layer. The random walker moves along the edges of its current layer, or jumps to the same
+/// analyte in a different layer with probability `inter_layer_probability`.
+///
+/// # Parameters
+/// - `edge_list_paths` - List of `String`s of the paths to the edge list files of each layer, file-like objects, or DataFrames. See [`nta`](./nta.md) for the format.
+/// - `analyte_list_path` - `String` of the path to the analyte list file of interest, with analytes separated by new lines, a file-like object, or a list of analytes
+/// - `nta_method` - a `NTAMethod` object specifying the NTA method for the analysis. Not used with `config`.
+/// - `n` - the number of seeds or nodes to identify according to `nta_method`. Not used with `config`.
+/// - `inter_layer_probability` - the probability of jumping between layers at each step (default: `0.5`)
+/// - `config` - If provided, a [`NTAConfig`](./NTAConfig.md) object with the method, `n`, and the parameters of the random walk, used instead of
+///   `nta_method` and `n`. With `weighted`, the walk follows the edges of each layer in proportion to their weight (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary object containing the `candidates` (seed nodes when using prioritization), `scores` (random-walk probabilities summed over all layers),
+/// `neighborhood` (identified nodes), and `layer_scores`. `layer_scores` contains a list for each layer, in the order of `edge_list_paths`, with the probabilities of the nodes in `neighborhood` for that layer.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any network or the analyte file is malformed or can not be read, if `config` is not provided
+/// instead of both `nta_method` and `n`, or if a weight is not positive and finite with `weighted`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// nta_method = webgestaltpy.NTAMethod.Expansion
+/// y = webgestaltpy.multiplex_nta(["ppi.net", "coexpression.net"], "data/net_genes.txt", nta_method, 5)
+/// print(y["layer_scores"][0]) # scores of the neighborhood in ppi.net
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_paths, analyte_list_path, nta_method=None, n=None, inter_layer_probability=0.5, config=None))]
+pub fn multiplex_nta<'a>(
+    py: Python<'a>,
+    edge_list_paths: Vec<Bound<'_, PyAny>>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    nta_method: Option<NTAMethod>,
+    n: Option<usize>,
+    inter_layer_probability: f64,
+    config: Option<&NTAConfig>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let config = NTAConfig::resolve(config, nta_method, n)?;
+    if edge_list_paths.is_empty() {
+        return Err(PyValueError::new_err("At least one network is required!"));
+    }
+    if !(0.0..=1.0).contains(&inter_layer_probability) {
+        return Err(PyValueError::new_err(format!(
+            "inter_layer_probability must be between 0 and 1, but is {}",
+            inter_layer_probability
+        )));
+    }
+    let edge_lists: Vec<Vec<Vec<String>>> = edge_list_paths
+        .into_iter()
+        .map(|x| inputs::edge_list(&x))
+        .collect::<PyResult<_>>()?;
+    let seed_set: FxHashSet<String> = inputs::unique_analyte_list(analyte_list_path)?
+        .into_iter()
+        .collect();
+    let seeds: Vec<String> = seed_set.iter().cloned().collect();
+    let multiplex = Multiplex::from_edge_lists(&edge_lists, config.weighted)?;
+    let layer_walks = config::install(py, || {
+        multiplex.random_walk(
+            &seeds,
+            inter_layer_probability,
+            config.reset_probability,
+            config.tolerance,
+            config.normalization,
+        )
+    })?;
+    let scores: Vec<f64> = (0..multiplex.nodes.len())
+        .map(|v| layer_walks.iter().map(|x| x[v]).sum())
+        .collect();
+    let is_seed: Vec<bool> = multiplex
+        .nodes
+        .iter()
+        .map(|x| seed_set.contains(x))
+        .collect();
+    let (neighborhood, candidates) = select_nodes(&scores, &is_seed, &config.method, config.n);
+    let names =
+        |x: &[usize]| -> Vec<String> { x.iter().map(|i| multiplex.nodes[*i].clone()).collect() };
+    let dict = PyDict::new(py);
+    dict.set_item("candidates", names(&candidates))?;
+    dict.set_item(
+        "scores",
+        neighborhood
+            .iter()
+            .map(|i| scores[*i])
+            .collect::<Vec<f64>>(),
+    )?;
+    dict.set_item("neighborhood", names(&neighborhood))?;
+    dict.set_item(
+        "layer_scores",
+        layer_walks
+            .iter()
+            .map(|walk| neighborhood.iter().map(|i| walk[*i]).collect())
+            .collect::<Vec<Vec<f64>>>(),
+    )?;
+    Ok(dict)
+}
+
+/// Find an approximate minimal subnetwork connecting the analytes of interest (Steiner tree).
+///
+/// Uses the Kou-Markowsky-Berman approximation with every edge counted as one step, so edge
+/// weights are ignored. Often more interpretable than the top-N neighborhood of [`nta`](./nta.md).
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with `source` and `target` columns. See [`nta`](./nta.md) for the format.
+/// - `analyte_list_path` - `String` of the path to the analyte list file of interest, with analytes separated by new lines, a file-like object, or a list of analytes
+///
+/// # Returns
+///
+/// Returns a dictionary object containing the `nodes` and `edges` of the subnetwork, and the `missing` analytes that are not in the network.
+/// Analytes that can not reach each other are connected in separate trees, and analytes with no path to any other analyte are returned as lone nodes.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network or the analyte file is malformed or can not be read.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// y = webgestaltpy.steiner_subnetwork("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", "data/net_genes.txt")
+/// print(y["edges"][0:2])
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// [['A1BG', 'GC'], ['GC', 'CFH']]
+/// ```
+#[pyfunction]
+pub fn steiner_subnetwork<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let network = Network::from_edge_list(&inputs::edge_list(edge_list_path)?);
+    let mut seeds: Vec<String> = inputs::unique_analyte_list(analyte_list_path)?
+        .into_iter()
+        .collect();
+    seeds.sort();
+    let (found, missing): (Vec<String>, Vec<String>) = seeds
+        .into_iter()
+        .partition(|x| network.node_map.contains_key(x));
+    let terminals: Vec<usize> = found.iter().map(|x| network.node_map[x]).collect();
+    let tree = network.steiner_tree(&terminals);
+    let mut nodes: Vec<usize> = terminals;
+    nodes.extend(tree.iter().flat_map(|(a, b)| [*a, *b]));
+    nodes.sort();
+    nodes.dedup();
+    let dict = PyDict::new(py);
+    dict.set_item(
+        "nodes",
+        nodes
+            .iter()
+            .map(|x| network.nodes[*x].clone())
+            .collect::<Vec<String>>(),
+    )?;
+    dict.set_item(
+        "edges",
+        tree.iter()
+            .map(|(a, b)| vec![network.nodes[*a].clone(), network.nodes[*b].clone()])
+            .collect::<Vec<Vec<String>>>(),
+    )?;
+    dict.set_item("missing", missing)?;
+    Ok(dict)
+}
+
+/// Compute the shortest-path distances between analytes in a network.
+///
+/// Useful to quantify how clustered the analytes of interest are on the network before and after NTA.
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with `source` and `target` columns. See [`nta`](./nta.md) for the format.
+/// - `nodes` - List of `String`s of the analytes to compute the distances between
+/// - `weighted` - If `True`, uses the edge weights (third column of the edge list) as the lengths of the edges. Otherwise, every edge has a length of one (default: `False`)
+///
+/// # Returns
+///
+/// Returns a dictionary object containing the `nodes` found in the network, the `distances` between them, and the `missing` analytes that are not in the network.
+/// `distances` is a square matrix (list of lists) in the order of `nodes`. Analytes with no path between them have a distance of `inf`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network file is malformed or can not be read.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// y = webgestaltpy.pairwise_distances("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", ["ACTA1", "ACTB", "GC"])
+/// print(y["distances"])
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// [[0.0, 1.0, 12.0], [1.0, 0.0, 12.0], [12.0, 12.0, 0.0]]
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_path, nodes, weighted=false))]
+pub fn pairwise_distances<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    nodes: Vec<String>,
+    weighted: bool,
+) -> PyResult<Bound<'a, PyDict>> {
+    let network = Network::from_edge_list(&inputs::edge_list(edge_list_path)?);
+    if weighted && network.adjacency.iter().flatten().any(|(_, w)| *w < 0.0) {
+        return Err(PyValueError::new_err(
+            "Edge weights must not be negative when computing weighted distances!",
+        ));
+    }
+    let (found, missing): (Vec<String>, Vec<String>) = nodes
+        .into_iter()
+        .partition(|x| network.node_map.contains_key(x));
+    let indices: Vec<usize> = found.iter().map(|x| network.node_map[x]).collect();
+    let distances: Vec<Vec<f64>> = config::install(py, || {
+        indices
+            .par_iter()
+            .map(|source| {
+                let dist: Vec<f64> = if weighted {
+                    network.dijkstra(*source)
+                } else {
+                    network
+                        .bfs(*source)
+                        .0
+                        .into_iter()
+                        .map(|x| x.map_or(f64::INFINITY, |d| d as f64))
+                        .collect()
+                };
+                indices.iter().map(|x| dist[*x]).collect()
+            })
+            .collect()
+    })?;
+    let dict = PyDict::new(py);
+    dict.set_item("nodes", found)?;
+    dict.set_item("distances", distances)?;
+    dict.set_item("missing", missing)?;
+    Ok(dict)
+}
+
+/// Find connected modules of high-scoring nodes after a random walk from the analytes of interest.
+///
+/// The `n` nodes with the highest random-walk probabilities are kept, and the network between them is split into connected modules.
+/// Unlike the flat neighborhood of [`nta`](./nta.md), each module is a connected piece of the network.
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with `source` and `target` columns. See [`nta`](./nta.md) for the format.
+/// - `analyte_list_path` - `String` of the path to the analyte list file of interest, with analytes separated by new lines, a file-like object, or a list of analytes
+/// - `n` - the number of top-scoring nodes to split into modules (default: `50`)
+/// - `min_size` - the minimum number of nodes in a reported module (default: `2`)
+/// - `config` - If provided, a [`NTAConfig`](./NTAConfig.md) object with the `reset_probability`, `tolerance`, `weighted`, and `normalization`
+///   of the random walk. Its method and `n` are not used. Otherwise, the walk is unweighted with the default parameters of `NTAConfig` (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries for each module, ranked by the total `score` of the module. Each dictionary contains the
+/// module `nodes` and their `scores` (sorted by score), the total `score`, and the `seeds` (analytes of interest) in the module.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network or the analyte file is malformed or can not be read, or if a weight is not positive and finite with `weighted`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// modules = webgestaltpy.nta_modules("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", "data/net_genes.txt", 50)
+/// print(modules[0]["nodes"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_path, analyte_list_path, n=50, min_size=2, config=None))]
+pub fn nta_modules<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    n: usize,
+    min_size: usize,
+    config: Option<&NTAConfig>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let config = NTAConfig::walk_parameters(config)?;
+    let network = Network::for_walk(&inputs::edge_list(edge_list_path)?, config.weighted)?;
+    let seed_set: FxHashSet<String> = inputs::unique_analyte_list(analyte_list_path)?
+        .into_iter()
+        .collect();
+    let seeds: Vec<String> = seed_set.iter().cloned().collect();
+    let scores = config::install(py, || {
+        network.normalized_walk(
+            &network.seed_vector(&seeds),
+            config.reset_probability,
+            config.tolerance,
+            config.normalization,
+        )
+    })?;
+    let mut top: Vec<usize> = (0..network.nodes.len()).collect();
+    top.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    top.truncate(n);
+    let mut modules: Vec<(f64, Vec<usize>)> = network
+        .components(&top)
+        .into_iter()
+        .filter(|x| x.len() >= min_size)
+        .map(|x| (x.iter().map(|i| scores[*i]).sum(), x))
+        .collect();
+    modules.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut res: Vec<Bound<'_, PyDict>> = Vec::new();
+    for (score, module) in modules {
+        let nodes: Vec<String> = module.iter().map(|x| network.nodes[*x].clone()).collect();
+        let dict = PyDict::new(py);
+        dict.set_item(
+            "seeds",
+            nodes
+                .iter()
+                .filter(|x| seed_set.contains(*x))
+                .collect::<Vec<&String>>(),
+        )?;
+        dict.set_item("nodes", nodes)?;
+        dict.set_item(
+            "scores",
+            module.iter().map(|x| scores[*x]).collect::<Vec<f64>>(),
+        )?;
+        dict.set_item("score", score)?;
+        res.push(dict);
+    }
+    Ok(res)
+}
+
+/// Benchmark NTA prioritization against known (held-out) true analytes.
+///
+/// Runs the random walk from the analytes of interest for each restart probability and ranks every
+/// other node by its score. The rankings are compared to `true_genes` with ROC and precision-recall curves,
+/// which helps tune the restart probability on known disease genes.
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with `source` and `target` columns. See [`nta`](./nta.md) for the format.
+/// - `analyte_list_path` - `String` of the path to the analyte list file of interest (seeds), with analytes separated by new lines, a file-like object, or a list of analytes
+/// - `true_genes` - List of the held-out analytes that should be ranked highly, as `String`s or integers
+/// - `restart_probabilities` - List of the restart probabilities to compare (default: `[0.1, 0.3, 0.5, 0.7, 0.9]`)
+/// - `config` - If provided, a [`NTAConfig`](./NTAConfig.md) object with the `tolerance`, `weighted`, and `normalization` of the random walk.
+///   Its method, `n`, and `reset_probability` are not used. Otherwise, the walk is unweighted with the default parameters of `NTAConfig` (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each restart probability, containing the `restart_probability`, `auroc`, `auprc` (average precision),
+/// and the curves `fpr`, `tpr`, `precision`, and `recall`. Seeds are not ranked, and true analytes that are not in the network are left out.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network or the analyte file is malformed or can not be read, if a restart probability is out of range,
+/// or if a weight is not positive and finite with `weighted`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.nta_benchmark("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", "data/net_genes.txt", ["MYH9", "MYL6"])
+/// best = max(res, key=lambda x: x["auroc"])
+/// print(best["restart_probability"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_path, analyte_list_path, true_genes, restart_probabilities=vec![0.1, 0.3, 0.5, 0.7, 0.9], config=None))]
+pub fn nta_benchmark<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    true_genes: &Bound<'_, PyAny>,
+    restart_probabilities: Vec<f64>,
+    config: Option<&NTAConfig>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if let Some(r) = restart_probabilities
+        .iter()
+        .find(|x| !(0.0..=1.0).contains(*x) || **x == 0.0)
+    {
+        return Err(PyValueError::new_err(format!(
+            "Restart probabilities must be greater than 0 and at most 1, but found {}",
+            r
+        )));
+    }
+    let config = NTAConfig::walk_parameters(config)?;
+    let network = Network::for_walk(&inputs::edge_list(edge_list_path)?, config.weighted)?;
+    let seed_set: FxHashSet<String> = inputs::unique_analyte_list(analyte_list_path)?
+        .into_iter()
+        .collect();
+    let seeds: Vec<String> = seed_set.iter().cloned().collect();
+    let true_set: FxHashSet<String> = inputs::analyte_ids(true_genes)?.into_iter().collect();
+    let ranked: Vec<usize> = (0..network.nodes.len())
+        .filter(|x| !seed_set.contains(&network.nodes[*x]))
+        .collect();
+    let labels: Vec<bool> = ranked
+        .iter()
+        .map(|x| true_set.contains(&network.nodes[*x]))
+        .collect();
+    let p0 = network.seed_vector(&seeds);
+    let curves: Vec<RankingCurves> = config::install(py, || {
+        restart_probabilities
+            .par_iter()
+            .map(|r| {
+                let walk = network.normalized_walk(&p0, *r, config.tolerance, config.normalization);
+                let scores: Vec<f64> = ranked.iter().map(|x| walk[*x]).collect();
+                RankingCurves::new(&scores, &labels)
+            })
+            .collect()
+    })?;
+    let mut res: Vec<Bound<'_, PyDict>> = Vec::new();
+    for (r, curve) in restart_probabilities.iter().zip(curves) {
+        let dict = PyDict::new(py);
+        dict.set_item("restart_probability", r)?;
+        dict.set_item("auroc", curve.auroc)?;
+        dict.set_item("auprc", curve.auprc)?;
+        dict.set_item("fpr", curve.fpr)?;
+        dict.set_item("tpr", curve.tpr)?;
+        dict.set_item("precision", curve.precision)?;
+        dict.set_item("recall", curve.recall)?;
+        res.push(dict);
+    }
+    Ok(res)
+}
+
+/// Run NTA for many lists of seeds on the same network, like one list for each patient.
+///
+/// The network and its transition matrix are only built once, and the random walks of the lists run in parallel,
+/// which is much faster than calling [`nta`](./nta.md) for each list.
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame with `source` and `target` columns. See [`nta`](./nta.md) for the format.
+/// - `seed_lists` - Dictionary mapping the name of each list to its seeds, in any form accepted by the `analyte_list_path` of [`nta`](./nta.md)
+/// - `nta_method` - a `NTAMethod` object specifying the NTA method for the analysis. Not used with `config`.
+/// - `n` - the number of seeds or nodes to identify according to `nta_method`. Not used with `config`.
+/// - `config` - If provided, a [`NTAConfig`](./NTAConfig.md) object with the method, `n`, and the parameters of the random walk, used instead of
+///   `nta_method` and `n` (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary mapping the name of each list to its results, with the `candidates`, `scores`, `neighborhood`, and `nodes` like [`nta`](./nta.md).
+/// With prioritization, `candidates` is the same as `neighborhood`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network or any list of seeds is malformed or can not be read, if `config` is not provided
+/// instead of both `nta_method` and `n`, or if a weight is not positive and finite with `weighted`.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// lists = {"patient_1": ["MYH9", "ACTB"], "patient_2": "patient_2_genes.txt"}
+/// res = webgestaltpy.nta_batch("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net", lists, webgestaltpy.NTAMethod.Expansion, 10)
+/// print(res["patient_1"]["neighborhood"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_path, seed_lists, nta_method=None, n=None, config=None))]
+pub fn nta_batch<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    seed_lists: &Bound<'_, PyDict>,
+    nta_method: Option<NTAMethod>,
+    n: Option<usize>,
+    config: Option<&NTAConfig>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let config = NTAConfig::resolve(config, nta_method, n)?;
+    let network = Network::for_walk(&inputs::edge_list(edge_list_path)?, config.weighted)?;
+    let mut names: Vec<String> = Vec::new();
+    let mut lists: Vec<Vec<String>> = Vec::new();
+    for (name, seeds) in seed_lists.iter() {
+        names.push(name.extract()?);
+        lists.push(inputs::unique_analyte_list(&seeds)?);
+    }
+    let walks: Vec<Vec<f64>> = config::install(py, || {
+        lists
+            .par_iter()
+            .map(|seeds| {
+                network.normalized_walk(
+                    &network.seed_vector(seeds),
+                    config.reset_probability,
+                    config.tolerance,
+                    config.normalization,
+                )
+            })
+            .collect()
+    })?;
+    let res = PyDict::new(py);
+    for ((name, seeds), walk) in names.iter().zip(lists.iter()).zip(walks) {
+        let seed_set: FxHashSet<&String> = seeds.iter().collect();
+        let is_seed: Vec<bool> = network.nodes.iter().map(|x| seed_set.contains(x)).collect();
+        let (neighborhood, candidates) = select_nodes(&walk, &is_seed, &config.method, config.n);
+        let node_names =
+            |x: &[usize]| -> Vec<String> { x.iter().map(|i| network.nodes[*i].clone()).collect() };
+        let scores: Vec<f64> = neighborhood.iter().map(|i| walk[*i]).collect();
+        let neighborhood = node_names(&neighborhood);
+        let dict = PyDict::new(py);
+        dict.set_item("candidates", node_names(&candidates))?;
+        dict.set_item("scores", &scores)?;
+        dict.set_item("neighborhood", &neighborhood)?;
+        dict.set_item(
+            "nodes",
+            node_records(py, &network, &neighborhood, &scores, &seed_set)?,
+        )?;
+        res.set_item(name, dict)?;
+    }
+    Ok(res)
+}
+
+/// Get the transition matrix of the random walk of NTA as a sparse matrix, to run other propagation methods on the same network.
+///
+/// The matrix is in compressed sparse row (CSR) format, which can be passed directly to `scipy.sparse.csr_matrix`.
+/// Multiplying the matrix by a vector of node scores gives the scores after one step of the walk, like each step of [`nta`](./nta.md).
+///
+/// # Parameters
+/// - `edge_list_path` - `String` of the path to the edge list file of the network, a file-like object, or a DataFrame. See [`nta`](./nta.md) for the format.
+/// - `weighted` - If `True`, edges are weighted by the third column of the edge list, which must be positive and finite. Otherwise, every edge
+///   has a weight of `1` (default: `False`)
+/// - `normalization` - Either `"column"`, `"row"`, or `"symmetric"`. See [`NTAConfig`](./NTAConfig.md) (default: `"column"`)
+/// - `source_column` - Name of the column with the source node of each edge when `edge_list_path` is a DataFrame (default: `"source"`)
+/// - `target_column` - Name of the column with the target node of each edge when `edge_list_path` is a DataFrame (default: `"target"`)
+/// - `weight_column` - Name of the column with the weight of each edge when `edge_list_path` is a DataFrame (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary with:
+///
+/// - `data` - The values of the matrix
+/// - `indices` - The column of each value
+/// - `indptr` - The position in `data` where each row starts, followed by the number of values
+/// - `shape` - The number of rows and columns, which is the number of nodes
+/// - `nodes` - The node of each row and column
+///
+/// The value at row `i` and column `j` is the probability of moving from `nodes[j]` to `nodes[i]` for `"column"` normalization. Nodes without edges have no values.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the network is malformed or can not be read, if a weight is not positive and finite when `weighted`,
+/// or if `normalization` is unknown.
+///
+/// # Example
+///
+/// ```python
+/// import scipy.sparse
+/// import webgestaltpy
+///
+/// m = webgestaltpy.transition_matrix("data/hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net")
+/// w = scipy.sparse.csr_matrix((m["data"], m["indices"], m["indptr"]), shape=m["shape"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (edge_list_path, weighted=false, normalization="column", source_column="source", target_column="target", weight_column=None))]
+pub fn transition_matrix<'a>(
+    py: Python<'a>,
+    edge_list_path: &Bound<'_, PyAny>,
+    weighted: bool,
+    normalization: &str,
+    source_column: &str,
+    target_column: &str,
+    weight_column: Option<&str>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let normalization = Normalization::parse(normalization)?;
+    let edges =
+        inputs::read_edge_list(edge_list_path, source_column, target_column, weight_column)?;
+    let network = Network::for_walk(&edges, weighted)?;
+    let (data, indices, indptr) = network.transition_matrix(normalization);
+    let dict = PyDict::new(py);
+    dict.set_item("data", data)?;
+    dict.set_item("indices", indices)?;
+    dict.set_item("indptr", indptr)?;
+    dict.set_item("shape", (network.nodes.len(), network.nodes.len()))?;
+    dict.set_item("nodes", network.nodes)?;
+    Ok(dict)
+}
@@ -0,0 +1,321 @@
+use crate::bias;
+use ahash::{AHashMap, AHashSet};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::readers::utils::Item;
+
+/// Size of each set within the reference list, in the order of `gmt`. Counted the same way as
+/// `webgestalt_lib::methods::ora::get_ora` does for the expected overlap.
+pub fn reference_set_sizes(gmt: &[Item], reference: &AHashSet<String>) -> Vec<usize> {
+    gmt.iter()
+        .map(|x| x.parts.iter().filter(|a| reference.contains(*a)).count())
+        .collect()
+}
+
+/// Sizes of the analyte and reference lists of an ORA, with the size of each set in the reference
+pub struct ORASizes {
+    pub list_size: usize,
+    pub reference_size: usize,
+    pub set_sizes: Vec<usize>,
+}
+
+impl ORASizes {
+    pub fn new(
+        gmt: &[Item],
+        analyte_list: &AHashSet<String>,
+        reference: &AHashSet<String>,
+    ) -> ORASizes {
+        ORASizes {
+            list_size: analyte_list.len(),
+            reference_size: reference.len(),
+            set_sizes: reference_set_sizes(gmt, reference),
+        }
+    }
+}
+
+/// 2×2 contingency table of a set in ORA. Every count is within the reference list.
+pub struct ContingencyTable {
+    /// Analytes in both the list and the set
+    pub overlap: usize,
+    /// Analytes in the list but not the set
+    pub list_only: usize,
+    /// Analytes in the set but not the list
+    pub set_only: usize,
+    /// Analytes in neither the list nor the set
+    pub neither: usize,
+}
+
+impl ContingencyTable {
+    pub fn new(
+        overlap: usize,
+        set_size: usize,
+        list_size: usize,
+        reference_size: usize,
+    ) -> ContingencyTable {
+        ContingencyTable {
+            overlap,
+            list_only: list_size.saturating_sub(overlap),
+            set_only: set_size.saturating_sub(overlap),
+            neither: (reference_size + overlap).saturating_sub(list_size + set_size),
+        }
+    }
+
+    /// Standard deviation of the overlap under the hypergeometric distribution
+    pub fn overlap_sd(&self) -> f64 {
+        let list_size = (self.overlap + self.list_only) as f64;
+        let set_size = (self.overlap + self.set_only) as f64;
+        let total = (self.overlap + self.list_only + self.set_only + self.neither) as f64;
+        if total <= 1.0 {
+            return 0.0;
+        }
+        (list_size * set_size / total * (total - set_size) / total * (total - list_size)
+            / (total - 1.0))
+            .sqrt()
+    }
+
+    /// Difference between the overlap and `expected` in standard deviations. `None` if the standard deviation is `0`.
+    pub fn z_score(&self, expected: f64) -> Option<f64> {
+        let sd = self.overlap_sd();
+        if sd > 0.0 {
+            Some((self.overlap as f64 - expected) / sd)
+        } else {
+            None
+        }
+    }
+
+    /// Adds the counts of the table, and the standard deviation and z-score of the overlap, to an ORA result
+    pub fn add_to_dict(&self, dict: &Bound<'_, PyDict>, expected: f64) -> PyResult<()> {
+        dict.set_item("expected_sd", self.overlap_sd())?;
+        dict.set_item("z", self.z_score(expected))?;
+        dict.set_item("list_only", self.list_only)?;
+        dict.set_item("set_only", self.set_only)?;
+        dict.set_item("neither", self.neither)?;
+        Ok(())
+    }
+}
+
+/// How the p-values of ORA are computed
+pub enum ORAMethod {
+    /// Hypergeometric test of `webgestalt_lib`
+    Hypergeometric,
+    /// Random analyte lists drawn from the reference list
+    Permutation,
+}
+
+impl ORAMethod {
+    pub fn parse(name: &str) -> PyResult<ORAMethod> {
+        match name {
+            "hypergeometric" => Ok(ORAMethod::Hypergeometric),
+            "permutation" => Ok(ORAMethod::Permutation),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown ORA method '{}'. Expected 'hypergeometric' or 'permutation'.",
+                name
+            ))),
+        }
+    }
+}
+
+/// Number of values of the sorted `values` that are at least `x`
+fn count_at_least(values: &[usize], x: usize) -> usize {
+    values.len() - values.partition_point(|v| *v < x)
+}
+
+/// Permutation p-values and FDRs of ORA, in the order of `gmt`.
+///
+/// Each permutation draws a list of the same size from the reference list, without replacement. The p-value of a set is
+/// `(1 + b) / (1 + permutations)`, where `b` is the number of permutations with an overlap at least as large as observed.
+/// The FDR at the p-value of a set is the mean number of sets per permutation with a permutation p-value at most that large,
+/// over the number of observed sets with a p-value at most that large, made monotonic like BH.
+///
+/// With `bias`, the reference is split into bins by bias like [`bias::probability_weights`], and each permutation draws as many
+/// analytes from each bin as the list has in it, so the random lists match the bias of the list.
+pub fn permutation_p(
+    gmt: &[Item],
+    analyte_list: &AHashSet<String>,
+    reference: &AHashSet<String>,
+    bias: Option<&FxHashMap<String, f64>>,
+    permutations: usize,
+    seed: u64,
+) -> (Vec<f64>, Vec<f64>) {
+    // sorted, since hash set order is random
+    let mut universe: Vec<&String> = reference.iter().collect();
+    universe.sort();
+    let positions: AHashMap<&String, usize> =
+        universe.iter().enumerate().map(|(i, x)| (*x, i)).collect();
+    let members: Vec<Vec<usize>> = gmt
+        .iter()
+        .map(|set| {
+            let mut indices: Vec<usize> = set
+                .parts
+                .iter()
+                .filter_map(|x| positions.get(x).copied())
+                .collect();
+            indices.sort_unstable();
+            indices.dedup();
+            indices
+        })
+        .collect();
+    let observed: Vec<usize> = members
+        .iter()
+        .map(|x| {
+            x.iter()
+                .filter(|i| analyte_list.contains(universe[**i]))
+                .count()
+        })
+        .collect();
+    // analytes to draw from and how many to draw, from a single bin of the whole reference without bias
+    let strata: Vec<(Vec<usize>, usize)> = match bias {
+        Some(bias) => bias::bias_bins(&universe, bias)
+            .into_iter()
+            .map(|bin| {
+                let listed = bin
+                    .iter()
+                    .filter(|i| analyte_list.contains(universe[**i]))
+                    .count();
+                (bin, listed)
+            })
+            .collect(),
+        None => vec![(
+            (0..universe.len()).collect(),
+            analyte_list.len().min(universe.len()),
+        )],
+    };
+    // overlaps of each permutation, with a row for each permutation
+    let null: Vec<Vec<usize>> = (0..permutations)
+        .into_par_iter()
+        .map(|b| {
+            let mut rng = rand::rngs::SmallRng::seed_from_u64(seed.wrapping_add(b as u64));
+            let mut in_list = vec![false; universe.len()];
+            for (bin, listed) in strata.iter() {
+                for i in rand::seq::index::sample(&mut rng, bin.len(), *listed) {
+                    in_list[bin[i]] = true;
+                }
+            }
+            members
+                .iter()
+                .map(|x| x.iter().filter(|i| in_list[**i]).count())
+                .collect()
+        })
+        .collect();
+    let sorted_null: Vec<Vec<usize>> = (0..gmt.len())
+        .map(|s| {
+            let mut values: Vec<usize> = null.iter().map(|x| x[s]).collect();
+            values.sort_unstable();
+            values
+        })
+        .collect();
+    let total = permutations as f64 + 1.0;
+    let p: Vec<f64> = (0..gmt.len())
+        .map(|s| (1 + count_at_least(&sorted_null[s], observed[s])) as f64 / total)
+        .collect();
+    let mut null_p: Vec<f64> = null
+        .iter()
+        .flat_map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(s, x)| count_at_least(&sorted_null[s], *x) as f64 / total)
+        })
+        .collect();
+    null_p.sort_by(|a, b| a.total_cmp(b));
+    let mut sorted_p = p.clone();
+    sorted_p.sort_by(|a, b| a.total_cmp(b));
+    let mut order: Vec<usize> = (0..p.len()).collect();
+    order.sort_by(|a, b| p[*b].total_cmp(&p[*a]));
+    let mut fdr = vec![1.0; p.len()];
+    let mut running_min: f64 = 1.0;
+    for i in order {
+        let expected = null_p.partition_point(|x| *x <= p[i]) as f64 / permutations.max(1) as f64;
+        let discoveries = sorted_p.partition_point(|x| *x <= p[i]) as f64;
+        running_min = running_min.min(expected / discoveries);
+        fdr[i] = running_min;
+    }
+    (p, fdr)
+}
+
+/// p-values of `tables` from a Python callable, called with at most `batch_size` tables at a time.
+/// Each table is passed as an `(overlap, list_only, set_only, neither)` tuple.
+pub fn statistic_p(
+    statistic: &Bound<'_, PyAny>,
+    tables: &[ContingencyTable],
+    batch_size: usize,
+) -> PyResult<Vec<f64>> {
+    let mut p: Vec<f64> = Vec::with_capacity(tables.len());
+    for batch in tables.chunks(batch_size) {
+        let counts: Vec<(usize, usize, usize, usize)> = batch
+            .iter()
+            .map(|x| (x.overlap, x.list_only, x.set_only, x.neither))
+            .collect();
+        let values: Vec<f64> = statistic
+            .call1((counts,))?
+            .extract()
+            .map_err(|_| PyValueError::new_err("statistic must return a sequence of p-values!"))?;
+        if values.len() != batch.len() {
+            return Err(PyValueError::new_err(format!(
+                "statistic must return a p-value for each table, but returned {} for {} tables",
+                values.len(),
+                batch.len()
+            )));
+        }
+        if let Some(x) = values.iter().find(|x| !(0.0..=1.0).contains(*x)) {
+            return Err(PyValueError::new_err(format!(
+                "p-values must be between 0 and 1, but statistic returned {}",
+                x
+            )));
+        }
+        p.extend(values);
+    }
+    Ok(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(id: &str, parts: &[&str]) -> Item {
+        Item {
+            id: id.to_owned(),
+            url: String::new(),
+            parts: parts.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn analytes(range: std::ops::Range<usize>) -> AHashSet<String> {
+        range.map(|i| format!("g{}", i)).collect()
+    }
+
+    #[test]
+    fn permutation_p_bounds_and_thread_count() {
+        let reference = analytes(0..200);
+        let analyte_list = analytes(0..20);
+        let listed: Vec<String> = (0..20).map(|i| format!("g{}", i)).collect();
+        let unlisted: Vec<String> = (100..120).map(|i| format!("g{}", i)).collect();
+        let gmt = vec![
+            set(
+                "listed",
+                &listed.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+            ),
+            set(
+                "unlisted",
+                &unlisted.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+            ),
+        ];
+        let run = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| permutation_p(&gmt, &analyte_list, &reference, None, 200, 7))
+        };
+        let (p, fdr) = run(1);
+        // no random list of 20 of 200 analytes is the set itself, and every overlap is at least 0
+        assert_eq!(p[0], 1.0 / 201.0);
+        assert_eq!(p[1], 1.0);
+        assert!(fdr.iter().all(|x| (0.0..=1.0).contains(x)));
+        assert!(fdr[0] <= fdr[1]);
+        assert_eq!((p, fdr), run(4));
+    }
+}
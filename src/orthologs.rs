@@ -0,0 +1,416 @@
+use crate::{config, ids, inputs, warnings};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
+use rustc_hash::{FxHashMap, FxHashSet};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+/// Directory of the ortholog tables of [HCOP](https://www.genenames.org/tools/hcop/), which map human genes to the genes of each organism
+const HCOP_URL: &str = "https://ftp.ebi.ac.uk/pub/databases/genenames/hcop/";
+
+/// Organisms with ortholog tables
+#[derive(Clone, Copy, PartialEq)]
+pub enum Organism {
+    Human,
+    Mouse,
+    Rat,
+    Zebrafish,
+    Fly,
+    Worm,
+    Yeast,
+}
+
+impl Organism {
+    pub fn parse(name: &str) -> PyResult<Organism> {
+        match name {
+            "human" => Ok(Organism::Human),
+            "mouse" => Ok(Organism::Mouse),
+            "rat" => Ok(Organism::Rat),
+            "zebrafish" => Ok(Organism::Zebrafish),
+            "fly" => Ok(Organism::Fly),
+            "worm" => Ok(Organism::Worm),
+            "yeast" => Ok(Organism::Yeast),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown organism '{}'. Expected 'human', 'mouse', 'rat', 'zebrafish', 'fly', 'worm', or 'yeast'.",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Organism::Human => "human",
+            Organism::Mouse => "mouse",
+            Organism::Rat => "rat",
+            Organism::Zebrafish => "zebrafish",
+            Organism::Fly => "fly",
+            Organism::Worm => "worm",
+            Organism::Yeast => "yeast",
+        }
+    }
+
+    /// URL of the HCOP table between human and the organism
+    fn hcop_url(self) -> String {
+        let species = match self {
+            Organism::Human => "human",
+            Organism::Mouse => "mouse",
+            Organism::Rat => "rat",
+            Organism::Zebrafish => "zebrafish",
+            Organism::Fly => "fruitfly",
+            Organism::Worm => "c.elegans",
+            Organism::Yeast => "s.cerevisiae",
+        };
+        format!("{}human_{}_hcop_fifteen_column.txt.gz", HCOP_URL, species)
+    }
+}
+
+/// Identifiers mapped between organisms
+#[derive(Clone, Copy)]
+pub enum IdType {
+    Symbol,
+    Entrez,
+    Ensembl,
+}
+
+impl IdType {
+    pub fn parse(name: &str) -> PyResult<IdType> {
+        match name {
+            "symbol" => Ok(IdType::Symbol),
+            "entrez" => Ok(IdType::Entrez),
+            "ensembl" => Ok(IdType::Ensembl),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown id_type '{}'. Expected 'symbol', 'entrez', or 'ensembl'.",
+                name
+            ))),
+        }
+    }
+
+    /// Suffix of the columns of the identifiers in an HCOP table
+    fn column_suffix(self) -> &'static str {
+        match self {
+            IdType::Symbol => "_symbol",
+            IdType::Entrez => "_entrez_gene",
+            IdType::Ensembl => "_ensembl_gene",
+        }
+    }
+}
+
+/// Pairs of orthologs of an ortholog table, as `(first, second)` identifiers, and whether the table is in the HCOP format.
+///
+/// If the header has two columns ending with the suffix of `id_type`, like the `human_symbol` and `mouse_symbol` columns of HCOP, these
+/// columns are used. Otherwise, the table has an identifier in the first column and its ortholog in the second, separated by a tab, and lines
+/// starting with `#` are skipped. Missing identifiers (empty or `-`) are skipped.
+pub fn parse_ortholog_table(
+    content: &str,
+    id_type: IdType,
+) -> Result<(Vec<(String, String)>, bool), String> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, x)| !x.is_empty() && !x.starts_with('#'))
+        .peekable();
+    let header: Vec<usize> = lines
+        .peek()
+        .map(|(_, x)| {
+            x.split('\t')
+                .enumerate()
+                .filter(|(_, x)| x.ends_with(id_type.column_suffix()))
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .unwrap_or_default();
+    let (first, second, hcop) = match header[..] {
+        [first, second, ..] => {
+            lines.next();
+            (first, second, true)
+        }
+        _ => (0, 1, false),
+    };
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (i, line) in lines {
+        let columns: Vec<&str> = line.split('\t').collect();
+        let (Some(a), Some(b)) = (columns.get(first), columns.get(second)) else {
+            return Err(format!("Line {} has no ortholog column.", i + 1));
+        };
+        let (a, b) = (a.trim(), b.trim());
+        if a.is_empty() || b.is_empty() || a == "-" || b == "-" {
+            continue;
+        }
+        pairs.push((a.to_owned(), b.to_owned()));
+    }
+    Ok((pairs, hcop))
+}
+
+/// Pairs of an ortholog table input, which can be a path or URL to (or file-like object of) an ortholog table,
+/// or a dictionary mapping each identifier to its ortholog or a list of them. Also returns whether the table is in the HCOP format.
+fn ortholog_pairs(
+    obj: &Bound<'_, PyAny>,
+    id_type: IdType,
+) -> PyResult<(Vec<(String, String)>, bool)> {
+    if let Some(table) =
+        inputs::parse_text_input(obj, "ortholog table", |x| parse_ortholog_table(x, id_type))?
+    {
+        return Ok(table);
+    }
+    let dict = obj.downcast::<PyDict>().map_err(|_| {
+        PyValueError::new_err("Ortholog table must be a path, a file-like object, or a dictionary!")
+    })?;
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (id, orthologs) in dict.iter() {
+        let id = inputs::analyte_to_string(&id)?;
+        if orthologs.extract::<&str>().is_ok() || orthologs.extract::<i64>().is_ok() {
+            pairs.push((id, inputs::analyte_to_string(&orthologs)?));
+        } else {
+            for ortholog in orthologs.try_iter()? {
+                pairs.push((id.clone(), inputs::analyte_to_string(&ortholog?)?));
+            }
+        }
+    }
+    Ok((pairs, false))
+}
+
+/// Pairs of orthologs, shared between the maps built from a downloaded table
+type Pairs = Arc<Vec<(String, String)>>;
+
+/// Whether `content` starts with the header of an HCOP table, to tell a table from an error page or an empty file
+fn is_hcop_table(content: &str) -> bool {
+    content
+        .lines()
+        .next()
+        .is_some_and(|x| x.split('\t').any(|x| x == "human_symbol"))
+}
+
+/// Downloads the HCOP table at `url`, with the retries of [`inputs::download`]
+fn download_hcop_table(url: &str) -> Result<String, String> {
+    let content = inputs::download(url)?;
+    if !is_hcop_table(&content) {
+        return Err("The download is not an HCOP table.".to_owned());
+    }
+    Ok(content)
+}
+
+/// Content of the HCOP table at `url`. With a `cache_dir` in the defaults, the table is only downloaded if it is not already
+/// in the cache, and is saved there for later sessions, with its SHA-256 in a `.sha256` file next to it. A cached table that
+/// does not match its SHA-256, like one cut short when it was saved, is downloaded again.
+fn hcop_table(url: &str) -> Result<String, String> {
+    let Some(cache_dir) = config::defaults().cache_dir else {
+        return download_hcop_table(url);
+    };
+    // the download is decompressed, so the cached copy is plain text
+    let name = url
+        .rsplit('/')
+        .next()
+        .unwrap_or(url)
+        .trim_end_matches(".gz");
+    let path = format!("{}/{}", cache_dir.trim_end_matches('/'), name);
+    let checksum_path = format!("{}.sha256", path);
+    if let (Ok(content), Ok(checksum)) =
+        (inputs::read_file(&path), inputs::read_file(&checksum_path))
+    {
+        if format!("{:x}", Sha256::digest(&content)) == checksum.trim() {
+            return Ok(content);
+        }
+    }
+    let content = download_hcop_table(url)?;
+    inputs::create_dir(&cache_dir)
+        .and_then(|_| inputs::write_file(&path, &content))
+        .and_then(|_| {
+            inputs::write_file(&checksum_path, &format!("{:x}", Sha256::digest(&content)))
+        })
+        .map_err(|e| format!("Could not save it to the cache at {}. {}", path, e))?;
+    Ok(content)
+}
+
+/// Pairs of human genes and their orthologs in `organism` from HCOP. Tables are read once per session.
+fn hcop_pairs(organism: Organism, id_type: IdType) -> PyResult<Pairs> {
+    static TABLES: Mutex<Vec<(String, Pairs)>> = Mutex::new(Vec::new());
+    let url = organism.hcop_url();
+    let key = format!("{}{}", url, id_type.column_suffix());
+    if let Some((_, pairs)) = TABLES.lock().unwrap().iter().find(|(x, _)| *x == key) {
+        return Ok(pairs.clone());
+    }
+    let pairs = hcop_table(&url)
+        .and_then(|x| parse_ortholog_table(&x, id_type))
+        .map(|(pairs, _)| Arc::new(pairs))
+        .map_err(|e| {
+            PyValueError::new_err(format!(
+                "Error when reading ortholog table at: {}. {}",
+                url, e
+            ))
+        })?;
+    TABLES.lock().unwrap().push((key, pairs.clone()));
+    Ok(pairs)
+}
+
+/// Maps the identifiers of one organism to their orthologs in another
+pub struct OrthologMap {
+    orthologs: FxHashMap<String, Vec<String>>,
+}
+
+impl OrthologMap {
+    /// Map from `(from, to)` pairs. Identifiers are normalized like the inputs of an analysis, so they match the same way.
+    fn new<'a>(pairs: impl Iterator<Item = (&'a String, &'a String)>) -> OrthologMap {
+        let normalizer = ids::Normalizer::from_defaults();
+        let mut orthologs: FxHashMap<String, Vec<String>> = FxHashMap::default();
+        for (from, to) in pairs {
+            let targets = orthologs.entry(normalizer.id(from.clone())).or_default();
+            if !targets.contains(to) {
+                targets.push(to.clone());
+            }
+        }
+        OrthologMap { orthologs }
+    }
+
+    /// Orthologs of `id`, in the order of the table
+    pub fn orthologs(&self, id: &str) -> &[String] {
+        self.orthologs.get(id).map_or(&[], |x| x.as_slice())
+    }
+
+    /// Map through human, for two organisms that are not human
+    fn then(&self, other: &OrthologMap) -> OrthologMap {
+        let normalizer = ids::Normalizer::from_defaults();
+        let orthologs = self
+            .orthologs
+            .iter()
+            .map(|(id, targets)| {
+                let mut seen: FxHashSet<&String> = FxHashSet::default();
+                let targets = targets
+                    .iter()
+                    .flat_map(|x| other.orthologs(&normalizer.id(x.clone())))
+                    .filter(|x| seen.insert(x))
+                    .cloned()
+                    .collect();
+                (id.clone(), targets)
+            })
+            .collect();
+        OrthologMap { orthologs }
+    }
+
+    /// Orthologs of each of `ids`, in their order and without duplicates, and the number of `ids` without orthologs
+    pub fn map_ids(&self, ids: &[String]) -> (Vec<String>, usize) {
+        let mut seen: FxHashSet<&String> = FxHashSet::default();
+        let mut mapped: Vec<String> = Vec::new();
+        let mut unmapped = 0;
+        for id in ids {
+            let orthologs = self.orthologs(id);
+            if orthologs.is_empty() {
+                unmapped += 1;
+            }
+            mapped.extend(orthologs.iter().filter(|x| seen.insert(x)).cloned());
+        }
+        (mapped, unmapped)
+    }
+}
+
+/// Ortholog map from `from` to `to`, from `table` if provided, or else from the HCOP tables, through human if neither organism is human.
+/// Tables in the HCOP format have the human genes first, so they are read backwards when mapping to human.
+pub fn ortholog_map(
+    from: Organism,
+    to: Organism,
+    table: Option<&Bound<'_, PyAny>>,
+    id_type: IdType,
+) -> PyResult<OrthologMap> {
+    if let Some(table) = table {
+        let (pairs, hcop) = ortholog_pairs(table, id_type)?;
+        if hcop && to == Organism::Human {
+            return Ok(OrthologMap::new(pairs.iter().map(|(a, b)| (b, a))));
+        }
+        return Ok(OrthologMap::new(pairs.iter().map(|(a, b)| (a, b))));
+    }
+    let from_human = |organism: Organism| -> PyResult<OrthologMap> {
+        let pairs = hcop_pairs(organism, id_type)?;
+        Ok(OrthologMap::new(pairs.iter().map(|(a, b)| (a, b))))
+    };
+    let to_human = |organism: Organism| -> PyResult<OrthologMap> {
+        let pairs = hcop_pairs(organism, id_type)?;
+        Ok(OrthologMap::new(pairs.iter().map(|(a, b)| (b, a))))
+    };
+    match (from, to) {
+        (Organism::Human, to) => from_human(to),
+        (from, Organism::Human) => to_human(from),
+        (from, to) => Ok(to_human(from)?.then(&from_human(to)?)),
+    }
+}
+
+/// Map analytes to their orthologs in another organism, like to analyze a mouse gene list against human pathways.
+///
+/// By default, the orthologs come from the tables of [HCOP](https://www.genenames.org/tools/hcop/), which combine the predictions of
+/// many orthology resources between human and each organism. Each table is downloaded the first time it is needed, which requires fsspec
+/// (`pip install webgestaltpy[cloud]`), and kept for the rest of the session. If a `cache_dir` is set with [`set_defaults`](./set_defaults.md),
+/// downloaded tables are saved there with their SHA-256, and read from there in later sessions if they still match it.
+///
+/// Failed or incomplete downloads are tried again up to 4 times, waiting 1, 2, then 4 seconds. Behind a firewall, set the `proxy` of
+/// [`set_defaults`](./set_defaults.md), or the `HTTPS_PROXY` environment variable. Between two organisms that are not human, analytes are mapped
+/// through their human orthologs. An analyte with more than one ortholog is mapped to all of them.
+///
+/// # Parameters
+/// - `ids` - The analytes in any form accepted by the `analyte_list` of [`ora`](./ora.md)
+/// - `from_organism` - The organism of `ids`. One of `"human"`, `"mouse"`, `"rat"`, `"zebrafish"`, `"fly"`, `"worm"`, or `"yeast"`
+/// - `to_organism` - The organism to map to, like `from_organism`
+/// - `id_type` - The type of identifiers of `ids` and of the orthologs in the HCOP tables. One of `"symbol"`, `"entrez"`, or `"ensembl"`
+///   (default: `"symbol"`)
+/// - `table` - If provided, the ortholog table to use instead of HCOP, as a path or URL to the table, a file-like object, or a dictionary
+///   mapping each identifier to its ortholog or a list of them. A table in the HCOP format, like a downloaded copy for offline use, is read from
+///   the columns of `id_type`, with the human genes in the first of them. Any other table has an identifier of `from_organism` in the first column and its ortholog in the second, separated by a tab,
+///   and lines starting with `#` are skipped (default: `None`)
+/// - `as_dict` - If `True`, returns a dictionary mapping each analyte to the list of its orthologs instead (default: `False`)
+///
+/// # Returns
+///
+/// Returns the list of the orthologs of `ids` in their order, without duplicates, which can be passed to any analysis. With `as_dict=True`, returns
+/// a dictionary mapping each analyte to the list of its orthologs, which is empty for analytes without orthologs.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, if an organism or `id_type` is unknown, or if an HCOP table can not be
+/// downloaded.
+///
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if some analytes have no ortholog.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// human = webgestaltpy.map_orthologs("mouse_genelist.txt", "mouse", "human")
+/// res = webgestaltpy.ora("kegg.gmt", human, webgestaltpy.map_orthologs("mouse_reference.txt", "mouse", "human"))
+/// ```
+#[pyfunction]
+#[pyo3(signature = (ids, from_organism, to_organism, id_type="symbol", table=None, as_dict=false))]
+pub fn map_orthologs(
+    py: Python<'_>,
+    ids: &Bound<'_, PyAny>,
+    from_organism: &str,
+    to_organism: &str,
+    id_type: &str,
+    table: Option<&Bound<'_, PyAny>>,
+    as_dict: bool,
+) -> PyResult<PyObject> {
+    let from = Organism::parse(from_organism)?;
+    let to = Organism::parse(to_organism)?;
+    let id_type = IdType::parse(id_type)?;
+    let ids = inputs::analyte_list(ids)?;
+    let map = if from == to && table.is_none() {
+        OrthologMap::new(ids.iter().map(|x| (x, x)))
+    } else {
+        ortholog_map(from, to, table, id_type)?
+    };
+    let (mapped, unmapped) = map.map_ids(&ids);
+    warnings::warn_left_out(
+        py,
+        unmapped,
+        ids.len(),
+        "analytes",
+        &format!("have no ortholog in {}", to.name()),
+    )?;
+    if as_dict {
+        let dict = PyDict::new(py);
+        for id in ids.iter() {
+            dict.set_item(id, map.orthologs(id))?;
+        }
+        return dict.into_py_any(py);
+    }
+    mapped.into_py_any(py)
+}
@@ -0,0 +1,368 @@
+use crate::gsea_stats::{running_sum, SortedRankList};
+use crate::inputs;
+use crate::report::escape_html;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::FxHashSet;
+use std::fmt::Write;
+use webgestalt_lib::methods::gsea::RankListItem;
+use webgestalt_lib::readers::utils::Item;
+
+/// Width of a plot in pixels
+const WIDTH: f64 = 600.0;
+/// Left edge of the plotting area
+const LEFT: f64 = 60.0;
+/// Right edge of the plotting area
+const RIGHT: f64 = 580.0;
+
+/// Get the running enrichment score of each set along a rank list, for plotting with [`write_gsea_plots`](./write_gsea_plots.md).
+///
+/// # Parameters
+/// - `gmt` - The GMT used for the analysis in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_file` - The rank list of the analysis in any form accepted by [`gsea`](./gsea.md)
+/// - `sets` - If provided, a list of the names of the sets to get curves for. Otherwise, gets every set (default: `None`)
+///
+/// # Returns
+///
+/// Returns a dictionary with the `analytes` and their `ranks`, sorted from highest to lowest rank like in the analysis,
+/// and `sets`, a dictionary mapping each set to a dictionary with its `running_sum` (the running enrichment score at every position
+/// of the rank list) and `hits` (the positions of the analytes of the set).
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// curves = webgestaltpy.gsea_curves("data/kegg.gmt", "data/test.rnk", sets=["hsa00010"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_file, sets=None))]
+pub fn gsea_curves<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_file: &Bound<'_, PyAny>,
+    sets: Option<Vec<String>>,
+) -> PyResult<Bound<'a, PyDict>> {
+    let mut rank_list: Vec<RankListItem> = inputs::rank_list(rank_file)?;
+    let sorted = SortedRankList::new(&rank_list);
+    // the same stable sort as SortedRankList, so the order matches
+    rank_list.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+    let wanted: Option<FxHashSet<String>> = sets.map(|x| x.into_iter().collect());
+    let items: Vec<Item> = inputs::gmt(gmt)?
+        .into_iter()
+        .filter(|x| wanted.as_ref().is_none_or(|w| w.contains(&x.id)))
+        .collect();
+    let set_curves = PyDict::new(py);
+    for item in items {
+        let members: FxHashSet<&String> = item.parts.iter().collect();
+        let hits: Vec<usize> = sorted
+            .analytes
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| members.contains(x))
+            .map(|(i, _)| i)
+            .collect();
+        let curve = PyDict::new(py);
+        curve.set_item("running_sum", running_sum(&sorted, &item))?;
+        curve.set_item("hits", hits)?;
+        set_curves.set_item(item.id, curve)?;
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("analytes", sorted.analytes)?;
+    dict.set_item(
+        "ranks",
+        rank_list.iter().map(|x| x.rank).collect::<Vec<f64>>(),
+    )?;
+    dict.set_item("sets", set_curves)?;
+    Ok(dict)
+}
+
+/// Horizontal position of index `i` of `n`
+fn x_position(i: usize, n: usize) -> f64 {
+    LEFT + (RIGHT - LEFT) * i as f64 / (n.max(2) - 1) as f64
+}
+
+/// Indices of `values` to draw, keeping the minimum and maximum of every pixel so peaks are not lost
+fn downsample(values: &[f64]) -> Vec<usize> {
+    let buckets = (RIGHT - LEFT) as usize;
+    if values.len() <= 2 * buckets {
+        return (0..values.len()).collect();
+    }
+    let mut indices: Vec<usize> = Vec::with_capacity(2 * buckets + 2);
+    indices.push(0);
+    for b in 0..buckets {
+        let range = (b * values.len() / buckets)..((b + 1) * values.len() / buckets);
+        let (mut low, mut high) = (range.start, range.start);
+        for i in range {
+            if values[i] < values[low] {
+                low = i;
+            }
+            if values[i] > values[high] {
+                high = i;
+            }
+        }
+        if low.min(high) > 0 {
+            indices.push(low.min(high));
+        }
+        if low != high {
+            indices.push(low.max(high));
+        }
+    }
+    indices.push(values.len() - 1);
+    indices
+}
+
+/// Formats a number for a plot label
+fn label(x: f64) -> String {
+    if x != 0.0 && x.abs() < 0.001 {
+        format!("{:.2e}", x)
+    } else {
+        format!("{:.3}", x)
+    }
+}
+
+/// Enrichment plot of a set like GSEA desktop: the running enrichment score, the positions of the analytes of the set,
+/// and the ranked list
+fn enrichment_plot(
+    set: &str,
+    subtitle: &str,
+    sums: &[f64],
+    hits: &[usize],
+    ranks: &[f64],
+) -> String {
+    let n = sums.len();
+    let (es_top, es_bottom) = (60.0, 250.0);
+    let max_es = sums.iter().copied().fold(0.0_f64, f64::max);
+    let min_es = sums.iter().copied().fold(0.0_f64, f64::min);
+    let es_range = (max_es - min_es).max(f64::EPSILON);
+    let es_y = |x: f64| es_bottom - (x - min_es) / es_range * (es_bottom - es_top);
+    let (rank_top, rank_bottom) = (300.0, 420.0);
+    let max_rank = ranks.iter().copied().fold(0.0_f64, |a, b| a.max(b.abs()));
+    let max_rank = if max_rank > 0.0 { max_rank } else { 1.0 };
+    let rank_mid = (rank_top + rank_bottom) / 2.0;
+    let rank_y = |x: f64| rank_mid - x / max_rank * (rank_bottom - rank_top) / 2.0;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="450" font-family="sans-serif" font-size="11">"#,
+        WIDTH
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="22" text-anchor="middle" font-size="14" font-weight="bold">{}</text>"#,
+        WIDTH / 2.0,
+        escape_html(set)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="40" text-anchor="middle">{}</text>"#,
+        WIDTH / 2.0,
+        escape_html(subtitle)
+    );
+    // running enrichment score
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#888"/>"##,
+        LEFT,
+        es_top,
+        RIGHT - LEFT,
+        es_bottom - es_top
+    );
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{}" x2="{}" y1="{y:.2}" y2="{y:.2}" stroke="#888" stroke-dasharray="3,3"/>"##,
+        LEFT,
+        RIGHT,
+        y = es_y(0.0)
+    );
+    let points: Vec<String> = downsample(sums)
+        .into_iter()
+        .map(|i| format!("{:.2},{:.2}", x_position(i, n), es_y(sums[i])))
+        .collect();
+    let _ = writeln!(
+        svg,
+        r##"<polyline points="{}" fill="none" stroke="#2ca02c" stroke-width="2"/>"##,
+        points.join(" ")
+    );
+    for value in [max_es, 0.0, min_es] {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{:.2}" text-anchor="end" dy="4">{}</text>"#,
+            LEFT - 4.0,
+            es_y(value),
+            label(value)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="16" y="{}" text-anchor="middle" transform="rotate(-90 16 {})">Enrichment score</text>"#,
+        (es_top + es_bottom) / 2.0,
+        (es_top + es_bottom) / 2.0
+    );
+    // positions of the analytes of the set
+    let mut ticks = String::new();
+    for i in hits {
+        let x = x_position(*i, n);
+        let _ = write!(ticks, "M{:.2} 258V290", x);
+    }
+    let _ = writeln!(
+        svg,
+        r#"<path d="{}" stroke="black" stroke-width="0.6"/>"#,
+        ticks
+    );
+    // ranked list
+    let mut area = format!("M{} {:.2}", LEFT, rank_mid);
+    for i in downsample(ranks) {
+        let _ = write!(area, "L{:.2} {:.2}", x_position(i, n), rank_y(ranks[i]));
+    }
+    let _ = write!(area, "L{} {:.2}Z", RIGHT, rank_mid);
+    let _ = writeln!(svg, r##"<path d="{}" fill="#999"/>"##, area);
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#888"/>"##,
+        LEFT,
+        rank_top,
+        RIGHT - LEFT,
+        rank_bottom - rank_top
+    );
+    if let Some(zero) = ranks.iter().position(|x| *x <= 0.0) {
+        let x = x_position(zero, n);
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x:.2}" x2="{x:.2}" y1="{}" y2="{}" stroke="#555" stroke-dasharray="3,3"/>"##,
+            rank_top, rank_bottom
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.2}" y="{}" dx="3">Zero cross at {}</text>"#,
+            x,
+            rank_top + 12.0,
+            zero
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="16" y="{}" text-anchor="middle" transform="rotate(-90 16 {})">Ranked list metric</text>"#,
+        rank_mid, rank_mid
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="440" text-anchor="middle">Rank in ordered list ({} analytes)</text>"#,
+        (LEFT + RIGHT) / 2.0,
+        n
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Gets `key` from `dict`, with an error if it is missing
+fn get<'a>(dict: &Bound<'a, PyDict>, key: &str) -> PyResult<Bound<'a, PyAny>> {
+    dict.get_item(key)?
+        .ok_or_else(|| PyValueError::new_err(format!("Missing '{}'!", key)))
+}
+
+/// File name of the plot of `set`, like the `enplot_` files of GSEA desktop
+fn plot_file_name(set: &str) -> String {
+    let name: String = set
+        .chars()
+        .map(|x| {
+            if x.is_ascii_alphanumeric() || x == '-' || x == '.' {
+                x
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("enplot_{}.svg", name)
+}
+
+/// Write an SVG enrichment plot for each of the top sets of a GSEA, like the `enplot` files of GSEA desktop.
+///
+/// Each plot has the running enrichment score of the set, the positions of the analytes of the set in the rank list,
+/// and the ranked list metric.
+///
+/// # Parameters
+/// - `results` - List of dictionaries of the results of [`gsea`](./gsea.md)
+/// - `curves` - Curves from [`gsea_curves`](./gsea_curves.md) with the same GMT and rank list
+/// - `out_dir` - `String` of the path of the directory to write the plots to. It is created if it does not exist.
+/// - `top_n` - Number of sets to plot, in order of FDR and then p-value (default: `20`)
+///
+/// Sets without a curve in `curves` are skipped.
+///
+/// # Returns
+///
+/// Returns a dictionary mapping each plotted set to the path of its plot, named `enplot_<set>.svg`. The paths can be passed as `plots`
+/// to [`enrichment_map_html`](./enrichment_map_html.md) to link them from the enrichment map.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if a result or curve is malformed, or if a plot can not be written.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// curves = webgestaltpy.gsea_curves("data/kegg.gmt", "data/test.rnk")
+/// plots = webgestaltpy.write_gsea_plots(res, curves, "plots", top_n=10)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, curves, out_dir, top_n=20))]
+pub fn write_gsea_plots<'a>(
+    py: Python<'a>,
+    results: Vec<Bound<'_, PyDict>>,
+    curves: &Bound<'_, PyDict>,
+    out_dir: String,
+    top_n: usize,
+) -> PyResult<Bound<'a, PyDict>> {
+    let ranks: Vec<f64> = get(curves, "ranks")?.extract()?;
+    let set_curves = get(curves, "sets")?.downcast_into::<PyDict>()?;
+    let mut rows: Vec<(String, f64, f64, f64)> = Vec::new();
+    for row in results {
+        let set: String = get(&row, "set")?.extract()?;
+        if set_curves.contains(&set)? {
+            rows.push((
+                set,
+                get(&row, "fdr")?.extract()?,
+                get(&row, "p")?.extract()?,
+                get(&row, "nes")?.extract()?,
+            ));
+        }
+    }
+    // NaN values, like from results read with pandas, sort last
+    rows.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)));
+    inputs::create_dir(&out_dir).map_err(|e| {
+        PyValueError::new_err(format!(
+            "Error when creating directory at: {}. {}",
+            out_dir, e
+        ))
+    })?;
+    let paths = PyDict::new(py);
+    for (set, fdr, p, nes) in rows.into_iter().take(top_n) {
+        let curve = get(&set_curves, &set)?.downcast_into::<PyDict>()?;
+        let sums: Vec<f64> = get(&curve, "running_sum")?.extract()?;
+        let hits: Vec<usize> = get(&curve, "hits")?.extract()?;
+        if sums.len() != ranks.len() {
+            return Err(PyValueError::new_err(format!(
+                "Curve of '{}' does not match the length of the rank list!",
+                set
+            )));
+        }
+        let subtitle = format!(
+            "NES: {}   p: {}   FDR: {}",
+            label(nes),
+            label(p),
+            label(fdr)
+        );
+        let svg = enrichment_plot(&set, &subtitle, &sums, &hits, &ranks);
+        let path = format!("{}/{}", out_dir.trim_end_matches('/'), plot_file_name(&set));
+        inputs::write_file(&path, &svg).map_err(|e| {
+            PyValueError::new_err(format!("Error when writing plot at: {}. {}", path, e))
+        })?;
+        paths.set_item(set, path)?;
+    }
+    Ok(paths)
+}
@@ -0,0 +1,320 @@
+use crate::inputs;
+use crate::meta::average_ranks;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// A column of a table, selected by its position or by its name in the header
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    pub fn extract(obj: &Bound<'_, PyAny>) -> PyResult<Column> {
+        if let Ok(index) = obj.extract::<usize>() {
+            Ok(Column::Index(index))
+        } else if let Ok(name) = obj.extract::<String>() {
+            Ok(Column::Name(name))
+        } else {
+            Err(PyValueError::new_err(
+                "Column must be a position or a column name!",
+            ))
+        }
+    }
+
+    /// Position of the column in `header`
+    fn position(&self, header: &[&str]) -> Result<usize, String> {
+        match self {
+            Column::Index(index) => Ok(*index),
+            Column::Name(name) => header
+                .iter()
+                .position(|x| x.trim() == name)
+                .ok_or_else(|| format!("Column '{}' not found in the header.", name)),
+        }
+    }
+}
+
+/// How the values of the rank column are turned into ranks
+pub enum Transform {
+    /// The values are used as they are
+    None,
+    /// `-log10(p) * sign(x)` of p-values and the values of a sign column, like fold changes
+    SignedLogP,
+    /// Average ranks of the values, centered at `0`
+    Rank,
+    /// Z-scores of the values
+    ZScore,
+}
+
+impl Transform {
+    pub fn parse(name: &str) -> PyResult<Transform> {
+        match name {
+            "none" => Ok(Transform::None),
+            "signed_log_p" => Ok(Transform::SignedLogP),
+            "rank" => Ok(Transform::Rank),
+            "zscore" => Ok(Transform::ZScore),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown transform '{}'. Expected 'none', 'signed_log_p', 'rank', or 'zscore'.",
+                name
+            ))),
+        }
+    }
+
+    /// Transforms `values`, with the values of the sign column in `signs` for [`Transform::SignedLogP`]
+    pub fn apply(&self, values: &[f64], signs: &[f64]) -> Result<Vec<f64>, String> {
+        match self {
+            Transform::None => Ok(values.to_vec()),
+            Transform::SignedLogP => {
+                if let Some(p) = values.iter().find(|x| !(0.0..=1.0).contains(*x)) {
+                    return Err(format!(
+                        "p-values must be between 0 and 1, but one is {}.",
+                        p
+                    ));
+                }
+                // p-values of 0 get the smallest p-value above 0, so their ranks stay finite
+                let min_p = values
+                    .iter()
+                    .copied()
+                    .filter(|x| *x > 0.0)
+                    .reduce(f64::min)
+                    .unwrap_or(f64::MIN_POSITIVE);
+                Ok(values
+                    .iter()
+                    .zip(signs)
+                    .map(|(p, sign)| {
+                        let score = -p.max(min_p).log10();
+                        if *sign < 0.0 {
+                            -score
+                        } else if *sign > 0.0 {
+                            score
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect())
+            }
+            Transform::Rank => {
+                let center = (values.len() as f64 + 1.0) / 2.0;
+                Ok(average_ranks(values)
+                    .into_iter()
+                    .map(|x| x - center)
+                    .collect())
+            }
+            Transform::ZScore => {
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let sd =
+                    (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+                if sd.is_nan() || sd == 0.0 {
+                    return Err(
+                        "Can not compute z-scores without at least two distinct values.".to_owned(),
+                    );
+                }
+                Ok(values.iter().map(|x| (x - mean) / sd).collect())
+            }
+        }
+    }
+}
+
+/// Whether a value of a table is missing
+fn is_missing(value: &str) -> bool {
+    matches!(value, "" | "NA" | "NaN" | "nan")
+}
+
+/// Table with an analyte on each row and any number of columns, separated by tabs, or by commas if the first line has no tabs.
+/// Returns each analyte with the values of `value_columns`. If any column is selected by name, the first line is the header.
+/// Rows with a missing value are skipped.
+pub fn parse_rank_table(
+    content: &str,
+    analyte_column: &Column,
+    value_columns: &[&Column],
+) -> Result<Vec<(String, Vec<f64>)>, String> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, x)| !x.is_empty())
+        .peekable();
+    let delimiter = match lines.peek() {
+        Some((_, first)) if !first.contains('\t') && first.contains(',') => ',',
+        _ => '\t',
+    };
+    let has_header = std::iter::once(analyte_column)
+        .chain(value_columns.iter().copied())
+        .any(|x| matches!(x, Column::Name(_)));
+    let header: Vec<&str> = if has_header {
+        lines
+            .next()
+            .map(|(_, x)| x.split(delimiter).collect())
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let analyte_position = analyte_column.position(&header)?;
+    let value_positions: Vec<usize> = value_columns
+        .iter()
+        .map(|x| x.position(&header))
+        .collect::<Result<_, String>>()?;
+    let mut rows: Vec<(String, Vec<f64>)> = Vec::new();
+    'lines: for (i, line) in lines {
+        let columns: Vec<&str> = line.split(delimiter).collect();
+        let get = |position: usize| {
+            columns
+                .get(position)
+                .map(|x| x.trim())
+                .ok_or_else(|| format!("Line {} has no column {}.", i + 1, position))
+        };
+        let mut values: Vec<f64> = Vec::with_capacity(value_positions.len());
+        for position in value_positions.iter() {
+            let value = get(*position)?;
+            if is_missing(value) {
+                continue 'lines;
+            }
+            values.push(
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("Could not parse value '{}' on line {}.", value, i + 1))?,
+            );
+        }
+        rows.push((get(analyte_position)?.to_owned(), values));
+    }
+    Ok(rows)
+}
+
+/// Gets the values of `column` of a DataFrame, selecting it by position in `columns` or by name
+fn frame_column<'a>(obj: &Bound<'a, PyAny>, column: &Column) -> PyResult<Vec<Bound<'a, PyAny>>> {
+    match column {
+        Column::Name(name) => inputs::column(obj, name),
+        Column::Index(index) => {
+            let name = obj
+                .getattr("columns")?
+                .get_item(*index)
+                .map_err(|_| PyValueError::new_err(format!("Column {} not found!", index)))?;
+            inputs::column(obj, &name.str()?.to_string())
+        }
+    }
+}
+
+/// Read a rank list from a table with several statistics for each analyte, like the output of a differential expression analysis,
+/// by selecting the column with the analytes and the column that ranks them.
+///
+/// # Parameters
+/// - `path` - `String` of the path to the table, a file-like object, or a DataFrame with a row for each analyte.
+///   Files are separated by tabs, or by commas if the first line has no tabs.
+/// - `rank_column` - The column that ranks the analytes, like a log2 fold change or a test statistic, as a `String` of its name or
+///   an `int` of its position, starting at `0` (default: `1`)
+/// - `analyte_column` - The column with the analytes, as a name or a position (default: `0`)
+/// - `transform` - How the values of `rank_column` are turned into ranks (default: `"none"`):
+///     - `"none"` - The values are used as they are
+///     - `"signed_log_p"` - `rank_column` has p-values, and the rank is `-log10(p) * sign(x)` with `x` from `sign_column`, like a fold change.
+///       p-values of `0` are raised to the smallest p-value above `0`, so the ranks stay finite.
+///     - `"rank"` - The ranks of the values, with tied values sharing their average rank, centered at `0`
+///     - `"zscore"` - The z-scores of the values, with the sample standard deviation
+/// - `sign_column` - The column with the sign of each analyte for `transform="signed_log_p"`, as a name or a position (default: `None`)
+/// - `is_content` - If `True`, `path` is the text of the table instead of its path (default: `False`)
+///
+/// If either column of a file is selected by name, the first line is the header. Otherwise, every line is read as a row.
+/// Rows with a missing value (empty, `NA`, or `NaN`) in `rank_column` or `sign_column` are skipped before the transform.
+///
+/// # Returns
+///
+/// Returns a list of `(analyte, rank)` tuples, which can be passed to any function that takes a rank list, like [`gsea`](./gsea.md).
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the table can not be read, if a column is not found, or if a value is not a number.
+/// Also raises a `ValueError` if `sign_column` is missing for `transform="signed_log_p"` or provided for another transform,
+/// if a p-value is not between `0` and `1`, or if the values for `transform="zscore"` do not have at least two distinct values.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// # a table with the columns gene, log2FC, p, and stat
+/// ranks = webgestaltpy.read_rank_file("deseq2_results.tsv", rank_column="stat", analyte_column="gene")
+/// res = webgestaltpy.gsea("data/kegg.gmt", ranks)
+///
+/// # ranks from the p-values, in the direction of the fold changes
+/// ranks = webgestaltpy.read_rank_file(
+///     "deseq2_results.tsv", rank_column="p", analyte_column="gene", transform="signed_log_p", sign_column="log2FC"
+/// )
+/// ```
+#[pyfunction]
+#[pyo3(signature = (path, rank_column=None, analyte_column=None, transform="none", sign_column=None, is_content=false))]
+pub fn read_rank_file(
+    path: &Bound<'_, PyAny>,
+    rank_column: Option<&Bound<'_, PyAny>>,
+    analyte_column: Option<&Bound<'_, PyAny>>,
+    transform: &str,
+    sign_column: Option<&Bound<'_, PyAny>>,
+    is_content: bool,
+) -> PyResult<Vec<(String, f64)>> {
+    let transform = Transform::parse(transform)?;
+    let sign_column = sign_column.map(Column::extract).transpose()?;
+    match (&transform, &sign_column) {
+        (Transform::SignedLogP, None) => {
+            return Err(PyValueError::new_err(
+                "sign_column must be provided for transform 'signed_log_p'!",
+            ))
+        }
+        (Transform::SignedLogP, Some(_)) | (_, None) => {}
+        (_, Some(_)) => {
+            return Err(PyValueError::new_err(
+                "sign_column can only be used with transform 'signed_log_p'!",
+            ))
+        }
+    }
+    let rank_column = rank_column
+        .map(Column::extract)
+        .transpose()?
+        .unwrap_or(Column::Index(1));
+    let analyte_column = analyte_column
+        .map(Column::extract)
+        .transpose()?
+        .unwrap_or(Column::Index(0));
+    let mut value_columns: Vec<&Column> = vec![&rank_column];
+    value_columns.extend(sign_column.as_ref());
+    let path = inputs::content(path, is_content)?;
+    let rows = match inputs::parse_text_input(&path, "rank file", |x| {
+        parse_rank_table(x, &analyte_column, &value_columns)
+    })? {
+        Some(rows) => rows,
+        None => frame_rows(&path, &analyte_column, &value_columns)?,
+    };
+    let values: Vec<f64> = rows.iter().map(|x| x.1[0]).collect();
+    let signs: Vec<f64> = rows.iter().map(|x| *x.1.last().unwrap()).collect();
+    let ranks = transform
+        .apply(&values, &signs)
+        .map_err(|e| PyValueError::new_err(format!("Error when transforming ranks. {}", e)))?;
+    Ok(rows.into_iter().map(|x| x.0).zip(ranks).collect())
+}
+
+/// [`parse_rank_table`] for a DataFrame
+fn frame_rows(
+    obj: &Bound<'_, PyAny>,
+    analyte_column: &Column,
+    value_columns: &[&Column],
+) -> PyResult<Vec<(String, Vec<f64>)>> {
+    if !obj.hasattr("columns")? {
+        return Err(PyValueError::new_err(
+            "Rank file must be a path, a file-like object, or a DataFrame!",
+        ));
+    }
+    let analytes = frame_column(obj, analyte_column)?;
+    let columns: Vec<Vec<Bound<'_, PyAny>>> = value_columns
+        .iter()
+        .map(|x| frame_column(obj, x))
+        .collect::<PyResult<_>>()?;
+    let mut rows: Vec<(String, Vec<f64>)> = Vec::with_capacity(analytes.len());
+    'rows: for (i, analyte) in analytes.into_iter().enumerate() {
+        let mut values: Vec<f64> = Vec::with_capacity(columns.len());
+        for column in columns.iter() {
+            match column[i].extract::<Option<f64>>()? {
+                Some(value) if !value.is_nan() => values.push(value),
+                _ => continue 'rows,
+            }
+        }
+        rows.push((inputs::analyte_to_string(&analyte)?, values));
+    }
+    Ok(rows)
+}
@@ -0,0 +1,487 @@
+use crate::{
+    config, gsea_result_to_dict, gsea_stats, inputs, ora_results_with_tables, ora_stats, RowOptions,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::methods::gsea::{GSEAResult, RankListItem};
+use webgestalt_lib::methods::ora::{get_ora, ORAResult};
+
+/// A genomic region from a BED file, with its score if it has one
+pub struct Region {
+    pub chrom: String,
+    pub start: i64,
+    pub end: i64,
+    pub score: Option<f64>,
+}
+
+/// Transcription start site of a gene
+pub struct Tss {
+    pub gene: String,
+    pub chrom: String,
+    pub position: i64,
+    /// `true` for genes on the minus strand, where upstream is towards higher positions
+    pub reverse: bool,
+}
+
+/// Regions in BED format: the chromosome, start, and end separated by tabs, with an optional name and score.
+/// Header lines starting with `track`, `browser`, or `#` are skipped.
+pub fn parse_bed(content: &str) -> Result<Vec<Region>, String> {
+    let mut regions: Vec<Region> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() < 3 {
+            return Err(format!("Line {} has fewer than three columns.", i + 1));
+        }
+        let position = |x: &str| {
+            x.parse::<i64>()
+                .map_err(|_| format!("Could not parse position '{}' on line {}.", x, i + 1))
+        };
+        let score = match columns.get(4) {
+            Some(x) => Some(
+                x.parse::<f64>()
+                    .map_err(|_| format!("Could not parse score '{}' on line {}.", x, i + 1))?,
+            ),
+            None => None,
+        };
+        regions.push(Region {
+            chrom: columns[0].to_owned(),
+            start: position(columns[1])?,
+            end: position(columns[2])?,
+            score,
+        });
+    }
+    Ok(regions)
+}
+
+/// Gene annotation with the gene, chromosome, TSS position, and strand (`+` or `-`) separated by tabs
+pub fn parse_annotation(content: &str) -> Result<Vec<Tss>, String> {
+    let mut genes: Vec<Tss> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() < 4 {
+            return Err(format!("Line {} has fewer than four columns.", i + 1));
+        }
+        genes.push(Tss {
+            gene: columns[0].to_owned(),
+            chrom: columns[1].to_owned(),
+            position: columns[2].parse::<i64>().map_err(|_| {
+                format!(
+                    "Could not parse position '{}' on line {}.",
+                    columns[2],
+                    i + 1
+                )
+            })?,
+            reverse: columns[3] == "-",
+        });
+    }
+    Ok(genes)
+}
+
+/// Gets regions from a path to (or file-like object of) a BED file, or a list of
+/// `(chrom, start, end)` or `(chrom, start, end, score)` tuples
+fn read_regions(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Region>> {
+    if let Some(regions) = inputs::parse_text_input(obj, "BED file", parse_bed)? {
+        return Ok(regions);
+    }
+    obj.try_iter()?
+        .map(|x| {
+            let x = x?;
+            let (chrom, start, end): (String, i64, i64) = match x.len()? {
+                3 => x.extract()?,
+                _ => {
+                    let (chrom, start, end, _): (String, i64, i64, Bound<'_, PyAny>) =
+                        x.extract()?;
+                    (chrom, start, end)
+                }
+            };
+            let score = if x.len()? > 3 {
+                Some(x.get_item(3)?.extract::<f64>()?)
+            } else {
+                None
+            };
+            Ok(Region {
+                chrom,
+                start,
+                end,
+                score,
+            })
+        })
+        .collect()
+}
+
+/// Gets a gene annotation from a path to (or file-like object of) an annotation file, or a list of
+/// `(gene, chrom, tss, strand)` tuples
+fn read_annotation(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Tss>> {
+    if let Some(genes) = inputs::parse_text_input(obj, "gene annotation", parse_annotation)? {
+        return Ok(genes);
+    }
+    obj.try_iter()?
+        .map(|x| {
+            let (gene, chrom, position, strand): (Bound<'_, PyAny>, String, i64, String) =
+                x?.extract()?;
+            Ok(Tss {
+                gene: inputs::analyte_to_string(&gene)?,
+                chrom,
+                position,
+                reverse: strand == "-",
+            })
+        })
+        .collect()
+}
+
+/// How the regulatory domain of each gene is defined, following [GREAT](https://doi.org/10.1038/nbt.1630)
+pub enum DomainRule {
+    /// A basal domain around the TSS, extended up to the basal domains of the neighboring genes
+    BasalPlusExtension { upstream: i64, downstream: i64 },
+    /// From the TSS of the previous gene to the TSS of the next gene
+    TwoNearest,
+    /// From halfway to the TSS of the previous gene to halfway to the TSS of the next gene
+    SingleNearest,
+}
+
+impl DomainRule {
+    pub fn parse(name: &str, upstream: i64, downstream: i64) -> PyResult<DomainRule> {
+        match name {
+            "basal_plus_extension" => Ok(DomainRule::BasalPlusExtension {
+                upstream,
+                downstream,
+            }),
+            "two_nearest" => Ok(DomainRule::TwoNearest),
+            "single_nearest" => Ok(DomainRule::SingleNearest),
+            _ => Err(PyValueError::new_err(format!(
+                "Unknown rule '{}'. Expected 'basal_plus_extension', 'two_nearest', or 'single_nearest'.",
+                name
+            ))),
+        }
+    }
+}
+
+/// Regulatory domains of the genes of one chromosome, sorted by TSS
+struct ChromosomeDomains {
+    tss: Vec<i64>,
+    /// `(gene, start, end)` of each domain, in the order of `tss`
+    domains: Vec<(String, i64, i64)>,
+    /// Largest distance from a TSS to either end of its domain
+    reach: i64,
+}
+
+/// Computes the regulatory domain of every gene, grouped by chromosome
+fn regulatory_domains(
+    genes: Vec<Tss>,
+    rule: &DomainRule,
+    extension: i64,
+) -> FxHashMap<String, ChromosomeDomains> {
+    let mut by_chrom: FxHashMap<String, Vec<Tss>> = FxHashMap::default();
+    for gene in genes {
+        by_chrom.entry(gene.chrom.clone()).or_default().push(gene);
+    }
+    by_chrom
+        .into_iter()
+        .map(|(chrom, mut genes)| {
+            genes.sort_by_key(|x| x.position);
+            let basal: Vec<(i64, i64)> = genes
+                .iter()
+                .map(|x| match rule {
+                    DomainRule::BasalPlusExtension {
+                        upstream,
+                        downstream,
+                    } if x.reverse => (x.position - downstream, x.position + upstream),
+                    DomainRule::BasalPlusExtension {
+                        upstream,
+                        downstream,
+                    } => (x.position - upstream, x.position + downstream),
+                    _ => (x.position, x.position),
+                })
+                .collect();
+            let mut domains: Vec<(String, i64, i64)> = Vec::with_capacity(genes.len());
+            let mut reach = 0;
+            for (i, gene) in genes.iter().enumerate() {
+                let tss = gene.position;
+                let (previous, next) = match rule {
+                    DomainRule::BasalPlusExtension { .. } => (
+                        basal[..i].iter().map(|x| x.1).max(),
+                        basal[i + 1..].iter().map(|x| x.0).min(),
+                    ),
+                    DomainRule::TwoNearest => (
+                        i.checked_sub(1).map(|j| genes[j].position),
+                        genes.get(i + 1).map(|x| x.position),
+                    ),
+                    DomainRule::SingleNearest => (
+                        i.checked_sub(1).map(|j| (genes[j].position + tss) / 2),
+                        genes.get(i + 1).map(|x| (x.position + tss) / 2),
+                    ),
+                };
+                let start = previous
+                    .unwrap_or(i64::MIN)
+                    .max(tss - extension)
+                    .min(basal[i].0)
+                    .max(0);
+                let end = next
+                    .unwrap_or(i64::MAX)
+                    .min(tss + extension)
+                    .max(basal[i].1);
+                reach = reach.max(tss - start).max(end - tss);
+                domains.push((gene.gene.clone(), start, end));
+            }
+            let tss = genes.iter().map(|x| x.position).collect();
+            (
+                chrom,
+                ChromosomeDomains {
+                    tss,
+                    domains,
+                    reach,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Genes whose regulatory domain overlaps each region, in the order of `regions`
+fn map_regions(
+    regions: &[Region],
+    domains: &FxHashMap<String, ChromosomeDomains>,
+) -> Vec<Vec<String>> {
+    regions
+        .par_iter()
+        .map(|region| match domains.get(&region.chrom) {
+            Some(chrom) => {
+                // only genes with a TSS within reach of the region can overlap it
+                let first = chrom
+                    .tss
+                    .partition_point(|x| *x < region.start - chrom.reach);
+                let last = chrom
+                    .tss
+                    .partition_point(|x| *x <= region.end + chrom.reach);
+                chrom.domains[first..last]
+                    .iter()
+                    .filter(|(_, start, end)| *start < region.end && *end > region.start)
+                    .map(|(gene, _, _)| gene.clone())
+                    .collect()
+            }
+            None => Vec::new(),
+        })
+        .collect()
+}
+
+/// Regions, the genes of each region, and every annotated gene
+type MappedRegions = (Vec<Region>, Vec<Vec<String>>, Vec<String>);
+
+/// Reads the inputs of a region analysis and maps every region to genes
+fn region_genes(
+    py: Python<'_>,
+    regions: &Bound<'_, PyAny>,
+    annotation: &Bound<'_, PyAny>,
+    rule: &str,
+    upstream: i64,
+    downstream: i64,
+    extension: i64,
+) -> PyResult<MappedRegions> {
+    let rule = DomainRule::parse(rule, upstream, downstream)?;
+    let regions = read_regions(regions)?;
+    let genes = read_annotation(annotation)?;
+    let all_genes: Vec<String> = genes.iter().map(|x| x.gene.clone()).collect();
+    let mapped = config::install(py, || {
+        let domains = regulatory_domains(genes, &rule, extension);
+        map_regions(&regions, &domains)
+    })?;
+    Ok((regions, mapped, all_genes))
+}
+
+/// Map genomic regions to the genes whose regulatory domains they overlap, like [GREAT](https://doi.org/10.1038/nbt.1630).
+///
+/// # Parameters
+/// - `regions` - `String` of the path to a BED file, a file-like object, or a list of `(chrom, start, end)` tuples
+/// - `annotation` - `String` of the path to a gene annotation file with the gene, chromosome, TSS position, and strand (`+` or `-`) of each gene
+///   separated by tabs, a file-like object, or a list of `(gene, chrom, tss, strand)` tuples
+/// - `rule` - How the regulatory domain of each gene is defined (default: `"basal_plus_extension"`):
+///     - `"basal_plus_extension"` - A basal domain of `upstream` bases before and `downstream` bases after the TSS, extended in both
+///       directions up to the basal domains of the neighboring genes
+///     - `"two_nearest"` - From the TSS of the previous gene to the TSS of the next gene
+///     - `"single_nearest"` - From halfway to the TSS of the previous gene to halfway to the TSS of the next gene
+/// - `upstream` - Bases upstream of the TSS in the basal domain (default: `5000`)
+/// - `downstream` - Bases downstream of the TSS in the basal domain (default: `1000`)
+/// - `extension` - Maximum distance a domain extends from the TSS in each direction (default: `1000000`)
+///
+/// # Returns
+///
+/// Returns a dictionary mapping each gene with at least one region to its number of regions.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// genes = webgestaltpy.regions_to_genes("peaks.bed", "hg38_tss.txt")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (regions, annotation, rule="basal_plus_extension", upstream=5000, downstream=1000, extension=1000000))]
+pub fn regions_to_genes<'a>(
+    py: Python<'a>,
+    regions: &Bound<'_, PyAny>,
+    annotation: &Bound<'_, PyAny>,
+    rule: &str,
+    upstream: i64,
+    downstream: i64,
+    extension: i64,
+) -> PyResult<Bound<'a, PyDict>> {
+    let (_, mapped, _) = region_genes(
+        py, regions, annotation, rule, upstream, downstream, extension,
+    )?;
+    let counts = PyDict::new(py);
+    for gene in mapped.iter().flatten() {
+        let count: usize = match counts.get_item(gene)? {
+            Some(x) => x.extract()?,
+            None => 0,
+        };
+        counts.set_item(gene, count + 1)?;
+    }
+    Ok(counts)
+}
+
+/// Run ORA on the genes near a set of genomic regions, like from ATAC-seq or ChIP-seq peaks.
+///
+/// Regions are mapped to genes like [`regions_to_genes`](./regions_to_genes.md). The analyte list is the genes with at least one region,
+/// and the reference list is every gene in `annotation`.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`ora`](./ora.md)
+/// - `regions` - The regions in any form accepted by [`regions_to_genes`](./regions_to_genes.md)
+/// - `annotation` - The gene annotation in any form accepted by [`regions_to_genes`](./regions_to_genes.md)
+/// - `rule`, `upstream`, `downstream`, `extension` - How regions are mapped to genes. See [`regions_to_genes`](./regions_to_genes.md).
+///
+/// # Returns
+///
+/// Returns the ORA results in the same format as [`ora`](./ora.md).
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.region_ora("data/kegg.gmt", "peaks.bed", "hg38_tss.txt")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, regions, annotation, rule="basal_plus_extension", upstream=5000, downstream=1000, extension=1000000))]
+#[allow(clippy::too_many_arguments)]
+pub fn region_ora<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    regions: &Bound<'_, PyAny>,
+    annotation: &Bound<'_, PyAny>,
+    rule: &str,
+    upstream: i64,
+    downstream: i64,
+    extension: i64,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let (_, mapped, all_genes) = region_genes(
+        py, regions, annotation, rule, upstream, downstream, extension,
+    )?;
+    let (gmt, analyte_list, reference) = inputs::filter_ora_inputs(
+        inputs::gmt(gmt)?,
+        mapped.into_iter().flatten().collect(),
+        all_genes,
+    );
+    let sizes = ora_stats::ORASizes::new(&gmt, &analyte_list, &reference);
+    let res: Vec<ORAResult> = config::install(py, || {
+        get_ora(&analyte_list, &reference, gmt, config::ora_config())
+    })?;
+    ora_results_with_tables(res, &sizes, py)
+}
+
+/// Run GSEA on the genes near a set of scored genomic regions.
+///
+/// Regions are mapped to genes like [`regions_to_genes`](./regions_to_genes.md), and each gene is ranked by the highest score
+/// of its regions. Genes without regions are left out.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `regions` - The regions in any form accepted by [`regions_to_genes`](./regions_to_genes.md). Every region needs a score,
+///   in the fifth column of a BED file or as the fourth element of a tuple.
+/// - `annotation` - The gene annotation in any form accepted by [`regions_to_genes`](./regions_to_genes.md)
+/// - `rule`, `upstream`, `downstream`, `extension` - How regions are mapped to genes. See [`regions_to_genes`](./regions_to_genes.md).
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns the GSEA results in the same format as [`gsea`](./gsea.md).
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.region_gsea("data/kegg.gmt", "peaks.bed", "hg38_tss.txt")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, regions, annotation, rule="basal_plus_extension", upstream=5000, downstream=1000, extension=1000000, seed=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn region_gsea<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    regions: &Bound<'_, PyAny>,
+    annotation: &Bound<'_, PyAny>,
+    rule: &str,
+    upstream: i64,
+    downstream: i64,
+    extension: i64,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let (regions, mapped, _) = region_genes(
+        py, regions, annotation, rule, upstream, downstream, extension,
+    )?;
+    let mut genes: Vec<String> = Vec::new();
+    let mut scores: FxHashMap<String, f64> = FxHashMap::default();
+    for (region, region_genes) in regions.iter().zip(mapped) {
+        let score = region.score.ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "Region {}:{}-{} has no score!",
+                region.chrom, region.start, region.end
+            ))
+        })?;
+        for gene in region_genes {
+            match scores.get_mut(&gene) {
+                Some(x) => *x = x.max(score),
+                None => {
+                    scores.insert(gene.clone(), score);
+                    genes.push(gene);
+                }
+            }
+        }
+    }
+    let rank_list: Vec<RankListItem> = genes
+        .into_iter()
+        .map(|x| RankListItem {
+            rank: scores[&x],
+            analyte: x,
+        })
+        .collect();
+    let gmt = inputs::gmt(gmt)?;
+    let gsea_config = config::gsea_config();
+    let seed = gsea_stats::seed_or_random(seed);
+    let permutations =
+        gsea_stats::make_permutations(gsea_config.permutations, rank_list.len(), seed);
+    let res: Vec<GSEAResult> = config::install(py, || {
+        gsea_stats::gsea(&rank_list, &gmt, &gsea_config, &permutations)
+    })?;
+    let options = RowOptions::from_defaults();
+    res.into_iter()
+        .map(|x| {
+            let row = gsea_result_to_dict(x, &options, py)?;
+            row.set_item("seed", seed)?;
+            Ok(row)
+        })
+        .collect()
+}
@@ -0,0 +1,884 @@
+use crate::export::restricted_sets;
+use crate::{inputs, links, manifest};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde_json::{json, Value};
+
+/// Escapes text for HTML
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Serializes `value` for a `<script>` block, so no string in it can close the block
+fn script_json(value: &Value) -> String {
+    value.to_string().replace("</", "<\\/")
+}
+
+/// Similarity of two sets like EnrichmentMap: the mean of the Jaccard and overlap coefficients
+fn combined_similarity(a: &FxHashSet<&String>, b: &FxHashSet<&String>) -> f64 {
+    let shared = a.intersection(b).count() as f64;
+    if shared == 0.0 {
+        return 0.0;
+    }
+    let jaccard = shared / ((a.len() + b.len()) as f64 - shared);
+    let overlap = shared / a.len().min(b.len()) as f64;
+    (jaccard + overlap) / 2.0
+}
+
+const ENRICHMENT_MAP_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { font-family: sans-serif; margin: 0; }
+h1 { font-size: 1.2em; margin: 12px; }
+#legend { margin: 0 12px; font-size: 0.85em; color: #555; }
+#tabs { margin: 8px 12px 0; }
+#tabs button { border: 1px solid #aaa; background: #f4f4f4; padding: 4px 10px; cursor: pointer; }
+#tabs button.active { background: #fff; font-weight: bold; }
+#overview { margin: 12px; font-size: 0.9em; border-collapse: collapse; }
+#overview td, #overview th { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+svg { display: block; width: 100vw; height: calc(100vh - 70px); }
+#tabs:not(:empty) ~ #maps svg { height: calc(100vh - 100px); }
+.edge { stroke: #999; stroke-opacity: 0.6; }
+.node circle { stroke: #333; stroke-width: 1; cursor: grab; }
+.node text { font-size: 10px; pointer-events: none; }
+#tooltip { position: absolute; display: none; background: #fff; border: 1px solid #aaa; padding: 6px; font-size: 0.85em; pointer-events: none; }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div id="legend">Nodes are sets with an FDR below {fdr}, sized by the number of analytes. Edges connect sets with a similarity of at least {cutoff}. {color} Drag nodes to move them.</div>
+<div id="tabs"></div>
+<div id="maps"></div>
+<div id="tooltip"></div>
+<script>
+const data = {data};
+const sections = data.sections || [{name: null, nodes: data.nodes, edges: data.edges}];
+const tooltip = document.getElementById("tooltip");
+const ns = "http://www.w3.org/2000/svg";
+function escape(text) {
+  const div = document.createElement("div");
+  div.textContent = text;
+  return div.innerHTML;
+}
+// the set of a node, linked to the page opened by double-clicking it, if any
+function setCell(n) {
+  const link = n.plot || n.link;
+  if (!link) return escape(n.set);
+  return `<a href="${escape(link).replace(/"/g, "&quot;")}" target="_blank">${escape(n.set)}</a>`;
+}
+function describe(n) {
+  const text = [`<b>${escape(n.set)}</b>`];
+  if (n.description) text.push(escape(n.description));
+  text.push(`Analytes: ${n.size}`, `p: ${n.p.toExponential(3)}`, `FDR: ${n.fdr.toExponential(3)}`);
+  if (n.nes !== null) text.push(`NES: ${n.nes.toFixed(3)}`);
+  if (n.enrichment_ratio !== null) text.push(`Enrichment ratio: ${n.enrichment_ratio.toFixed(3)}`);
+  if (n.plot) text.push("Double-click to open the enrichment plot");
+  return text.join("<br>");
+}
+let dragged = null;
+window.addEventListener("mouseup", () => { dragged = null; });
+// lays out and draws the map of a section, once its SVG is visible and has a size
+function drawMap(svg, nodes, edges) {
+  const width = svg.clientWidth, height = svg.clientHeight;
+  const maxSize = Math.max(1, ...nodes.map(n => n.size));
+  nodes.forEach((n, i) => {
+    const angle = 2 * Math.PI * i / Math.max(nodes.length, 1);
+    n.x = width / 2 + Math.cos(angle) * width / 4;
+    n.y = height / 2 + Math.sin(angle) * height / 4;
+    n.r = 5 + 20 * Math.sqrt(n.size / maxSize);
+  });
+  // force-directed layout: repulsion between every pair of nodes, springs along edges, and gravity to the center
+  for (let step = 0; step < 300; step++) {
+    const cooling = 1 - step / 300;
+    nodes.forEach(n => { n.dx = (width / 2 - n.x) * 0.01; n.dy = (height / 2 - n.y) * 0.01; });
+    for (let i = 0; i < nodes.length; i++) {
+      for (let j = i + 1; j < nodes.length; j++) {
+        const a = nodes[i], b = nodes[j];
+        let dx = a.x - b.x, dy = a.y - b.y;
+        const d2 = Math.max(dx * dx + dy * dy, 1);
+        const f = 2000 / d2;
+        a.dx += dx * f; a.dy += dy * f; b.dx -= dx * f; b.dy -= dy * f;
+      }
+    }
+    edges.forEach(e => {
+      const a = nodes[e.source], b = nodes[e.target];
+      const dx = b.x - a.x, dy = b.y - a.y;
+      const f = 0.02 * e.similarity;
+      a.dx += dx * f; a.dy += dy * f; b.dx -= dx * f; b.dy -= dy * f;
+    });
+    nodes.forEach(n => {
+      const d = Math.sqrt(n.dx * n.dx + n.dy * n.dy);
+      const limit = 20 * cooling + 0.5;
+      if (d > limit) { n.dx *= limit / d; n.dy *= limit / d; }
+      n.x = Math.min(width - n.r, Math.max(n.r, n.x + n.dx));
+      n.y = Math.min(height - n.r, Math.max(n.r, n.y + n.dy));
+    });
+  }
+  const lines = edges.map(e => {
+    const line = document.createElementNS(ns, "line");
+    line.setAttribute("class", "edge");
+    line.setAttribute("stroke-width", 1 + 4 * e.similarity);
+    svg.appendChild(line);
+    return line;
+  });
+  const groups = nodes.map(n => {
+    const g = document.createElementNS(ns, "g");
+    g.setAttribute("class", "node");
+    const circle = document.createElementNS(ns, "circle");
+    circle.setAttribute("r", n.r);
+    circle.setAttribute("fill", n.color);
+    const label = document.createElementNS(ns, "text");
+    label.setAttribute("dx", n.r + 2);
+    label.setAttribute("dy", 3);
+    label.textContent = n.set;
+    g.appendChild(circle);
+    g.appendChild(label);
+    svg.appendChild(g);
+    return g;
+  });
+  function draw() {
+    edges.forEach((e, i) => {
+      const a = nodes[e.source], b = nodes[e.target];
+      lines[i].setAttribute("x1", a.x); lines[i].setAttribute("y1", a.y);
+      lines[i].setAttribute("x2", b.x); lines[i].setAttribute("y2", b.y);
+    });
+    nodes.forEach((n, i) => groups[i].setAttribute("transform", `translate(${n.x},${n.y})`));
+  }
+  draw();
+  groups.forEach((g, i) => {
+    g.addEventListener("mousedown", () => { dragged = nodes[i]; tooltip.style.display = "none"; });
+    g.addEventListener("mousemove", ev => {
+      if (dragged) return;
+      tooltip.innerHTML = describe(nodes[i]);
+      tooltip.style.left = (ev.pageX + 12) + "px";
+      tooltip.style.top = (ev.pageY + 12) + "px";
+      tooltip.style.display = "block";
+    });
+    g.addEventListener("mouseleave", () => { tooltip.style.display = "none"; });
+    g.addEventListener("dblclick", () => {
+      const target = nodes[i].plot || nodes[i].link;
+      if (target) window.open(target, "_blank");
+    });
+  });
+  svg.addEventListener("mousemove", ev => {
+    if (!dragged || !nodes.includes(dragged)) return;
+    const box = svg.getBoundingClientRect();
+    dragged.x = ev.clientX - box.left;
+    dragged.y = ev.clientY - box.top;
+    draw();
+  });
+}
+const tabs = document.getElementById("tabs"), maps = document.getElementById("maps");
+const views = sections.map(section => {
+  const svg = document.createElementNS(ns, "svg");
+  maps.appendChild(svg);
+  return {section: section, svg: svg, drawn: false};
+});
+// with several sections, an overview table and a tab for each section, with maps laid out when first shown
+if (sections.length > 1) {
+  const overview = document.createElement("table");
+  overview.id = "overview";
+  const rows = sections.map(section => {
+    const top = section.nodes.reduce((a, b) => (a === null || b.fdr < a.fdr ? b : a), null);
+    return `<tr><td>${escape(section.name)}</td><td>${section.nodes.length}</td><td>${section.edges.length}</td>`
+      + `<td>${top ? setCell(top) : ""}</td><td>${top ? top.fdr.toExponential(3) : ""}</td></tr>`;
+  });
+  overview.innerHTML = "<tr><th>Section</th><th>Sets</th><th>Edges</th><th>Top set</th><th>Top FDR</th></tr>" + rows.join("");
+  maps.insertBefore(overview, maps.firstChild);
+  const buttons = [];
+  function show(index) {
+    overview.style.display = index < 0 ? "table" : "none";
+    views.forEach((view, i) => { view.svg.style.display = i === index ? "block" : "none"; });
+    buttons.forEach((button, i) => button.classList.toggle("active", i === index + 1));
+    tooltip.style.display = "none";
+    const view = views[index];
+    if (view && !view.drawn) {
+      drawMap(view.svg, view.section.nodes, view.section.edges);
+      view.drawn = true;
+    }
+  }
+  ["Overview", ...sections.map(x => x.name)].forEach((name, i) => {
+    const button = document.createElement("button");
+    button.textContent = name;
+    button.addEventListener("click", () => show(i - 1));
+    tabs.appendChild(button);
+    buttons.push(button);
+  });
+  show(-1);
+} else {
+  views.forEach(view => drawMap(view.svg, view.section.nodes, view.section.edges));
+}
+</script>
+</body>
+</html>
+"#;
+
+/// Nodes and edges of the enrichment map of a section, and whether its results are from GSEA
+fn section_map(
+    results: &[Bound<'_, PyDict>],
+    gmt: &Bound<'_, PyAny>,
+    fdr: f64,
+    similarity_cutoff: f64,
+    genes: Option<&[String]>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+    plots: Option<&FxHashMap<String, String>>,
+) -> PyResult<(Vec<Value>, Vec<Value>, bool)> {
+    let sets = restricted_sets(results, gmt, fdr, genes, rank_file)?;
+    let rows: FxHashMap<String, Bound<'_, PyDict>> = results
+        .iter()
+        .filter_map(|x| {
+            let set: String = x.get_item("set").ok()??.extract().ok()?;
+            Some((set, x.clone()))
+        })
+        .collect();
+    let value = |set: &str, key: &str| -> PyResult<Option<f64>> {
+        match rows[set].get_item(key)? {
+            Some(x) if !x.is_none() => Ok(Some(x.extract()?)),
+            _ => Ok(None),
+        }
+    };
+    let is_gsea = sets
+        .first()
+        .map(|x| rows[&x.set].contains("nes"))
+        .transpose()?
+        .unwrap_or(false);
+    let min_fdr = sets
+        .iter()
+        .map(|x| x.fdr)
+        .fold(f64::INFINITY, f64::min)
+        .max(f64::MIN_POSITIVE);
+    let mut nodes: Vec<Value> = Vec::with_capacity(sets.len());
+    for set in sets.iter() {
+        let nes = value(&set.set, "nes")?;
+        // darker for lower FDRs, relative to the most significant set
+        let strength = if fdr > min_fdr {
+            ((fdr.ln() - set.fdr.max(min_fdr).ln()) / (fdr.ln() - min_fdr.ln())).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let light = (235.0 - 180.0 * strength) as u8;
+        let color = if nes.unwrap_or(1.0) < 0.0 {
+            format!("rgb({},{},255)", light, light)
+        } else {
+            format!("rgb(255,{},{})", light, light)
+        };
+        let link = if links::is_web_link(&set.url) {
+            Some(set.url.clone())
+        } else {
+            links::result_link(&rows[&set.set])?
+        };
+        nodes.push(json!({
+            "set": set.set,
+            "description": rows[&set.set].get_item("description")?.and_then(|x| x.extract::<String>().ok()),
+            "size": set.analytes.len(),
+            "p": set.p,
+            "fdr": set.fdr,
+            "nes": nes,
+            "enrichment_ratio": value(&set.set, "enrichment_ratio")?,
+            "color": color,
+            "link": link,
+            "plot": plots.and_then(|x| x.get(&set.set)),
+        }));
+    }
+    let members: Vec<FxHashSet<&String>> =
+        sets.iter().map(|x| x.analytes.iter().collect()).collect();
+    let mut edges: Vec<Value> = Vec::new();
+    for i in 0..sets.len() {
+        for j in (i + 1)..sets.len() {
+            let similarity = combined_similarity(&members[i], &members[j]);
+            if similarity >= similarity_cutoff {
+                edges.push(json!({"source": i, "target": j, "similarity": similarity}));
+            }
+        }
+    }
+    Ok((nodes, edges, is_gsea))
+}
+
+/// `obj` as a dictionary mapping section names to their values, if it is a dictionary keyed by the names of `sections`
+/// instead of a single value for every section
+fn per_section<'a>(
+    obj: &Bound<'a, PyAny>,
+    sections: &[Section],
+) -> PyResult<Option<Bound<'a, PyDict>>> {
+    let Ok(dict) = obj.downcast::<PyDict>() else {
+        return Ok(None);
+    };
+    if dict.is_empty() {
+        return Ok(None);
+    }
+    for key in dict.keys() {
+        let key = key.str()?.to_string();
+        if !sections.iter().any(|x| x.name.as_deref() == Some(&key)) {
+            return Ok(None);
+        }
+    }
+    Ok(Some(dict.clone()))
+}
+
+/// Write an interactive enrichment map of the significant sets of an analysis to a self-contained HTML file.
+///
+/// Each significant set is a node, and sets that share analytes are connected by an edge. The similarity of two sets is the mean
+/// of their Jaccard and overlap coefficients, like the "combined" similarity of [EnrichmentMap](https://doi.org/10.1371/journal.pone.0013984).
+/// The file has no dependencies, so it can be opened in any browser. Hovering over a node shows its results, nodes can be dragged,
+/// and double-clicking a node opens its enrichment plot from `plots`, or if it has no plot, the URL of the set from the GMT or the `link` of its
+/// result (see [`set_defaults`](./set_defaults.md)). Top sets in the overview tab link to the same page.
+///
+/// Results of several databases, or of a meta-analysis, are written to a single file with a tab for each database or list, and an
+/// overview tab with the number of sets and the top set of each map. Each map is laid out when its tab is first opened.
+///
+/// # Parameters
+/// - `results` - Results to map, in any form accepted by [`generate_markdown`](./generate_markdown.md): the results of an analysis,
+///   a dictionary mapping the name of each database to its results, or the output of a meta-analysis
+/// - `gmt` - The GMT used for the analysis in any form accepted by [`ora`](./ora.md). For results with several sections, either a single GMT
+///   with the sets of every section, or a dictionary mapping the name of each section to its GMT
+/// - `path` - `String` of the path to write the HTML file to
+/// - `fdr` - FDR threshold for a set to be included (default: `0.05`)
+/// - `similarity_cutoff` - Minimum similarity for two sets to be connected (default: `0.375`)
+/// - `genes` - If provided, a list of analytes as `String`s or integers, such as the analyte list of an ORA. Similarities only count the analytes in this list (default: `None`)
+/// - `rank_file` - If provided, the rank list of a GSEA in any form accepted by [`gsea`](./gsea.md). Similarities only count the analytes in the leading edge of each set.
+///   For results with several sections, can also be a dictionary mapping the name of each section to its rank list, where sections left out,
+///   like the meta-analysis, count every analyte (default: `None`)
+/// - `title` - Title of the page (default: `"Enrichment Map"`)
+/// - `plots` - If provided, a dictionary mapping sets to the paths of their enrichment plots, like the output of [`write_gsea_plots`](./write_gsea_plots.md).
+///   Paths are linked as they are, so relative paths are relative to the HTML file. (default: `None`)
+///
+/// Nodes are colored by the sign of the NES for GSEA results (red for positive and blue for negative), and red otherwise,
+/// with darker colors for lower FDRs.
+///
+/// # Returns
+///
+/// Returns the number of sets in the map, summed over every section.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `results` is not in a form accepted by [`generate_markdown`](./generate_markdown.md), if a section has no GMT,
+/// if a significant set is not in the GMT, or if the file can not be written.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.gsea("data/kegg.gmt", "data/test.rnk")
+/// webgestaltpy.enrichment_map_html(res, "data/kegg.gmt", "enrichment_map.html", rank_file="data/test.rnk")
+/// ```
+///
+/// With a tab for each list of a meta-analysis:
+///
+/// ```python
+/// res = webgestaltpy.meta_gsea("data/kegg.gmt", ["data/test.rnk", "data/second_test.rnk"])
+/// webgestaltpy.enrichment_map_html(res, "data/kegg.gmt", "enrichment_map.html")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, gmt, path, fdr=0.05, similarity_cutoff=0.375, genes=None, rank_file=None, title="Enrichment Map", plots=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn enrichment_map_html(
+    results: &Bound<'_, PyAny>,
+    gmt: &Bound<'_, PyAny>,
+    path: String,
+    fdr: f64,
+    similarity_cutoff: f64,
+    genes: Option<&Bound<'_, PyAny>>,
+    rank_file: Option<&Bound<'_, PyAny>>,
+    title: &str,
+    plots: Option<FxHashMap<String, String>>,
+) -> PyResult<usize> {
+    let genes = genes.map(inputs::analyte_ids).transpose()?;
+    let sections = report_sections(results)?;
+    let single = sections.len() == 1 && sections[0].name.is_none();
+    let gmts = if single {
+        None
+    } else {
+        per_section(gmt, &sections)?
+    };
+    let rank_files = match rank_file {
+        Some(rank_file) if !single => per_section(rank_file, &sections)?,
+        _ => None,
+    };
+    let mut maps: Vec<Value> = Vec::with_capacity(sections.len());
+    let mut total = 0;
+    let mut analyses = FxHashSet::default();
+    for section in sections.iter() {
+        let name = section.name.as_deref().unwrap_or_default();
+        let section_gmt = match &gmts {
+            Some(gmts) => gmts
+                .get_item(name)?
+                .ok_or_else(|| PyValueError::new_err(format!("No GMT provided for '{}'!", name)))?,
+            None => gmt.clone(),
+        };
+        let section_rank_file = match &rank_files {
+            Some(rank_files) => rank_files.get_item(name)?,
+            None => rank_file.cloned(),
+        };
+        let (nodes, edges, is_gsea) = section_map(
+            &section.rows,
+            &section_gmt,
+            fdr,
+            similarity_cutoff,
+            genes.as_deref(),
+            section_rank_file.as_ref(),
+            plots.as_ref(),
+        )
+        .map_err(|e| match &section.name {
+            Some(name) => PyValueError::new_err(format!("Error in section '{}'. {}", name, e)),
+            None => e,
+        })?;
+        total += nodes.len();
+        if !nodes.is_empty() {
+            analyses.insert(is_gsea);
+        }
+        maps.push(json!({"name": section.name, "nodes": nodes, "edges": edges}));
+    }
+    let color = if analyses.len() > 1 {
+        "For GSEA, red nodes have a positive NES and blue nodes a negative NES. Darker nodes have lower FDRs."
+    } else if analyses.contains(&true) {
+        "Red nodes have a positive NES and blue nodes a negative NES, darker for lower FDRs."
+    } else {
+        "Darker nodes have lower FDRs."
+    };
+    let data = if single {
+        json!({
+            "schema_version": manifest::SCHEMA_VERSION,
+            "nodes": maps[0]["nodes"],
+            "edges": maps[0]["edges"],
+        })
+    } else {
+        json!({
+            "schema_version": manifest::SCHEMA_VERSION,
+            "sections": maps,
+        })
+    };
+    let html = ENRICHMENT_MAP_TEMPLATE
+        .replace("{title}", &escape_html(title))
+        .replace("{fdr}", &fdr.to_string())
+        .replace("{cutoff}", &similarity_cutoff.to_string())
+        .replace("{color}", color)
+        .replace("{data}", &script_json(&data));
+    inputs::write_file(&path, &html).map_err(|e| {
+        PyValueError::new_err(format!("Error when writing HTML file at: {}. {}", path, e))
+    })?;
+    Ok(total)
+}
+
+/// Escapes text for a Markdown table cell
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Formats a number for a Markdown table, with 3 significant digits in plain notation for small numbers if `scientific` is `false`
+fn format_number(x: f64, scientific: bool) -> String {
+    if !x.is_finite() || x == 0.0 {
+        format!("{:.3}", x)
+    } else if x.abs() < 0.001 {
+        if scientific {
+            format!("{:.2e}", x)
+        } else {
+            let decimals = 2 - x.abs().log10().floor() as i32;
+            format!("{:.*}", decimals as usize, x)
+        }
+    } else if x.abs() >= 1e6 && scientific {
+        format!("{:.2e}", x)
+    } else {
+        format!("{:.3}", x)
+    }
+}
+
+/// Formats a value of a result for a Markdown table
+fn format_value(value: Option<Bound<'_, PyAny>>, scientific: bool) -> PyResult<String> {
+    match value {
+        None => Ok(String::new()),
+        Some(x) if x.is_none() => Ok(String::new()),
+        Some(x) => match x.extract::<f64>() {
+            Ok(number) if x.extract::<i64>().is_err() => Ok(format_number(number, scientific)),
+            _ => Ok(escape_cell(&x.str()?.to_string())),
+        },
+    }
+}
+
+/// Results of one section of a report, like one database or one list of a meta-analysis
+struct Section<'a> {
+    /// `None` for the results of a single analysis, which are not split into sections
+    name: Option<String>,
+    rows: Vec<Bound<'a, PyDict>>,
+    /// Whether the rows are the combined results of a meta-analysis, which have no ES or overlap
+    meta: bool,
+}
+
+/// Splits the results passed to a report into sections.
+///
+/// A list of results is a single section. The list of lists of a meta-analysis, or its dictionary with `meta` and `lists`,
+/// has a section for the meta-analysis and one for each list. Any other dictionary maps the name of each section to its results,
+/// except for the `results` of `with_metadata=True` or `on_error="skip"`, which are split like the results themselves.
+fn report_sections<'py>(results: &Bound<'py, PyAny>) -> PyResult<Vec<Section<'py>>> {
+    if let Ok(dict) = results.downcast::<PyDict>() {
+        if let (Some(meta), Some(lists)) = (dict.get_item("meta")?, dict.get_item("lists")?) {
+            let mut sections = vec![Section {
+                name: Some("Meta-analysis".to_string()),
+                rows: meta.extract()?,
+                meta: true,
+            }];
+            for (name, rows) in lists.downcast::<PyDict>()?.iter() {
+                sections.push(Section {
+                    name: Some(name.str()?.to_string()),
+                    rows: rows.extract()?,
+                    meta: false,
+                });
+            }
+            return Ok(sections);
+        }
+        if let Some(inner) = dict.get_item("results")? {
+            return report_sections(&inner);
+        }
+        return dict
+            .iter()
+            .map(|(name, rows)| {
+                Ok(Section {
+                    name: Some(name.str()?.to_string()),
+                    rows: rows.extract()?,
+                    meta: false,
+                })
+            })
+            .collect();
+    }
+    if let Ok(rows) = results.extract::<Vec<Bound<'py, PyDict>>>() {
+        return Ok(vec![Section {
+            name: None,
+            rows,
+            meta: false,
+        }]);
+    }
+    let lists: Vec<Vec<Bound<'py, PyDict>>> = results.extract().map_err(|_| {
+        PyValueError::new_err(
+            "Results must be a list of results, the list of lists of a meta-analysis, or a dictionary of results!",
+        )
+    })?;
+    Ok(lists
+        .into_iter()
+        .enumerate()
+        .map(|(i, rows)| Section {
+            name: Some(if i == 0 {
+                "Meta-analysis".to_string()
+            } else {
+                format!("List {}", i)
+            }),
+            rows,
+            meta: i == 0,
+        })
+        .collect())
+}
+
+/// Summary of a section of a Markdown report
+struct SectionSummary<'a> {
+    analysis: &'static str,
+    /// Keys and headers of the columns of the table of top sets, besides the set, p-value, and FDR
+    columns: Vec<(&'static str, &'static str)>,
+    /// Rows with their p-value and FDR, in order of FDR and then p-value
+    rows: Vec<(Bound<'a, PyDict>, f64, f64)>,
+    tested: usize,
+    significant: usize,
+    /// Number of significant sets with a positive NES, if the results have an NES
+    positive: Option<usize>,
+}
+
+fn summarize_section<'a>(section: &Section<'a>, fdr: f64) -> PyResult<SectionSummary<'a>> {
+    let get = |row: &Bound<'_, PyDict>, key: &str| -> PyResult<f64> {
+        row.get_item(key)?
+            .ok_or_else(|| PyValueError::new_err(format!("Result is missing '{}'!", key)))?
+            .extract()
+    };
+    let first = section.rows.first();
+    let has = |key: &str| -> PyResult<bool> {
+        Ok(first.map(|x| x.contains(key)).transpose()? == Some(true))
+    };
+    let is_gsea = has("nes")? && !section.meta;
+    let (analysis, mut columns) = if section.meta {
+        ("Meta-analysis", Vec::new())
+    } else if is_gsea {
+        (
+            "GSEA",
+            vec![
+                ("es", "ES"),
+                ("nes", "NES"),
+                ("leading_edge", "Leading edge"),
+            ],
+        )
+    } else if has("overlap")? {
+        (
+            "ORA",
+            vec![
+                ("overlap", "Overlap"),
+                ("expected", "Expected"),
+                ("enrichment_ratio", "Enrichment ratio"),
+            ],
+        )
+    } else {
+        ("Enrichment", Vec::new())
+    };
+    // the pooled NES of a meta-analysis with nes_combination
+    let pooled = section.meta && has("nes_se")?;
+    if pooled {
+        columns.push(("nes", "Pooled NES"));
+    }
+    if section.meta && has("votes")? {
+        columns.push(("votes", "Votes"));
+    }
+    let mut rows: Vec<(Bound<'_, PyDict>, f64, f64)> = Vec::with_capacity(section.rows.len());
+    for row in section.rows.iter() {
+        rows.push((row.clone(), get(row, "p")?, get(row, "fdr")?));
+    }
+    let mut tested = 0;
+    for (row, p, _) in rows.iter() {
+        let untested = if section.meta {
+            !p.is_finite()
+        } else {
+            let key = if is_gsea { "es" } else { "overlap" };
+            match row.get_item(key)? {
+                Some(x) => x.extract::<f64>().map(|x| x == 0.0).unwrap_or(false),
+                None => false,
+            }
+        };
+        if !untested {
+            tested += 1;
+        }
+    }
+    let mut significant = 0;
+    let mut positive = 0;
+    for (row, _, row_fdr) in rows.iter() {
+        if *row_fdr < fdr {
+            significant += 1;
+            if (is_gsea || pooled)
+                && row
+                    .get_item("nes")?
+                    .and_then(|x| x.extract::<f64>().ok())
+                    .is_some_and(|x| x > 0.0)
+            {
+                positive += 1;
+            }
+        }
+    }
+    rows.sort_by(|a, b| a.2.total_cmp(&b.2).then(a.1.total_cmp(&b.1)));
+    Ok(SectionSummary {
+        analysis,
+        columns,
+        rows,
+        tested,
+        significant,
+        positive: (is_gsea || pooled).then_some(positive),
+    })
+}
+
+/// Set of a result for a table, as a Markdown link if the result has a `link`
+fn linked_set(row: &Bound<'_, PyDict>, scientific: bool) -> PyResult<String> {
+    let set = format_value(row.get_item("set")?, scientific)?;
+    Ok(match links::result_link(row)? {
+        Some(link) => format!(
+            "[{}]({})",
+            set,
+            link.replace(' ', "%20")
+                .replace('(', "%28")
+                .replace(')', "%29")
+        ),
+        None => set,
+    })
+}
+
+/// Writes the quality control counts and the table of top sets of a section, under headings of `level`
+fn write_section(
+    text: &mut String,
+    summary: &SectionSummary,
+    level: &str,
+    fdr: f64,
+    top_n: usize,
+    scientific: bool,
+) -> PyResult<()> {
+    text.push_str(&format!("{} Quality Control\n\n", level));
+    text.push_str(&format!("- Sets in the results: {}\n", summary.rows.len()));
+    text.push_str(&format!("- Sets tested: {}\n", summary.tested));
+    text.push_str(&format!(
+        "- Significant sets (FDR < {}): {}\n",
+        fdr, summary.significant
+    ));
+    if let Some(positive) = summary.positive {
+        text.push_str(&format!(
+            "- Significant sets with a positive NES: {}\n- Significant sets with a negative NES: {}\n",
+            positive,
+            summary.significant - positive
+        ));
+    }
+    text.push_str(&format!(
+        "\n{} Top {} Sets\n\n",
+        level,
+        top_n.min(summary.rows.len())
+    ));
+    if summary.rows.is_empty() {
+        text.push_str("No results.\n");
+        return Ok(());
+    }
+    let mut header = vec!["Set"];
+    header.extend(summary.columns.iter().map(|x| x.1));
+    header.extend(["p", "FDR"]);
+    text.push_str(&format!("| {} |\n", header.join(" | ")));
+    text.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+    for (row, p, row_fdr) in summary.rows.iter().take(top_n) {
+        let mut cells = vec![linked_set(row, scientific)?];
+        for (key, _) in summary.columns.iter() {
+            cells.push(format_value(row.get_item(*key)?, scientific)?);
+        }
+        cells.push(format_number(*p, scientific));
+        cells.push(format_number(*row_fdr, scientific));
+        text.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    Ok(())
+}
+
+/// Write a concise Markdown summary of the results of an analysis, for Quarto or Jupyter Book pipelines and pull request comments.
+///
+/// The summary has the parameters of the run, quality control counts (how many sets were tested and how many are significant),
+/// and a table of the top sets. The columns of the table depend on the analysis: the overlap and enrichment ratio for ORA,
+/// and the ES, NES, and leading edge for GSEA.
+///
+/// Runs against several databases, and meta-analyses, are summarized in a single report: an overview table with the number of tested and
+/// significant sets and the top set of each database or list, followed by a section for each one.
+///
+/// # Parameters
+/// - `results` - Results to summarize, either:
+///   - a list of dictionaries of the results of an analysis, like the output of [`ora`](./ora.md) or [`gsea`](./gsea.md)
+///   - a dictionary mapping the name of each database to its results, with a section for each database
+///   - the output of [`meta_ora`](./meta_ora.md) or [`meta_gsea`](./meta_gsea.md), with a section for the meta-analysis and for each list
+///
+///   The output of `with_metadata=True` or `on_error="skip"` is also accepted.
+/// - `path` - `String` of the path to write the Markdown file to
+/// - `title` - Title of the summary (default: `"Enrichment Analysis"`)
+/// - `fdr` - FDR threshold for a set to be significant (default: `0.05`)
+/// - `top_n` - Number of sets in the table, in order of FDR and then p-value (default: `10`)
+/// - `parameters` - If provided, a dictionary of parameters to list, like the input files (default: `None`)
+/// - `scientific` - If `False`, numbers are never written in scientific notation, so a p-value of `1.23e-08` is written as `0.0000000123`,
+///   for tools that parse the table (default: `True`)
+///
+/// # Returns
+///
+/// Returns the Markdown text, which is also written to `path`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if `results` is not in one of the forms above, if a result is missing its `set`, `p`, or `fdr`, or if the file can not be written.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt")
+/// webgestaltpy.generate_markdown(res, "summary.md", parameters={"gmt": "data/kegg.gmt", "analyte_list": "data/genelist.txt"})
+/// ```
+///
+/// A single report for several databases:
+///
+/// ```python
+/// res = {
+///     "KEGG": webgestaltpy.ora("data/kegg.gmt", "data/genelist.txt", "data/reference.txt"),
+///     "GO": webgestaltpy.ora("data/go.gmt", "data/genelist.txt", "data/reference.txt"),
+/// }
+/// webgestaltpy.generate_markdown(res, "summary.md")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (results, path, title="Enrichment Analysis", fdr=0.05, top_n=10, parameters=None, scientific=true))]
+pub fn generate_markdown(
+    results: &Bound<'_, PyAny>,
+    path: String,
+    title: &str,
+    fdr: f64,
+    top_n: usize,
+    parameters: Option<&Bound<'_, PyDict>>,
+    scientific: bool,
+) -> PyResult<String> {
+    let sections = report_sections(results)?;
+    let summaries = sections
+        .iter()
+        .map(|x| summarize_section(x, fdr))
+        .collect::<PyResult<Vec<SectionSummary>>>()?;
+    let single = sections.len() == 1 && sections[0].name.is_none();
+    let mut text = format!("# {}\n\n## Parameters\n\n", title);
+    text.push_str("| Parameter | Value |\n| --- | --- |\n");
+    if single {
+        text.push_str(&format!("| Analysis | {} |\n", summaries[0].analysis));
+    }
+    if let Some(parameters) = parameters {
+        for (key, value) in parameters.iter() {
+            text.push_str(&format!(
+                "| {} | {} |\n",
+                escape_cell(&key.str()?.to_string()),
+                format_value(Some(value), scientific)?
+            ));
+        }
+    }
+    if let Some(seed) = sections
+        .first()
+        .and_then(|x| x.rows.first())
+        .map(|x| x.get_item("seed"))
+        .transpose()?
+        .flatten()
+    {
+        text.push_str(&format!(
+            "| Seed | {} |\n",
+            format_value(Some(seed), scientific)?
+        ));
+    }
+    text.push_str(&format!("| FDR threshold | {} |\n", fdr));
+    text.push_str(&format!(
+        "| webgestaltpy | {} |\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    text.push_str(&format!(
+        "| Schema version | {} |\n\n",
+        manifest::SCHEMA_VERSION
+    ));
+    if single {
+        write_section(&mut text, &summaries[0], "##", fdr, top_n, scientific)?;
+    } else {
+        text.push_str("## Overview\n\n");
+        text.push_str("| Section | Analysis | Sets | Tested | Significant | Top set | Top FDR |\n");
+        text.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+        for (section, summary) in sections.iter().zip(summaries.iter()) {
+            let (top_set, top_fdr) = match summary.rows.first() {
+                Some((row, _, row_fdr)) => (
+                    linked_set(row, scientific)?,
+                    format_number(*row_fdr, scientific),
+                ),
+                None => (String::new(), String::new()),
+            };
+            text.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} |\n",
+                escape_cell(section.name.as_deref().unwrap_or_default()),
+                summary.analysis,
+                summary.rows.len(),
+                summary.tested,
+                summary.significant,
+                top_set,
+                top_fdr
+            ));
+        }
+        for (section, summary) in sections.iter().zip(summaries.iter()) {
+            text.push_str(&format!(
+                "\n## {}\n\n",
+                section.name.as_deref().unwrap_or_default()
+            ));
+            write_section(&mut text, summary, "###", fdr, top_n, scientific)?;
+        }
+    }
+    inputs::write_file(&path, &text).map_err(|e| {
+        PyValueError::new_err(format!(
+            "Error when writing Markdown file at: {}. {}",
+            path, e
+        ))
+    })?;
+    Ok(text)
+}
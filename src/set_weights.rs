@@ -0,0 +1,416 @@
+use crate::{config, gsea_result_to_dict, gsea_stats, inputs, warnings, RowOptions};
+use ahash::{AHashMap, AHashSet};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use webgestalt_lib::methods::gsea::RankListItem;
+use webgestalt_lib::readers::utils::Item;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// Weighted overlap of each set, the sum of the weights of its analytes in the list, where `in_list` says which analytes
+/// of the reference are in the list
+fn weighted_overlaps(members: &[Vec<(usize, f64)>], in_list: &[bool]) -> Vec<f64> {
+    members
+        .iter()
+        .map(|x| x.iter().filter(|(i, _)| in_list[*i]).map(|(_, w)| w).sum())
+        .collect()
+}
+
+/// Read a weighted GMT file, where each analyte of a set has a weight, like the specificity of a cell-type marker.
+///
+/// Each line has the name and URL of the set, like a GMT file, followed by its analytes. Each analyte has its weight either in the
+/// next column (`gene\tweight`), or after a comma in the same column (`gene,weight`), like the weighted libraries of Enrichr.
+/// Analytes listed more than once in a set keep their first weight.
+///
+/// # Parameters
+/// - `path` - `String` of the path to the weighted GMT file, or a file-like object
+/// - `is_content` - If `True`, `path` is the text of the file instead of its path (default: `False`)
+///
+/// # Returns
+///
+/// Returns a dictionary mapping each set to a dictionary of the weight of each analyte, which can be passed as the `gmt` of
+/// [`weighted_gmt_ora`](./weighted_gmt_ora.md) and [`weighted_gmt_gsea`](./weighted_gmt_gsea.md).
+///
+/// # Errors
+///
+/// Raises a `ValueError` if the file can not be read, or if an analyte has no weight or a weight that is not a number.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// markers = webgestaltpy.read_weighted_gmt("SET_A\tna\tCD3E\t2.5\tCD3D\t1.8\n", is_content=True)
+/// print(markers)
+/// ```
+///
+/// **Output**
+///
+/// ```
+/// {'SET_A': {'CD3E': 2.5, 'CD3D': 1.8}}
+/// ```
+#[pyfunction]
+#[pyo3(signature = (path, is_content=false))]
+pub fn read_weighted_gmt<'a>(
+    py: Python<'a>,
+    path: &Bound<'_, PyAny>,
+    is_content: bool,
+) -> PyResult<Bound<'a, PyDict>> {
+    let gmt = inputs::weighted_gmt(&inputs::content(path, is_content)?)?;
+    let dict = PyDict::new(py);
+    for (item, weights) in gmt.items.iter().zip(gmt.weights.iter()) {
+        let set = PyDict::new(py);
+        for (analyte, weight) in item.parts.iter().zip(weights.iter()) {
+            set.set_item(analyte, weight)?;
+        }
+        dict.set_item(&item.id, set)?;
+    }
+    Ok(dict)
+}
+
+/// Run ORA with a weighted GMT, where the analytes of each set count by their weight in the set instead of counting the same.
+///
+/// The statistic of each set is its weighted overlap: the sum of the weights of its analytes in the analyte list. The p-value is
+/// the fraction of random lists of the same size, drawn from the reference list without replacement, with a weighted overlap
+/// at least as large, with a pseudo-count of one: `(1 + hits) / (1 + permutations)`. A set with a weight of `1` for every analyte
+/// gets the p-value of a permutation ORA of its overlap.
+///
+/// # Parameters
+/// - `gmt` - The weighted GMT as a path to a weighted GMT file (see [`read_weighted_gmt`](./read_weighted_gmt.md)), a file-like object,
+///   a dictionary mapping each set to a dictionary of the weight of each analyte or to a list of analytes, or a [`Gmt`](./Gmt.md). Analytes without
+///   a weight have a weight of `1`. Since the analytes of a set can be numbers, a path is always read as a weighted GMT file, so read an unweighted
+///   GMT file with [`Gmt`](./Gmt.md).
+/// - `analyte_list` - The analyte list in any form accepted by [`ora`](./ora.md)
+/// - `reference_list` - The reference list in any form accepted by [`ora`](./ora.md)
+/// - `permutations` - Number of random lists (default: `1000`)
+/// - `seed` - Seed for the random lists. If not provided, a new seed is generated (default: `None`)
+///
+/// Like [`ora`](./ora.md), the reference list is limited to the analytes in the GMT, and the analyte list to the reference list.
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set in the order of the GMT, with the `set`, `p`, `fdr` (BH-adjusted), the `overlap`
+/// (number of analytes of the set in the list), the `weighted_overlap`, the `expected` weighted overlap (the sum of the weights of the set
+/// in the reference times the fraction of the reference in the list), the `enrichment_ratio` of the two (`None` if the set has no analytes
+/// in the reference), the `size` of the set in the reference, and the `seed`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, or if a weight is not positive.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// markers = {"T cell": {"CD3E": 2.5, "CD3D": 1.8, "CD2": 0.6}, "B cell": {"CD19": 2.2, "MS4A1": 2.0, "CD79A": 1.1}}
+/// res = webgestaltpy.weighted_gmt_ora(markers, ["CD3E", "CD2", "GAPDH"], ["CD3E", "CD3D", "CD2", "CD19", "MS4A1", "CD79A", "GAPDH"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, analyte_list, reference_list, permutations=1000, seed=None))]
+pub fn weighted_gmt_ora<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    analyte_list: &Bound<'_, PyAny>,
+    reference_list: &Bound<'_, PyAny>,
+    permutations: usize,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let gmt = inputs::weighted_gmt(gmt)?;
+    gmt.check_positive()?;
+    let annotated: AHashSet<&String> = gmt.items.iter().flat_map(|x| x.parts.iter()).collect();
+    let reference: Vec<String> = inputs::unique_analyte_list(reference_list)?
+        .into_iter()
+        .filter(|x| annotated.contains(x))
+        .collect();
+    let positions: AHashMap<&String, usize> =
+        reference.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    let mut in_list = vec![false; reference.len()];
+    for analyte in inputs::analyte_list(analyte_list)? {
+        if let Some(i) = positions.get(&analyte) {
+            in_list[*i] = true;
+        }
+    }
+    let list_size = in_list.iter().filter(|x| **x).count();
+    let members: Vec<Vec<(usize, f64)>> = gmt
+        .items
+        .iter()
+        .zip(gmt.weights.iter())
+        .map(|(item, weights)| {
+            let mut members: Vec<(usize, f64)> = item
+                .parts
+                .iter()
+                .zip(weights.iter())
+                .filter_map(|(x, w)| positions.get(x).map(|i| (*i, *w)))
+                .collect();
+            // sums in a fixed order, so they are reproducible
+            members.sort_by_key(|x| x.0);
+            members
+        })
+        .collect();
+    let seed = gsea_stats::seed_or_random(seed);
+    let (observed, hits) = config::install(py, || {
+        let observed = weighted_overlaps(&members, &in_list);
+        let hits = (0..permutations)
+            .into_par_iter()
+            .map(|b| {
+                let mut rng = rand::rngs::SmallRng::seed_from_u64(seed.wrapping_add(b as u64));
+                let mut random_list = vec![false; reference.len()];
+                for i in rand::seq::index::sample(&mut rng, reference.len(), list_size) {
+                    random_list[i] = true;
+                }
+                weighted_overlaps(&members, &random_list)
+                    .iter()
+                    .zip(&observed)
+                    .map(|(sum, x)| (sum >= x) as usize)
+                    .collect::<Vec<usize>>()
+            })
+            .reduce(
+                || vec![0; members.len()],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(x, y)| *x += y);
+                    a
+                },
+            );
+        (observed, hits)
+    })?;
+    let p: Vec<f64> = members
+        .iter()
+        .zip(&hits)
+        .map(|(set, hit)| {
+            if set.is_empty() {
+                1.0
+            } else {
+                (1 + hit) as f64 / (1 + permutations) as f64
+            }
+        })
+        .collect();
+    let fdr = adjust(&p, AdjustmentMethod::BH);
+    let list_fraction = list_size as f64 / reference.len().max(1) as f64;
+    gmt.items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let expected = members[i].iter().map(|(_, w)| w).sum::<f64>() * list_fraction;
+            let ratio = if expected > 0.0 {
+                Some(observed[i] / expected)
+            } else {
+                None
+            };
+            let overlap = members[i].iter().filter(|(x, _)| in_list[*x]).count();
+            let dict = PyDict::new(py);
+            dict.set_item("set", &item.id)?;
+            dict.set_item("p", p[i])?;
+            dict.set_item("fdr", fdr[i])?;
+            dict.set_item("overlap", overlap)?;
+            dict.set_item("weighted_overlap", observed[i])?;
+            dict.set_item("expected", expected)?;
+            dict.set_item("enrichment_ratio", ratio)?;
+            dict.set_item("size", members[i].len())?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Suffix of the repressed copy of each analyte in the signed rank list. GMT columns are split on tabs, so no analyte ends with it.
+const REPRESSED: &str = "\t-";
+
+/// Signed rank list and sets for signed enrichment. Each analyte is in the rank list twice, once with its rank and once, for its
+/// repressed targets, with its negated rank. Each set keeps its activated targets, and the repressed copies of its repressed
+/// targets, with the absolute value of their weights.
+fn signed_inputs(
+    rank_list: Vec<RankListItem>,
+    gmt: inputs::WeightedGmt,
+) -> (Vec<RankListItem>, inputs::WeightedGmt) {
+    let repressed: Vec<RankListItem> = rank_list
+        .iter()
+        .map(|x| RankListItem {
+            analyte: format!("{}{}", x.analyte, REPRESSED),
+            rank: -x.rank,
+        })
+        .collect();
+    let items = gmt
+        .items
+        .into_iter()
+        .zip(gmt.weights.iter())
+        .map(|(item, weights)| Item {
+            parts: item
+                .parts
+                .into_iter()
+                .zip(weights.iter())
+                .map(|(x, w)| if *w < 0.0 { x + REPRESSED } else { x })
+                .collect(),
+            ..item
+        })
+        .collect();
+    let weights = gmt
+        .weights
+        .iter()
+        .map(|x| x.iter().map(|w| w.abs()).collect())
+        .collect();
+    (
+        rank_list.into_iter().chain(repressed).collect(),
+        inputs::WeightedGmt { items, weights },
+    )
+}
+
+/// Permutations of the signed rank list from `permutations` of its analytes. The scores are sorted like [`gsea_stats::SortedRankList`],
+/// and each analyte moves to the positions of the rank and negated rank of another, so its repressed copy always mirrors it.
+fn signed_permutations(rank_list: &[RankListItem], permutations: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = rank_list.len() / 2;
+    let mut sorted: Vec<usize> = (0..rank_list.len()).collect();
+    sorted.sort_by(|a, b| rank_list[*b].rank.total_cmp(&rank_list[*a].rank));
+    let mut position = vec![0; rank_list.len()];
+    for (i, x) in sorted.into_iter().enumerate() {
+        position[x] = i;
+    }
+    permutations
+        .iter()
+        .map(|permutation| {
+            let mut order = vec![0; rank_list.len()];
+            for (i, j) in permutation.iter().enumerate() {
+                order[position[i]] = position[*j];
+                order[position[i + n]] = position[j + n];
+            }
+            order
+        })
+        .collect()
+}
+
+/// Run GSEA with a weighted GMT, where the analytes of each set count by their weight in the set.
+///
+/// At each analyte of the set, the running sum steps up by its absolute rank times its weight in the set, normalized by the sum of
+/// these steps over the set, so analytes with larger weights move the enrichment score more. A set with a weight of `1` for every analyte
+/// scores the same as with [`gsea`](./gsea.md). The p-values, NES, and FDR are computed from permutations of the rank list, like [`gsea`](./gsea.md),
+/// with the number of permutations and the set size limits of the defaults (see [`set_defaults`](./set_defaults.md)).
+///
+/// With `signed=True`, the sets are regulons, like those of DoRothEA or VIPER, and the sign of the weight of each target is its mode of
+/// regulation: positive for activation, and negative for repression. The enrichment is signed, so a regulator scores up when its activated
+/// targets are up and its repressed targets are down. Each analyte is added to the rank list a second time with its rank negated, and the
+/// repressed targets of each regulon are looked up in these negated copies, so the NES and FDR are those of a weighted GSEA on the rank list
+/// of twice the size. The permutations shuffle the analytes, and the negated copy of each analyte takes the negated rank it was shuffled to.
+///
+/// # Parameters
+/// - `gmt` - The weighted GMT in any form accepted by [`weighted_gmt_ora`](./weighted_gmt_ora.md)
+/// - `rank_list` - The rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+/// - `signed` - If `True`, the sign of each weight is the mode of regulation of the analyte in the set (default: `False`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set in the order of the GMT, with the `set`, `p`, `fdr`, `es`, `nes`, `leading_edge`,
+/// `rank_at_max`, and `direction`, like [`gsea`](./gsea.md), and the `seed`. With `signed=True`, `rank_at_max` is `None`, since the
+/// running sum is along the signed rank list.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, or if a weight is not positive. With `signed=True`, raises a `ValueError`
+/// if a weight is `0` instead.
+///
+/// Emits a [`WebGestaltWarning`](./WebGestaltWarning.md) if some sets were not tested.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.weighted_gmt_gsea("markers.gmt", "data/test.rnk", seed=1)
+///
+/// regulons = {"TP53": {"CDKN1A": 1, "BAX": 1, "MDM2": 1, "SESN1": 1, "CCNB1": -1, "CDC25C": -1}}
+/// activity = webgestaltpy.weighted_gmt_gsea(regulons, "data/test.rnk", seed=1, signed=True)
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_list, seed=None, signed=false))]
+pub fn weighted_gmt_gsea<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_list: &Bound<'_, PyAny>,
+    seed: Option<u64>,
+    signed: bool,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let gmt = inputs::weighted_gmt(gmt)?;
+    let rank_list = inputs::rank_list(rank_list)?;
+    let (rank_list, gmt) = if signed {
+        gmt.check_signed()?;
+        signed_inputs(rank_list, gmt)
+    } else {
+        gmt.check_positive()?;
+        (rank_list, gmt)
+    };
+    let config = config::gsea_config();
+    let seed = gsea_stats::seed_or_random(seed);
+    let permutations = if signed {
+        let analytes =
+            gsea_stats::make_permutations(config.permutations, rank_list.len() / 2, seed);
+        signed_permutations(&rank_list, &analytes)
+    } else {
+        gsea_stats::make_permutations(config.permutations, rank_list.len(), seed)
+    };
+    let res = config::install(py, || {
+        gsea_stats::weighted_gsea(&rank_list, &gmt, &config, &permutations)
+    })?;
+    let untested = res.iter().filter(|x| x.running_sum.is_empty()).count();
+    if untested > 0 {
+        warnings::warn(
+            py,
+            &format!(
+                "{} of {} sets were not tested, since their overlap with the rank list is below min_overlap or above max_overlap.",
+                untested,
+                res.len()
+            ),
+        )?;
+    }
+    let options = RowOptions::from_defaults();
+    res.into_iter()
+        .map(|x| {
+            let dict = gsea_result_to_dict(x, &options, py)?;
+            if signed {
+                dict.set_item("rank_at_max", py.None())?;
+            }
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_permutations_keep_repressed_copies_mirrored() {
+        let rank_list: Vec<RankListItem> = [("a", 2.0), ("b", -0.5), ("c", 1.0), ("d", -3.0)]
+            .iter()
+            .map(|(analyte, rank)| RankListItem {
+                analyte: analyte.to_string(),
+                rank: *rank,
+            })
+            .collect();
+        let gmt = inputs::WeightedGmt {
+            items: Vec::new(),
+            weights: Vec::new(),
+        };
+        let (rank_list, _) = signed_inputs(rank_list, gmt);
+        let sorted = gsea_stats::SortedRankList::new(&rank_list);
+        let base = |x: &str| x.trim_end_matches(REPRESSED).to_string();
+        for order in signed_permutations(&rank_list, &gsea_stats::make_permutations(20, 4, 1)) {
+            let mut seen = order.clone();
+            seen.sort();
+            assert_eq!(seen, (0..8).collect::<Vec<usize>>());
+            // the analyte at each rank is also the repressed copy at the negated rank
+            for i in 0..8 {
+                let mirror = sorted
+                    .weights
+                    .iter()
+                    .zip(sorted.analytes.iter())
+                    .position(|(w, x)| *w == sorted.weights[i] && *x != sorted.analytes[i])
+                    .unwrap();
+                let (x, y) = (&sorted.analytes[order[i]], &sorted.analytes[order[mirror]]);
+                assert_eq!(base(x), base(y));
+                assert_ne!(x.ends_with(REPRESSED), y.ends_with(REPRESSED));
+            }
+        }
+    }
+}
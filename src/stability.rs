@@ -0,0 +1,159 @@
+use crate::{config, gsea_stats, inputs};
+use ahash::AHashSet;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::methods::gsea::RankListItem;
+use webgestalt_lib::methods::ora::get_ora;
+
+/// How often a set was significant over the subsamples, with the sum of its effect size
+#[derive(Default)]
+struct SetStability {
+    significant: usize,
+    effect_sum: f64,
+    runs: usize,
+}
+
+/// Random subsample of `fraction` of `items`, without replacement
+fn subsample<T: Clone>(items: &[T], fraction: f64, rng: &mut rand::rngs::SmallRng) -> Vec<T> {
+    let size = ((items.len() as f64) * fraction).round() as usize;
+    items.choose_multiple(rng, size).cloned().collect()
+}
+
+/// Run GSEA or ORA on random subsamples of the input list and count how often each set is significant.
+///
+/// GSEA runs on subsamples of the analytes in the rank list. If `reference_list` is provided, ORA runs
+/// instead, on subsamples of the analyte list in `rank_list` against the full reference list.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_list` - The rank list in any form accepted by [`gsea`](./gsea.md), or the analyte list in any form accepted by [`ora`](./ora.md) when `reference_list` is provided
+/// - `fraction` - Fraction of the analytes kept in each subsample (default: `0.8`)
+/// - `n` - Number of subsamples (default: `50`)
+/// - `fdr` - FDR threshold for a set to count as significant in a subsample (default: `0.05`)
+/// - `reference_list` - If provided, the reference list for ORA in any form accepted by [`ora`](./ora.md) (default: `None`)
+/// - `seed` - Seed for the subsamples and the GSEA permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries with the `set`, its selection `frequency` (the fraction of subsamples where
+/// its FDR was below `fdr`), and the mean `nes` (GSEA) or `enrichment_ratio` (ORA) over the subsamples where
+/// it was tested. Every result also has the `seed`. Sets are sorted by `frequency`, highest first.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.stability("data/kegg.gmt", "data/test.rnk", fraction=0.8, n=20)
+/// print(res[0]["set"], res[0]["frequency"])
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_list, fraction=0.8, n=50, fdr=0.05, reference_list=None, seed=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn stability<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_list: &Bound<'_, PyAny>,
+    fraction: f64,
+    n: usize,
+    fdr: f64,
+    reference_list: Option<&Bound<'_, PyAny>>,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(PyValueError::new_err(format!(
+            "fraction must be greater than 0 and at most 1, but is {}",
+            fraction
+        )));
+    }
+    if n == 0 {
+        return Err(PyValueError::new_err("n must be at least 1!"));
+    }
+    let gmt_vec = inputs::gmt(gmt)?;
+    let seed = gsea_stats::seed_or_random(seed);
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+    let mut sets: Vec<String> = Vec::new();
+    let mut stability: FxHashMap<String, SetStability> = FxHashMap::default();
+    let mut record = |set: String, fdr_value: f64, effect: f64, tested: bool| {
+        let entry = stability.entry(set.clone()).or_insert_with(|| {
+            sets.push(set);
+            SetStability::default()
+        });
+        if fdr_value < fdr {
+            entry.significant += 1;
+        }
+        if tested {
+            entry.effect_sum += effect;
+            entry.runs += 1;
+        }
+    };
+    let effect_name = match reference_list {
+        Some(reference_list) => {
+            let (gmt_vec, analytes, reference) =
+                inputs::ora_inputs(gmt_vec, rank_list, reference_list)?;
+            let mut analytes: Vec<String> = analytes.into_iter().collect();
+            // hash set order is random, so sort for reproducible subsamples
+            analytes.sort();
+            for _ in 0..n {
+                let sample: AHashSet<String> = subsample(&analytes, fraction, &mut rng)
+                    .into_iter()
+                    .collect();
+                let res = config::install(py, || {
+                    get_ora(&sample, &reference, gmt_vec.clone(), config::ora_config())
+                })?;
+                for row in res {
+                    let tested = row.expected > 0.0;
+                    record(row.set, row.fdr, row.enrichment_ratio, tested);
+                }
+            }
+            "enrichment_ratio"
+        }
+        None => {
+            let rank_list: Vec<RankListItem> = inputs::rank_list(rank_list)?;
+            let gsea_config = config::gsea_config();
+            for i in 0..n {
+                let sample = subsample(&rank_list, fraction, &mut rng);
+                let permutations = gsea_stats::make_permutations(
+                    gsea_config.permutations,
+                    sample.len(),
+                    seed.wrapping_add(i as u64),
+                );
+                let res = config::install(py, || {
+                    gsea_stats::gsea(&sample, &gmt_vec, &gsea_config, &permutations)
+                })?;
+                for row in res {
+                    let tested = row.es != 0.0;
+                    record(row.set, row.fdr, row.nes, tested);
+                }
+            }
+            "nes"
+        }
+    };
+    let mut rows: Vec<(String, SetStability)> = sets
+        .into_iter()
+        .map(|x| {
+            let value = stability.remove(&x).unwrap();
+            (x, value)
+        })
+        .collect();
+    rows.sort_by_key(|x| std::cmp::Reverse(x.1.significant));
+    rows.into_iter()
+        .map(|(set, value)| {
+            let dict = PyDict::new(py);
+            dict.set_item("set", set)?;
+            dict.set_item("frequency", value.significant as f64 / n as f64)?;
+            let mean = if value.runs > 0 {
+                Some(value.effect_sum / value.runs as f64)
+            } else {
+                None
+            };
+            dict.set_item(effect_name, mean)?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
@@ -0,0 +1,266 @@
+use crate::{
+    config, gsea_result_to_dict, gsea_stats, inputs, ora_result_with_table, ora_stats, RowOptions,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use webgestalt_lib::methods::gsea::{GSEAConfig, GSEAResult, RankListItem};
+use webgestalt_lib::methods::ora::{get_ora, ORAResult};
+use webgestalt_lib::readers::utils::Item;
+
+enum Analysis {
+    /// ORA results are computed up front, since the FDR needs the p-values of every set.
+    /// Only the conversion to Python objects happens in chunks.
+    Ora {
+        results: std::iter::Zip<std::vec::IntoIter<ORAResult>, std::vec::IntoIter<usize>>,
+        sizes: ora_stats::ORASizes,
+    },
+    /// GSEA runs on the next chunk of sets each time, with the same permutations for every chunk
+    Gsea {
+        rank_list: Vec<RankListItem>,
+        sets: std::vec::IntoIter<Item>,
+        config: GSEAConfig,
+        permutations: Vec<Vec<usize>>,
+        seed: u64,
+    },
+}
+
+/// Iterator over the results of an analysis in chunks, returned by [`ora_stream`](./ora_stream.md)
+/// and [`gsea_stream`](./gsea_stream.md).
+///
+/// Each step yields a list of at most `chunk_size` result dictionaries, in the same format as the
+/// results of [`ora`](./ora.md) and [`gsea`](./gsea.md).
+///
+/// # Methods
+///
+/// - `write_tsv(path)` - Writes the remaining results to a tab-separated file with a header, and returns the number of rows written
+#[pyclass]
+pub struct ResultStream {
+    analysis: Analysis,
+    chunk_size: usize,
+    /// Read when the stream is created, so every chunk has the same columns
+    options: RowOptions,
+}
+
+impl ResultStream {
+    fn next_chunk<'a>(&mut self, py: Python<'a>) -> PyResult<Option<Vec<Bound<'a, PyDict>>>> {
+        let chunk_size = self.chunk_size;
+        let options = &self.options;
+        let rows: Vec<Bound<'_, PyDict>> = match &mut self.analysis {
+            Analysis::Ora { results, sizes } => results
+                .by_ref()
+                .take(chunk_size)
+                .map(|(x, set_size)| ora_result_with_table(x, set_size, sizes, options, py))
+                .collect::<PyResult<_>>()?,
+            Analysis::Gsea {
+                rank_list,
+                sets,
+                config: gsea_config,
+                permutations,
+                seed,
+            } => loop {
+                let chunk: Vec<Item> = sets.by_ref().take(chunk_size).collect();
+                if chunk.is_empty() {
+                    return Ok(None);
+                }
+                let res: Vec<GSEAResult> = config::install(py, || {
+                    gsea_stats::gsea(rank_list, &chunk, gsea_config, permutations)
+                })?;
+                // a chunk can be empty if none of its sets pass the size filters
+                if !res.is_empty() {
+                    let rows = res
+                        .into_iter()
+                        .map(|x| gsea_result_to_dict(x, options, py))
+                        .collect::<PyResult<Vec<Bound<'_, PyDict>>>>()?;
+                    for row in rows.iter() {
+                        row.set_item("seed", *seed)?;
+                    }
+                    break rows;
+                }
+            },
+        };
+        if rows.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(rows))
+        }
+    }
+}
+
+#[pymethods]
+impl ResultStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'a>(&mut self, py: Python<'a>) -> PyResult<Option<Vec<Bound<'a, PyDict>>>> {
+        self.next_chunk(py)
+    }
+
+    /// Write the remaining results to a tab-separated file. Each chunk is appended to the file as it is computed.
+    ///
+    /// # Parameters
+    /// - `path` - `String` of the path to write the results to
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of rows written, not counting the header.
+    fn write_tsv(&mut self, py: Python<'_>, path: &str) -> PyResult<usize> {
+        let error = |e: String| {
+            PyValueError::new_err(format!("Error when writing results at: {}. {}", path, e))
+        };
+        let mut file = inputs::OutputFile::create(py, path).map_err(error)?;
+        let mut columns: Vec<String> = Vec::new();
+        let mut count = 0;
+        while let Some(rows) = self.next_chunk(py)? {
+            let mut content = String::new();
+            if columns.is_empty() {
+                columns = rows[0].keys().iter().map(|x| x.to_string()).collect();
+                content.push_str(&columns.join("\t"));
+                content.push('\n');
+            }
+            for row in rows {
+                let values: Vec<String> = columns
+                    .iter()
+                    .map(|x| match row.get_item(x)? {
+                        Some(value) => Ok(value.str()?.to_string()),
+                        None => Ok(String::new()),
+                    })
+                    .collect::<PyResult<_>>()?;
+                content.push_str(&values.join("\t"));
+                content.push('\n');
+                count += 1;
+            }
+            file.write(py, &content).map_err(error)?;
+        }
+        file.close(py).map_err(error)?;
+        Ok(count)
+    }
+}
+
+fn check_chunk_size(chunk_size: usize) -> PyResult<()> {
+    if chunk_size == 0 {
+        Err(PyValueError::new_err("chunk_size must be at least 1!"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Run ORA and get the results in chunks, for GMTs with too many sets to hold every result at once.
+///
+/// The results, including the FDR, are the same as [`ora`](./ora.md). Since the FDR needs the p-value of every
+/// set, the results of every set are computed in full when the stream is created, and only the conversion to
+/// Python objects happens one chunk at a time. Unlike [`gsea_stream`](./gsea_stream.md), the memory used is
+/// not bounded by `chunk_size`.
+///
+/// # Parameters
+/// - `gmt_path` - The GMT in any form accepted by [`ora`](./ora.md)
+/// - `analyte_list_path` - The analyte list in any form accepted by [`ora`](./ora.md)
+/// - `reference_list_path` - The reference list in any form accepted by [`ora`](./ora.md)
+/// - `chunk_size` - Maximum number of sets in each chunk (default: `1000`)
+///
+/// # Returns
+///
+/// Returns a [`ResultStream`](./ResultStream.md) yielding lists of result dictionaries.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// for chunk in webgestaltpy.ora_stream("data/kegg.gmt", "data/genelist.txt", "data/reference.txt", chunk_size=100):
+///     print(len(chunk))
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt_path, analyte_list_path, reference_list_path, chunk_size=1000))]
+pub fn ora_stream(
+    py: Python<'_>,
+    gmt_path: &Bound<'_, PyAny>,
+    analyte_list_path: &Bound<'_, PyAny>,
+    reference_list_path: &Bound<'_, PyAny>,
+    chunk_size: usize,
+) -> PyResult<ResultStream> {
+    check_chunk_size(chunk_size)?;
+    let (gmt, analyte_list, reference) = inputs::ora_inputs(
+        inputs::gmt(gmt_path)?,
+        analyte_list_path,
+        reference_list_path,
+    )?;
+    let mut sizes = ora_stats::ORASizes::new(&gmt, &analyte_list, &reference);
+    let res: Vec<ORAResult> = config::install(py, || {
+        get_ora(&analyte_list, &reference, gmt, config::ora_config())
+    })?;
+    let set_sizes = std::mem::take(&mut sizes.set_sizes);
+    Ok(ResultStream {
+        analysis: Analysis::Ora {
+            results: res.into_iter().zip(set_sizes),
+            sizes,
+        },
+        chunk_size,
+        options: RowOptions::from_defaults(),
+    })
+}
+
+/// Run GSEA on chunks of sets at a time, for GMTs with too many sets to hold every result at once.
+///
+/// Each chunk is analyzed when it is requested, so only the running sums and null distributions of
+/// one chunk are kept in memory. Every chunk uses the same permutations. Since the FDR of GSEA is
+/// estimated from the NES of the analyzed sets, it is computed within each chunk and can differ
+/// slightly from [`gsea`](./gsea.md). The p-values, ES, and NES are the same.
+///
+/// # Parameters
+/// - `gmt_path` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_file_path` - The rank list in any form accepted by [`gsea`](./gsea.md)
+/// - `chunk_size` - Maximum number of sets in each chunk (default: `1000`)
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+/// - `permutations` - If provided, the permutations of the rank list to use instead of random shuffles, as a matrix of indices or a callable returning one,
+///   like the `permutations` of [`gsea`](./gsea.md) (default: `None`)
+///
+/// # Returns
+///
+/// Returns a [`ResultStream`](./ResultStream.md) yielding lists of result dictionaries.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// stream = webgestaltpy.gsea_stream("data/kegg.gmt", "data/test.rnk", chunk_size=100)
+/// stream.write_tsv("gsea_results.tsv")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt_path, rank_file_path, chunk_size=1000, seed=None, permutations=None))]
+pub fn gsea_stream(
+    gmt_path: &Bound<'_, PyAny>,
+    rank_file_path: &Bound<'_, PyAny>,
+    chunk_size: usize,
+    seed: Option<u64>,
+    permutations: Option<&Bound<'_, PyAny>>,
+) -> PyResult<ResultStream> {
+    check_chunk_size(chunk_size)?;
+    let rank_list = inputs::rank_list(rank_file_path)?;
+    let gmt = inputs::gmt(gmt_path)?;
+    let mut config = config::gsea_config();
+    let seed = gsea_stats::seed_or_random(seed);
+    let permutations = match permutations {
+        Some(permutations) => gsea_stats::custom_permutations(
+            permutations,
+            &gsea_stats::SortedRankList::new(&rank_list).analytes,
+            config.permutations,
+            seed,
+        )?,
+        None => gsea_stats::make_permutations(config.permutations, rank_list.len(), seed),
+    };
+    config.permutations = permutations.len() as i32;
+    Ok(ResultStream {
+        analysis: Analysis::Gsea {
+            rank_list,
+            sets: gmt.into_iter(),
+            config,
+            permutations,
+            seed,
+        },
+        chunk_size,
+        options: RowOptions::from_defaults(),
+    })
+}
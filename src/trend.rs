@@ -0,0 +1,150 @@
+use crate::{compare, config, gsea_stats, inputs};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use statrs::distribution::{ContinuousCDF, Normal};
+use std::cmp::Ordering;
+use webgestalt_lib::methods::gsea::GSEAResult;
+use webgestalt_lib::readers::utils::Item;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// Two-sided p-value of the Mann-Kendall test for a monotonic trend in `values`, in order
+fn mann_kendall(values: &[f64]) -> f64 {
+    let n = values.len();
+    let mut s = 0.0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            s += match values[j].partial_cmp(&values[i]) {
+                Some(Ordering::Greater) => 1.0,
+                Some(Ordering::Less) => -1.0,
+                _ => 0.0,
+            };
+        }
+    }
+    let n = n as f64;
+    let var = n * (n - 1.0) * (2.0 * n + 5.0) / 18.0;
+    // continuity correction
+    let z = if s > 0.0 {
+        (s - 1.0) / var.sqrt()
+    } else if s < 0.0 {
+        (s + 1.0) / var.sqrt()
+    } else {
+        0.0
+    };
+    2.0 * (1.0 - Normal::new(0.0, 1.0).unwrap().cdf(z.abs()))
+}
+
+/// Least-squares slope of `values` against their position
+fn slope(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var = 0.0;
+    for (x, y) in values.iter().enumerate() {
+        cov += (x as f64 - mean_x) * (y - mean_y);
+        var += (x as f64 - mean_x).powi(2);
+    }
+    cov / var
+}
+
+/// `"increasing"` or `"decreasing"` if every step goes the same way, otherwise `"mixed"`
+fn direction(values: &[f64]) -> &'static str {
+    if values.windows(2).all(|x| x[1] > x[0]) {
+        "increasing"
+    } else if values.windows(2).all(|x| x[1] < x[0]) {
+        "decreasing"
+    } else {
+        "mixed"
+    }
+}
+
+/// Run GSEA on an ordered series of rank lists, like the time points of an experiment, and test each set for a trend in its NES.
+///
+/// Each rank list is analyzed like in [`meta_gsea`](./meta_gsea.md), with the list at index `i` using `seed + i`.
+/// The trend of each set is tested with the Mann-Kendall test on its NES over the lists, and the p-values are adjusted with BH.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`gsea`](./gsea.md)
+/// - `rank_files` - List of at least two rank lists in any form accepted by [`gsea`](./gsea.md), in order
+/// - `seed` - Seed for the random permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set, with:
+///
+/// - `set` - Name of the set
+/// - `nes`, `p`, `fdr` - Lists of the GSEA results of the set in each rank list, in order. Sets that were not tested in a list have an NES of `0`.
+/// - `slope` - Least-squares slope of the NES over the lists
+/// - `spearman` - Spearman correlation between the NES and the position of the list. `None` if the NES is constant.
+/// - `direction` - `"increasing"` or `"decreasing"` if the NES changes the same way between every pair of consecutive lists, otherwise `"mixed"`
+/// - `trend_p` - Mann-Kendall p-value of a monotonic trend in the NES
+/// - `trend_fdr` - BH-adjusted `trend_p`
+/// - `seed` - Seed used for the first list
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// res = webgestaltpy.gsea_trend("data/kegg.gmt", ["day0.rnk", "day1.rnk", "day3.rnk", "day7.rnk"])
+/// increasing = [x["set"] for x in res if x["direction"] == "increasing" and x["trend_fdr"] < 0.05]
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, rank_files, seed=None))]
+pub fn gsea_trend<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    rank_files: Vec<Bound<'_, PyAny>>,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    if rank_files.len() < 2 {
+        return Err(PyValueError::new_err(
+            "At least two rank lists are required for a trend!",
+        ));
+    }
+    let gmt_vec: Vec<Item> = inputs::gmt(gmt)?;
+    let rank_lists = rank_files
+        .into_iter()
+        .map(|x| inputs::rank_list(&x))
+        .collect::<PyResult<Vec<_>>>()?;
+    let seed = gsea_stats::seed_or_random(seed);
+    let gsea_config = config::gsea_config();
+    let mut results: Vec<Vec<GSEAResult>> = Vec::new();
+    for (i, rank_list) in rank_lists.into_iter().enumerate() {
+        let permutations = gsea_stats::make_permutations(
+            gsea_config.permutations,
+            rank_list.len(),
+            seed.wrapping_add(i as u64),
+        );
+        results.push(config::install(py, || {
+            gsea_stats::gsea(&rank_list, &gmt_vec, &gsea_config, &permutations)
+        })?);
+    }
+    // every list has a result for every set, in GMT order
+    let series = |i: usize, get: fn(&GSEAResult) -> f64| -> Vec<f64> {
+        results.iter().map(|x| get(&x[i])).collect()
+    };
+    let positions: Vec<f64> = (0..results.len()).map(|x| x as f64).collect();
+    let trend_p: Vec<f64> = (0..gmt_vec.len())
+        .map(|i| mann_kendall(&series(i, |x| x.nes)))
+        .collect();
+    let trend_fdr = adjust(&trend_p, AdjustmentMethod::BH);
+    (0..gmt_vec.len())
+        .map(|i| {
+            let nes = series(i, |x| x.nes);
+            let dict = PyDict::new(py);
+            dict.set_item("set", &results[0][i].set)?;
+            dict.set_item("nes", &nes)?;
+            dict.set_item("p", series(i, |x| x.p))?;
+            dict.set_item("fdr", series(i, |x| x.fdr))?;
+            dict.set_item("slope", slope(&nes))?;
+            dict.set_item("spearman", compare::spearman(&positions, &nes))?;
+            dict.set_item("direction", direction(&nes))?;
+            dict.set_item("trend_p", trend_p[i])?;
+            dict.set_item("trend_fdr", trend_fdr[i])?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
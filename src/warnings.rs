@@ -0,0 +1,52 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyUserWarning, PyValueError};
+use pyo3::prelude::*;
+use std::ffi::CString;
+
+create_exception!(
+    webgestaltpy,
+    WebGestaltWarning,
+    PyUserWarning,
+    "Warning for a non-fatal issue in an analysis, like analytes that were left out or sets that were not tested.
+
+The analysis still runs, so these issues are easy to miss. Warnings can be collected with the `warnings` module, or turned into errors with
+`warnings.simplefilter(\"error\", webgestaltpy.WebGestaltWarning)`.
+
+# Example
+
+```python
+import warnings
+import webgestaltpy
+
+with warnings.catch_warnings(record=True) as caught:
+    warnings.simplefilter(\"always\", webgestaltpy.WebGestaltWarning)
+    res = webgestaltpy.ora(\"data/kegg.gmt\", \"data/genelist.txt\", \"data/reference.txt\")
+issues = [str(x.message) for x in caught if issubclass(x.category, webgestaltpy.WebGestaltWarning)]
+```"
+);
+
+/// Emits a `WebGestaltWarning` with `message`
+pub fn warn(py: Python<'_>, message: &str) -> PyResult<()> {
+    let message = CString::new(message).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    PyErr::warn(py, &py.get_type::<WebGestaltWarning>(), &message, 1)
+}
+
+/// Warns about the `left_out` of `total` analytes that were not used, if any
+pub fn warn_left_out(
+    py: Python<'_>,
+    left_out: usize,
+    total: usize,
+    kind: &str,
+    reason: &str,
+) -> PyResult<()> {
+    if left_out == 0 {
+        return Ok(());
+    }
+    warn(
+        py,
+        &format!(
+            "{} of {} {} {}, and were left out.",
+            left_out, total, kind, reason
+        ),
+    )
+}
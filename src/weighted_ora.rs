@@ -0,0 +1,161 @@
+use crate::{config, gsea_stats, inputs};
+use ahash::AHashSet;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::prelude::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use webgestalt_lib::stat::{adjust, AdjustmentMethod};
+
+/// Sum of `weights` over the analytes of each set, given as indices
+fn set_sums(sets: &[Vec<usize>], weights: &[f64]) -> Vec<f64> {
+    sets.par_iter()
+        .map(|x| x.iter().map(|i| weights[*i]).sum())
+        .collect()
+}
+
+/// Run a weighted ORA, where each analyte has a weight (like the posterior probability of being differentially expressed)
+/// instead of being in or out of the analyte list.
+///
+/// The statistic of each set is the sum of the weights of its analytes in the reference list. The p-value is the
+/// fraction of permutations of the weights over the reference list where the sum is at least as large, with a
+/// pseudo-count of one: `(1 + hits) / (1 + permutations)`.
+///
+/// # Parameters
+/// - `gmt` - The GMT in any form accepted by [`ora`](./ora.md)
+/// - `weights` - The weight of each analyte in any form accepted as a rank list by [`gsea`](./gsea.md). Weights must not be negative.
+/// - `reference_list` - The reference list in any form accepted by [`ora`](./ora.md). Analytes of the reference without a weight have
+///   a weight of `0`. If not provided, the reference is every analyte in `weights` (default: `None`)
+/// - `permutations` - Number of permutations of the weights (default: `1000`)
+/// - `seed` - Seed for the permutations. If not provided, a new seed is generated (default: `None`)
+///
+/// Like [`ora`](./ora.md), the reference list is limited to the analytes in the GMT.
+///
+/// # Returns
+///
+/// Returns a list of dictionaries, one for each set in the order of the GMT, with the `set`, `p`, `fdr` (BH-adjusted),
+/// the `weighted_overlap` (sum of the weights of the set), the `expected` sum (the size of the set in the reference times the mean weight),
+/// the `enrichment_ratio` of the two (`None` if the set has no analytes in the reference), the `size` of the set in the reference, and the `seed`.
+///
+/// # Errors
+///
+/// Raises a `ValueError` if any input is malformed or can not be read, or if a weight is negative.
+///
+/// # Example
+///
+/// ```python
+/// import webgestaltpy
+///
+/// posteriors = {"TP53": 0.98, "BRCA1": 0.75, "EGFR": 0.1}
+/// res = webgestaltpy.weighted_ora("data/kegg.gmt", posteriors, "data/reference.txt")
+/// ```
+#[pyfunction]
+#[pyo3(signature = (gmt, weights, reference_list=None, permutations=1000, seed=None))]
+pub fn weighted_ora<'a>(
+    py: Python<'a>,
+    gmt: &Bound<'_, PyAny>,
+    weights: &Bound<'_, PyAny>,
+    reference_list: Option<&Bound<'_, PyAny>>,
+    permutations: usize,
+    seed: Option<u64>,
+) -> PyResult<Vec<Bound<'a, PyDict>>> {
+    let gmt = inputs::gmt(gmt)?;
+    let weights: FxHashMap<String, f64> = inputs::rank_list(weights)?
+        .into_iter()
+        .map(|x| (x.analyte, x.rank))
+        .collect();
+    if let Some((analyte, weight)) = weights.iter().find(|(_, x)| **x < 0.0) {
+        return Err(PyValueError::new_err(format!(
+            "Weight of '{}' is negative ({})!",
+            analyte, weight
+        )));
+    }
+    let reference: Vec<String> = match reference_list {
+        Some(x) => inputs::unique_analyte_list(x)?,
+        None => {
+            let mut analytes: Vec<String> = weights.keys().cloned().collect();
+            // hash map order is random, so sort for reproducible permutations
+            analytes.sort();
+            analytes
+        }
+    };
+    let annotated: AHashSet<&String> = gmt.iter().flat_map(|x| x.parts.iter()).collect();
+    let reference: Vec<String> = reference
+        .into_iter()
+        .filter(|x| annotated.contains(x))
+        .collect();
+    let index: FxHashMap<&String, usize> =
+        reference.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    let mut reference_weights: Vec<f64> = reference
+        .iter()
+        .map(|x| weights.get(x).copied().unwrap_or(0.0))
+        .collect();
+    let sets: Vec<Vec<usize>> = gmt
+        .iter()
+        .map(|x| {
+            let members: AHashSet<usize> = x
+                .parts
+                .iter()
+                .filter_map(|a| index.get(a).copied())
+                .collect();
+            let mut members: Vec<usize> = members.into_iter().collect();
+            // sums in a fixed order, so they are reproducible
+            members.sort_unstable();
+            members
+        })
+        .collect();
+    let seed = gsea_stats::seed_or_random(seed);
+    let mean_weight = reference_weights.iter().sum::<f64>() / reference.len().max(1) as f64;
+    let (observed, hits) = config::install(py, || {
+        let observed = set_sums(&sets, &reference_weights);
+        let mut hits = vec![0_usize; sets.len()];
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        for _ in 0..permutations {
+            reference_weights.shuffle(&mut rng);
+            for (hit, (sum, x)) in hits
+                .iter_mut()
+                .zip(set_sums(&sets, &reference_weights).iter().zip(&observed))
+            {
+                if *sum >= *x {
+                    *hit += 1;
+                }
+            }
+        }
+        (observed, hits)
+    })?;
+    let p: Vec<f64> = sets
+        .iter()
+        .zip(&hits)
+        .map(|(set, hit)| {
+            if set.is_empty() {
+                1.0
+            } else {
+                (1 + hit) as f64 / (1 + permutations) as f64
+            }
+        })
+        .collect();
+    let fdr = adjust(&p, AdjustmentMethod::BH);
+    gmt.iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let expected = sets[i].len() as f64 * mean_weight;
+            let ratio = if expected > 0.0 {
+                Some(observed[i] / expected)
+            } else {
+                None
+            };
+            let dict = PyDict::new(py);
+            dict.set_item("set", &item.id)?;
+            dict.set_item("p", p[i])?;
+            dict.set_item("fdr", fdr[i])?;
+            dict.set_item("weighted_overlap", observed[i])?;
+            dict.set_item("expected", expected)?;
+            dict.set_item("enrichment_ratio", ratio)?;
+            dict.set_item("size", sets[i].len())?;
+            dict.set_item("seed", seed)?;
+            Ok(dict)
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+"""Concurrent calls from several Python threads must give the same results as serial calls.
+
+Run from the root of the repository after `maturin develop`:
+
+    python -m unittest discover tests
+"""
+
+import os
+import sys
+import sysconfig
+import time
+import unittest
+from concurrent.futures import ThreadPoolExecutor
+
+import webgestaltpy
+
+DATA = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "data")
+GMT = os.path.join(DATA, "kegg.gmt")
+RANK_LIST = os.path.join(DATA, "test.rnk")
+ANALYTE_LIST = os.path.join(DATA, "genelist.txt")
+SECOND_ANALYTE_LIST = os.path.join(DATA, "second_genelist.txt")
+REFERENCE = os.path.join(DATA, "reference.txt")
+NETWORK = os.path.join(DATA, "hsapiens_network_CPTAC_Proteomics_OV_entrezgene.net")
+SEEDS = os.path.join(DATA, "net_genes.txt")
+
+THREADS = 8
+FREE_THREADED = bool(sysconfig.get_config_var("Py_GIL_DISABLED"))
+
+
+def concurrently(calls):
+    """Runs each callable of `calls` in its own Python thread and returns their results in order"""
+    with ThreadPoolExecutor(max_workers=len(calls)) as pool:
+        return [f.result() for f in [pool.submit(call) for call in calls]]
+
+
+class ConcurrentCallsTest(unittest.TestCase):
+    def setUp(self):
+        self.defaults = webgestaltpy.get_defaults()
+        webgestaltpy.set_defaults(permutations=100)
+
+    def tearDown(self):
+        webgestaltpy.set_defaults(
+            threads=self.defaults["threads"] or 0,
+            permutations=self.defaults["permutations"],
+        )
+
+    def assert_same_as_serial(self, call):
+        serial = call()
+        for res in concurrently([call] * THREADS):
+            self.assertEqual(res, serial)
+
+    def test_gsea(self):
+        self.assert_same_as_serial(lambda: webgestaltpy.gsea(GMT, RANK_LIST, seed=1))
+
+    def test_ora(self):
+        self.assert_same_as_serial(lambda: webgestaltpy.ora(GMT, ANALYTE_LIST, REFERENCE))
+
+    def test_nta(self):
+        for method in [webgestaltpy.NTAMethod.Prioritization, webgestaltpy.NTAMethod.Expansion]:
+            self.assert_same_as_serial(lambda: webgestaltpy.nta(NETWORK, SEEDS, method, 10))
+
+    def test_mixed_analyses(self):
+        calls = [
+            lambda: webgestaltpy.gsea(GMT, RANK_LIST, seed=1),
+            lambda: webgestaltpy.ora(GMT, ANALYTE_LIST, REFERENCE),
+            lambda: webgestaltpy.nta(NETWORK, SEEDS, webgestaltpy.NTAMethod.Prioritization, 10),
+        ]
+        serial = [call() for call in calls]
+        repeats = THREADS // len(calls) + 1
+        self.assertEqual(concurrently(calls * repeats), serial * repeats)
+
+    def test_mixed_thread_counts(self):
+        # each number of threads runs in its own pool, and the results do not depend on it
+        lists = [ANALYTE_LIST, SECOND_ANALYTE_LIST]
+        serial = webgestaltpy.meta_ora(GMT, lists, [REFERENCE] * 2, threads=1)
+        calls = [
+            lambda threads=threads: webgestaltpy.meta_ora(GMT, lists, [REFERENCE] * 2, threads=threads)
+            for threads in [1, 2, 3, 4] * (THREADS // 4)
+        ]
+        for res in concurrently(calls):
+            self.assertEqual(res, serial)
+
+    def test_set_defaults_during_calls(self):
+        serial = webgestaltpy.gsea(GMT, RANK_LIST, seed=1)
+
+        def change_threads():
+            for threads in [1, 2, 0, 3]:
+                webgestaltpy.set_defaults(threads=threads)
+
+        calls = [lambda: webgestaltpy.gsea(GMT, RANK_LIST, seed=1)] * (THREADS - 1) + [change_threads]
+        for res in concurrently(calls)[:-1]:
+            self.assertEqual(res, serial)
+
+
+@unittest.skipUnless(FREE_THREADED, "needs a free-threaded build of Python")
+class FreeThreadingTest(unittest.TestCase):
+    def test_gil_stays_disabled(self):
+        self.assertFalse(sys._is_gil_enabled())
+
+    @unittest.skipUnless((os.cpu_count() or 1) >= 4, "needs at least 4 cores")
+    def test_calls_scale_with_threads(self):
+        # each call runs on one thread, so four calls in parallel take about as long as one
+        threads = webgestaltpy.get_defaults()["threads"]
+        webgestaltpy.set_defaults(threads=1)
+        try:
+            call = lambda: webgestaltpy.gsea(GMT, RANK_LIST, seed=1)
+            start = time.perf_counter()
+            for _ in range(4):
+                call()
+            serial = time.perf_counter() - start
+            start = time.perf_counter()
+            concurrently([call] * 4)
+            parallel = time.perf_counter() - start
+        finally:
+            webgestaltpy.set_defaults(threads=threads or 0)
+        self.assertLess(parallel, serial / 2)
+
+
+if __name__ == "__main__":
+    unittest.main()